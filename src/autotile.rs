@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use crate::spatial::GID_MASK;
+
+/// Neighbour bit for the cell above.
+pub const AUTOTILE_N: u8 = 1;
+/// Neighbour bit for the cell to the right.
+pub const AUTOTILE_E: u8 = 2;
+/// Neighbour bit for the cell below.
+pub const AUTOTILE_S: u8 = 4;
+/// Neighbour bit for the cell to the left.
+pub const AUTOTILE_W: u8 = 8;
+
+/// Picks tile gids from a 4-bit neighbourhood mask (see [`AUTOTILE_N`] and friends).
+///
+/// A cell takes part in autotiling when its clean gid appears in the table. Its mask has a
+/// bit set for every orthogonal neighbour that also takes part; neighbours outside the
+/// layer count as members so terrain runs off the map edge without a border.
+#[derive(Clone, Debug)]
+pub struct AutoTiler {
+    table: [u32; 16],
+    members: HashSet<u32>,
+}
+
+impl AutoTiler {
+    /// Builds a tiler from a gid per mask value (`table[mask]`).
+    ///
+    /// A `0` entry means "no tile for this mask": [`Map::autotile_region`] leaves such
+    /// cells unchanged instead of erasing them.
+    ///
+    /// [`Map::autotile_region`]: crate::Map::autotile_region
+    pub fn from_bitmask_table(table: [u32; 16]) -> Self {
+        let members = table
+            .iter()
+            .map(|gid| gid & GID_MASK)
+            .filter(|&gid| gid != 0)
+            .collect();
+        AutoTiler { table, members }
+    }
+
+    /// Builds a tiler from the tiles of a Tiled edge Wang set.
+    ///
+    /// `tiles` yields `(tile id, wangid)` pairs as stored in the tileset, `firstgid` is the
+    /// tileset's first gid and `color` the Wang color that stands for "same terrain". Edge
+    /// entries of the wangid (top, right, bottom, left) form the mask; corners are ignored.
+    /// Masks without a matching tile fall back to the fully connected tile, if any.
+    pub fn from_wang_edges(
+        firstgid: u32,
+        tiles: impl IntoIterator<Item = (u32, [u8; 8])>,
+        color: u8,
+    ) -> Self {
+        let mut table = [0u32; 16];
+        for (tile_id, wangid) in tiles {
+            let mask = [AUTOTILE_N, AUTOTILE_E, AUTOTILE_S, AUTOTILE_W]
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| wangid[i * 2] == color)
+                .fold(0, |m, (_, bit)| m | bit);
+            if table[mask as usize] == 0 {
+                table[mask as usize] = firstgid + tile_id;
+            }
+        }
+        let full = table[15];
+        for gid in &mut table {
+            if *gid == 0 {
+                *gid = full;
+            }
+        }
+        Self::from_bitmask_table(table)
+    }
+
+    /// Returns `true` if `gid` (flip flags ignored) is one of this tiler's tiles.
+    pub fn is_member(&self, gid: u32) -> bool {
+        self.members.contains(&(gid & GID_MASK))
+    }
+
+    /// Gid for a neighbourhood mask; only the low four bits are used.
+    pub fn gid_for_mask(&self, mask: u8) -> u32 {
+        self.table[(mask & 0xF) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wang_edges_fill_missing_masks_with_the_full_tile() {
+        // Tile 3 is connected on all edges, tile 7 only to the north.
+        let tiles = [(3, [1, 0, 1, 0, 1, 0, 1, 0]), (7, [1, 0, 2, 0, 2, 0, 2, 0])];
+        let tiler = AutoTiler::from_wang_edges(10, tiles, 1);
+        assert_eq!(tiler.gid_for_mask(15), 13);
+        assert_eq!(tiler.gid_for_mask(AUTOTILE_N), 17);
+        assert_eq!(tiler.gid_for_mask(AUTOTILE_E | AUTOTILE_W), 13);
+        assert!(tiler.is_member(17 | 0x8000_0000));
+        assert!(!tiler.is_member(11));
+    }
+}
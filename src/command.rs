@@ -0,0 +1,154 @@
+use crate::map::LayerId;
+use macroquad::prelude::*;
+
+/// Rectangle of tile cells: `w x h` cells starting at column `x`, row `y`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TileRegion {
+    /// First column.
+    pub x: u32,
+    /// First row.
+    pub y: u32,
+    /// Width in cells.
+    pub w: u32,
+    /// Height in cells.
+    pub h: u32,
+}
+
+impl TileRegion {
+    /// Creates a region from its first cell and size in cells.
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        TileRegion { x, y, w, h }
+    }
+}
+
+/// Per-call placement and tint applied on top of resolved tile draws.
+///
+/// Positions and sizes are scaled about the world origin, then offset; the tint
+/// multiplies each command's own tint (which carries layer opacity).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawParams {
+    /// Added to every destination after scaling, in screen/world pixels.
+    pub offset: Vec2,
+    /// Multiplied into every command's tint.
+    pub tint: Color,
+    /// Uniform scale of destinations and sizes.
+    pub scale: f32,
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        DrawParams {
+            offset: Vec2::ZERO,
+            tint: WHITE,
+            scale: 1.0,
+        }
+    }
+}
+
+/// One tile draw, fully resolved: source rect, destination, flips and tint.
+///
+/// The texture is referenced by `tileset` (position in the map's tileset list) so
+/// commands can be built headless and replayed, sorted or merged by custom renderers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawCommand {
+    /// Layer the tile belongs to.
+    pub layer: LayerId,
+    /// Position of the tile's tileset in map tileset order.
+    pub tileset: usize,
+    /// Raw gid, including Tiled flip flags.
+    pub gid: u32,
+    /// Source rect in the tileset texture (pixels).
+    pub source: Rect,
+    /// Top-left destination in world pixels.
+    pub dest: Vec2,
+    /// Destination width in world pixels.
+    pub dest_w: f32,
+    /// Destination height in world pixels.
+    pub dest_h: f32,
+    /// Horizontal flip derived from the gid flags.
+    pub flip_x: bool,
+    /// Vertical flip derived from the gid flags.
+    pub flip_y: bool,
+    /// Rotation in radians derived from the gid flags.
+    pub rotation: f32,
+    /// Rotation pivot passed to `draw_texture_ex`.
+    pub pivot: Option<Vec2>,
+    /// Tint, carrying layer opacity in alpha.
+    pub tint: Color,
+}
+
+impl DrawCommand {
+    /// Destination rectangle in world pixels.
+    pub fn dest_rect(&self) -> Rect {
+        Rect::new(self.dest.x, self.dest.y, self.dest_w, self.dest_h)
+    }
+
+    /// Returns this command placed and tinted per `params`; the source rect is unchanged.
+    pub fn transformed(&self, params: &DrawParams) -> DrawCommand {
+        let t = params.tint;
+        DrawCommand {
+            dest: self.dest * params.scale + params.offset,
+            dest_w: self.dest_w * params.scale,
+            dest_h: self.dest_h * params.scale,
+            tint: Color::new(
+                self.tint.r * t.r,
+                self.tint.g * t.g,
+                self.tint.b * t.b,
+                self.tint.a * t.a,
+            ),
+            ..self.clone()
+        }
+    }
+
+    /// Draws this command with `texture`, which should be the texture of `self.tileset`.
+    pub fn draw(&self, texture: &Texture2D) {
+        draw_texture_ex(
+            texture,
+            self.dest.x,
+            self.dest.y,
+            self.tint,
+            DrawTextureParams {
+                source: Some(self.source),
+                dest_size: Some(vec2(self.dest_w, self.dest_h)),
+                rotation: self.rotation,
+                flip_x: self.flip_x,
+                flip_y: self.flip_y,
+                pivot: self.pivot,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformed_scales_then_offsets_and_multiplies_tint() {
+        let cmd = DrawCommand {
+            layer: 0,
+            tileset: 0,
+            gid: 3,
+            source: Rect::new(16.0, 0.0, 16.0, 16.0),
+            dest: vec2(32.0, 16.0),
+            dest_w: 16.0,
+            dest_h: 16.0,
+            flip_x: false,
+            flip_y: false,
+            rotation: 0.0,
+            pivot: None,
+            tint: Color::new(1.0, 1.0, 1.0, 0.5),
+        };
+        let params = DrawParams {
+            offset: vec2(-10.0, 5.0),
+            tint: Color::new(0.5, 1.0, 1.0, 0.5),
+            scale: 2.0,
+        };
+
+        let out = cmd.transformed(&params);
+        assert_eq!(out.dest_rect(), Rect::new(54.0, 37.0, 32.0, 32.0));
+        assert_eq!(out.source, cmd.source);
+        assert_eq!(out.tint, Color::new(0.5, 1.0, 1.0, 0.25));
+        assert_eq!(cmd.transformed(&DrawParams::default()), cmd);
+    }
+}
@@ -1,8 +1,13 @@
+use crate::loader::report::ValidationReport;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Typed error returned by map loading/parsing APIs.
+///
+/// Errors raised while decoding a file carry that file's path (the external tileset for
+/// problems inside one, otherwise the map); see [`MapError::path`]. Errors from runtime
+/// edits of a [`Map`](crate::Map) have no path.
 #[derive(Debug)]
 pub enum MapError {
     /// File read/write failure with source path.
@@ -20,11 +25,18 @@ pub enum MapError {
         source: serde_json::Error,
     },
     /// Invalid map contract or unsupported format for current version.
-    InvalidMap(String),
+    InvalidMap {
+        /// File the problem is in, if any.
+        path: Option<PathBuf>,
+        /// What is wrong.
+        message: String,
+    },
     /// Non-UTF-8 path encountered where UTF-8 is required by API surface.
     InvalidUtf8Path(PathBuf),
     /// Unsupported explicit property type encountered in JSON.
     UnsupportedPropertyType {
+        /// File the property is in.
+        path: Option<PathBuf>,
         /// Property name.
         name: String,
         /// Property type string.
@@ -32,33 +44,203 @@ pub enum MapError {
     },
     /// Tile layer references a gid outside known tileset range.
     InvalidTileGid {
+        /// File the layer is in; `None` for [`Map::set_tile`](crate::Map::set_tile).
+        path: Option<PathBuf>,
         /// Layer name.
         layer: String,
-        /// Invalid gid.
+        /// Invalid gid, flip flags masked off.
         gid: u32,
+        /// The gid as written, flip flags included.
+        raw_gid: u32,
         /// Maximum valid gid.
         max_gid: u32,
     },
     /// Object tile reference (`gid`) is outside known tileset range.
     InvalidObjectGid {
+        /// File the object is in.
+        path: Option<PathBuf>,
         /// Layer name.
         layer: String,
         /// Object id.
         object_id: u32,
-        /// Invalid gid.
+        /// Invalid gid, flip flags masked off.
         gid: u32,
+        /// The gid as written, flip flags included.
+        raw_gid: u32,
         /// Maximum valid gid.
         max_gid: u32,
     },
+    /// Two tilesets claim some of the same gids.
+    OverlappingTilesets {
+        /// Map file referencing both tilesets.
+        path: Option<PathBuf>,
+        /// `source` of the tileset with the lower `first_gid`.
+        a: String,
+        /// `source` of the tileset with the higher `first_gid`.
+        b: String,
+        /// Gids claimed by both.
+        range: std::ops::RangeInclusive<u32>,
+    },
+    /// A tileset declares more tiles than its image can hold at its tile size, margin,
+    /// spacing and column count.
+    TilesetGeometryMismatch {
+        /// Tileset file.
+        path: Option<PathBuf>,
+        /// Tileset `source` path.
+        tileset: String,
+        /// Declared tile count.
+        tilecount: u32,
+        /// Tiles that fit in the image.
+        capacity: u32,
+    },
+    /// A size field that must be nonzero is zero, which would break source rects and
+    /// cell math.
+    InvalidGeometry {
+        /// File the entity is in; `None` for [`Map::new`](crate::Map::new) and
+        /// [`Map::add_tileset`](crate::Map::add_tileset).
+        path: Option<PathBuf>,
+        /// `map`, or `tileset '<source>'`.
+        entity: String,
+        /// The zero field, e.g. `tilewidth`.
+        field: String,
+    },
+    /// Tile layer data does not cover its grid exactly.
+    InvalidLayerSize {
+        /// File the layer is in; `None` for [`Map::add_layer`](crate::Map::add_layer).
+        path: Option<PathBuf>,
+        /// Layer name.
+        layer: String,
+        /// `width * height` cells, `usize::MAX` if that overflows.
+        expected: usize,
+        /// Cells actually present.
+        actual: usize,
+    },
+    /// Tile layer data has an element that is not a gid, such as a negative number, a
+    /// float, or the leftover bytes of a truncated base64 payload.
+    LayerDataElement {
+        /// File the layer is in.
+        path: Option<PathBuf>,
+        /// Layer name.
+        layer: String,
+        /// Index of the offending element.
+        index: usize,
+        /// What was found there.
+        found: String,
+    },
+    /// No layer with this name, or none of the kind the call needs.
+    LayerNotFound(String),
+    /// A [`Map::set_layer_draw_order`](crate::Map::set_layer_draw_order) list that repeats
+    /// a layer or leaves some out; says which.
+    InvalidDrawOrder(String),
+    /// [`MapLoader::step`](crate::MapLoader::step) called after it returned the map or
+    /// failed.
+    LoaderFinished,
+    /// Tile cell coordinates outside the layer grid.
+    TileOutOfBounds {
+        /// Layer name.
+        layer: String,
+        /// Requested column.
+        x: u32,
+        /// Requested row.
+        y: u32,
+    },
+    /// Every problem found in one pass, when loading with
+    /// [`LoadOptions::collect_all_errors`](crate::LoadOptions::collect_all_errors).
+    Validation {
+        /// Map file that was checked.
+        path: Option<PathBuf>,
+        /// The problems, in file order.
+        report: ValidationReport,
+    },
     /// Texture load failure for a tileset image.
     TextureLoad {
-        /// Texture path.
+        /// Absolute path of the image.
+        path: PathBuf,
+        /// `source` of the tileset, as written in the map.
+        tileset: String,
+        /// Gids the tileset covers.
+        gids: std::ops::RangeInclusive<u32>,
+        /// Error from macroquad's loader or from a
+        /// [tileset resolver](crate::Map::load_with_tileset_resolver).
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A tileset image that does not exist or cannot be opened, found by
+    /// [`LoadOptions::check_images`](crate::LoadOptions::check_images) before any
+    /// texture is loaded.
+    MissingImage {
+        /// `source` of the tileset, as written in the map.
+        tileset: String,
+        /// The image path, resolved against the map directory.
         path: PathBuf,
-        /// Error message from backend loader.
-        message: String,
     },
 }
 
+impl MapError {
+    /// File the error originates from, when there is one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            MapError::Io { path, .. }
+            | MapError::Json { path, .. }
+            | MapError::InvalidUtf8Path(path)
+            | MapError::TextureLoad { path, .. }
+            | MapError::MissingImage { path, .. } => Some(path),
+            MapError::LayerNotFound(_)
+            | MapError::InvalidDrawOrder(_)
+            | MapError::LoaderFinished
+            | MapError::TileOutOfBounds { .. } => None,
+            _ => self.context_path().as_deref(),
+        }
+    }
+
+    pub(crate) fn invalid(message: impl Into<String>) -> Self {
+        MapError::InvalidMap {
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    /// Attributes a decode error to `file` unless a nested file already claimed it.
+    pub(crate) fn in_file(mut self, file: &Path) -> Self {
+        if let Some(path @ None) = self.context_path_mut() {
+            *path = Some(file.to_path_buf());
+        }
+        self
+    }
+
+    fn context_path(&self) -> &Option<PathBuf> {
+        const NONE: &Option<PathBuf> = &None;
+        match self {
+            MapError::InvalidMap { path, .. }
+            | MapError::UnsupportedPropertyType { path, .. }
+            | MapError::InvalidTileGid { path, .. }
+            | MapError::InvalidObjectGid { path, .. }
+            | MapError::OverlappingTilesets { path, .. }
+            | MapError::TilesetGeometryMismatch { path, .. }
+            | MapError::InvalidGeometry { path, .. }
+            | MapError::InvalidLayerSize { path, .. }
+            | MapError::LayerDataElement { path, .. }
+            | MapError::Validation { path, .. } => path,
+            _ => NONE,
+        }
+    }
+
+    fn context_path_mut(&mut self) -> Option<&mut Option<PathBuf>> {
+        match self {
+            MapError::InvalidMap { path, .. }
+            | MapError::UnsupportedPropertyType { path, .. }
+            | MapError::InvalidTileGid { path, .. }
+            | MapError::InvalidObjectGid { path, .. }
+            | MapError::OverlappingTilesets { path, .. }
+            | MapError::TilesetGeometryMismatch { path, .. }
+            | MapError::InvalidGeometry { path, .. }
+            | MapError::InvalidLayerSize { path, .. }
+            | MapError::LayerDataElement { path, .. }
+            | MapError::Validation { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
 impl Display for MapError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -68,11 +250,11 @@ impl Display for MapError {
             MapError::Json { path, source } => {
                 write!(f, "JSON parse error at {}: {}", path.display(), source)
             }
-            MapError::InvalidMap(msg) => write!(f, "Invalid map: {msg}"),
+            MapError::InvalidMap { message, .. } => write!(f, "Invalid map: {message}"),
             MapError::InvalidUtf8Path(path) => {
                 write!(f, "Path is not valid UTF-8: {}", path.display())
             }
-            MapError::UnsupportedPropertyType { name, kind } => {
+            MapError::UnsupportedPropertyType { name, kind, .. } => {
                 write!(
                     f,
                     "Unsupported property type '{}' for property '{}'",
@@ -82,26 +264,118 @@ impl Display for MapError {
             MapError::InvalidTileGid {
                 layer,
                 gid,
+                raw_gid,
                 max_gid,
-            } => write!(
-                f,
-                "Invalid tile gid {} in layer '{}'; max known gid is {}",
-                gid, layer, max_gid
-            ),
+                ..
+            } => {
+                write!(f, "Invalid tile gid {}", gid)?;
+                write_gid_context(f, *raw_gid)?;
+                write!(f, " in layer '{}'", layer)?;
+                write_gid_range(f, *gid, *max_gid)
+            }
             MapError::InvalidObjectGid {
                 layer,
                 object_id,
                 gid,
+                raw_gid,
                 max_gid,
+                ..
+            } => {
+                write!(f, "Invalid object tile gid {}", gid)?;
+                write_gid_context(f, *raw_gid)?;
+                write!(f, " in layer '{}' object id {}", layer, object_id)?;
+                write_gid_range(f, *gid, *max_gid)
+            }
+            MapError::OverlappingTilesets { a, b, range, .. } => write!(
+                f,
+                "Tilesets '{}' and '{}' both claim gids {}..={}",
+                a,
+                b,
+                range.start(),
+                range.end()
+            ),
+            MapError::TilesetGeometryMismatch {
+                tileset,
+                tilecount,
+                capacity,
+                ..
             } => write!(
                 f,
-                "Invalid object tile gid {} in layer '{}' object id {}; max known gid is {}",
-                gid, layer, object_id, max_gid
+                "Tileset '{tileset}' declares {tilecount} tiles but its image holds only {capacity}"
             ),
-            MapError::TextureLoad { path, message } => {
-                write!(f, "Failed to load texture {}: {}", path.display(), message)
+            MapError::InvalidGeometry { entity, field, .. } => {
+                write!(f, "Invalid geometry: {entity} has zero {field}")
             }
+            MapError::InvalidLayerSize {
+                layer,
+                expected,
+                actual,
+                ..
+            } => write!(f, "Layer '{layer}' has {actual} cells, expected {expected}"),
+            MapError::LayerDataElement {
+                layer,
+                index,
+                found,
+                ..
+            } => write!(
+                f,
+                "Layer '{layer}' has {found} at data element {index}, expected a gid"
+            ),
+            MapError::LayerNotFound(name) => write!(f, "layer not found: '{name}'"),
+            MapError::InvalidDrawOrder(reason) => write!(f, "Invalid layer draw order: {reason}"),
+            MapError::LoaderFinished => write!(f, "Map loader already finished"),
+            MapError::TileOutOfBounds { layer, x, y } => {
+                write!(f, "Tile ({x}, {y}) is outside layer '{layer}'")
+            }
+            MapError::Validation { report, .. } => write!(f, "Invalid map: {report}"),
+            MapError::TextureLoad {
+                path,
+                tileset,
+                gids,
+                source,
+            } => write!(
+                f,
+                "Failed to load texture {} for tileset '{}' (gids {}..={}): {}",
+                path.display(),
+                tileset,
+                gids.start(),
+                gids.end(),
+                source
+            ),
+            MapError::MissingImage { tileset, path } => write!(
+                f,
+                "Image {} of tileset '{}' is missing or unreadable",
+                path.display(),
+                tileset
+            ),
+        }?;
+        if let Some(path) = self.context_path() {
+            write!(f, " (in {})", path.display())?;
         }
+        Ok(())
+    }
+}
+
+// ` (raw 0x80000005, flipped H)` when the gid was written with flip flags.
+fn write_gid_context(f: &mut Formatter<'_>, raw_gid: u32) -> std::fmt::Result {
+    use crate::spatial::{FLIP_D, FLIP_H, FLIP_V};
+
+    let flags: String = [(FLIP_H, 'H'), (FLIP_V, 'V'), (FLIP_D, 'D')]
+        .into_iter()
+        .filter(|(bit, _)| raw_gid & bit != 0)
+        .map(|(_, name)| name)
+        .collect();
+    if flags.is_empty() {
+        return Ok(());
+    }
+    write!(f, " (raw {raw_gid:#010x}, flipped {flags})")
+}
+
+fn write_gid_range(f: &mut Formatter<'_>, gid: u32, max_gid: u32) -> std::fmt::Result {
+    if gid != 0 && gid <= max_gid {
+        write!(f, "; no tileset owns it within 1..={max_gid}")
+    } else {
+        write!(f, "; max known gid is {max_gid}")
     }
 }
 
@@ -110,7 +384,44 @@ impl Error for MapError {
         match self {
             MapError::Io { source, .. } => Some(source),
             MapError::Json { source, .. } => Some(source),
+            MapError::TextureLoad { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
 }
+
+/// Why a strict property getter such as
+/// [`Properties::get_bool_strict`](crate::Properties::get_bool_strict) failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyError {
+    /// No property with that name.
+    NotFound,
+    /// The property exists with another type.
+    WrongType {
+        /// Tiled type name of the stored value (`bool`, `int`, `float` or `string`).
+        found: String,
+        /// Tiled type name the getter expected.
+        expected: String,
+    },
+    /// An `int` property does not fit the requested integer type.
+    OutOfRange {
+        /// Stored value.
+        value: i64,
+    },
+}
+
+impl Display for PropertyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyError::NotFound => write!(f, "Property not found"),
+            PropertyError::WrongType { found, expected } => {
+                write!(f, "Property has type '{found}', expected '{expected}'")
+            }
+            PropertyError::OutOfRange { value } => {
+                write!(f, "Property value {value} is out of range")
+            }
+        }
+    }
+}
+
+impl Error for PropertyError {}
@@ -0,0 +1,211 @@
+//! Gid to tileset lookup.
+
+/// Highest gid still served from a dense table (128 KiB of slots).
+const DENSE_MAX_GID: u32 = 1 << 16;
+
+/// Which tileset owns each gid, kept as sorted, disjoint gid ranges.
+///
+/// Memory grows with the number of tilesets rather than with the highest gid, so maps
+/// whose tilesets start at large firstgids (Tiled keeps counting after tilesets are
+/// removed and re-added) stay cheap: their lookups are a binary search over the ranges.
+/// Up to [`DENSE_MAX_GID`] a dense table answers instead, since lookups sit on the
+/// per-tile draw path.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GidRanges {
+    // Sorted by `first`; never overlapping or empty.
+    ranges: Vec<GidRange>,
+    // Slot per gid up to the max gid (`u16::MAX` = none); empty once above the limit.
+    dense: Vec<u16>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct GidRange {
+    first: u32,
+    // Exclusive.
+    end: u32,
+    tileset: u16,
+}
+
+impl GidRanges {
+    /// Gives tileset slot `tileset` the gids `first_gid..first_gid + tilecount`. Where
+    /// they overlap earlier ranges, the later insert wins and the rest of the earlier
+    /// ranges is kept.
+    pub(crate) fn insert(&mut self, first_gid: u32, tilecount: u32, tileset: u16) {
+        let end = first_gid.saturating_add(tilecount);
+        if first_gid == end {
+            return;
+        }
+        let start = self.ranges.partition_point(|r| r.end <= first_gid);
+        let stop = self.ranges.partition_point(|r| r.first < end);
+        let mut replacement = Vec::with_capacity(3);
+        if let Some(head) = self.ranges[start..stop].first() {
+            if head.first < first_gid {
+                replacement.push(GidRange {
+                    end: first_gid,
+                    ..*head
+                });
+            }
+        }
+        replacement.push(GidRange {
+            first: first_gid,
+            end,
+            tileset,
+        });
+        if let Some(tail) = self.ranges[start..stop].last() {
+            if tail.end > end {
+                replacement.push(GidRange {
+                    first: end,
+                    ..*tail
+                });
+            }
+        }
+        self.ranges.splice(start..stop, replacement);
+
+        let max_gid = self.max_gid();
+        if max_gid <= DENSE_MAX_GID {
+            self.dense.resize(max_gid as usize + 1, u16::MAX);
+            self.dense[first_gid as usize..end as usize].fill(tileset);
+        } else {
+            self.dense = Vec::new();
+        }
+    }
+
+    /// Tileset slot owning the clean gid `gid`, if any.
+    #[inline]
+    pub(crate) fn get(&self, gid: u32) -> Option<u16> {
+        if !self.dense.is_empty() {
+            let slot = *self.dense.get(gid as usize)?;
+            return (slot != u16::MAX).then_some(slot);
+        }
+        let i = self.ranges.partition_point(|r| r.end <= gid);
+        let range = self.ranges.get(i)?;
+        (range.first <= gid).then_some(range.tileset)
+    }
+
+    /// The highest owned gid, or 0 when nothing is owned.
+    pub(crate) fn max_gid(&self) -> u32 {
+        self.ranges.last().map_or(0, |r| r.end - 1)
+    }
+
+    /// Every owned gid with its tileset slot, in ascending gid order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u32, u16)> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|r| (r.first..r.end).map(move |gid| (gid, r.tileset)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(tilesets: &[(u32, u32)]) -> GidRanges {
+        let mut out = GidRanges::default();
+        for (slot, &(first_gid, tilecount)) in tilesets.iter().enumerate() {
+            out.insert(first_gid, tilecount, slot as u16);
+        }
+        out
+    }
+
+    #[test]
+    fn lookups_respect_range_boundaries_gaps_and_the_last_tileset() {
+        // 1..=4, gap 5..=9, 10..=10, then a tileset far up as Tiled assigns after edits.
+        let gids = ranges(&[(1, 4), (10, 1), (1_000_001, 16)]);
+        let at = |gid| gids.get(gid);
+
+        assert_eq!(at(0), None);
+        assert_eq!((at(1), at(4)), (Some(0), Some(0)));
+        assert_eq!((at(5), at(9)), (None, None));
+        assert_eq!((at(10), at(11)), (Some(1), None));
+        assert_eq!(at(1_000_000), None);
+        assert_eq!((at(1_000_001), at(1_000_016)), (Some(2), Some(2)));
+        assert_eq!((at(1_000_017), at(u32::MAX)), (None, None));
+        assert_eq!(gids.max_gid(), 1_000_016);
+        assert_eq!(gids.iter().count(), 21);
+        assert_eq!(GidRanges::default().max_gid(), 0);
+    }
+
+    #[test]
+    fn later_tilesets_win_overlaps_without_hiding_the_rest() {
+        // Slot 1 sits inside slot 0; slot 2 straddles the tail slot 1 left behind.
+        let gids = ranges(&[(1, 100), (5, 4), (50, 60), (200, 0)]);
+        let owners: Vec<_> = [1, 4, 5, 8, 9, 49, 50, 100, 109, 110]
+            .map(|gid| gids.get(gid))
+            .into();
+        assert_eq!(
+            owners,
+            [
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(0),
+                Some(0),
+                Some(2),
+                Some(2),
+                Some(2),
+                None
+            ]
+        );
+        assert_eq!(gids.max_gid(), 109, "empty tilesets own nothing");
+
+        // Same answers as writing a dense table in insert order.
+        let mut dense = vec![None; 111];
+        for (slot, (first, count)) in [(1, 100), (5, 4), (50, 60)].into_iter().enumerate() {
+            dense[first..first + count].fill(Some(slot as u16));
+        }
+        let from_ranges: Vec<_> = (0..111).map(|gid| gids.get(gid)).collect();
+        assert_eq!(from_ranges, dense);
+
+        // Past the dense limit the binary search gives the same answers.
+        let shift = DENSE_MAX_GID;
+        let sparse = ranges(&[(1 + shift, 100), (5 + shift, 4), (50 + shift, 60)]);
+        assert!(sparse.dense.is_empty());
+        let from_search: Vec<_> = (0..111).map(|gid| sparse.get(gid + shift)).collect();
+        assert_eq!(from_search, dense);
+        assert_eq!(sparse.get(shift), None);
+    }
+
+    #[test]
+    #[ignore = "timing comparison; run with `cargo test -- --ignored --nocapture`"]
+    fn bench_range_lookup_vs_dense_lut() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        let tilesets = [(1, 256), (257, 64), (321, 1024)];
+        let mut lut = vec![u16::MAX; 1345];
+        for (slot, &(first, count)) in tilesets.iter().enumerate() {
+            lut[first as usize..(first + count) as usize].fill(slot as u16);
+        }
+        let probes: Vec<u32> = (0..1_000_000u32)
+            .map(|i| i.wrapping_mul(7919) % 1346)
+            .collect();
+
+        let start = Instant::now();
+        let mut from_lut = 0u64;
+        for &gid in &probes {
+            let slot = lut.get(black_box(gid) as usize).copied();
+            from_lut += slot.filter(|&s| s != u16::MAX).map_or(0, u64::from);
+        }
+        let baseline = start.elapsed();
+
+        // Same layout served densely, then shifted up so only the search can answer.
+        for shift in [0, 1_000_000] {
+            let shifted = tilesets.map(|(first, count)| (first + shift, count));
+            let gids = ranges(&shifted);
+            let start = Instant::now();
+            let mut found = 0u64;
+            for &gid in &probes {
+                found += gids.get(black_box(gid + shift)).map_or(0, u64::from);
+            }
+            let elapsed = start.elapsed();
+            assert_eq!(found, from_lut);
+            println!(
+                "3 tilesets from gid {}, {} lookups: plain LUT {baseline:?}, GidRanges {elapsed:?} ({:.2}x)",
+                shift + 1,
+                probes.len(),
+                elapsed.as_secs_f64() / baseline.as_secs_f64()
+            );
+        }
+    }
+}
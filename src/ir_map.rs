@@ -1,4 +1,6 @@
 // src/ir.rs
+use crate::error::PropertyError;
+use crate::loader::report::ValidationIssue;
 use macroquad::prelude::*;
 use std::collections::HashMap;
 
@@ -13,6 +15,15 @@ pub enum PropertyValue {
     F32(f32),
     /// String-like property (`string`, `file`, `color`, `class`).
     String(String),
+    /// Property of a type this crate does not know, kept when loading with
+    /// [`Strictness::Lenient`](crate::Strictness::Lenient) and its value is not a plain
+    /// bool, number or string.
+    Unknown {
+        /// Tiled type name as written in the file.
+        type_name: String,
+        /// The value as JSON text, e.g. `[4,2]`.
+        value: String,
+    },
 }
 
 /// Property map attached to map/layer/object/tileset/tile entities.
@@ -30,6 +41,11 @@ impl Properties {
         self.0.insert(key, value);
     }
 
+    /// Returns `true` if no properties are set.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Returns raw property value by key.
     pub fn get(&self, key: &str) -> Option<&PropertyValue> {
         self.0.get(key)
@@ -76,11 +92,126 @@ impl Properties {
     }
 }
 
+impl PropertyValue {
+    /// Tiled type name of the value: `bool`, `int`, `float`, `string`, or the original
+    /// name of an [`PropertyValue::Unknown`] type.
+    pub fn type_name(&self) -> &str {
+        match self {
+            PropertyValue::Bool(_) => "bool",
+            PropertyValue::I64(_) => "int",
+            PropertyValue::F32(_) => "float",
+            PropertyValue::String(_) => "string",
+            PropertyValue::Unknown { type_name, .. } => type_name,
+        }
+    }
+}
+
+impl Properties {
+    fn get_strict<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &str,
+        pick: impl FnOnce(&'a PropertyValue) -> Option<T>,
+    ) -> Result<T, PropertyError> {
+        let value = self.0.get(key).ok_or(PropertyError::NotFound)?;
+        pick(value).ok_or_else(|| PropertyError::WrongType {
+            found: value.type_name().to_string(),
+            expected: expected.to_string(),
+        })
+    }
+
+    /// Like [`Properties::get_bool`], telling a missing property apart from one of
+    /// another type.
+    pub fn get_bool_strict(&self, key: &str) -> Result<bool, PropertyError> {
+        self.get_strict(key, "bool", |v| match v {
+            PropertyValue::Bool(v) => Some(*v),
+            _ => None,
+        })
+    }
+
+    /// Strict [`Properties::get_i32`]; values outside `i32` are
+    /// [`PropertyError::OutOfRange`].
+    pub fn get_i32_strict(&self, key: &str) -> Result<i32, PropertyError> {
+        let value = self.get_i64_strict(key)?;
+        i32::try_from(value).map_err(|_| PropertyError::OutOfRange { value })
+    }
+
+    /// Strict [`Properties::get_i64`].
+    pub fn get_i64_strict(&self, key: &str) -> Result<i64, PropertyError> {
+        self.get_strict(key, "int", |v| match v {
+            PropertyValue::I64(v) => Some(*v),
+            _ => None,
+        })
+    }
+
+    /// Strict [`Properties::get_f32`].
+    pub fn get_f32_strict(&self, key: &str) -> Result<f32, PropertyError> {
+        self.get_strict(key, "float", |v| match v {
+            PropertyValue::F32(v) => Some(*v),
+            _ => None,
+        })
+    }
+
+    /// Strict [`Properties::get_string`].
+    pub fn get_string_strict(&self, key: &str) -> Result<&str, PropertyError> {
+        self.get_strict(key, "string", |v| match v {
+            PropertyValue::String(v) => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Tiled JSON `properties` array of `{"name", "type", "value"}` objects, sorted by
+    /// name so output is stable.
+    ///
+    /// Loading the array back gives the same properties. `color`, `file` and `class`
+    /// properties load as [`PropertyValue::String`], so they are written as `string`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut names: Vec<&String> = self.0.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let prop = &self.0[name];
+                let value = match prop {
+                    PropertyValue::Bool(v) => serde_json::json!(v),
+                    PropertyValue::I64(v) => serde_json::json!(v),
+                    PropertyValue::F32(v) => serde_json::json!(v),
+                    PropertyValue::String(v) => serde_json::json!(v),
+                    PropertyValue::Unknown { value, .. } => {
+                        serde_json::from_str(value).unwrap_or_else(|_| serde_json::json!(value))
+                    }
+                };
+                serde_json::json!({ "name": name, "type": prop.type_name(), "value": value })
+            })
+            .collect()
+    }
+}
+
+impl FromIterator<(String, PropertyValue)> for Properties {
+    fn from_iter<I: IntoIterator<Item = (String, PropertyValue)>>(iter: I) -> Self {
+        Properties(iter.into_iter().collect())
+    }
+}
+
+impl From<HashMap<String, PropertyValue>> for Properties {
+    fn from(map: HashMap<String, PropertyValue>) -> Self {
+        Properties(map)
+    }
+}
+
+impl From<Properties> for HashMap<String, PropertyValue> {
+    fn from(props: Properties) -> Self {
+        props.0
+    }
+}
+
 /// Parsed Tiled object shape kind.
 #[derive(Clone, Debug, PartialEq)]
 pub enum IrObjectShape {
     /// Axis-aligned rectangle object.
     Rectangle,
+    /// Ellipse inscribed in the object's `width x height` rect.
+    Ellipse,
     /// Point object.
     Point,
     /// Closed polygon in object-local coordinates.
@@ -94,6 +225,22 @@ pub enum IrObjectShape {
     },
 }
 
+impl IrObjectShape {
+    /// Returns `true` if the outline ends where it starts: everything except polylines
+    /// and points. Renderers should join the last vertex back to the first.
+    pub fn is_closed(&self) -> bool {
+        !matches!(self, IrObjectShape::Polyline(_) | IrObjectShape::Point)
+    }
+
+    /// Returns `true` if the shape encloses an area and can back a collision body.
+    ///
+    /// Same set as [`IrObjectShape::is_closed`]; the area of a polygon can still be zero
+    /// when its points are collinear.
+    pub fn is_area(&self) -> bool {
+        self.is_closed()
+    }
+}
+
 /// Parsed object record from an object layer (or tile objectgroup metadata).
 #[derive(Clone, Debug, PartialEq)]
 pub struct IrObject {
@@ -121,11 +268,90 @@ pub struct IrObject {
     pub properties: Properties,
 }
 
+impl IrObject {
+    /// Returns the center of the object in layer coordinates (pixels).
+    ///
+    /// Rectangles and ellipses use `(x + width/2, y + height/2)`. Tile objects are anchored at their
+    /// bottom-left corner in Tiled, so their center is `(x + width/2, y - height/2)`.
+    /// Points return `(x, y)`, and polygons/polylines return the average of their vertices.
+    pub fn center(&self) -> Vec2 {
+        let origin = vec2(self.x, self.y);
+        match &self.shape {
+            IrObjectShape::Rectangle | IrObjectShape::Ellipse => {
+                origin + vec2(self.width, self.height) / 2.0
+            }
+            IrObjectShape::Tile { .. } => origin + vec2(self.width, -self.height) / 2.0,
+            IrObjectShape::Point => origin,
+            IrObjectShape::Polygon(points) | IrObjectShape::Polyline(points) => {
+                if points.is_empty() {
+                    return origin;
+                }
+                let sum: Vec2 = points.iter().copied().sum();
+                origin + sum / points.len() as f32
+            }
+        }
+    }
+
+    /// Tests whether `p` (layer coordinates, pixels) lies on the object's shape.
+    ///
+    /// Rotation is applied around `(x, y)` like Tiled does. Tile objects use their drawn
+    /// quad, anchored at the bottom-left corner. Points and polylines have no area, so
+    /// they match within `pick_radius` pixels.
+    pub fn contains_point(&self, p: Vec2, pick_radius: f32) -> bool {
+        // Undo the object rotation so every shape can be tested in its local frame.
+        let local = Vec2::from_angle(-self.rotation.to_radians()).rotate(p - vec2(self.x, self.y));
+        let in_box = |min: Vec2, max: Vec2| {
+            local.x >= min.x.min(max.x)
+                && local.x <= min.x.max(max.x)
+                && local.y >= min.y.min(max.y)
+                && local.y <= min.y.max(max.y)
+        };
+        match &self.shape {
+            IrObjectShape::Rectangle => in_box(Vec2::ZERO, vec2(self.width, self.height)),
+            IrObjectShape::Tile { .. } => in_box(vec2(0.0, -self.height), vec2(self.width, 0.0)),
+            IrObjectShape::Ellipse => {
+                let radii = vec2(self.width, self.height).abs() / 2.0;
+                if radii.x == 0.0 || radii.y == 0.0 {
+                    return false;
+                }
+                let d = (local - vec2(self.width, self.height) / 2.0) / radii;
+                d.length_squared() <= 1.0
+            }
+            IrObjectShape::Point => local.length() <= pick_radius,
+            IrObjectShape::Polygon(points) => {
+                // Even-odd rule, so concave outlines work.
+                let mut inside = false;
+                for (i, a) in points.iter().enumerate() {
+                    let b = points[(i + 1) % points.len()];
+                    if (a.y > local.y) != (b.y > local.y)
+                        && local.x < a.x + (local.y - a.y) / (b.y - a.y) * (b.x - a.x)
+                    {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            IrObjectShape::Polyline(points) => points.windows(2).any(|seg| {
+                let (a, b) = (seg[0], seg[1]);
+                let ab = b - a;
+                let t = if ab == Vec2::ZERO {
+                    0.0
+                } else {
+                    ((local - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+                };
+                local.distance(a + ab * t) <= pick_radius
+            }),
+        }
+    }
+}
+
 /// Per-tile metadata parsed from a tileset.
 #[derive(Clone, Debug, PartialEq)]
 pub struct IrTileMetadata {
     /// Local tile id within the tileset.
     pub id: u32,
+    /// Tile class (`class`, or legacy `type`); empty if unset.
+    pub class_name: String,
     /// Tile-level properties.
     pub properties: Properties,
     /// Tile-local objectgroup objects (if present).
@@ -144,16 +370,24 @@ pub struct IrMap {
     pub tilesets: Vec<IrTileset>,
     /// Parsed layers in draw order (Tiled array order).
     pub layers: Vec<IrLayer>,
+    /// Problems the decoder tolerated (see [`Strictness`](crate::Strictness)).
+    pub warnings: Vec<ValidationIssue>,
 }
 
 /// Parsed tileset representation.
 pub enum IrTileset {
     /// One image atlas with a regular grid.
     Atlas {
+        /// Tileset name from Tiled (may be empty).
+        name: String,
         /// First global tile id assigned to this tileset.
         first_gid: u32,
+        /// External tileset file as referenced by the map, relative to the map directory.
+        source: String,
         /// Tileset image path.
         image: String,
+        /// Image size in pixels, when the tileset file states it.
+        image_size: Option<(u32, u32)>,
         /// Tile width (pixels).
         tile_w: u32,
         /// Tile height (pixels).
@@ -174,6 +408,39 @@ pub enum IrTileset {
     // (later) ImagePerTile { first_gid, tiles: Vec<IrTileImage> },
 }
 
+impl IrTileset {
+    /// First global tile id assigned to this tileset.
+    pub fn first_gid(&self) -> u32 {
+        match self {
+            IrTileset::Atlas { first_gid, .. } => *first_gid,
+        }
+    }
+
+    /// Number of tiles in the tileset.
+    pub fn tilecount(&self) -> u32 {
+        match self {
+            IrTileset::Atlas { tilecount, .. } => *tilecount,
+        }
+    }
+
+    /// Last global tile id of this tileset, or `None` if it has no tiles or its range
+    /// runs past `u32::MAX`.
+    pub fn last_gid(&self) -> Option<u32> {
+        let last = self.tilecount().checked_sub(1)?;
+        self.first_gid().checked_add(last)
+    }
+}
+
+/// Object layer draw order (Tiled `draworder`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ObjectDrawOrder {
+    /// Objects are drawn sorted by y, so lower objects overlap higher ones (Tiled default).
+    #[default]
+    TopDown,
+    /// Objects are drawn in file order.
+    Index,
+}
+
 /// Parsed layer payload kind.
 pub enum IrLayerKind {
     /// Finite tile layer.
@@ -189,6 +456,8 @@ pub enum IrLayerKind {
     Objects {
         /// Parsed objects.
         objects: Vec<IrObject>,
+        /// Order tile objects are drawn in.
+        draw_order: ObjectDrawOrder,
     },
     /// Unsupported layer kind, preserved as skipped.
     Unsupported,
@@ -203,6 +472,8 @@ pub struct IrLayer {
     pub visible: bool,
     /// Layer opacity (`0.0..=1.0`).
     pub opacity: f32,
+    /// Tint multiplied into every tile (`tintcolor`), `WHITE` when unset.
+    pub tint: Color,
     /// Layer world offset (pixels).
     pub offset: Vec2,
     /// Layer properties.
@@ -210,3 +481,120 @@ pub struct IrLayer {
     /// Layer payload.
     pub kind: IrLayerKind,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_getters_separate_missing_from_mistyped() {
+        let props: Properties = [
+            ("solid".to_string(), PropertyValue::I64(1)),
+            ("big".to_string(), PropertyValue::I64(i64::MAX)),
+            (
+                "name".to_string(),
+                PropertyValue::String("door".to_string()),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            props.get_bool_strict("solid"),
+            Err(PropertyError::WrongType {
+                found: "int".to_string(),
+                expected: "bool".to_string(),
+            })
+        );
+        assert_eq!(props.get_bool("solid"), None);
+        assert_eq!(
+            props.get_bool_strict("missing"),
+            Err(PropertyError::NotFound)
+        );
+        assert_eq!(props.get_i32_strict("solid"), Ok(1));
+        assert_eq!(
+            props.get_i32_strict("big"),
+            Err(PropertyError::OutOfRange { value: i64::MAX })
+        );
+        assert_eq!(props.get_string_strict("name"), Ok("door"));
+        assert!(matches!(
+            props.get_f32_strict("name"),
+            Err(PropertyError::WrongType { .. })
+        ));
+    }
+
+    fn object(shape: IrObjectShape) -> IrObject {
+        IrObject {
+            id: 1,
+            name: String::new(),
+            class_name: String::new(),
+            x: 10.0,
+            y: 20.0,
+            width: 16.0,
+            height: 8.0,
+            rotation: 0.0,
+            visible: true,
+            shape,
+            properties: Properties::default(),
+        }
+    }
+
+    #[test]
+    fn center_matches_each_shape_geometry() {
+        assert_eq!(object(IrObjectShape::Rectangle).center(), vec2(18.0, 24.0));
+        assert_eq!(
+            object(IrObjectShape::Tile { gid: 1 }).center(),
+            vec2(18.0, 16.0)
+        );
+        assert_eq!(object(IrObjectShape::Point).center(), vec2(10.0, 20.0));
+
+        let triangle = vec![vec2(0.0, 0.0), vec2(6.0, 0.0), vec2(0.0, 6.0)];
+        assert_eq!(
+            object(IrObjectShape::Polygon(triangle)).center(),
+            vec2(12.0, 22.0)
+        );
+        assert_eq!(
+            object(IrObjectShape::Polyline(vec![
+                vec2(0.0, 0.0),
+                vec2(4.0, -4.0)
+            ]))
+            .center(),
+            vec2(12.0, 18.0)
+        );
+        assert_eq!(
+            object(IrObjectShape::Polygon(vec![])).center(),
+            vec2(10.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn properties_round_trip_through_iterators_and_hash_maps() {
+        let props = Properties::from_iter([
+            ("x".to_string(), PropertyValue::I64(5)),
+            ("solid".to_string(), PropertyValue::Bool(true)),
+        ]);
+        assert_eq!(props.get_i64("x"), Some(5));
+        assert_eq!(props.get_bool("solid"), Some(true));
+
+        let map: HashMap<String, PropertyValue> = props.clone().into();
+        assert_eq!(map.len(), 2);
+        assert_eq!(Properties::from(map.clone()), props);
+        assert_eq!(map.into_iter().collect::<Properties>(), props);
+    }
+
+    #[test]
+    fn only_polylines_and_points_are_open_shapes() {
+        let line = vec![vec2(0.0, 0.0), vec2(4.0, 0.0)];
+        let shapes = [
+            (IrObjectShape::Rectangle, true),
+            (IrObjectShape::Ellipse, true),
+            (IrObjectShape::Point, false),
+            (IrObjectShape::Polygon(line.clone()), true),
+            (IrObjectShape::Polyline(line), false),
+            (IrObjectShape::Tile { gid: 1 }, true),
+        ];
+        for (shape, closed) in shapes {
+            assert_eq!(shape.is_closed(), closed, "{shape:?}");
+            assert_eq!(shape.is_area(), closed, "{shape:?}");
+        }
+    }
+}
@@ -2,18 +2,41 @@
 
 //! Minimal Tiled JSON loader/renderer for Macroquad.
 
+mod autotile;
+mod command;
 mod error;
+mod gid_ranges;
 #[allow(dead_code)]
 mod ir_map;
 mod loader {
     pub mod json_loader;
+    pub mod json_writer;
+    pub mod layer_data;
+    pub mod options;
+    pub mod report;
 }
 mod map;
+mod nav;
 #[allow(dead_code)]
 mod render;
 #[allow(dead_code)]
 mod spatial;
+mod triggers;
 
-pub use error::MapError;
-pub use ir_map::{IrObject, IrObjectShape, Properties, PropertyValue};
-pub use map::{LayerId, Map, ObjectLayer};
+pub use autotile::{AutoTiler, AUTOTILE_E, AUTOTILE_N, AUTOTILE_S, AUTOTILE_W};
+pub use command::{DrawCommand, DrawParams, TileRegion};
+pub use error::{MapError, PropertyError};
+pub use ir_map::{IrObject, IrObjectShape, ObjectDrawOrder, Properties, PropertyValue};
+pub use loader::json_loader::validate_map_file;
+pub use loader::options::{LoadOptions, LoadPhase, MapLoadConfig, Strictness};
+pub use loader::report::{IssueCode, IssueLocation, Severity, ValidationIssue, ValidationReport};
+pub use map::{
+    ChunkRenderMode, InjectedSprite, LayerId, Map, MapLoader, ObjectDrawOverride, ObjectLayer,
+    ObjectRef, TileLayerRef, TileMatch, TilesetInfo,
+};
+pub use nav::NavOptions;
+pub use spatial::{
+    ChunkCoord, GlobalChunk, LayerBucket, LayerIdx, ObjectHandle, ObjectRec, TileHandle, TileId,
+    TileRec, CHUNK_SIZE,
+};
+pub use triggers::{TriggerEvent, TriggerEvents, TriggerProbe, Triggers};
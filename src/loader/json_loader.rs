@@ -1,15 +1,26 @@
 // src/loader/json.rs
 use crate::error::MapError;
 use crate::ir_map::*;
+use crate::loader::layer_data::{decode_layer_data, JsonLayerData};
+use crate::loader::options::{LoadOptions, LoadPhase, Strictness};
+use crate::loader::report::{
+    IssueCode, IssueLocation, Issues, Severity, ValidationIssue, ValidationReport,
+};
 use macroquad::prelude::*;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Deserialize)]
 struct JsonLayer {
     #[serde(default)]
-    data: Vec<u32>,
+    data: JsonLayerData,
+    #[serde(default)]
+    encoding: String,
+    #[serde(default)]
+    compression: String,
     #[serde(default)]
     width: usize,
     #[serde(default)]
@@ -19,6 +30,8 @@ struct JsonLayer {
     #[serde(default = "one")]
     opacity: f32,
     #[serde(default)]
+    tintcolor: Option<String>,
+    #[serde(default)]
     offsetx: f32,
     #[serde(default)]
     offsety: f32,
@@ -30,6 +43,8 @@ struct JsonLayer {
     properties: Vec<JsonProperty>,
     #[serde(default)]
     objects: Vec<JsonObject>,
+    #[serde(default)]
+    draworder: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -47,6 +62,10 @@ struct JsonTilesetRef {
 
 #[derive(Deserialize)]
 struct JsonMap {
+    #[serde(default)]
+    width: Option<u32>,
+    #[serde(default)]
+    height: Option<u32>,
     tilewidth: u32,
     tileheight: u32,
     layers: Vec<JsonLayer>,
@@ -57,15 +76,21 @@ struct JsonMap {
 
 #[derive(Deserialize)]
 struct ExternalTileset {
+    #[serde(default)]
+    name: Option<String>,
     tilewidth: u32,
     tileheight: u32,
     tilecount: u32,
     columns: u32,
     image: String,
     #[serde(default)]
-    spacing: u32,
+    imagewidth: Option<u32>,
     #[serde(default)]
-    margin: u32,
+    imageheight: Option<u32>,
+    #[serde(default)]
+    spacing: Option<u32>,
+    #[serde(default)]
+    margin: Option<u32>,
     #[serde(default)]
     properties: Vec<JsonProperty>,
     #[serde(default)]
@@ -105,6 +130,8 @@ struct JsonObject {
     #[serde(default)]
     point: bool,
     #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
     polygon: Vec<JsonObjectPoint>,
     #[serde(default)]
     polyline: Vec<JsonObjectPoint>,
@@ -129,59 +156,128 @@ struct JsonObjectGroup {
 #[derive(Deserialize)]
 struct JsonTile {
     id: u32,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
     #[serde(default)]
     properties: Vec<JsonProperty>,
     #[serde(default)]
     objectgroup: JsonObjectGroup,
 }
 
-fn json_property_to_ir(prop: JsonProperty) -> Result<Option<(String, PropertyValue)>, MapError> {
+// Unknown property types are kept as strings (the value's JSON text unless it already
+// is a string) when lenient. Numbers and bools written as strings are coerced, and values
+// that fit their type in neither form are dropped, both with a warning.
+fn json_property_to_ir(
+    prop: JsonProperty,
+    at: impl FnOnce() -> IssueLocation,
+    issues: &mut Issues,
+) -> Result<Option<(String, PropertyValue)>, MapError> {
     let JsonProperty { name, kind, value } = prop;
 
     let parsed = match kind.as_deref() {
-        Some("bool") => value.as_bool().map(PropertyValue::Bool),
-        Some("int") | Some("object") => value.as_i64().map(PropertyValue::I64),
-        Some("float") => value.as_f64().map(|n| PropertyValue::F32(n as f32)),
-        Some("string") | Some("file") | Some("color") | Some("class") => {
-            value.as_str().map(|s| PropertyValue::String(s.to_owned()))
+        Some(kind @ ("bool" | "int" | "object" | "float")) => {
+            let direct = match kind {
+                "bool" => value.as_bool().map(PropertyValue::Bool),
+                "float" => value.as_f64().map(|n| PropertyValue::F32(n as f32)),
+                _ => value.as_i64().map(PropertyValue::I64),
+            };
+            if direct.is_some() {
+                direct
+            } else {
+                let coerced = value.as_str().and_then(|text| {
+                    let text = text.trim();
+                    match kind {
+                        "bool" => text.parse().ok().map(PropertyValue::Bool),
+                        "float" => text.parse().ok().map(PropertyValue::F32),
+                        _ => text.parse().ok().map(PropertyValue::I64),
+                    }
+                });
+                let (code, message) = match coerced {
+                    Some(_) => (
+                        IssueCode::PropertyCoerced,
+                        format!("property '{name}': string {value} read as {kind}"),
+                    ),
+                    None => (
+                        IssueCode::PropertyDropped,
+                        format!("property '{name}': {value} is not a valid {kind}"),
+                    ),
+                };
+                issues.warn(code, at(), message);
+                coerced
+            }
+        }
+        Some(kind @ ("string" | "file" | "color" | "class")) => {
+            let text = value.as_str().map(|s| PropertyValue::String(s.to_owned()));
+            if text.is_none() {
+                let message = format!("property '{name}': {value} is not a valid {kind}");
+                issues.warn(IssueCode::PropertyDropped, at(), message);
+            }
+            text
         }
         Some(other) => {
-            return Err(MapError::UnsupportedPropertyType {
-                name,
+            let err = MapError::UnsupportedPropertyType {
+                path: None,
+                name: name.clone(),
                 kind: other.to_owned(),
-            });
-        }
-        None => {
-            if let Some(v) = value.as_bool() {
-                Some(PropertyValue::Bool(v))
-            } else if let Some(v) = value.as_i64() {
-                Some(PropertyValue::I64(v))
-            } else if let Some(v) = value.as_f64() {
-                Some(PropertyValue::F32(v as f32))
-            } else {
-                value.as_str().map(|s| PropertyValue::String(s.to_owned()))
+            };
+            let code = IssueCode::UnsupportedPropertyType;
+            if !issues.tolerate(Strictness::Lenient, code, at, err)? {
+                return Ok(None);
             }
+            Some(
+                infer_property_value(&value).unwrap_or_else(|| PropertyValue::Unknown {
+                    type_name: other.to_owned(),
+                    value: value.to_string(),
+                }),
+            )
         }
+        None => infer_property_value(&value),
     };
 
     Ok(parsed.map(|value| (name, value)))
 }
 
-fn properties_from_json(props: Vec<JsonProperty>) -> Result<Properties, MapError> {
+/// Value of an untyped property, read from the JSON value alone.
+fn infer_property_value(value: &JsonValue) -> Option<PropertyValue> {
+    if let Some(v) = value.as_bool() {
+        Some(PropertyValue::Bool(v))
+    } else if let Some(v) = value.as_i64() {
+        Some(PropertyValue::I64(v))
+    } else if let Some(v) = value.as_f64() {
+        Some(PropertyValue::F32(v as f32))
+    } else {
+        value.as_str().map(|s| PropertyValue::String(s.to_owned()))
+    }
+}
+
+// Properties with unsupported types are reported at `at` and skipped when collecting.
+fn properties_from_json(
+    props: Vec<JsonProperty>,
+    at: impl Fn() -> IssueLocation,
+    issues: &mut Issues,
+) -> Result<Properties, MapError> {
     let mut out = Properties::new();
     for p in props {
-        if let Some((name, value)) = json_property_to_ir(p)? {
+        if let Some((name, value)) = json_property_to_ir(p, &at, issues)? {
             out.insert(name, value);
         }
     }
     Ok(out)
 }
 
-fn object_to_ir(obj: JsonObject) -> Result<IrObject, MapError> {
+fn object_to_ir(
+    obj: JsonObject,
+    at: impl Fn() -> IssueLocation,
+    issues: &mut Issues,
+) -> Result<IrObject, MapError> {
     let shape = if let Some(gid) = obj.gid {
         IrObjectShape::Tile { gid }
     } else if obj.point {
         IrObjectShape::Point
+    } else if obj.ellipse {
+        IrObjectShape::Ellipse
     } else if !obj.polygon.is_empty() {
         IrObjectShape::Polygon(obj.polygon.into_iter().map(|p| vec2(p.x, p.y)).collect())
     } else if !obj.polyline.is_empty() {
@@ -207,164 +303,685 @@ fn object_to_ir(obj: JsonObject) -> Result<IrObject, MapError> {
         rotation: obj.rotation,
         visible: obj.visible,
         shape,
-        properties: properties_from_json(obj.properties)?,
+        properties: properties_from_json(obj.properties, at, issues)?,
     })
 }
 
+/// Returns `true` if the flip-masked `raw_gid` belongs to one of `tilesets` (sorted by
+/// `first_gid`). Gid 0 is valid for tile cells, where it means empty, but not for objects.
+fn gid_is_valid(raw_gid: u32, tilesets: &[IrTileset], for_object: bool) -> bool {
+    let gid = raw_gid & crate::spatial::GID_MASK;
+    if gid == 0 {
+        return !for_object;
+    }
+    let candidates = tilesets.partition_point(|t| t.first_gid() <= gid);
+    // Overlapping tilesets (tolerated when lenient) can own gids past a later one's range.
+    tilesets[..candidates]
+        .iter()
+        .rev()
+        .any(|t| t.last_gid().is_some_and(|last| gid <= last))
+}
+
+/// Validates a gid from tile layer `layer`, or from its object `object_id`, reporting the
+/// gid as written alongside the masked one.
+fn check_gid(
+    raw_gid: u32,
+    tilesets: &[IrTileset],
+    max_gid: u32,
+    layer: &str,
+    object_id: Option<u32>,
+) -> Result<(), MapError> {
+    if gid_is_valid(raw_gid, tilesets, object_id.is_some()) {
+        return Ok(());
+    }
+    let (layer, gid) = (layer.to_string(), raw_gid & crate::spatial::GID_MASK);
+    Err(match object_id {
+        None => MapError::InvalidTileGid {
+            path: None,
+            layer,
+            gid,
+            raw_gid,
+            max_gid,
+        },
+        Some(object_id) => MapError::InvalidObjectGid {
+            path: None,
+            layer,
+            object_id,
+            gid,
+            raw_gid,
+            max_gid,
+        },
+    })
+}
+
+/// Checks that a `tilecount`-tile atlas laid out `columns` wide fits in an image of
+/// `image_size` pixels, counting tiles the way Tiled does from margin and spacing.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn check_tileset_geometry(
+    tileset: &str,
+    image_size: (u32, u32),
+    tile_w: u32,
+    tile_h: u32,
+    tilecount: u32,
+    columns: u32,
+    spacing: u32,
+    margin: u32,
+) -> Result<(), MapError> {
+    // Widened so a crafted `spacing` cannot overflow; the quotient never exceeds `image`.
+    let fit = |image: u32, tile: u32| {
+        let inner = u64::from(image.saturating_sub(margin.saturating_mul(2)));
+        ((inner + u64::from(spacing)) / (u64::from(tile) + u64::from(spacing))) as u32
+    };
+    let (cols_fit, rows_fit) = (fit(image_size.0, tile_w), fit(image_size.1, tile_h));
+    // Tiles are placed `columns` per row, so a layout wider than the image only keeps the
+    // part of the first row that fits.
+    let placed = columns.saturating_mul(rows_fit);
+    let capacity = if columns <= cols_fit {
+        placed
+    } else {
+        cols_fit.min(placed)
+    };
+    if tilecount > capacity {
+        return Err(MapError::TilesetGeometryMismatch {
+            path: None,
+            tileset: tileset.to_string(),
+            tilecount,
+            capacity,
+        });
+    }
+    Ok(())
+}
+
+fn require_nonzero(entity: &str, field: &str, value: u32) -> Result<(), MapError> {
+    if value == 0 {
+        return Err(MapError::InvalidGeometry {
+            path: None,
+            entity: entity.to_string(),
+            field: field.to_string(),
+        });
+    }
+    Ok(())
+}
+
 pub fn decode_map_file_to_ir(path: &str) -> Result<(IrMap, PathBuf), MapError> {
+    decode_map_file_to_ir_with(path, &LoadOptions::default())
+}
+
+/// Decodes a map like [`decode_map_file_to_ir`], dropping whatever `options` filters out.
+pub fn decode_map_file_to_ir_with(
+    path: &str,
+    options: &LoadOptions,
+) -> Result<(IrMap, PathBuf), MapError> {
+    options.report(LoadPhase::Parse, 0.0);
     let p = Path::new(path);
+    check_map_extension(p)?;
+
+    let txt = read_file(p)?;
+    let map_dir = map_dir_of(p);
+    let ir = decode_map_str_to_ir_with(&txt, p, &map_dir, options)?;
+    Ok((ir, map_dir))
+}
+
+/// Checks the map file at `path` in one pass, collecting every recoverable problem
+/// instead of stopping at the first (see [`LoadOptions::collect_all_errors`]).
+///
+/// Fatal problems, such as map JSON that does not parse, end the pass early and are
+/// reported as the last issue. Tileset images must exist (see
+/// [`LoadOptions::check_images`]) but are not decoded.
+pub fn validate_map_file(path: &str) -> ValidationReport {
+    let options = LoadOptions::default()
+        .with_all_errors_collected()
+        .with_image_check();
+    match decode_map_file_to_ir_with(path, &options) {
+        Ok((ir, _)) => ValidationReport {
+            issues: ir.warnings,
+        },
+        Err(MapError::Validation { report, .. }) => report,
+        Err(err) => ValidationReport {
+            issues: vec![ValidationIssue {
+                severity: Severity::Error,
+                code: IssueCode::of(&err),
+                location: IssueLocation::Map,
+                message: err.to_string(),
+            }],
+        },
+    }
+}
+
+/// Decodes a map like [`decode_map_file_to_ir_with`], reading the map and its external
+/// tilesets through `read` instead of `std::fs` (e.g. macroquad's `load_string` on wasm).
+///
+/// `read` gets the full path of each file and should report failures as
+/// [`MapError::Io`] carrying that path.
+pub async fn decode_map_file_to_ir_async<F>(
+    path: &str,
+    options: &LoadOptions,
+    mut read: F,
+) -> Result<(IrMap, PathBuf), MapError>
+where
+    F: AsyncFnMut(&Path) -> Result<String, MapError>,
+{
+    options.report(LoadPhase::Parse, 0.0);
+    let p = Path::new(path);
+    check_map_extension(p)?;
+
+    let txt = read(p).await?;
+    let map_dir = map_dir_of(p);
+    let j = parse_map_json(&txt, p, options)?;
+    // Fetch external tilesets up front; the decoder itself is synchronous.
+    let mut fetched = HashMap::new();
+    for ts in j.tilesets.iter().filter(|ts| ts.source.ends_with(".json")) {
+        let ts_path = map_dir.join(&ts.source);
+        if let Entry::Vacant(slot) = fetched.entry(ts_path.clone()) {
+            slot.insert(read(&ts_path).await?);
+        }
+    }
+    let ir = map_json_to_ir(j, &map_dir, options, |ts_path| {
+        // Every `.json` source was fetched above.
+        Ok(fetched.get(ts_path).cloned().unwrap_or_default())
+    })
+    .map_err(|e| e.in_file(p))?;
+    Ok((ir, map_dir))
+}
+
+fn check_map_extension(p: &Path) -> Result<(), MapError> {
     if p.extension().and_then(|e| e.to_str()) != Some("json") {
-        return Err(MapError::InvalidMap(format!(
-            "Map file must be a JSON file: {path}"
-        )));
+        return Err(MapError::invalid("Map file must be a JSON file").in_file(p));
     }
+    Ok(())
+}
 
-    let txt = std::fs::read_to_string(p).map_err(|source| MapError::Io {
-        path: p.to_path_buf(),
-        source,
-    })?;
-    let j: JsonMap = serde_json::from_str(&txt).map_err(|source| MapError::Json {
+fn map_dir_of(p: &Path) -> PathBuf {
+    p.parent()
+        .map(|d| d.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("./"))
+}
+
+fn read_file(p: &Path) -> Result<String, MapError> {
+    std::fs::read_to_string(p).map_err(|source| MapError::Io {
         path: p.to_path_buf(),
         source,
+    })
+}
+
+/// Decodes map JSON already in memory. External tilesets resolve against `map_dir`;
+/// `source` only labels JSON errors.
+pub fn decode_map_str_to_ir_with(
+    txt: &str,
+    source: &Path,
+    map_dir: &Path,
+    options: &LoadOptions,
+) -> Result<IrMap, MapError> {
+    let j = parse_map_json(txt, source, options)?;
+    map_json_to_ir(j, map_dir, options, read_file).map_err(|e| e.in_file(source))
+}
+
+fn parse_map_json(txt: &str, source: &Path, options: &LoadOptions) -> Result<JsonMap, MapError> {
+    let j: JsonMap = serde_json::from_str(txt).map_err(|e| MapError::Json {
+        path: source.to_path_buf(),
+        source: e,
     })?;
+    options.report(LoadPhase::Parse, 1.0);
+    Ok(j)
+}
 
-    let map_dir = p
-        .parent()
-        .map(|d| d.to_path_buf())
-        .unwrap_or_else(|| PathBuf::from("./"));
+// `read_tileset` returns the text of an external tileset given its full path.
+//
+// Recoverable problems go through `issues`: with `collect_all_errors` the broken item is
+// skipped and decoding continues, so one pass reports everything.
+fn map_json_to_ir(
+    j: JsonMap,
+    map_dir: &Path,
+    options: &LoadOptions,
+    mut read_tileset: impl FnMut(&Path) -> Result<String, MapError>,
+) -> Result<IrMap, MapError> {
+    let mut issues = Issues::new(options);
+    let header = require_nonzero("map", "tilewidth", j.tilewidth)
+        .and(require_nonzero("map", "tileheight", j.tileheight))
+        .and(require_nonzero("map", "width", j.width.unwrap_or(1)))
+        .and(require_nonzero("map", "height", j.height.unwrap_or(1)));
+    issues.recover(|| IssueLocation::Map, header)?;
 
     // Build IR tilesets
     let mut ir_tilesets = Vec::with_capacity(j.tilesets.len());
-    for ts in &j.tilesets {
-        if !ts.source.ends_with(".json") {
-            return Err(MapError::InvalidMap(format!(
-                "External tileset must be JSON: {}",
-                ts.source
-            )));
+    let tileset_count = j.tilesets.len();
+    if tileset_count == 0 {
+        options.report(LoadPhase::Tilesets, 1.0);
+    }
+    for (ts_idx, ts) in j.tilesets.iter().enumerate() {
+        let at = || IssueLocation::Tileset(ts.source.clone());
+        let tileset = decode_tileset(ts, map_dir, options, &mut read_tileset, &mut issues)
+            .map_err(|e| e.in_file(&map_dir.join(&ts.source)));
+        if let Some(tileset) = issues.recover(at, tileset)? {
+            ir_tilesets.push(tileset);
         }
-        let ts_path = map_dir.join(&ts.source);
-        let ext_txt = std::fs::read_to_string(&ts_path).map_err(|source| MapError::Io {
-            path: ts_path.clone(),
-            source,
-        })?;
-        let ext: ExternalTileset =
-            serde_json::from_str(&ext_txt).map_err(|source| MapError::Json {
-                path: ts_path,
-                source,
-            })?;
-
-        // (We keep image path relative; Map::from_ir will join with map_dir)
-        ir_tilesets.push(IrTileset::Atlas {
-            first_gid: ts.firstgid,
-            image: ext.image,
-            tile_w: ext.tilewidth,
-            tile_h: ext.tileheight,
-            tilecount: ext.tilecount,
-            columns: ext.columns,
-            spacing: ext.spacing,
-            margin: ext.margin,
-            properties: properties_from_json(ext.properties)?,
-            tiles: ext
-                .tiles
-                .into_iter()
-                .map(|tile| -> Result<IrTileMetadata, MapError> {
-                    Ok(IrTileMetadata {
-                        id: tile.id,
-                        properties: properties_from_json(tile.properties)?,
-                        objects: tile
-                            .objectgroup
-                            .objects
-                            .into_iter()
-                            .map(object_to_ir)
-                            .collect::<Result<Vec<_>, _>>()?,
-                    })
-                })
-                .collect::<Result<Vec<_>, _>>()?,
-        });
+        options.report(
+            LoadPhase::Tilesets,
+            (ts_idx + 1) as f32 / tileset_count as f32,
+        );
     }
 
     // Sort by first_gid to make LUT building trivial
-    ir_tilesets.sort_by_key(|t| match t {
-        IrTileset::Atlas { first_gid, .. } => *first_gid,
-    });
+    ir_tilesets.sort_by_key(IrTileset::first_gid);
+    // Each tileset is checked against the one reaching furthest so far, which also
+    // catches a wide range covering several later tilesets.
+    let mut furthest: Option<(u32, &str)> = None;
+    for ts in &ir_tilesets {
+        let IrTileset::Atlas { source: b, .. } = ts;
+        let next_gid = ts.first_gid();
+        let b_end = next_gid.saturating_add(ts.tilecount());
+        if let Some((end, a)) = furthest {
+            let overlap_end = end.min(b_end);
+            if overlap_end > next_gid {
+                let err = MapError::OverlappingTilesets {
+                    path: None,
+                    a: a.to_string(),
+                    b: b.clone(),
+                    range: next_gid..=(overlap_end - 1),
+                };
+                let at = || IssueLocation::Tileset(b.clone());
+                issues.tolerate(Strictness::Lenient, IssueCode::OverlappingTilesets, at, err)?;
+            }
+        }
+        if furthest.is_none_or(|(end, _)| b_end > end) {
+            furthest = Some((b_end, b));
+        }
+    }
+
+    if options.check_images {
+        issues.recover_all(missing_images(&ir_tilesets, map_dir))?;
+    }
 
     let max_gid = ir_tilesets
         .iter()
-        .map(|t| match t {
-            IrTileset::Atlas {
-                first_gid,
-                tilecount,
-                ..
-            } => first_gid + tilecount - 1,
-        })
+        .filter_map(IrTileset::last_gid)
         .max()
         .unwrap_or(0);
 
     // Build IR layers
     let mut ir_layers = Vec::with_capacity(j.layers.len());
-    for l in j.layers {
-        let layer_name = l.name.clone();
-        let properties = properties_from_json(l.properties)?;
-        let layer_kind = match l.kind.as_deref().unwrap_or("tilelayer") {
-            "tilelayer" => {
-                for &raw_gid in &l.data {
-                    let gid = raw_gid & crate::spatial::GID_MASK;
-                    if gid != 0 && gid > max_gid {
-                        return Err(MapError::InvalidTileGid {
-                            layer: layer_name.clone(),
-                            gid,
-                            max_gid,
-                        });
-                    }
-                }
-                IrLayerKind::Tiles {
-                    width: l.width,
-                    height: l.height,
-                    data: l.data,
-                }
+    let mut layers_filtered = false;
+    for mut l in j.layers {
+        let kind = l.kind.take();
+        let kind = kind.as_deref().unwrap_or("tilelayer");
+        if !options.keeps_layer(&l.name)
+            || (options.skip_invisible_layers && !l.visible)
+            || (options.skip_tile_layers && kind == "tilelayer")
+            || (options.skip_object_layers && kind == "objectgroup")
+        {
+            layers_filtered = true;
+            continue;
+        }
+
+        let issues_before = issues.len();
+        let decoded = layer_to_ir(&mut l, kind, &ir_tilesets, max_gid, options, &mut issues);
+        let (properties, layer_kind) = match decoded {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => continue,
+            Err(err) if options.skip_broken_layers => {
+                // The skip replaces whatever the layer reported so far, and which tilesets
+                // it used is unknown, like for filtered layers.
+                issues.truncate(issues_before);
+                layers_filtered = true;
+                let message = format!("layer '{}' skipped: {err}", l.name);
+                let at = IssueLocation::Layer(l.name.clone());
+                issues.warn(IssueCode::LayerSkipped, at, message);
+                (Properties::default(), IrLayerKind::Unsupported)
+            }
+            Err(err) => return Err(err),
+        };
+        let tint = match l.tintcolor.as_deref().map(|hex| (hex, parse_tint(hex))) {
+            None => WHITE,
+            Some((_, Some(tint))) => tint,
+            Some((hex, None)) => {
+                let message = format!("layer '{}' has invalid tintcolor '{hex}'", l.name);
+                issues.warn(
+                    IssueCode::Other,
+                    IssueLocation::Layer(l.name.clone()),
+                    message,
+                );
+                WHITE
             }
-            "objectgroup" => IrLayerKind::Objects {
-                objects: l
-                    .objects
-                    .into_iter()
-                    .map(|obj| {
-                        if let Some(raw_gid) = obj.gid {
-                            let gid = raw_gid & crate::spatial::GID_MASK;
-                            if gid == 0 || gid > max_gid {
-                                return Err(MapError::InvalidObjectGid {
-                                    layer: layer_name.clone(),
-                                    object_id: obj.id,
-                                    gid,
-                                    max_gid,
-                                });
-                            }
-                        }
-                        object_to_ir(obj)
-                    })
-                    .collect::<Result<Vec<_>, _>>()?,
-            },
-            _ => IrLayerKind::Unsupported,
         };
         ir_layers.push(IrLayer {
             name: l.name,
             visible: l.visible,
             opacity: l.opacity,
+            tint,
             offset: vec2(l.offsetx, l.offsety),
             properties,
             kind: layer_kind,
         });
     }
 
-    Ok((
-        IrMap {
-            tile_w: j.tilewidth,
-            tile_h: j.tileheight,
-            properties: properties_from_json(j.properties)?,
-            tilesets: ir_tilesets,
-            layers: ir_layers,
-        },
-        map_dir,
-    ))
+    // Filtered-out layers may be the only users of a tileset.
+    if !layers_filtered {
+        warn_unused_tilesets(&ir_tilesets, &ir_layers, &mut issues);
+    }
+
+    let properties = properties_from_json(j.properties, || IssueLocation::Map, &mut issues)?;
+    let warnings = issues.finish()?;
+    Ok(IrMap {
+        tile_w: j.tilewidth,
+        tile_h: j.tileheight,
+        properties,
+        tilesets: ir_tilesets,
+        layers: ir_layers,
+        warnings,
+    })
+}
+
+/// Parses a Tiled color, `#RRGGBB` or `#AARRGGBB` (the `#` is optional).
+fn parse_tint(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if !matches!(digits.len(), 6 | 8) || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let argb = u32::from_str_radix(digits, 16).ok()?;
+    let argb = if digits.len() == 6 {
+        0xFF00_0000 | argb
+    } else {
+        argb
+    };
+    let [a, r, g, b] = argb.to_be_bytes();
+    Some(Color::from_rgba(r, g, b, a))
+}
+
+/// Tileset images under `map_dir` that cannot be opened, in tileset order.
+fn missing_images(tilesets: &[IrTileset], map_dir: &Path) -> Vec<(IssueLocation, MapError)> {
+    tilesets
+        .iter()
+        .filter_map(|ts| {
+            let IrTileset::Atlas { source, image, .. } = ts;
+            let path = map_dir.join(image);
+            std::fs::File::open(&path).err().map(|_| {
+                let err = MapError::MissingImage {
+                    tileset: source.clone(),
+                    path,
+                };
+                (IssueLocation::Tileset(source.clone()), err)
+            })
+        })
+        .collect()
+}
+
+/// Decodes one layer's properties and contents. `Ok(None)` drops the layer: a problem was
+/// collected or tolerated by skipping it.
+fn layer_to_ir(
+    l: &mut JsonLayer,
+    kind: &str,
+    tilesets: &[IrTileset],
+    max_gid: u32,
+    options: &LoadOptions,
+    issues: &mut Issues,
+) -> Result<Option<(Properties, IrLayerKind)>, MapError> {
+    let layer_name = l.name.clone();
+    let at = || IssueLocation::Layer(layer_name.clone());
+    let properties = properties_from_json(std::mem::take(&mut l.properties), at, issues)?;
+    let layer_kind = match kind {
+        "tilelayer" => {
+            let data = decode_layer_data(
+                &layer_name,
+                std::mem::take(&mut l.data),
+                &l.encoding,
+                &l.compression,
+            )
+            .and_then(|data| {
+                // Zero width or height only fits a layer without data. A grid too large to
+                // count can never match, so it reports `usize::MAX` cells.
+                let expected = l.width.checked_mul(l.height);
+                if expected != Some(data.len()) {
+                    return Err(MapError::InvalidLayerSize {
+                        path: None,
+                        layer: layer_name.clone(),
+                        expected: expected.unwrap_or(usize::MAX),
+                        actual: data.len(),
+                    });
+                }
+                Ok(data)
+            });
+            let Some(mut data) = issues.recover(at, data)? else {
+                return Ok(None);
+            };
+            // One issue per layer is enough to locate the problem.
+            let bad_gid = data
+                .iter()
+                .filter(|_| !options.skip_gid_validation)
+                .find_map(|&raw_gid| {
+                    check_gid(raw_gid, tilesets, max_gid, &layer_name, None).err()
+                });
+            if let Some(err) = bad_gid {
+                if issues.tolerate(Strictness::Lenient, IssueCode::InvalidGid, at, err)? {
+                    for cell in &mut data {
+                        if !gid_is_valid(*cell, tilesets, false) {
+                            *cell = 0;
+                        }
+                    }
+                }
+            }
+            if data.iter().all(|&gid| gid == 0) {
+                let message = format!("tile layer '{layer_name}' has no tiles");
+                issues.warn(IssueCode::EmptyLayer, at(), message);
+            }
+            IrLayerKind::Tiles {
+                width: l.width,
+                height: l.height,
+                data,
+            }
+        }
+        "objectgroup" => {
+            let mut objects = Vec::with_capacity(l.objects.len());
+            for mut obj in std::mem::take(&mut l.objects) {
+                let at_object = || IssueLocation::Object {
+                    layer: layer_name.clone(),
+                    id: obj.id,
+                };
+                if let Some(raw_gid) = obj.gid.filter(|_| !options.skip_gid_validation) {
+                    let checked = check_gid(raw_gid, tilesets, max_gid, &layer_name, Some(obj.id));
+                    if let Err(err) = checked {
+                        let code = IssueCode::InvalidGid;
+                        if issues.tolerate(Strictness::Lenient, code, at_object, err)? {
+                            obj.gid = None;
+                        }
+                    }
+                }
+                let at_object = {
+                    let (layer, id) = (layer_name.clone(), obj.id);
+                    move || IssueLocation::Object {
+                        layer: layer.clone(),
+                        id,
+                    }
+                };
+                objects.push(object_to_ir(obj, at_object, issues)?);
+            }
+            if objects.is_empty() {
+                let message = format!("object layer '{layer_name}' has no objects");
+                issues.warn(IssueCode::EmptyLayer, at(), message);
+            }
+            IrLayerKind::Objects {
+                objects,
+                draw_order: match l.draworder.as_deref() {
+                    Some("index") => ObjectDrawOrder::Index,
+                    _ => ObjectDrawOrder::TopDown,
+                },
+            }
+        }
+        other => {
+            let err = MapError::invalid(format!(
+                "layer '{layer_name}' has unsupported type '{other}'"
+            ));
+            let code = IssueCode::UnsupportedLayer;
+            if !issues.tolerate(Strictness::Standard, code, at, err)? {
+                return Ok(None);
+            }
+            IrLayerKind::Unsupported
+        }
+    };
+    Ok(Some((properties, layer_kind)))
+}
+
+fn warn_unused_tilesets(tilesets: &[IrTileset], layers: &[IrLayer], issues: &mut Issues) {
+    let first_gids: Vec<u32> = tilesets.iter().map(IrTileset::first_gid).collect();
+    let mut used = vec![false; tilesets.len()];
+    let mut mark = |raw_gid: u32| {
+        let gid = raw_gid & crate::spatial::GID_MASK;
+        // Tilesets are sorted by first_gid; the owner is the last one starting at or below.
+        if let Some(i) = first_gids
+            .partition_point(|&first| first <= gid)
+            .checked_sub(1)
+        {
+            used[i] = true;
+        }
+    };
+    for layer in layers {
+        match &layer.kind {
+            IrLayerKind::Tiles { data, .. } => {
+                data.iter().filter(|&&g| g != 0).for_each(|&g| mark(g))
+            }
+            IrLayerKind::Objects { objects, .. } => {
+                for obj in objects {
+                    if let IrObjectShape::Tile { gid } = obj.shape {
+                        mark(gid);
+                    }
+                }
+            }
+            IrLayerKind::Unsupported => {}
+        }
+    }
+    for (tileset, used) in tilesets.iter().zip(used) {
+        let IrTileset::Atlas { source, .. } = tileset;
+        if !used {
+            let message = format!("no tile uses tileset '{source}'");
+            issues.warn(
+                IssueCode::UnusedTileset,
+                IssueLocation::Tileset(source.clone()),
+                message,
+            );
+        }
+    }
+}
+
+// Tiled always writes these fields, so a missing one hints at a hand-edited or foreign
+// file; it defaults unless strict.
+fn optional_field<T: Default>(
+    value: Option<T>,
+    what: &str,
+    field: &str,
+    at: impl FnOnce() -> IssueLocation,
+    issues: &mut Issues,
+) -> Result<T, MapError> {
+    if value.is_none() {
+        let err = MapError::invalid(format!("{what} is missing '{field}'"));
+        issues.tolerate(
+            Strictness::Standard,
+            IssueCode::MissingTilesetField,
+            at,
+            err,
+        )?;
+    }
+    Ok(value.unwrap_or_default())
+}
+
+fn decode_tileset(
+    ts: &JsonTilesetRef,
+    map_dir: &Path,
+    options: &LoadOptions,
+    read_tileset: &mut impl FnMut(&Path) -> Result<String, MapError>,
+    issues: &mut Issues,
+) -> Result<IrTileset, MapError> {
+    if !ts.source.ends_with(".json") {
+        return Err(MapError::invalid(format!(
+            "External tileset must be JSON: {}",
+            ts.source
+        )));
+    }
+    let ts_path = map_dir.join(&ts.source);
+    let ext_txt = read_tileset(&ts_path)?;
+    let ext: ExternalTileset = serde_json::from_str(&ext_txt).map_err(|source| MapError::Json {
+        path: ts_path,
+        source,
+    })?;
+    let what = format!("tileset '{}'", ts.source);
+    require_nonzero(&what, "tilewidth", ext.tilewidth)?;
+    require_nonzero(&what, "tileheight", ext.tileheight)?;
+    require_nonzero(&what, "columns", ext.columns)?;
+    require_nonzero(&what, "tilecount", ext.tilecount)?;
+    let gid_end = u64::from(ts.firstgid) + u64::from(ext.tilecount);
+    if gid_end > u64::from(crate::spatial::GID_MASK) + 1 {
+        return Err(MapError::invalid(format!(
+            "{what} with firstgid {} and {} tiles runs past the largest gid {}",
+            ts.firstgid,
+            ext.tilecount,
+            crate::spatial::GID_MASK
+        )));
+    }
+
+    let at = || IssueLocation::Tileset(ts.source.clone());
+    let image_size = ext.imagewidth.zip(ext.imageheight);
+    if let Some(size) = image_size {
+        let geometry = check_tileset_geometry(
+            &ts.source,
+            size,
+            ext.tilewidth,
+            ext.tileheight,
+            ext.tilecount,
+            ext.columns,
+            ext.spacing.unwrap_or_default(),
+            ext.margin.unwrap_or_default(),
+        );
+        if let Err(err) = geometry {
+            let code = IssueCode::TilesetGeometryMismatch;
+            issues.tolerate(Strictness::Lenient, code, at, err)?;
+        }
+    }
+    let name = optional_field(ext.name, &what, "name", at, issues)?;
+    let spacing = optional_field(ext.spacing, &what, "spacing", at, issues)?;
+    let margin = optional_field(ext.margin, &what, "margin", at, issues)?;
+    let properties = properties_from_json(ext.properties, at, issues)?;
+    let mut tiles = Vec::new();
+    if !options.skip_tile_metadata {
+        for tile in ext.tiles {
+            if tile.id >= ext.tilecount {
+                let err = MapError::invalid(format!(
+                    "{what} has metadata for tile {} but only {} tiles",
+                    tile.id, ext.tilecount
+                ));
+                issues.tolerate(Strictness::Lenient, IssueCode::InvalidTileId, at, err)?;
+                continue;
+            }
+            let mut objects = Vec::with_capacity(tile.objectgroup.objects.len());
+            for obj in tile.objectgroup.objects {
+                objects.push(object_to_ir(obj, at, issues)?);
+            }
+            tiles.push(IrTileMetadata {
+                id: tile.id,
+                class_name: if !tile.class.is_empty() {
+                    tile.class
+                } else {
+                    tile.kind
+                },
+                properties: properties_from_json(tile.properties, at, issues)?,
+                objects,
+            });
+        }
+    }
+
+    // (We keep image path relative; Map::from_ir will join with map_dir)
+    Ok(IrTileset::Atlas {
+        name,
+        first_gid: ts.firstgid,
+        source: ts.source.clone(),
+        image: ext.image,
+        image_size,
+        tile_w: ext.tilewidth,
+        tile_h: ext.tileheight,
+        tilecount: ext.tilecount,
+        columns: ext.columns,
+        spacing,
+        margin,
+        properties,
+        tiles,
+    })
 }
 
 #[cfg(test)]
@@ -383,6 +1000,37 @@ mod tests {
         dir
     }
 
+    fn fixture_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn decode_with_options_filters_layers_and_tile_metadata() {
+        let path = fixture_path("external_props_map.json");
+        let options = LoadOptions {
+            exclude_layers: vec!["f*".to_string()],
+            skip_tile_metadata: true,
+            ..Default::default()
+        };
+
+        let (ir, _) = decode_map_file_to_ir_with(path.to_str().expect("path utf8"), &options)
+            .expect("decode");
+
+        let names: Vec<&str> = ir.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["bg", "objects"]);
+        match &ir.tilesets[0] {
+            IrTileset::Atlas {
+                properties, tiles, ..
+            } => {
+                assert_eq!(properties.get_string("biome"), Some("forest"));
+                assert!(tiles.is_empty());
+            }
+        }
+    }
+
     #[test]
     fn parses_properties_for_map_layer_object_tileset_and_tile() {
         let dir = temp_dir();
@@ -412,6 +1060,7 @@ mod tests {
             {
               "type":"objectgroup",
               "name":"spawns",
+              "draworder":"index",
               "objects":[
                 {
                   "id": 7,
@@ -436,6 +1085,7 @@ mod tests {
           "tiles":[
             {
               "id":0,
+              "type":"hazard",
               "properties":[{"name":"damage","type":"int","value":10}],
               "objectgroup":{
                 "objects":[
@@ -459,7 +1109,11 @@ mod tests {
         assert_eq!(ir.layers[0].properties.get_i32("difficulty"), Some(3));
 
         match &ir.layers[1].kind {
-            IrLayerKind::Objects { objects } => {
+            IrLayerKind::Objects {
+                objects,
+                draw_order,
+            } => {
+                assert_eq!(*draw_order, ObjectDrawOrder::Index);
                 assert_eq!(objects.len(), 1);
                 assert_eq!(objects[0].properties.get_string("kind"), Some("player"));
             }
@@ -472,6 +1126,7 @@ mod tests {
             } => {
                 assert_eq!(properties.get_string("biome"), Some("forest"));
                 assert_eq!(tiles.len(), 1);
+                assert_eq!(tiles[0].class_name, "hazard");
                 assert_eq!(tiles[0].properties.get_i32("damage"), Some(10));
                 assert_eq!(tiles[0].objects.len(), 1);
                 assert_eq!(
@@ -482,6 +1137,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tile_metadata_ids_beyond_the_tilecount_fail_unless_lenient() {
+        let dir = temp_dir();
+        let map_path = dir.join("map.json");
+        let map_json = r#"{
+          "tilewidth": 16,
+          "tileheight": 16,
+          "layers": [{"type":"tilelayer","name":"ground","width":1,"height":1,"data":[1]}],
+          "tilesets":[{"firstgid":1,"source":"tileset.json"}]
+        }"#;
+        let tileset_json = r#"{
+          "name":"walls",
+          "tilewidth":16,
+          "tileheight":16,
+          "tilecount":4,
+          "columns":2,
+          "spacing":0,
+          "margin":0,
+          "image":"tiles.png",
+          "tiles":[
+            {"id":1,"class":"floor"},
+            {"id":4,"class":"next"},
+            {"id":4294967295,"class":"wall"}
+          ]
+        }"#;
+        fs::write(&map_path, map_json).expect("failed to write map");
+        fs::write(dir.join("tileset.json"), tileset_json).expect("failed to write tileset");
+        let path = map_path.to_str().expect("path utf8");
+
+        assert!(matches!(
+            decode_map_file_to_ir(path),
+            Err(MapError::InvalidMap { message: msg, .. }) if msg.contains("tile 4 but only 4")
+        ));
+
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("lenient load");
+        let IrTileset::Atlas { tiles, .. } = &ir.tilesets[0];
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].class_name, "floor");
+        let codes: Vec<_> = ir.warnings.iter().map(|w| w.code).collect();
+        assert_eq!(codes, vec![IssueCode::InvalidTileId; 2]);
+    }
+
+    #[test]
+    fn tilesets_reaching_past_the_largest_gid_are_rejected() {
+        let dir = temp_dir();
+        let map_path = dir.join("map.json");
+        let map_json = r#"{
+          "tilewidth": 16,
+          "tileheight": 16,
+          "layers": [{"type":"tilelayer","name":"ground","width":1,"height":1,"data":[0]}],
+          "tilesets":[{"firstgid":4294967295,"source":"tileset.json"}]
+        }"#;
+        let tileset_json = r#"{
+          "name":"walls",
+          "tilewidth":16,
+          "tileheight":16,
+          "tilecount":4,
+          "columns":2,
+          "spacing":0,
+          "margin":0,
+          "image":"tiles.png"
+        }"#;
+        fs::write(&map_path, map_json).expect("failed to write map");
+        fs::write(dir.join("tileset.json"), tileset_json).expect("failed to write tileset");
+        let path = map_path.to_str().expect("path utf8");
+
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        assert!(matches!(
+            decode_map_file_to_ir_with(path, &options),
+            Err(MapError::InvalidMap { message: msg, .. }) if msg.contains("runs past the largest gid")
+        ));
+
+        let fits = map_json.replace("4294967295", "536870908");
+        fs::write(&map_path, fits).expect("failed to write map");
+        let (ir, _) = decode_map_file_to_ir(path).expect("last tile is GID_MASK");
+        assert_eq!(ir.tilesets[0].last_gid(), Some(crate::spatial::GID_MASK));
+    }
+
     #[test]
     fn keeps_large_int_property_values() {
         let dir = temp_dir();
@@ -514,6 +1248,52 @@ mod tests {
         assert_eq!(ir.properties.get_i32("big_id"), None);
     }
 
+    #[test]
+    fn properties_round_trip_through_tiled_json() {
+        let props: Properties = [
+            ("solid", PropertyValue::Bool(true)),
+            ("hp", PropertyValue::I64(-5_000_000_000)),
+            ("speed", PropertyValue::F32(1.5)),
+            ("tint", PropertyValue::String("#ff102030".to_string())),
+            (
+                "dir",
+                PropertyValue::Unknown {
+                    type_name: "vector".to_string(),
+                    value: "[4,2]".to_string(),
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+
+        let json = props.to_json();
+        let names: Vec<_> = json
+            .as_array()
+            .expect("properties are an array")
+            .iter()
+            .map(|p| (p["name"].as_str(), p["type"].as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Some("dir"), Some("vector")),
+                (Some("hp"), Some("int")),
+                (Some("solid"), Some("bool")),
+                (Some("speed"), Some("float")),
+                (Some("tint"), Some("string")),
+            ]
+        );
+
+        let map = format!(
+            r#"{{"tilewidth":16,"tileheight":16,"layers":[],"tilesets":[],"properties":{json}}}"#
+        );
+        let lenient = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let ir = decode_map_str_to_ir_with(&map, Path::new("m.json"), &temp_dir(), &lenient)
+            .expect("decode");
+        assert_eq!(ir.properties, props);
+    }
+
     #[test]
     fn returns_typed_error_for_malformed_json() {
         let dir = temp_dir();
@@ -599,6 +1379,9 @@ mod tests {
         }"#;
 
         let tileset_json = r#"{
+          "name":"tiles",
+          "spacing":0,
+          "margin":0,
           "tilewidth":16,
           "tileheight":16,
           "tilecount":1,
@@ -609,9 +1392,774 @@ mod tests {
         fs::write(&map_path, map_json).expect("failed to write map");
         fs::write(&ts_path, tileset_json).expect("failed to write tileset");
 
-        let err = decode_map_file_to_ir(map_path.to_str().expect("path utf8"))
+        let path = map_path.to_str().expect("path utf8");
+        let err = decode_map_file_to_ir(path)
             .err()
             .expect("expected decode error");
         assert!(matches!(err, MapError::UnsupportedPropertyType { .. }));
+        let strict = LoadOptions::default().with_strictness(Strictness::Strict);
+        assert!(matches!(
+            decode_map_file_to_ir_with(path, &strict),
+            Err(MapError::UnsupportedPropertyType { kind, .. }) if kind == "not_supported"
+        ));
+
+        let lenient = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let (ir, _) = decode_map_file_to_ir_with(path, &lenient).expect("lenient load");
+        assert_eq!(ir.properties.get_string("mystery"), Some("x"));
+        let codes: Vec<_> = ir.warnings.iter().map(|w| w.code).collect();
+        assert!(codes.contains(&IssueCode::UnsupportedPropertyType));
+    }
+
+    #[test]
+    fn options_can_drop_hidden_layers_and_skip_gid_checks() {
+        let dir = temp_dir();
+        let map_path = dir.join("map.json");
+        fs::write(
+            dir.join("tileset.json"),
+            r#"{"tilewidth":16,"tileheight":16,"tilecount":4,"columns":2,"image":"t.png"}"#,
+        )
+        .expect("write tileset");
+        fs::write(
+            &map_path,
+            r#"{
+              "tilewidth":16,"tileheight":16,
+              "layers":[
+                {"type":"tilelayer","name":"ground","width":2,"height":1,"data":[1,9]},
+                {"type":"tilelayer","name":"hidden","visible":false,"width":1,"height":1,"data":[1]}
+              ],
+              "tilesets":[{"firstgid":1,"source":"tileset.json"}]
+            }"#,
+        )
+        .expect("write map");
+        let path = map_path.to_str().expect("path utf8");
+
+        assert!(matches!(
+            decode_map_file_to_ir(path),
+            Err(MapError::InvalidTileGid { gid: 9, .. })
+        ));
+        let options = LoadOptions::default()
+            .without_gid_validation()
+            .without_invisible_layers();
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("decode");
+        let names: Vec<&str> = ir.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["ground"]);
+    }
+
+    #[test]
+    fn strictness_decides_between_errors_and_warnings_per_issue() {
+        let path = fixture_path("flawed_map.json");
+        let path = path.to_str().expect("fixture path must be utf-8");
+        let outcome = |strictness: Strictness| {
+            let options = LoadOptions::default()
+                .with_all_errors_collected()
+                .with_strictness(strictness);
+            let issues = match decode_map_file_to_ir_with(path, &options) {
+                Ok((ir, _)) => ir.warnings,
+                Err(MapError::Validation { report, .. }) => report.issues,
+                Err(err) => panic!("unexpected fatal error: {err}"),
+            };
+            issues
+                .into_iter()
+                .map(|i| (i.location.to_string(), i.severity))
+                .collect::<Vec<_>>()
+        };
+        let tileset = "tileset 'sparse_tileset.json'".to_string();
+        let (ground, sky, map) = (
+            "layer 'ground'".to_string(),
+            "layer 'sky'".to_string(),
+            "map".to_string(),
+        );
+        use Severity::{Error, Warning};
+
+        let all = |severity| {
+            vec![
+                (tileset.clone(), severity),
+                (tileset.clone(), severity),
+                (tileset.clone(), severity),
+                (ground.clone(), severity),
+                (sky.clone(), severity),
+                (map.clone(), severity),
+            ]
+        };
+        assert_eq!(outcome(Strictness::Strict), all(Error));
+        assert_eq!(outcome(Strictness::Lenient), all(Warning));
+        assert_eq!(
+            outcome(Strictness::Standard),
+            vec![
+                (tileset.clone(), Warning),
+                (tileset.clone(), Warning),
+                (tileset.clone(), Warning),
+                (ground, Error),
+                (sky, Warning),
+                (map, Error),
+            ]
+        );
+
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("lenient load");
+        let IrLayerKind::Tiles { data, .. } = &ir.layers[0].kind else {
+            panic!("ground must stay a tile layer");
+        };
+        assert_eq!(data, &vec![1, 0, 2], "out-of-range gids become empty cells");
+        assert!(matches!(ir.layers[1].kind, IrLayerKind::Unsupported));
+        assert_eq!(
+            ir.properties.get("spawn"),
+            Some(&PropertyValue::Unknown {
+                type_name: "vector".to_string(),
+                value: "[4,2]".to_string(),
+            })
+        );
+
+        let strict = LoadOptions::default().with_strictness(Strictness::Strict);
+        assert!(matches!(
+            decode_map_file_to_ir_with(path, &strict),
+            Err(MapError::InvalidMap { message: msg, .. }) if msg.contains("missing 'name'")
+        ));
+    }
+
+    #[test]
+    fn overlapping_tileset_ranges_fail_unless_lenient() {
+        let path = fixture_path("overlapping_tilesets_map.json");
+        let path = path.to_str().expect("fixture path must be utf-8");
+        let err = decode_map_file_to_ir(path)
+            .err()
+            .expect("overlap must be rejected");
+        let MapError::OverlappingTilesets {
+            path: Some(file),
+            a,
+            b,
+            range,
+        } = err
+        else {
+            panic!("expected OverlappingTilesets with a path, got {err}");
+        };
+        assert_eq!(file, PathBuf::from(path));
+        assert_eq!(
+            (a.as_str(), b.as_str()),
+            ("basic_tileset.json", "props_tileset.json")
+        );
+        assert_eq!(range, 3..=4);
+
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("lenient load");
+        assert_eq!(ir.tilesets.len(), 2);
+        let ranges: Vec<_> = ir
+            .tilesets
+            .iter()
+            .map(|t| (t.first_gid(), t.tilecount()))
+            .collect();
+        assert_eq!(ranges, vec![(1, 4), (3, 4)]);
+        assert_eq!(ir.warnings.len(), 1);
+        assert_eq!(
+            ir.warnings[0].location,
+            IssueLocation::Tileset("props_tileset.json".to_string())
+        );
+    }
+
+    #[test]
+    fn a_wide_tileset_overlapping_several_later_ones_reports_each() {
+        let dir = temp_dir();
+        for (name, tilecount) in [("wide.json", 100), ("b.json", 4), ("c.json", 4)] {
+            let tileset = format!(
+                r#"{{"tilewidth":16,"tileheight":16,"tilecount":{tilecount},"columns":4,"image":"t.png"}}"#
+            );
+            fs::write(dir.join(name), tileset).expect("write tileset");
+        }
+        // b (10..=13) and c (50..=53) both sit inside wide (1..=100) but not in each other.
+        let map_json = r#"{"width":1,"height":1,"tilewidth":16,"tileheight":16,"layers":[],
+            "tilesets":[{"firstgid":1,"source":"wide.json"},{"firstgid":10,"source":"b.json"},
+                        {"firstgid":50,"source":"c.json"}]}"#;
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let ir = decode_map_str_to_ir_with(map_json, Path::new("m.json"), &dir, &options)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let overlaps: Vec<(&IssueLocation, &str)> = ir
+            .warnings
+            .iter()
+            .filter(|w| w.code == IssueCode::OverlappingTilesets)
+            .map(|w| (&w.location, w.message.as_str()))
+            .collect();
+        assert_eq!(overlaps.len(), 2, "{overlaps:?}");
+        assert_eq!(overlaps[0].0, &IssueLocation::Tileset("b.json".to_string()));
+        assert_eq!(overlaps[1].0, &IssueLocation::Tileset("c.json".to_string()));
+        assert!(
+            overlaps.iter().all(|(_, m)| m.contains("wide.json")),
+            "{overlaps:?}"
+        );
+        assert!(overlaps[1].1.contains("50") && overlaps[1].1.contains("53"));
+    }
+
+    #[test]
+    fn tileset_capacity_follows_margin_spacing_and_columns() {
+        let check = |image, columns, spacing, margin, tilecount| match check_tileset_geometry(
+            "t.json", image, 16, 16, tilecount, columns, spacing, margin,
+        ) {
+            Ok(()) => None,
+            Err(MapError::TilesetGeometryMismatch { capacity, .. }) => Some(capacity),
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+        assert_eq!(check((128, 128), 8, 0, 0, 64), None);
+        assert_eq!(check((128, 128), 8, 0, 0, 65), Some(64));
+        // 1px margin and spacing: 1 + 16 + 1 + 16 + 1 = 35 fits two tiles per axis.
+        assert_eq!(check((35, 35), 2, 1, 1, 5), Some(4));
+        assert_eq!(
+            check((34, 35), 2, 1, 1, 3),
+            Some(1),
+            "second column does not fit"
+        );
+        assert_eq!(check((8, 8), 1, 0, 0, 1), Some(0));
+        // Spacing near u32::MAX neither overflows nor makes room for a second tile.
+        assert_eq!(check((128, 128), 8, u32::MAX, 0, 2), Some(1));
+        assert_eq!(check((8, 8), 1, u32::MAX - 1, 0, 1), Some(0));
+    }
+
+    #[test]
+    fn inflated_tilecount_is_rejected_unless_lenient() {
+        let path = fixture_path("inflated_tilecount_map.json");
+        let path = path.to_str().expect("fixture path must be utf-8");
+        assert!(matches!(
+            decode_map_file_to_ir(path),
+            Err(MapError::TilesetGeometryMismatch {
+                tilecount: 100,
+                capacity: 4,
+                ..
+            })
+        ));
+
+        let options = LoadOptions::default().with_strictness(Strictness::Lenient);
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("lenient load");
+        assert_eq!(ir.warnings.len(), 1);
+        assert!(matches!(
+            &ir.tilesets[0],
+            IrTileset::Atlas {
+                tilecount: 100,
+                image_size: Some((32, 32)),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn warnings_carry_codes_and_stream_through_the_callback() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static STREAMED: AtomicUsize = AtomicUsize::new(0);
+
+        let path = fixture_path("warnings_map.json");
+        let path = path.to_str().expect("fixture path must be utf-8");
+        let options = LoadOptions {
+            on_warning: Some(|_| {
+                STREAMED.fetch_add(1, Ordering::Relaxed);
+            }),
+            ..Default::default()
+        };
+        let (ir, _) = decode_map_file_to_ir_with(path, &options).expect("warnings never fail");
+        let codes: Vec<_> = ir
+            .warnings
+            .iter()
+            .map(|w| (w.code, w.location.to_string()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (IssueCode::PropertyCoerced, "layer 'ground'".to_string()),
+                (IssueCode::EmptyLayer, "layer 'decals'".to_string()),
+                (IssueCode::EmptyLayer, "layer 'spawns'".to_string()),
+                (
+                    IssueCode::UnusedTileset,
+                    "tileset 'props_tileset.json'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(STREAMED.load(Ordering::Relaxed), 4);
+        assert_eq!(
+            ir.layers[0].properties.get("speed"),
+            Some(&PropertyValue::I64(3))
+        );
+
+        // Usage is only judged when no layer was filtered out; options that drop nothing
+        // here keep the warning.
+        let filtered = LoadOptions::default().without_invisible_layers();
+        let (ir, _) = decode_map_file_to_ir_with(path, &filtered).expect("load");
+        assert!(ir
+            .warnings
+            .iter()
+            .any(|w| w.code == IssueCode::UnusedTileset));
+        let filtered = LoadOptions {
+            exclude_layers: vec!["ground".to_string()],
+            ..Default::default()
+        };
+        let (ir, _) = decode_map_file_to_ir_with(path, &filtered).expect("load");
+        assert!(!ir
+            .warnings
+            .iter()
+            .any(|w| w.code == IssueCode::UnusedTileset));
+    }
+
+    #[test]
+    fn validation_report_lists_every_recoverable_problem() {
+        let path = fixture_path("three_problems_map.json");
+        let path_str = path.to_str().expect("fixture path must be utf-8");
+        let report = validate_map_file(path_str);
+        let found: Vec<_> = report
+            .issues
+            .iter()
+            .map(|i| (i.severity, i.location.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    Severity::Error,
+                    IssueLocation::Tileset("missing_tileset.json".to_string())
+                ),
+                (Severity::Error, IssueLocation::Layer("ground".to_string())),
+                (
+                    Severity::Error,
+                    IssueLocation::Object {
+                        layer: "objects".to_string(),
+                        id: 3,
+                    }
+                ),
+            ],
+            "{report}"
+        );
+        assert!(report.issues[1].message.contains("gid 99"));
+        assert!(report.issues[2].message.contains("vector"));
+
+        // Without collection the first problem still stops decoding.
+        assert!(matches!(
+            decode_map_file_to_ir(path_str),
+            Err(MapError::Io { .. })
+        ));
+        let collected = decode_map_file_to_ir_with(
+            path_str,
+            &LoadOptions::default().with_all_errors_collected(),
+        );
+        assert!(matches!(collected, Err(MapError::Validation { report: r, .. }) if r == report));
+
+        let clean = fixture_path("external_props_map.json");
+        assert!(validate_map_file(clean.to_str().expect("utf-8")).is_ok());
+        let garbage = validate_map_file(fixture_path("props_tiles.png").to_str().expect("utf-8"));
+        assert_eq!(garbage.errors().count(), 1);
+    }
+
+    #[test]
+    fn image_check_reports_every_missing_tileset_image_before_layers() {
+        let path = fixture_path("missing_image_map.json");
+        let path_str = path.to_str().expect("fixture path must be utf-8");
+        assert!(decode_map_file_to_ir(path_str).is_ok(), "off by default");
+
+        let checked = LoadOptions::default().with_image_check();
+        let Err(err) = decode_map_file_to_ir_with(path_str, &checked) else {
+            panic!("the absent image must fail the load");
+        };
+        let MapError::MissingImage {
+            tileset,
+            path: image,
+        } = &err
+        else {
+            panic!("expected MissingImage, got {err}");
+        };
+        assert_eq!(tileset, "absent_image_tileset.json");
+        assert_eq!(image, &fixture_path("absent_tiles.png"));
+        assert_eq!(err.path(), Some(image.as_path()));
+
+        let report = validate_map_file(path_str);
+        let codes: Vec<_> = report
+            .errors()
+            .map(|i| (i.code, i.location.clone()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![(
+                IssueCode::MissingImage,
+                IssueLocation::Tileset("absent_image_tileset.json".to_string())
+            )],
+            "{report}"
+        );
+
+        // Several missing images are listed together, even without collecting.
+        let dir = temp_dir();
+        fs::write(
+            dir.join("tileset.json"),
+            r#"{"tilewidth":16,"tileheight":16,"tilecount":4,"columns":2,"image":"t.png"}"#,
+        )
+        .expect("write tileset");
+        let map = r#"{"tilewidth":16,"tileheight":16,
+            "layers":[{"type":"tilelayer","name":"bad","width":1,"height":1,"data":[99]}],
+            "tilesets":[{"firstgid":1,"source":"tileset.json"},{"firstgid":5,"source":"tileset.json"}]}"#;
+        let Err(err) = decode_map_str_to_ir_with(map, Path::new("m.json"), &dir, &checked) else {
+            panic!("both images are missing");
+        };
+        let MapError::Validation { report, .. } = err else {
+            panic!("expected both images in one error, got {err}");
+        };
+        let codes: Vec<_> = report.errors().map(|i| i.code).collect();
+        assert_eq!(
+            codes,
+            vec![IssueCode::MissingImage; 2],
+            "checked before layers"
+        );
+        // Warnings found before the check (the bare tilesets' defaulted fields) are kept.
+        let warnings: Vec<_> = report
+            .issues
+            .iter()
+            .filter(|i| i.severity == Severity::Warning)
+            .map(|i| i.code)
+            .collect();
+        assert!(!warnings.is_empty(), "{report}");
+        assert!(warnings
+            .iter()
+            .all(|&c| c == IssueCode::MissingTilesetField));
+    }
+
+    #[test]
+    fn layer_data_must_cover_the_layer_grid_exactly() {
+        let dir = temp_dir();
+        fs::write(
+            dir.join("tileset.json"),
+            r#"{"tilewidth":16,"tileheight":16,"tilecount":4,"columns":2,"image":"t.png"}"#,
+        )
+        .expect("write tileset");
+        let decode = |width: usize, height: usize, data: &str| {
+            let map_json = format!(
+                r#"{{"width":2,"height":2,"tilewidth":16,"tileheight":16,
+                    "layers":[{{"type":"tilelayer","name":"ground","width":{width},"height":{height},"data":{data}}}],
+                    "tilesets":[{{"firstgid":1,"source":"tileset.json"}}]}}"#
+            );
+            decode_map_str_to_ir_with(
+                &map_json,
+                Path::new("m.json"),
+                &dir,
+                &LoadOptions::default(),
+            )
+            .map(|_| ())
+        };
+        let size_error = |result: Result<(), MapError>| match result {
+            Err(MapError::InvalidLayerSize {
+                path: Some(path),
+                layer,
+                expected,
+                actual,
+            }) if layer == "ground" && path == Path::new("m.json") => (expected, actual),
+            other => panic!("expected InvalidLayerSize, got {:?}", other.err()),
+        };
+        assert!(decode(2, 2, "[1,2,3,4]").is_ok());
+        assert!(
+            decode(0, 0, "[]").is_ok(),
+            "an empty layer has no grid to cover"
+        );
+        assert_eq!(size_error(decode(2, 2, "[1,2,3]")), (4, 3), "short");
+        assert_eq!(size_error(decode(2, 2, "[1,2,3,4,1]")), (4, 5), "long");
+        assert_eq!(size_error(decode(0, 2, "[1,2]")), (0, 2), "zero width");
+        assert_eq!(size_error(decode(2, 0, "[1,2]")), (0, 2), "zero height");
+
+        let huge = u32::MAX as usize;
+        assert_eq!(
+            size_error(decode(huge, huge, "[1]")),
+            (huge.saturating_mul(huge), 1)
+        );
+        let overflow = size_error(decode(usize::MAX, 2, "[1,2]"));
+        assert_eq!(overflow, (usize::MAX, 2), "product overflows usize");
+    }
+
+    #[test]
+    fn skipped_layers_leave_only_the_skip_warning() {
+        let dir = temp_dir();
+        for name in ["a.json", "b.json"] {
+            fs::write(
+                dir.join(name),
+                r#"{"tilewidth":16,"tileheight":16,"tilecount":4,"columns":2,"image":"t.png"}"#,
+            )
+            .expect("write tileset");
+        }
+        // `broken` coerces a property, then fails on gid 99; it is the only user of b.json.
+        let map_json = r#"{"width":2,"height":1,"tilewidth":16,"tileheight":16,
+            "tilesets":[{"firstgid":1,"source":"a.json"},{"firstgid":5,"source":"b.json"}],
+            "layers":[
+                {"type":"tilelayer","name":"ground","width":2,"height":1,"data":[1,2]},
+                {"type":"tilelayer","name":"broken","width":2,"height":1,"data":[5,99],
+                 "properties":[{"name":"n","type":"int","value":"3"}]}
+            ]}"#;
+        let options = LoadOptions::default().with_broken_layers_skipped();
+        let ir = decode_map_str_to_ir_with(map_json, Path::new("m.json"), &dir, &options)
+            .unwrap_or_else(|e| panic!("{e}"));
+        // The bare tilesets warn about optional fields; those are beside the point here.
+        let codes: Vec<IssueCode> = ir
+            .warnings
+            .iter()
+            .map(|w| w.code)
+            .filter(|&c| c != IssueCode::MissingTilesetField)
+            .collect();
+        assert_eq!(codes, [IssueCode::LayerSkipped]);
+
+        // Without the skip, the coerced property is reported as usual.
+        let ok_json = map_json.replace("99", "6");
+        let ir = decode_map_str_to_ir_with(&ok_json, Path::new("m.json"), &dir, &options)
+            .unwrap_or_else(|e| panic!("{e}"));
+        let codes: Vec<IssueCode> = ir
+            .warnings
+            .iter()
+            .map(|w| w.code)
+            .filter(|&c| c != IssueCode::MissingTilesetField)
+            .collect();
+        assert_eq!(codes, [IssueCode::PropertyCoerced]);
+    }
+
+    #[test]
+    fn unsupported_layer_kinds_stay_as_empty_layers_unless_strict() {
+        let dir = temp_dir();
+        let map_json = r#"{"width":1,"height":1,"tilewidth":16,"tileheight":16,"tilesets":[],
+            "layers":[
+                {"type":"imagelayer","name":"sky"},
+                {"type":"tilelayer","name":"ground","width":1,"height":1,"data":[0]}
+            ]}"#;
+        let decode = |strictness| {
+            let options = LoadOptions::default().with_strictness(strictness);
+            decode_map_str_to_ir_with(map_json, Path::new("m.json"), &dir, &options)
+        };
+        for strictness in [Strictness::Standard, Strictness::Lenient] {
+            let ir = decode(strictness).unwrap_or_else(|e| panic!("{strictness:?}: {e}"));
+            let names: Vec<&str> = ir.layers.iter().map(|l| l.name.as_str()).collect();
+            assert_eq!(names, ["sky", "ground"], "{strictness:?}");
+            assert!(matches!(ir.layers[0].kind, IrLayerKind::Unsupported));
+            assert!(ir
+                .warnings
+                .iter()
+                .any(|w| w.code == IssueCode::UnsupportedLayer));
+        }
+        assert!(decode(Strictness::Strict).is_err());
+    }
+
+    #[test]
+    fn layer_tintcolor_accepts_both_tiled_forms_and_warns_on_garbage() {
+        let dir = temp_dir();
+        let map_json = r##"{"width":1,"height":1,"tilewidth":16,"tileheight":16,"tilesets":[],
+            "layers":[
+                {"type":"tilelayer","name":"argb","width":1,"height":1,"data":[0],"tintcolor":"#80ff0000"},
+                {"type":"tilelayer","name":"rgb","width":1,"height":1,"data":[0],"tintcolor":"00ff00"},
+                {"type":"tilelayer","name":"bad","width":1,"height":1,"data":[0],"tintcolor":"#12"},
+                {"type":"tilelayer","name":"plain","width":1,"height":1,"data":[0]}
+            ]}"##;
+        let ir =
+            decode_map_str_to_ir_with(map_json, Path::new("m.json"), &dir, &LoadOptions::default())
+                .unwrap_or_else(|e| panic!("{e}"));
+        let tints: Vec<Color> = ir.layers.iter().map(|l| l.tint).collect();
+        assert_eq!(
+            tints,
+            [
+                Color::from_rgba(255, 0, 0, 0x80),
+                Color::from_rgba(0, 255, 0, 255),
+                WHITE,
+                WHITE
+            ]
+        );
+        let bad: Vec<_> = ir
+            .warnings
+            .iter()
+            .filter(|w| w.message.contains("tintcolor"))
+            .map(|w| &w.location)
+            .collect();
+        assert_eq!(bad, [&IssueLocation::Layer("bad".to_string())]);
+    }
+
+    #[test]
+    fn decode_errors_name_the_file_they_come_from() {
+        let dir = temp_dir();
+        let map_path = dir.join("m.json");
+        let map_json = |gid: u32| {
+            format!(
+                r#"{{"width":1,"height":1,"tilewidth":16,"tileheight":16,
+                    "layers":[{{"type":"tilelayer","name":"ground","width":1,"height":1,"data":[{gid}]}}],
+                    "tilesets":[{{"firstgid":1,"source":"nested/tileset.json"}}]}}"#
+            )
+        };
+        let tileset = |prop_type: &str| {
+            format!(
+                r#"{{"name":"t","tilewidth":16,"tileheight":16,"tilecount":4,"columns":2,
+                    "spacing":0,"margin":0,"image":"t.png",
+                    "properties":[{{"name":"p","type":"{prop_type}","value":1}}]}}"#
+            )
+        };
+        let tileset_path = dir.join("nested").join("tileset.json");
+        fs::create_dir_all(tileset_path.parent().expect("parent")).expect("mkdir");
+        let decode_with = |gid: u32, prop_type: &str| {
+            fs::write(&map_path, map_json(gid)).expect("write map");
+            fs::write(&tileset_path, tileset(prop_type)).expect("write tileset");
+            decode_map_file_to_ir(map_path.to_str().expect("utf-8"))
+                .err()
+                .expect("decoding must fail")
+        };
+
+        let err = decode_with(1, "vector");
+        assert!(
+            matches!(err, MapError::UnsupportedPropertyType { .. }),
+            "{err}"
+        );
+        assert_eq!(err.path(), Some(tileset_path.as_path()));
+        assert!(err
+            .to_string()
+            .ends_with(&format!("(in {})", tileset_path.display())));
+
+        let err = decode_with(99, "int");
+        assert!(
+            matches!(err, MapError::InvalidTileGid { gid: 99, .. }),
+            "{err}"
+        );
+        assert_eq!(err.path(), Some(map_path.as_path()));
+
+        let io = decode_map_file_to_ir(dir.join("missing.json").to_str().expect("utf-8"))
+            .err()
+            .expect("missing map");
+        assert!(
+            std::error::Error::source(&io).is_some(),
+            "I/O errors chain to their cause"
+        );
+        assert_eq!(MapError::LayerNotFound("x".to_string()).path(), None);
+    }
+
+    #[test]
+    fn gid_errors_keep_flip_flags_and_catch_gaps_between_tilesets() {
+        use crate::spatial::{FLIP_H, FLIP_V};
+
+        // Gids 1..=4 and 10..=13; 5..=9 belong to no tileset.
+        let decode = |data: &str, object_gid: Option<u32>, strictness| {
+            let objects = object_gid.map_or(String::new(), |gid| {
+                format!(r#",{{"type":"objectgroup","name":"things","objects":[{{"id":7,"gid":{gid}}}]}}"#)
+            });
+            let json = format!(
+                r#"{{"width":3,"height":1,"tilewidth":16,"tileheight":16,
+                    "layers":[{{"type":"tilelayer","name":"ground","width":3,"height":1,"data":{data}}}{objects}],
+                    "tilesets":[{{"firstgid":1,"source":"basic_tileset.json"}},
+                                {{"firstgid":10,"source":"props_tileset.json"}}]}}"#
+            );
+            let options = LoadOptions::default().with_strictness(strictness);
+            let dir = fixture_path("");
+            decode_map_str_to_ir_with(&json, Path::new("m.json"), &dir, &options)
+        };
+
+        let flipped = format!("[1, {}, 0]", 20 | FLIP_H);
+        let err = decode(&flipped, None, Strictness::Standard)
+            .err()
+            .expect("out of range");
+        assert!(matches!(
+            &err,
+            MapError::InvalidTileGid { layer, gid: 20, raw_gid, max_gid: 13, .. }
+                if layer == "ground" && *raw_gid == 20 | FLIP_H
+        ));
+        assert!(
+            err.to_string()
+                .contains("gid 20 (raw 0x80000014, flipped H)"),
+            "{err}"
+        );
+
+        let err = decode("[1, 7, 0]", None, Strictness::Standard)
+            .err()
+            .expect("gap");
+        assert!(matches!(
+            err,
+            MapError::InvalidTileGid {
+                gid: 7,
+                raw_gid: 7,
+                ..
+            }
+        ));
+        assert!(
+            err.to_string().contains("no tileset owns it within 1..=13"),
+            "{err}"
+        );
+
+        let err = decode("[1, 2, 3]", Some(6 | FLIP_V), Strictness::Standard)
+            .err()
+            .expect("object gap");
+        assert!(matches!(
+            err,
+            MapError::InvalidObjectGid { object_id: 7, gid: 6, raw_gid, .. } if raw_gid == 6 | FLIP_V
+        ));
+
+        let data = format!("[1, 7, {}]", 10 | FLIP_V);
+        let ir = decode(&data, None, Strictness::Lenient).expect("lenient");
+        let IrLayerKind::Tiles { data, .. } = &ir.layers[0].kind else {
+            panic!("ground must stay a tile layer");
+        };
+        assert_eq!(
+            data,
+            &vec![1, 0, 10 | FLIP_V],
+            "gap gids become empty cells"
+        );
+    }
+
+    #[test]
+    fn zero_map_and_tileset_dimensions_are_rejected() {
+        let tileset = |tw: u32, th: u32, cols: u32, count: u32| {
+            format!(
+                r#"{{"tilewidth":{tw},"tileheight":{th},"tilecount":{count},"columns":{cols},"image":"t.png"}}"#
+            )
+        };
+        let map = |fields: &str| {
+            format!(
+                r#"{{{fields},"layers":[],"tilesets":[{{"firstgid":1,"source":"tileset.json"}}]}}"#
+            )
+        };
+        let valid = r#""width":2,"height":2,"tilewidth":16,"tileheight":16"#;
+        let ok_tileset = tileset(16, 16, 2, 4);
+        let ts = "tileset 'tileset.json'";
+        let cases = [
+            (
+                map(r#""width":2,"height":2,"tilewidth":0,"tileheight":16"#),
+                ok_tileset.clone(),
+                ("map", "tilewidth"),
+            ),
+            (
+                map(r#""width":2,"height":2,"tilewidth":16,"tileheight":0"#),
+                ok_tileset.clone(),
+                ("map", "tileheight"),
+            ),
+            (
+                map(r#""width":0,"height":2,"tilewidth":16,"tileheight":16"#),
+                ok_tileset.clone(),
+                ("map", "width"),
+            ),
+            (
+                map(r#""width":2,"height":0,"tilewidth":16,"tileheight":16"#),
+                ok_tileset.clone(),
+                ("map", "height"),
+            ),
+            (map(valid), tileset(0, 16, 2, 4), (ts, "tilewidth")),
+            (map(valid), tileset(16, 0, 2, 4), (ts, "tileheight")),
+            (map(valid), tileset(16, 16, 0, 4), (ts, "columns")),
+            (map(valid), tileset(16, 16, 2, 0), (ts, "tilecount")),
+        ];
+        for (map_json, tileset_json, (entity, field)) in cases {
+            let dir = temp_dir();
+            fs::write(dir.join("tileset.json"), tileset_json).expect("write tileset");
+            let err = decode_map_str_to_ir_with(
+                &map_json,
+                Path::new("m.json"),
+                &dir,
+                &LoadOptions::default(),
+            )
+            .err()
+            .unwrap_or_else(|| panic!("expected zero {field} in {entity} to fail"));
+            assert!(
+                matches!(
+                    &err,
+                    MapError::InvalidGeometry { entity: e, field: f, .. } if e == entity && f == field
+                ),
+                "{err}"
+            );
+            assert!(err
+                .to_string()
+                .contains(&format!("{entity} has zero {field}")));
+        }
+
+        let dir = temp_dir();
+        fs::write(dir.join("tileset.json"), ok_tileset).expect("write tileset");
+        assert!(decode_map_str_to_ir_with(
+            &map(valid),
+            Path::new("m.json"),
+            &dir,
+            &LoadOptions::default()
+        )
+        .is_ok());
     }
 }
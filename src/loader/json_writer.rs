@@ -0,0 +1,157 @@
+use crate::error::MapError;
+use crate::ir_map::*;
+use serde_json::{json, Value as JsonValue};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Tiled JSON for one object, the inverse of the loader's object decoding.
+pub fn object_to_json(obj: &IrObject) -> JsonValue {
+    let mut out = json!({
+        "id": obj.id,
+        "name": obj.name,
+        "type": obj.class_name,
+        "x": obj.x,
+        "y": obj.y,
+        "width": obj.width,
+        "height": obj.height,
+        "rotation": obj.rotation,
+        "visible": obj.visible,
+        "properties": obj.properties.to_json(),
+    });
+    let points = |pts: &[macroquad::math::Vec2]| -> JsonValue {
+        pts.iter().map(|p| json!({ "x": p.x, "y": p.y })).collect()
+    };
+    match &obj.shape {
+        IrObjectShape::Rectangle => {}
+        IrObjectShape::Ellipse => out["ellipse"] = json!(true),
+        IrObjectShape::Point => out["point"] = json!(true),
+        IrObjectShape::Polygon(pts) => out["polygon"] = points(pts),
+        IrObjectShape::Polyline(pts) => out["polyline"] = points(pts),
+        IrObjectShape::Tile { gid } => out["gid"] = json!(gid),
+    }
+    out
+}
+
+/// Tiled `tintcolor` for `tint`: `#rrggbb`, or `#aarrggbb` unless fully opaque.
+pub fn tint_to_hex(tint: macroquad::color::Color) -> String {
+    let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let (r, g, b, a) = (byte(tint.r), byte(tint.g), byte(tint.b), byte(tint.a));
+    if a == 255 {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{a:02x}{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// `target` relative to directory `base`, with `/` separators as Tiled writes them.
+pub fn relative_path(target: &Path, base: &Path) -> Result<String, MapError> {
+    let absolute = |p: &Path| -> Result<PathBuf, MapError> {
+        let abs = std::path::absolute(p).map_err(|source| MapError::Io {
+            path: p.to_path_buf(),
+            source,
+        })?;
+        // Resolve `.` and `..` lexically so both sides compare component-wise.
+        let mut out = PathBuf::new();
+        for c in abs.components() {
+            match c {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    out.pop();
+                }
+                c => out.push(c),
+            }
+        }
+        Ok(out)
+    };
+    let target = absolute(target)?;
+    let base = absolute(base)?;
+    let common = target
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = base
+        .components()
+        .skip(common)
+        .map(|_| "..".to_string())
+        .collect();
+    for c in target.components().skip(common) {
+        let part = c.as_os_str();
+        let part = part
+            .to_str()
+            .ok_or_else(|| MapError::InvalidUtf8Path(target.clone()))?;
+        parts.push(part.to_string());
+    }
+    Ok(parts.join("/"))
+}
+
+/// Writes `contents` next to `path` first and renames it over `path`, so a crash never
+/// leaves a half-written file behind. Creates missing parent directories.
+///
+/// The temporary file is named after `path`, the process id and a per-process counter,
+/// so concurrent saves to the same path never share one.
+pub fn write_atomic(path: &Path, contents: &str) -> Result<(), MapError> {
+    let io_err = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| MapError::Io { path, source }
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(io_err(parent))?;
+    }
+    static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+    let mut tmp = path.as_os_str().to_owned();
+    let n = NEXT_TMP.fetch_add(1, Ordering::Relaxed);
+    tmp.push(format!(".{}.{n}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents).map_err(io_err(&tmp))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        io_err(path)(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_walk_up_to_the_common_ancestor() {
+        let rel = |t: &str, b: &str| relative_path(Path::new(t), Path::new(b)).expect("path");
+        assert_eq!(rel("/a/b/tiles.json", "/a/b"), "tiles.json");
+        assert_eq!(rel("/a/b/tiles.json", "/a/c/d"), "../../b/tiles.json");
+        assert_eq!(rel("/a/./b/../b/tiles.json", "/a/b/"), "tiles.json");
+    }
+
+    #[test]
+    fn concurrent_atomic_writes_use_their_own_temp_files() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mq_tiled_write_{nanos}"));
+        let path = dir.join("map.json");
+        // A stale temp file from an older writer must be left alone.
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        std::fs::write(dir.join("map.json.tmp"), "stale").expect("stale temp file");
+
+        let contents: Vec<String> = (0..8).map(|i| format!("{{\"writer\": {i}}}")).collect();
+        std::thread::scope(|s| {
+            for c in &contents {
+                s.spawn(|| write_atomic(&path, c).expect("write"));
+            }
+        });
+
+        let saved = std::fs::read_to_string(&path).expect("saved map");
+        assert!(contents.contains(&saved), "{saved}");
+        let mut left: Vec<_> = std::fs::read_dir(&dir)
+            .expect("dir")
+            .map(|e| e.expect("entry").file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["map.json", "map.json.tmp"]);
+        let stale = std::fs::read_to_string(dir.join("map.json.tmp")).expect("stale");
+        assert_eq!(stale, "stale");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
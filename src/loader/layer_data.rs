@@ -0,0 +1,290 @@
+// src/loader/layer_data.rs
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::error::MapError;
+
+/// Tile layer `data` as it appears in Tiled JSON: a gid array, or an encoded string.
+pub enum JsonLayerData {
+    /// Plain `"data": [1, 2, ...]` (`"encoding": "csv"` or absent).
+    Gids(Vec<u32>),
+    /// `"encoding": "base64"` payload, optionally compressed.
+    Encoded(String),
+    /// A gid array whose element at `index` is not a gid; the rest is skipped.
+    BadElement {
+        /// Position of the first bad element.
+        index: usize,
+        /// The element as written, e.g. `-1`, `1.5` or `"x"`.
+        found: String,
+    },
+}
+
+impl Default for JsonLayerData {
+    fn default() -> Self {
+        JsonLayerData::Gids(Vec::new())
+    }
+}
+
+// Hand-written so a bad element is reported by index instead of by byte offset.
+impl<'de> Deserialize<'de> for JsonLayerData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DataVisitor;
+
+        impl<'de> Visitor<'de> for DataVisitor {
+            type Value = JsonLayerData;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a gid array or an encoded string")
+            }
+
+            fn visit_str<E>(self, text: &str) -> Result<JsonLayerData, E> {
+                Ok(JsonLayerData::Encoded(text.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<JsonLayerData, A::Error> {
+                let mut gids = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(element) = seq.next_element::<Element>()? {
+                    match element {
+                        Element::Gid(gid) => gids.push(gid),
+                        Element::Other(found) => {
+                            while seq.next_element::<IgnoredAny>()?.is_some() {}
+                            return Ok(JsonLayerData::BadElement {
+                                index: gids.len(),
+                                found,
+                            });
+                        }
+                    }
+                }
+                Ok(JsonLayerData::Gids(gids))
+            }
+        }
+
+        deserializer.deserialize_any(DataVisitor)
+    }
+}
+
+/// One entry of a gid array: a gid, or a description of whatever else was there.
+enum Element {
+    Gid(u32),
+    Other(String),
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ElementVisitor;
+
+        impl<'de> Visitor<'de> for ElementVisitor {
+            type Value = Element;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a gid")
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Element, E> {
+                Ok(u32::try_from(v).map_or_else(|_| Element::Other(v.to_string()), Element::Gid))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Element, E> {
+                Ok(u32::try_from(v).map_or_else(|_| Element::Other(v.to_string()), Element::Gid))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Element, E> {
+                Ok(Element::Other(format!("{v:?}")))
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Element, E> {
+                Ok(Element::Other(v.to_string()))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Element, E> {
+                Ok(Element::Other(format!("{v:?}")))
+            }
+
+            fn visit_unit<E>(self) -> Result<Element, E> {
+                Ok(Element::Other("null".to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Element, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(Element::Other("an array".to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Element, A::Error> {
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                Ok(Element::Other("an object".to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(ElementVisitor)
+    }
+}
+
+/// Decodes layer data into raw gids according to the layer's `encoding` and
+/// `compression` fields (empty strings meaning "not set").
+pub fn decode_layer_data(
+    layer: &str,
+    data: JsonLayerData,
+    encoding: &str,
+    compression: &str,
+) -> Result<Vec<u32>, MapError> {
+    let invalid = |msg: String| MapError::invalid(format!("layer '{layer}': {msg}"));
+    match (data, encoding) {
+        (JsonLayerData::Gids(gids), "" | "csv") => Ok(gids),
+        (JsonLayerData::BadElement { index, found }, "" | "csv") => {
+            Err(MapError::LayerDataElement {
+                path: None,
+                layer: layer.to_string(),
+                index,
+                found,
+            })
+        }
+        (JsonLayerData::Encoded(text), "base64") => {
+            let bytes =
+                decode_base64(&text).ok_or_else(|| invalid("invalid base64 data".into()))?;
+            let bytes = match compression {
+                "" => bytes,
+                "zlib" => inflate(ZlibDecoder::new(bytes.as_slice()))
+                    .map_err(|e| invalid(format!("zlib data: {e}")))?,
+                "gzip" => inflate(GzDecoder::new(bytes.as_slice()))
+                    .map_err(|e| invalid(format!("gzip data: {e}")))?,
+                other => return Err(invalid(format!("unsupported compression '{other}'"))),
+            };
+            if bytes.len() % 4 != 0 {
+                return Err(MapError::LayerDataElement {
+                    path: None,
+                    layer: layer.to_string(),
+                    index: bytes.len() / 4,
+                    found: format!("{} leftover byte(s)", bytes.len() % 4),
+                });
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect())
+        }
+        (_, "" | "csv" | "base64") => Err(invalid(format!(
+            "data does not match encoding '{}'",
+            if encoding.is_empty() { "csv" } else { encoding }
+        ))),
+        (_, other) => Err(invalid(format!("unsupported encoding '{other}'"))),
+    }
+}
+
+fn inflate(mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+// Standard alphabet with optional `=` padding; whitespace is ignored.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let symbols: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let trimmed = symbols
+        .strip_suffix(b"==")
+        .or_else(|| symbols.strip_suffix(b"="))
+        .unwrap_or(&symbols);
+    if trimmed.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(trimmed.len() * 3 / 4);
+    for group in trimmed.chunks(4) {
+        let mut acc = 0u32;
+        for &c in group {
+            acc = (acc << 6) | value(c)?;
+        }
+        acc <<= 6 * (4 - group.len() as u32);
+        out.extend_from_slice(&acc.to_be_bytes()[1..group.len()]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIDS: [u32; 4] = [1, 2, 0, 0x8000_0003];
+
+    fn decode(data: JsonLayerData, encoding: &str, compression: &str) -> Vec<u32> {
+        decode_layer_data("ground", data, encoding, compression).expect("decode")
+    }
+
+    #[test]
+    fn decodes_every_encoding_and_compression_combination() {
+        let encoded = |s: &str| JsonLayerData::Encoded(s.to_string());
+        assert_eq!(decode(JsonLayerData::Gids(GIDS.to_vec()), "", ""), GIDS);
+        assert_eq!(
+            decode(encoded("AQAAAAIAAAAAAAAAAwAAgA=="), "base64", ""),
+            GIDS
+        );
+        assert_eq!(
+            decode(encoded("eJxjZGBgYGKAAGYGhgYAAMQAhw=="), "base64", "zlib"),
+            GIDS
+        );
+        assert_eq!(
+            decode(
+                encoded("H4sIAAAAAAACA2NkYGBgYoAAZgaGBgCVaOVREAAAAA=="),
+                "base64",
+                "gzip"
+            ),
+            GIDS
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_or_malformed_data() {
+        let encoded = || JsonLayerData::Encoded("AQAAAA==".to_string());
+        assert!(decode_layer_data("l", encoded(), "base64", "zstd").is_err());
+        assert!(decode_layer_data("l", encoded(), "base64", "zlib").is_err());
+        assert!(decode_layer_data("l", encoded(), "", "").is_err());
+        let short = JsonLayerData::Encoded("AQAA".to_string());
+        assert!(decode_layer_data("l", short, "base64", "").is_err());
+    }
+
+    #[test]
+    fn bad_elements_are_reported_by_layer_and_index() {
+        let element_error = |json: &str, encoding: &str| {
+            let data: JsonLayerData = serde_json::from_str(json).expect("layer data");
+            match decode_layer_data("ground", data, encoding, "") {
+                Err(MapError::LayerDataElement {
+                    layer,
+                    index,
+                    found,
+                    ..
+                }) if layer == "ground" => (index, found),
+                other => panic!(
+                    "expected LayerDataElement, got {:?}",
+                    other.map(|d| d.len())
+                ),
+            }
+        };
+        assert_eq!(element_error("[1, 2, 1.5, 3]", ""), (2, "1.5".to_string()));
+        assert_eq!(
+            element_error("[0, -4, \"x\"]", "csv"),
+            (1, "-4".to_string())
+        );
+        assert_eq!(
+            element_error("[4294967296]", ""),
+            (0, "4294967296".to_string())
+        );
+        // Six bytes: one whole gid, then two bytes of a truncated second one.
+        assert_eq!(
+            element_error("\"AQAAAAIA\"", "base64"),
+            (1, "2 leftover byte(s)".to_string())
+        );
+    }
+}
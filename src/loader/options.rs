@@ -0,0 +1,362 @@
+use crate::loader::report::ValidationIssue;
+use crate::spatial::CHUNK_SIZE;
+use macroquad::texture::FilterMode;
+
+/// Options controlling which parts of a map are decoded and loaded, and how.
+///
+/// Build with struct update syntax or by chaining the builder methods, e.g.
+/// `LoadOptions::default().without_invisible_layers().with_cull_padding(64.0)`.
+///
+/// Filtered layers are dropped before a [`Map`](crate::Map) is built, so they never
+/// show up in layer accessors or draw order. Layer ids are assigned over the
+/// retained layers in Tiled array order, which means ids stay contiguous but do not
+/// match the source file's layer positions once anything is filtered out; use layer
+/// names to relate loaded layers back to the file.
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    /// Layer name patterns to keep. Empty means every layer is a candidate.
+    ///
+    /// Patterns match whole names and support `*` as a wildcard (e.g. `"bg_*"`).
+    pub include_layers: Vec<String>,
+    /// Layer name patterns to drop, applied after `include_layers`.
+    pub exclude_layers: Vec<String>,
+    /// Drops every tile layer without validating or indexing its data.
+    pub skip_tile_layers: bool,
+    /// Drops every object layer.
+    pub skip_object_layers: bool,
+    /// Skips tileset texture loading (headless use). Tiles are indexed but never drawn.
+    pub skip_textures: bool,
+    /// Skips per-tile metadata (tile properties and tile objectgroups) in tilesets.
+    pub skip_tile_metadata: bool,
+    /// Drops layers that are hidden in Tiled, so they cannot be shown at runtime.
+    pub skip_invisible_layers: bool,
+    /// Skips checking tile and object gids against the tilesets' range. Unknown gids are
+    /// then kept in layer data and silently not drawn.
+    pub skip_gid_validation: bool,
+    /// Keeps decoding past recoverable problems (bad gids, missing or broken tilesets,
+    /// unsupported property types, layer size mismatches) and fails once at the end with
+    /// [`MapError::Validation`](crate::MapError::Validation) listing all of them.
+    pub collect_all_errors: bool,
+    /// Replaces layers that fail to decode (bad gids, wrong sizes, undecodable data,
+    /// broken properties) with empty placeholder layers instead of failing the load.
+    ///
+    /// Each skipped layer is reported as one
+    /// [`IssueCode::LayerSkipped`](crate::IssueCode::LayerSkipped) warning in
+    /// [`Map::load_warnings`](crate::Map::load_warnings), replacing any warnings the layer
+    /// raised before failing. Map-wide problems such as broken tilesets still fail. With
+    /// `collect_all_errors` set, errors are collected instead.
+    pub skip_broken_layers: bool,
+    /// Checks that every tileset image exists and can be opened before any layer is
+    /// decoded, reporting all missing images at once as
+    /// [`MapError::MissingImage`](crate::MapError::MissingImage). Uses `std::fs`, so
+    /// leave it off where images are not plain files (wasm).
+    pub check_images: bool,
+    /// How suspicious-but-decodable input is treated; see [`Strictness`].
+    pub strictness: Strictness,
+    /// Initial [`Map::set_cull_padding`](crate::Map::set_cull_padding) value in world
+    /// pixels. `None` keeps the default of one chunk.
+    pub cull_padding: Option<f32>,
+    /// Filter applied to tileset textures. `None` means [`FilterMode::Nearest`].
+    ///
+    /// Textures from a tileset resolver keep their own filter; see
+    /// [`Map::load_with_tileset_resolver`](crate::Map::load_with_tileset_resolver).
+    pub texture_filter: Option<FilterMode>,
+    /// Called with the current phase and its completion fraction (`0.0..=1.0`).
+    ///
+    /// Invoked at phase boundaries, once per tileset, and every few thousand indexed tiles.
+    pub on_progress: Option<fn(LoadPhase, f32)>,
+    /// Called with each warning as it is found, before loading finishes. Warnings are
+    /// also kept in [`Map::load_warnings`](crate::Map::load_warnings).
+    pub on_warning: Option<fn(&ValidationIssue)>,
+}
+
+/// How the decoder treats input it can make sense of but that looks wrong.
+///
+/// Every tolerated problem is kept as a warning in
+/// [`Map::load_warnings`](crate::Map::load_warnings), so nothing is dropped silently.
+/// Layers of an unsupported kind stay in the layer list without contents, so names and
+/// indices still match the file, and draw nothing.
+///
+/// | Problem                              | `Strict` | `Standard` | `Lenient`          |
+/// |--------------------------------------|----------|------------|--------------------|
+/// | Unknown property type                | error    | error      | value inferred     |
+/// | Gid owned by no tileset              | error    | error      | treated as empty   |
+/// | Overlapping tileset gid ranges       | error    | error      | later tileset wins |
+/// | Tileset tilecount beyond its image   | error    | error      | kept as declared   |
+/// | Tile metadata id beyond tilecount    | error    | error      | metadata dropped   |
+/// | Unsupported layer kind               | error    | kept empty | kept empty         |
+/// | Missing optional tileset field       | error    | defaulted  | defaulted          |
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strictness {
+    /// Rejects anything suspicious; meant for tooling and CI checks.
+    Strict,
+    /// Tolerates what older versions of this crate always accepted.
+    #[default]
+    Standard,
+    /// Loads whatever can be loaded; meant for shipped games reading slightly stale maps.
+    Lenient,
+}
+
+/// Coarse loading phase reported through [`LoadOptions::on_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadPhase {
+    /// Reading and decoding the map JSON.
+    Parse,
+    /// Reading and decoding external tileset files.
+    Tilesets,
+    /// Loading tileset textures.
+    Textures,
+    /// Building the spatial index from layer data.
+    Index,
+}
+
+impl LoadPhase {
+    /// All phases in the order they run.
+    pub const ALL: [LoadPhase; 4] = [
+        LoadPhase::Parse,
+        LoadPhase::Tilesets,
+        LoadPhase::Textures,
+        LoadPhase::Index,
+    ];
+
+    pub(crate) fn ordinal(self) -> usize {
+        self as usize
+    }
+}
+
+impl LoadOptions {
+    /// Returns `true` if a layer with this name passes the include/exclude filters.
+    pub fn keeps_layer(&self, name: &str) -> bool {
+        let included = self.include_layers.is_empty()
+            || self.include_layers.iter().any(|p| pattern_matches(p, name));
+        included && !self.exclude_layers.iter().any(|p| pattern_matches(p, name))
+    }
+
+    /// Sets [`LoadOptions::skip_textures`].
+    pub fn headless(mut self) -> Self {
+        self.skip_textures = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::skip_invisible_layers`].
+    pub fn without_invisible_layers(mut self) -> Self {
+        self.skip_invisible_layers = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::skip_gid_validation`].
+    pub fn without_gid_validation(mut self) -> Self {
+        self.skip_gid_validation = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::collect_all_errors`].
+    pub fn with_all_errors_collected(mut self) -> Self {
+        self.collect_all_errors = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::skip_broken_layers`].
+    pub fn with_broken_layers_skipped(mut self) -> Self {
+        self.skip_broken_layers = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::check_images`].
+    pub fn with_image_check(mut self) -> Self {
+        self.check_images = true;
+        self
+    }
+
+    /// Sets [`LoadOptions::strictness`].
+    pub fn with_strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Sets [`LoadOptions::cull_padding`].
+    pub fn with_cull_padding(mut self, padding: f32) -> Self {
+        self.cull_padding = Some(padding);
+        self
+    }
+
+    /// Sets [`LoadOptions::texture_filter`].
+    pub fn with_texture_filter(mut self, filter: FilterMode) -> Self {
+        self.texture_filter = Some(filter);
+        self
+    }
+
+    pub(crate) fn report(&self, phase: LoadPhase, fraction: f32) {
+        if let Some(on_progress) = self.on_progress {
+            on_progress(phase, fraction);
+        }
+    }
+}
+
+/// The common load settings under one name, for [`Map::load_with_config`].
+///
+/// A narrower front end to [`LoadOptions`], which it converts into; reach for
+/// `LoadOptions` directly for layer filters, error collection and callbacks. Chain the
+/// builder methods, e.g. `MapLoadConfig::default().with_invisible_layers().with_cull_margin(2)`.
+///
+/// Unlike [`Map::load`], the default config drops layers that are hidden in Tiled.
+///
+/// [`Map::load_with_config`]: crate::Map::load_with_config
+/// [`Map::load`]: crate::Map::load
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapLoadConfig {
+    /// Keeps layers that are hidden in Tiled, so they can be shown at runtime.
+    pub load_invisible_layers: bool,
+    /// Checks tile and object gids against the tilesets' range; see
+    /// [`LoadOptions::skip_gid_validation`].
+    pub validate_gids: bool,
+    /// Cull padding in whole chunks around the view; see [`LoadOptions::cull_padding`].
+    /// Must not be negative.
+    pub cull_margin: i32,
+    /// Side of a spatial index chunk in world pixels. Only
+    /// [`CHUNK_SIZE`](crate::CHUNK_SIZE) is supported for now; other values fail the load.
+    pub chunk_size: i32,
+    /// Filter applied to tileset textures.
+    pub texture_filter: FilterMode,
+}
+
+impl Default for MapLoadConfig {
+    fn default() -> Self {
+        Self {
+            load_invisible_layers: false,
+            validate_gids: true,
+            cull_margin: 1,
+            chunk_size: CHUNK_SIZE,
+            texture_filter: FilterMode::Nearest,
+        }
+    }
+}
+
+impl MapLoadConfig {
+    /// Sets [`MapLoadConfig::load_invisible_layers`].
+    pub fn with_invisible_layers(mut self) -> Self {
+        self.load_invisible_layers = true;
+        self
+    }
+
+    /// Clears [`MapLoadConfig::validate_gids`].
+    pub fn without_gid_validation(mut self) -> Self {
+        self.validate_gids = false;
+        self
+    }
+
+    /// Sets [`MapLoadConfig::cull_margin`].
+    pub fn with_cull_margin(mut self, chunks: i32) -> Self {
+        self.cull_margin = chunks;
+        self
+    }
+
+    /// Sets [`MapLoadConfig::chunk_size`].
+    pub fn with_chunk_size(mut self, chunk_size: i32) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets [`MapLoadConfig::texture_filter`].
+    pub fn with_filter_mode(mut self, filter: FilterMode) -> Self {
+        self.texture_filter = filter;
+        self
+    }
+}
+
+impl From<MapLoadConfig> for LoadOptions {
+    fn from(config: MapLoadConfig) -> Self {
+        LoadOptions {
+            skip_invisible_layers: !config.load_invisible_layers,
+            skip_gid_validation: !config.validate_gids,
+            cull_padding: Some(config.cull_margin as f32 * CHUNK_SIZE as f32),
+            texture_filter: Some(config.texture_filter),
+            ..Default::default()
+        }
+    }
+}
+
+/// Whole-string match where `*` matches any (possibly empty) run of characters.
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No wildcard at all: the prefix must be the entire name.
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_whole_names_with_wildcards() {
+        assert!(pattern_matches("ground", "ground"));
+        assert!(!pattern_matches("ground", "ground_2"));
+        assert!(pattern_matches("bg_*", "bg_far"));
+        assert!(pattern_matches("*_fg", "trees_fg"));
+        assert!(pattern_matches("a*b*c", "a__b__c"));
+        assert!(!pattern_matches("a*b*c", "a__c"));
+        assert!(pattern_matches("*", ""));
+    }
+
+    #[test]
+    fn exclude_is_applied_after_include() {
+        let options = LoadOptions {
+            include_layers: vec!["bg_*".to_string()],
+            exclude_layers: vec!["bg_debug".to_string()],
+            ..Default::default()
+        };
+        assert!(options.keeps_layer("bg_far"));
+        assert!(!options.keeps_layer("bg_debug"));
+        assert!(!options.keeps_layer("objects"));
+    }
+
+    #[test]
+    fn builder_methods_chain_onto_defaults() {
+        let options = LoadOptions::default()
+            .headless()
+            .without_invisible_layers()
+            .with_cull_padding(64.0)
+            .with_texture_filter(FilterMode::Linear)
+            .with_strictness(Strictness::Lenient);
+        assert!(options.skip_textures && options.skip_invisible_layers);
+        assert!(!options.skip_gid_validation);
+        assert_eq!(options.cull_padding, Some(64.0));
+        assert_eq!(options.texture_filter, Some(FilterMode::Linear));
+        assert_eq!(options.strictness, Strictness::Lenient);
+        assert_eq!(LoadOptions::default().strictness, Strictness::Standard);
+    }
+
+    #[test]
+    fn map_load_config_maps_onto_load_options() {
+        let options = LoadOptions::from(MapLoadConfig::default());
+        assert!(options.skip_invisible_layers && !options.skip_gid_validation);
+        assert_eq!(options.cull_padding, Some(CHUNK_SIZE as f32));
+        assert_eq!(options.texture_filter, Some(FilterMode::Nearest));
+
+        let config = MapLoadConfig::default()
+            .with_invisible_layers()
+            .without_gid_validation()
+            .with_cull_margin(2)
+            .with_filter_mode(FilterMode::Linear);
+        let options = LoadOptions::from(config);
+        assert!(!options.skip_invisible_layers && options.skip_gid_validation);
+        assert_eq!(options.cull_padding, Some(2.0 * CHUNK_SIZE as f32));
+        assert_eq!(options.texture_filter, Some(FilterMode::Linear));
+    }
+}
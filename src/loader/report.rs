@@ -0,0 +1,297 @@
+use crate::error::MapError;
+use crate::loader::options::{LoadOptions, Strictness};
+use std::fmt::{Display, Formatter};
+
+/// How serious a [`ValidationIssue`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The map loads, but something was dropped or guessed.
+    Warning,
+    /// The map cannot load as is.
+    Error,
+}
+
+/// Where in the map a [`ValidationIssue`] was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IssueLocation {
+    /// The map itself (header fields, map properties, or the file as a whole).
+    Map,
+    /// A tileset, by its `source` path as written in the map.
+    Tileset(String),
+    /// A layer, by name.
+    Layer(String),
+    /// An object, by layer name and object id.
+    Object {
+        /// Layer name.
+        layer: String,
+        /// Object id.
+        id: u32,
+    },
+}
+
+impl Display for IssueLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueLocation::Map => write!(f, "map"),
+            IssueLocation::Tileset(source) => write!(f, "tileset '{source}'"),
+            IssueLocation::Layer(name) => write!(f, "layer '{name}'"),
+            IssueLocation::Object { layer, id } => write!(f, "object {id} in layer '{layer}'"),
+        }
+    }
+}
+
+/// Machine-readable kind of a [`ValidationIssue`], stable across message rewording.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IssueCode {
+    /// No tile or tile object uses any gid of the tileset.
+    UnusedTileset,
+    /// A tile layer with only empty cells, or an object layer without objects.
+    EmptyLayer,
+    /// A string property value was converted to its declared `bool`, `int` or `float`.
+    PropertyCoerced,
+    /// A property value did not match its declared type and was dropped.
+    PropertyDropped,
+    /// A property has a type this crate does not know.
+    UnsupportedPropertyType,
+    /// A tile or object gid outside every tileset.
+    InvalidGid,
+    /// A layer kind this crate cannot draw (image layers, groups).
+    UnsupportedLayer,
+    /// A tileset lacks a field Tiled always writes.
+    MissingTilesetField,
+    /// Two tilesets claim some of the same gids.
+    OverlappingTilesets,
+    /// Tileset tile metadata names a tile id at or beyond the tileset's tile count.
+    InvalidTileId,
+    /// A tileset declares more tiles than its image holds.
+    TilesetGeometryMismatch,
+    /// A tile size, column count or tile count is zero.
+    InvalidGeometry,
+    /// Tile layer data does not cover its grid.
+    InvalidLayerSize,
+    /// Tile layer data has an element that is not a gid.
+    InvalidLayerData,
+    /// A layer failed to decode and was replaced by an empty placeholder, see
+    /// [`LoadOptions::skip_broken_layers`](crate::LoadOptions::skip_broken_layers).
+    LayerSkipped,
+    /// A tileset image does not exist or cannot be opened.
+    MissingImage,
+    /// Anything else, such as unreadable files or malformed JSON.
+    Other,
+}
+
+impl IssueCode {
+    pub(crate) fn of(err: &MapError) -> IssueCode {
+        match err {
+            MapError::UnsupportedPropertyType { .. } => IssueCode::UnsupportedPropertyType,
+            MapError::InvalidTileGid { .. } | MapError::InvalidObjectGid { .. } => {
+                IssueCode::InvalidGid
+            }
+            MapError::OverlappingTilesets { .. } => IssueCode::OverlappingTilesets,
+            MapError::TilesetGeometryMismatch { .. } => IssueCode::TilesetGeometryMismatch,
+            MapError::InvalidGeometry { .. } => IssueCode::InvalidGeometry,
+            MapError::InvalidLayerSize { .. } => IssueCode::InvalidLayerSize,
+            MapError::LayerDataElement { .. } => IssueCode::InvalidLayerData,
+            MapError::MissingImage { .. } => IssueCode::MissingImage,
+            _ => IssueCode::Other,
+        }
+    }
+}
+
+/// One problem found while decoding a map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+    /// Error or warning.
+    pub severity: Severity,
+    /// What kind of problem this is.
+    pub code: IssueCode,
+    /// Where the problem is.
+    pub location: IssueLocation,
+    /// Human-readable description.
+    pub message: String,
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity} in {}: {}", self.location, self.message)
+    }
+}
+
+/// Every problem found in one pass over a map, in file order.
+///
+/// Returned by [`validate_map_file`](crate::validate_map_file), and carried by
+/// [`MapError::Validation`] when loading with
+/// [`LoadOptions::collect_all_errors`](crate::LoadOptions::collect_all_errors).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Issues in the order they were found.
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no issue is an error (warnings are allowed).
+    pub fn is_ok(&self) -> bool {
+        !self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    /// Issues with [`Severity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} problem(s) found", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Decoder-side sink deciding whether a recoverable error stops decoding.
+pub(crate) struct Issues {
+    collect: bool,
+    strictness: Strictness,
+    on_warning: Option<fn(&ValidationIssue)>,
+    report: ValidationReport,
+}
+
+impl Issues {
+    pub(crate) fn new(options: &LoadOptions) -> Self {
+        Issues {
+            collect: options.collect_all_errors,
+            strictness: options.strictness,
+            on_warning: options.on_warning,
+            report: ValidationReport::default(),
+        }
+    }
+
+    /// Records a problem that never stops decoding.
+    pub(crate) fn warn(&mut self, code: IssueCode, location: IssueLocation, message: String) {
+        self.push(Severity::Warning, code, location, message);
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: IssueCode,
+        location: IssueLocation,
+        message: String,
+    ) {
+        let issue = ValidationIssue {
+            severity,
+            code,
+            location,
+            message,
+        };
+        if severity == Severity::Warning {
+            if let Some(on_warning) = self.on_warning {
+                on_warning(&issue);
+            }
+        }
+        self.report.issues.push(issue);
+    }
+
+    /// For problems the decoder can work around: from strictness `tolerated_from` on,
+    /// records `err` as a warning and returns `true` so the caller applies its fallback.
+    /// Stricter modes treat it like [`Issues::recover`] and return `false`.
+    pub(crate) fn tolerate(
+        &mut self,
+        tolerated_from: Strictness,
+        code: IssueCode,
+        location: impl FnOnce() -> IssueLocation,
+        err: MapError,
+    ) -> Result<bool, MapError> {
+        if self.strictness < tolerated_from {
+            self.recover_as(code, location, Err::<(), _>(err))?;
+            return Ok(false);
+        }
+        self.warn(code, location(), err.to_string());
+        Ok(true)
+    }
+
+    /// Passes `Ok` values through. When collecting, records an error as an issue at
+    /// `location` and yields `None` so the caller can skip the broken item; otherwise
+    /// returns the error.
+    pub(crate) fn recover<T>(
+        &mut self,
+        location: impl FnOnce() -> IssueLocation,
+        result: Result<T, MapError>,
+    ) -> Result<Option<T>, MapError> {
+        let code = result
+            .as_ref()
+            .err()
+            .map_or(IssueCode::Other, IssueCode::of);
+        self.recover_as(code, location, result)
+    }
+
+    /// Like [`Issues::recover`] for problems found together: when collecting, records
+    /// each one; otherwise returns the only error, or all of them as
+    /// [`MapError::Validation`] when there are several, after the warnings found so far.
+    pub(crate) fn recover_all(
+        &mut self,
+        errors: Vec<(IssueLocation, MapError)>,
+    ) -> Result<(), MapError> {
+        if self.collect || errors.len() < 2 {
+            for (location, err) in errors {
+                self.recover(|| location, Err::<(), _>(err))?;
+            }
+            return Ok(());
+        }
+        let mut report = std::mem::take(&mut self.report);
+        report
+            .issues
+            .extend(errors.into_iter().map(|(location, err)| ValidationIssue {
+                severity: Severity::Error,
+                code: IssueCode::of(&err),
+                location,
+                message: err.to_string(),
+            }));
+        Err(MapError::Validation { path: None, report })
+    }
+
+    fn recover_as<T>(
+        &mut self,
+        code: IssueCode,
+        location: impl FnOnce() -> IssueLocation,
+        result: Result<T, MapError>,
+    ) -> Result<Option<T>, MapError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.collect => {
+                self.push(Severity::Error, code, location(), err.to_string());
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Number of issues recorded so far, for [`Issues::truncate`].
+    pub(crate) fn len(&self) -> usize {
+        self.report.issues.len()
+    }
+
+    /// Drops the issues recorded after the first `len`, e.g. those of an item that was
+    /// discarded as a whole. `on_warning` has already seen dropped warnings.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.report.issues.truncate(len);
+    }
+
+    /// Ends decoding: the warnings, unless errors were collected.
+    pub(crate) fn finish(self) -> Result<Vec<ValidationIssue>, MapError> {
+        if self.report.is_ok() {
+            Ok(self.report.issues)
+        } else {
+            Err(MapError::Validation {
+                path: None,
+                report: self.report,
+            })
+        }
+    }
+}
@@ -1,29 +1,81 @@
+use crate::autotile::*;
+use crate::command::{DrawCommand, DrawParams, TileRegion};
+use crate::gid_ranges::GidRanges;
 use crate::ir_map::*;
 use crate::loader::json_loader::*;
+use crate::loader::json_writer::{object_to_json, relative_path, tint_to_hex, write_atomic};
+use crate::loader::options::{LoadOptions, LoadPhase, MapLoadConfig, Strictness};
+use crate::loader::report::{IssueCode, IssueLocation, Issues, ValidationIssue};
+use crate::nav::{trace_walkable_contours, NavOptions};
+use crate::render::atlas::{compose_atlas, pack_shelves};
 use crate::render::*;
-use crate::spatial::{world_to_chunk, GlobalIndex, LayerIdx, TileId, CHUNK_SIZE};
+use crate::spatial::{
+    world_to_chunk, ChunkCoord, GlobalChunk, GlobalIndex, LayerIdx, ObjectIndex, ObjectKey,
+    TileHandle, TileId, CHUNK_SIZE, FLIP_H, FLIP_V, GID_MASK,
+};
+use crate::triggers::Triggers;
 use crate::MapError;
 use macroquad::prelude::*;
-use std::collections::HashMap;
-use std::path::Path;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 
 /// Stable layer identifier used by the renderer draw order.
 ///
 /// The value maps to Tiled layer array order in the loaded map.
 pub type LayerId = u32;
 
+/// A loaded tileset atlas.
 pub struct TilesetInfo {
+    /// Tileset name from Tiled (may be empty).
+    pub name: String,
+    /// First global tile id assigned to this tileset.
     pub first_gid: u32,
-    #[allow(dead_code)]
+    /// External tileset file as referenced by the map, relative to the map directory.
+    /// Empty for tilesets added in code; such maps cannot be saved.
+    pub source: String,
+    /// Atlas image path as written in the tileset, relative to the map directory.
+    pub image: String,
+    /// Number of tiles in the atlas.
     pub tilecount: u32,
+    /// Atlas column count.
     pub cols: u32,
-    pub tex: Texture2D,
+    /// `None` when loaded with [`LoadOptions::skip_textures`].
+    pub tex: Option<Texture2D>,
+    /// Tile width (pixels).
     pub tile_w: u32,
+    /// Tile height (pixels).
     pub tile_h: u32,
+    /// Pixel spacing between tiles.
     pub spacing: u32,
+    /// Pixel margin around the atlas.
     pub margin: u32,
 }
 
+impl TilesetInfo {
+    /// Atlas source rect of the tile with local id `local_id`, honouring margin and spacing.
+    pub fn source_rect(&self, local_id: u32) -> Rect {
+        // Loaded tilesets always have columns; guard hand-built ones against division by 0.
+        let cols = self.cols.max(1);
+        let col = local_id % cols;
+        let row = local_id / cols;
+        Rect::new(
+            (self.margin + col * (self.tile_w + self.spacing)) as f32,
+            (self.margin + row * (self.tile_h + self.spacing)) as f32,
+            self.tile_w as f32,
+            self.tile_h as f32,
+        )
+    }
+
+    // `source_rect` of every tile, indexed by local id.
+    fn source_rects(&self) -> Box<[Rect]> {
+        (0..self.tilecount)
+            .map(|local| self.source_rect(local))
+            .collect()
+    }
+}
+
 /// A Tiled object layer parsed from the map.
 ///
 /// Stable API: this struct is exposed for inspection/querying (`Map::object_layers`),
@@ -37,12 +89,17 @@ pub struct ObjectLayer {
     pub visible: bool,
     /// Opacity from Tiled (0.0..=1.0).
     pub opacity: f32,
+    /// Tint from Tiled (`tintcolor`), `WHITE` when unset; opacity multiplies its alpha.
+    pub tint: Color,
     /// Layer offset in world coordinates.
     pub offset: Vec2,
     /// Custom layer properties.
     pub properties: Properties,
     /// Parsed objects in this layer.
     pub objects: Vec<IrObject>,
+    /// Order tile objects are drawn in. Top-down sorting only applies to the culled
+    /// objects of each frame, so its cost follows what is on screen.
+    pub draw_order: ObjectDrawOrder,
     bucket_layer: LayerIdx,
     // Separate dedupe buffers let tile-object rendering and debug overlay
     // each draw an object once per frame, using the same frame stamp.
@@ -50,11 +107,80 @@ pub struct ObjectLayer {
     seen_stamp_debug: Vec<u32>,
 }
 
+/// A game-side sprite drawn inside an object layer's pass by [`Map::draw_interleaved`].
+///
+/// In [`ObjectDrawOrder::TopDown`] layers the sprite joins the y-sort of that layer's tile
+/// objects (compare against the object's `y`, i.e. its bottom edge). In
+/// [`ObjectDrawOrder::Index`] layers sprites are drawn after the layer's objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InjectedSprite {
+    /// Object layer the sprite is drawn with.
+    pub layer: LayerId,
+    /// World-space y used as the sort key.
+    pub y: f32,
+}
+
+/// An object together with the object layer that owns it.
+#[derive(Clone, Copy)]
+pub struct ObjectRef<'a> {
+    /// Owning object layer.
+    pub layer: &'a ObjectLayer,
+    /// The object itself.
+    pub object: &'a IrObject,
+}
+
+/// Read-only view of one tile layer, yielded by [`Map::iter_tile_layers`].
 #[derive(Clone, Copy)]
-struct TileLayerDrawInfo {
+pub struct TileLayerRef<'a> {
+    /// Index in Tiled layer array order.
+    pub id: LayerIdx,
+    /// Layer name from Tiled.
+    pub name: &'a str,
+    /// Current visibility.
+    pub visible: bool,
+    /// Current opacity (0.0..=1.0).
+    pub opacity: f32,
+    /// Width in cells.
+    pub width: usize,
+    /// Height in cells.
+    pub height: usize,
+    /// Raw gids in row-major order (may include Tiled flip flags).
+    pub data: &'a [u32],
+    /// Custom layer properties.
+    pub properties: &'a Properties,
+}
+
+/// A tile found by [`Map::find_tiles`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileMatch {
+    /// Layer holding the tile.
+    pub layer: LayerIdx,
+    /// Column in the layer grid.
+    pub col: usize,
+    /// Row in the layer grid.
+    pub row: usize,
+    /// Top-left world position (layer offset applied).
+    pub world: Vec2,
+    /// Raw gid, including flip flags.
+    pub gid: u32,
+}
+
+/// Pick tolerance in world pixels for shapes without area (points, polylines).
+const OBJECT_PICK_RADIUS: f32 = 4.0;
+// Largest PNG side `Map::export_as_png` renders: macroquad images store sizes as `u16`.
+const MAX_EXPORT_SIDE: u32 = u16::MAX as u32;
+
+struct TileLayer {
     layer_id: LayerIdx,
     visible: bool,
     opacity: f32,
+    tint: Color,
+    width: usize,
+    /// Raw gids in row-major order (may include Tiled flip flags).
+    data: Vec<u32>,
+    /// Index handle of each non-empty cell, parallel to `data`.
+    handles: Vec<Option<TileHandle>>,
+    properties: Properties,
 }
 
 #[derive(Clone, Copy)]
@@ -70,9 +196,31 @@ enum LayerKindInfo {
 struct MapRenderer {
     debug_draw: bool,
     cull_padding: f32,
+    // Filter applied to tileset textures on load and on replacement.
+    texture_filter: FilterMode,
+    // Texture shared by the tilesets `Map::build_atlas` packed.
+    atlas: Option<Texture2D>,
     frame_stamp: u32,
+    // Reused per object layer each frame to avoid reallocating the sort buffer.
+    object_draw_items: Vec<ObjectDrawItem>,
+    object_draw_overrides: HashMap<u32, ObjectDrawOverride>,
+    // Occupied visible chunks of the current draw call, taken for a call and put back
+    // after like `ysort_scratch`.
+    chunk_coords: Cell<Vec<ChunkCoord>>,
+    // Sorted occupied chunks of `Map::try_each_chunk_in_view` when it walks the buckets,
+    // reused the same way.
+    visit_scratch: Cell<Vec<ChunkCoord>>,
+    // Y-sort buffer of the `&self` draw paths: taken for a call and put back after, so a
+    // nested draw only misses the reuse.
+    ysort_scratch: Cell<Vec<(usize, DrawCommand)>>,
+    // Tiles of `Map::draw_tile_columns_sorted` keyed by grid cell, reused the same way.
+    column_scratch: Cell<Vec<((i32, i32), DrawCommand)>>,
 }
 
+/// Custom renderer for one tile object, called with the object and its world-space
+/// anchor (bottom-left, layer offset applied) instead of the default sprite draw.
+pub type ObjectDrawOverride = Box<dyn FnMut(&IrObject, Vec2)>;
+
 impl MapRenderer {
     fn new() -> Self {
         Self::default()
@@ -101,7 +249,37 @@ impl Default for MapRenderer {
         Self {
             debug_draw: false,
             cull_padding: CHUNK_SIZE as f32,
+            texture_filter: FilterMode::Nearest,
+            atlas: None,
             frame_stamp: 0,
+            object_draw_items: Vec::new(),
+            object_draw_overrides: HashMap::new(),
+            chunk_coords: Cell::new(Vec::new()),
+            visit_scratch: Cell::new(Vec::new()),
+            ysort_scratch: Cell::new(Vec::new()),
+            column_scratch: Cell::new(Vec::new()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ObjectDrawItem {
+    Object { object_idx: usize, origin: Vec2 },
+    Sprite { sprite_idx: usize, y: f32 },
+}
+
+impl ObjectDrawItem {
+    fn y(&self) -> f32 {
+        match self {
+            ObjectDrawItem::Object { origin, .. } => origin.y,
+            ObjectDrawItem::Sprite { y, .. } => *y,
+        }
+    }
+
+    fn order_key(&self) -> (u8, usize) {
+        match self {
+            ObjectDrawItem::Object { object_idx, .. } => (0, *object_idx),
+            ObjectDrawItem::Sprite { sprite_idx, .. } => (1, *sprite_idx),
         }
     }
 }
@@ -148,6 +326,20 @@ fn ensure_object_layer_stamp_invariant(layer: &mut ObjectLayer) {
     debug_assert_eq!(layer.seen_stamp_debug.len(), object_count);
 }
 
+/// How tiles inside each visible chunk are ordered when drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkRenderMode {
+    /// Tiles are drawn in index insertion order (the default).
+    #[default]
+    Standard,
+    /// Tiles of `base_layer` are drawn by ascending bottom edge within each chunk, so
+    /// taller tiles lower on screen overlap the ones above them. Other layers draw normally.
+    YSort {
+        /// Layer whose tiles are sorted.
+        base_layer: LayerIdx,
+    },
+}
+
 /// Loaded Tiled map with rendering helpers.
 ///
 /// Coordinate contract:
@@ -161,12 +353,36 @@ fn ensure_object_layer_stamp_invariant(layer: &mut ObjectLayer) {
 pub struct Map {
     index: GlobalIndex,
     tilesets: Vec<TilesetInfo>,
+    // Atlas rect of every tile, parallel to `tilesets`, so draws skip the grid math.
+    source_rects: Vec<Box<[Rect]>>,
+    // Tileset name -> position in `tilesets`; the first tileset wins on duplicates.
+    tileset_idx_by_name: HashMap<String, usize>,
     object_layers: Vec<ObjectLayer>,
+    object_index: ObjectIndex,
     renderer: MapRenderer,
-    gid_lut: Vec<u16>, //lookup table for tile GIDs to tileset indices
-    tile_layers: Vec<TileLayerDrawInfo>,
+    // Gid -> position in `tilesets`.
+    gids: GidRanges,
+    tile_layers: Vec<TileLayer>,
     draw_order: Vec<LayerId>,
     layer_kind_by_id: HashMap<LayerId, LayerKindInfo>,
+    layer_offsets: HashMap<String, Vec2>,
+    // Names indexed by `LayerIdx` (Tiled array order, including invisible layers).
+    layer_names: Vec<String>,
+    layer_idx_by_name: HashMap<String, LayerIdx>,
+    // Clean gid -> tile class, only for tiles with a non-empty class.
+    tile_classes: HashMap<u32, String>,
+    // Clean gid -> tile properties, only for tiles that have any.
+    tile_properties: HashMap<u32, Properties>,
+    render_mode: ChunkRenderMode,
+    properties: Properties,
+    load_warnings: Vec<ValidationIssue>,
+    // Directory tileset `source` and image paths are relative to.
+    map_dir: PathBuf,
+    // Map size in tiles: as given to `Map::new`, or the largest loaded tile layer.
+    width: u32,
+    height: u32,
+    tile_w: u32,
+    tile_h: u32,
 }
 
 impl Map {
@@ -178,6 +394,386 @@ impl Map {
         Self::from_ir(ir, &base).await
     }
 
+    /// Loads a map like [`Map::load`], filtering layers and assets per `options`.
+    ///
+    /// See [`LoadOptions`] for how filtered layers affect layer ids.
+    pub async fn load_with(path: &str, options: LoadOptions) -> Result<Self, MapError> {
+        let (ir, base) = decode_map_file_to_ir_with(path, &options)?;
+        Self::from_ir_with(ir, &base, &options).await
+    }
+
+    /// Loads a map like [`Map::load_with`], with `config` turned into [`LoadOptions`].
+    ///
+    /// Fails before reading anything if `config.chunk_size` is not [`CHUNK_SIZE`], the
+    /// only chunk size the spatial index supports, or if `config.cull_margin` is
+    /// negative; load errors are [`MapError`]s.
+    pub async fn load_with_config(path: &str, config: MapLoadConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.chunk_size == CHUNK_SIZE,
+            "unsupported chunk size {}; maps always use {CHUNK_SIZE}",
+            config.chunk_size
+        );
+        anyhow::ensure!(
+            config.cull_margin >= 0,
+            "negative cull margin {}; use 0 to cull to the view",
+            config.cull_margin
+        );
+        Ok(Self::load_with(path, config.into()).await?)
+    }
+
+    /// Loads a map like [`Map::load`], getting each tileset texture from `resolver`
+    /// instead of reading it from disk.
+    ///
+    /// `resolver` receives the tileset image path relative to the map directory, as
+    /// written in the tileset, and may serve textures from `include_bytes!` data, an
+    /// asset manager or test doubles. Its errors become [`MapError::TextureLoad`] with the
+    /// original error as `source`; any error type works, `anyhow::Error` included.
+    ///
+    /// Resolved textures are used as returned: [`LoadOptions::texture_filter`] only applies
+    /// to textures the map loads itself, so set the filter in `resolver` if needed.
+    pub async fn load_with_tileset_resolver<E>(
+        path: &str,
+        resolver: impl Fn(&str) -> Result<Texture2D, E>,
+    ) -> Result<Self, MapError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::load_with_tileset_resolver_with_options(path, LoadOptions::default(), resolver).await
+    }
+
+    /// Like [`Map::load_with_tileset_resolver`], filtering layers and assets and
+    /// handling problems per `options`, as [`Map::load_with`] does.
+    pub async fn load_with_tileset_resolver_with_options<E>(
+        path: &str,
+        options: LoadOptions,
+        resolver: impl Fn(&str) -> Result<Texture2D, E>,
+    ) -> Result<Self, MapError>
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (ir, base) = decode_map_file_to_ir_with(path, &options)?;
+        let resolve = |image: &str| resolver(image).map_err(Into::into);
+        let textures = TextureSource::Resolver(&resolve);
+        Self::from_ir_textured(ir, &base, &options, textures).await
+    }
+
+    /// Loads the map file at `path` synchronously and headless: layers, objects,
+    /// properties and the spatial index are built, tileset textures are not loaded
+    /// (`TilesetInfo::tex` is `None`).
+    ///
+    /// Suits tools, servers and tests; no macroquad window is needed. Drawing such a map
+    /// draws nothing until textures are supplied, e.g. via [`Map::execute_draw_commands`].
+    pub fn load_from_file(path: &str) -> Result<Self, MapError> {
+        let options = Self::headless_options();
+        let (ir, base) = decode_map_file_to_ir_with(path, &options)?;
+        Self::from_ir_headless(ir, &base, &options)
+    }
+
+    /// Like [`Map::load_from_file`], for map JSON already in memory. External tileset
+    /// `source` paths resolve against `base_dir`.
+    pub fn load_from_str(json: &str, base_dir: impl AsRef<Path>) -> Result<Self, MapError> {
+        let options = Self::headless_options();
+        let base_dir = base_dir.as_ref();
+        let ir = decode_map_str_to_ir_with(json, Path::new("<string>"), base_dir, &options)?;
+        Self::from_ir_headless(ir, base_dir, &options)
+    }
+
+    /// Serializes the map to Tiled JSON, with tileset `source` paths rewritten relative
+    /// to `base_dir` (the directory the JSON will be saved in).
+    ///
+    /// Writes the current state: edited tiles, layer visibility, opacity, tint and
+    /// offsets. Tilesets stay external files and are not rewritten. Maps holding layers
+    /// the loader keeps only as empty placeholders (image and group layers), or tilesets
+    /// added in code without a `source`, cannot be represented and fail rather than
+    /// being written without them.
+    pub fn serialize_to_json(&self, base_dir: &Path) -> Result<String, MapError> {
+        let mut tilesets = Vec::with_capacity(self.tilesets.len());
+        for ts in &self.tilesets {
+            if ts.source.is_empty() {
+                return Err(MapError::invalid(format!(
+                    "tileset '{}' has no source file to reference",
+                    ts.name
+                )));
+            }
+            let source = relative_path(&self.map_dir.join(&ts.source), base_dir)?;
+            tilesets.push(serde_json::json!({ "firstgid": ts.first_gid, "source": source }));
+        }
+
+        let mut layers = Vec::with_capacity(self.draw_order.len());
+        for &id in &self.draw_order {
+            let name = self.layer_names[id as usize].as_str();
+            let offset = self.layer_offset(name).unwrap_or(Vec2::ZERO);
+            let mut layer = serde_json::json!({
+                "id": id + 1,
+                "name": name,
+                "offsetx": offset.x,
+                "offsety": offset.y,
+            });
+            match self.layer_kind_by_id.get(&id) {
+                Some(LayerKindInfo::Tiles(i)) => {
+                    let tl = &self.tile_layers[*i];
+                    layer["type"] = "tilelayer".into();
+                    layer["width"] = tl.width.into();
+                    layer["height"] = tl.data.len().checked_div(tl.width).unwrap_or(0).into();
+                    layer["data"] = tl.data.clone().into();
+                    layer["visible"] = tl.visible.into();
+                    layer["opacity"] = tl.opacity.into();
+                    if tl.tint != WHITE {
+                        layer["tintcolor"] = tint_to_hex(tl.tint).into();
+                    }
+                    layer["properties"] = tl.properties.to_json();
+                }
+                Some(LayerKindInfo::Objects(i)) => {
+                    let ol = &self.object_layers[*i];
+                    layer["type"] = "objectgroup".into();
+                    layer["draworder"] = match ol.draw_order {
+                        ObjectDrawOrder::TopDown => "topdown",
+                        ObjectDrawOrder::Index => "index",
+                    }
+                    .into();
+                    layer["objects"] = ol.objects.iter().map(object_to_json).collect();
+                    layer["visible"] = ol.visible.into();
+                    layer["opacity"] = ol.opacity.into();
+                    if ol.tint != WHITE {
+                        layer["tintcolor"] = tint_to_hex(ol.tint).into();
+                    }
+                    layer["properties"] = ol.properties.to_json();
+                }
+                Some(LayerKindInfo::Unsupported) | None => {
+                    return Err(MapError::invalid(format!(
+                        "layer '{name}' is of a kind that was not loaded and cannot be saved"
+                    )));
+                }
+            }
+            layers.push(layer);
+        }
+
+        let map = serde_json::json!({
+            "type": "map",
+            "orientation": "orthogonal",
+            "renderorder": "right-down",
+            "infinite": false,
+            "width": self.width,
+            "height": self.height,
+            "tilewidth": self.tile_w,
+            "tileheight": self.tile_h,
+            "properties": self.properties.to_json(),
+            "tilesets": tilesets,
+            "layers": layers,
+        });
+        serde_json::to_string_pretty(&map)
+            .map_err(|e| MapError::invalid(format!("map could not be serialized: {e}")))
+    }
+
+    /// Saves the map as Tiled JSON at `path` (see [`Map::serialize_to_json`] for
+    /// `base_dir`), creating missing parent directories.
+    ///
+    /// The file is written to a temporary sibling and renamed into place, so an existing
+    /// map is never left half-written.
+    pub fn save_to_file(&self, path: &Path, base_dir: &Path) -> Result<(), MapError> {
+        let json = self.serialize_to_json(base_dir)?;
+        write_atomic(path, &json)
+    }
+
+    /// Creates an empty `width x height` map (in tiles) with `tile_w x tile_h` pixel
+    /// tiles, for building maps in code with [`Map::add_tileset`], [`Map::add_layer`] and
+    /// [`Map::set_tile`].
+    ///
+    /// Fails with [`MapError::InvalidGeometry`] if `tile_w` or `tile_h` is zero.
+    pub fn new(width: u32, height: u32, tile_w: u32, tile_h: u32) -> Result<Self, MapError> {
+        let sizes = [("tilewidth", tile_w), ("tileheight", tile_h)];
+        if let Some((field, _)) = sizes.iter().find(|(_, value)| *value == 0) {
+            return Err(MapError::InvalidGeometry {
+                path: None,
+                entity: "map".to_string(),
+                field: field.to_string(),
+            });
+        }
+        let ir = IrMap {
+            tile_w,
+            tile_h,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![],
+            warnings: Vec::new(),
+        };
+        let options = Self::headless_options();
+        let mut map = Self::from_ir_headless(ir, Path::new("."), &options)?;
+        map.width = width;
+        map.height = height;
+        Ok(map)
+    }
+
+    /// Appends a tileset. Its gids must start after every gid already in use, matching
+    /// how Tiled assigns `firstgid`.
+    ///
+    /// `tileset.tex` may be `None` (headless) and supplied later through
+    /// [`Map::replace_tileset_texture`].
+    pub fn add_tileset(&mut self, tileset: TilesetInfo) -> Result<(), MapError> {
+        let what = format!("tileset '{}'", tileset.name);
+        let sizes = [
+            ("tilewidth", tileset.tile_w),
+            ("tileheight", tileset.tile_h),
+            ("columns", tileset.cols),
+            ("tilecount", tileset.tilecount),
+        ];
+        if let Some((field, _)) = sizes.iter().find(|(_, value)| *value == 0) {
+            return Err(MapError::InvalidGeometry {
+                path: None,
+                entity: what,
+                field: field.to_string(),
+            });
+        }
+        let max_gid = self.max_gid();
+        if tileset.first_gid <= max_gid {
+            return Err(MapError::invalid(format!(
+                "{what} firstgid {} must be above the current max gid {max_gid}",
+                tileset.first_gid
+            )));
+        }
+        let Ok(slot) = u16::try_from(self.tilesets.len()) else {
+            return Err(MapError::invalid(format!(
+                "{what} exceeds the tileset limit"
+            )));
+        };
+        self.gids.insert(tileset.first_gid, tileset.tilecount, slot);
+        self.tileset_idx_by_name
+            .entry(tileset.name.clone())
+            .or_insert(self.tilesets.len());
+        self.source_rects.push(tileset.source_rects());
+        self.tilesets.push(tileset);
+        Ok(())
+    }
+
+    /// Appends a visible tile layer named `name` on top of the existing layers.
+    ///
+    /// `data` holds raw gids row by row and must cover the whole map
+    /// (`width * height` cells); gids must belong to added tilesets.
+    pub fn add_layer(&mut self, name: &str, data: Vec<u32>) -> Result<(), MapError> {
+        let expected = self.width as usize * self.height as usize;
+        if data.len() != expected {
+            return Err(MapError::InvalidLayerSize {
+                path: None,
+                layer: name.to_string(),
+                expected,
+                actual: data.len(),
+            });
+        }
+        if self.layer_idx_by_name.contains_key(name) {
+            return Err(MapError::invalid(format!("layer '{name}' already exists")));
+        }
+        let max_gid = self.max_gid();
+        if let Some(&bad) = data.iter().find(|&&raw| {
+            let gid = raw & GID_MASK;
+            gid != 0 && self.gids.get(gid).is_none()
+        }) {
+            return Err(MapError::InvalidTileGid {
+                path: None,
+                layer: name.to_string(),
+                gid: bad & GID_MASK,
+                raw_gid: bad,
+                max_gid,
+            });
+        }
+
+        let lz = self.layer_names.len() as LayerIdx;
+        let mut layer = TileLayer {
+            layer_id: lz,
+            visible: true,
+            opacity: 1.0,
+            tint: WHITE,
+            width: self.width as usize,
+            handles: vec![None; data.len()],
+            data,
+            properties: Properties::default(),
+        };
+        let cells = 0..layer.data.len();
+        Self::index_tiles(
+            &mut self.index,
+            &mut layer,
+            cells,
+            Vec2::ZERO,
+            self.tile_w,
+            self.tile_h,
+        );
+        self.draw_order.push(lz as LayerId);
+        self.layer_kind_by_id
+            .insert(lz as LayerId, LayerKindInfo::Tiles(self.tile_layers.len()));
+        self.layer_offsets.insert(name.to_string(), Vec2::ZERO);
+        self.layer_idx_by_name.insert(name.to_string(), lz);
+        self.layer_names.push(name.to_string());
+        self.tile_layers.push(layer);
+        Ok(())
+    }
+
+    /// Loads a map like [`Map::load`], reading the map and external tileset JSON through
+    /// macroquad's `load_string` instead of `std::fs`, so it also works on wasm32 (and
+    /// honours `set_pc_assets_folder` on desktop).
+    ///
+    /// Unlike [`Map::load_from_file`] this loads textures and needs a macroquad window.
+    /// Read failures are reported as [`MapError::Io`] with the failing path.
+    pub async fn load_from_file_async(path: &str) -> Result<Self, MapError> {
+        Self::load_through(path, LoadOptions::default(), async |p: &Path| {
+            let path_str = p
+                .to_str()
+                .ok_or_else(|| MapError::InvalidUtf8Path(p.to_path_buf()))?;
+            load_string(path_str).await.map_err(|e| MapError::Io {
+                path: p.to_path_buf(),
+                source: std::io::Error::other(e.to_string()),
+            })
+        })
+        .await
+    }
+
+    // Async load with every JSON file read through `read`.
+    async fn load_through<F>(path: &str, options: LoadOptions, read: F) -> Result<Self, MapError>
+    where
+        F: AsyncFnMut(&Path) -> Result<String, MapError>,
+    {
+        let (ir, base) = decode_map_file_to_ir_async(path, &options, read).await?;
+        Self::from_ir_with(ir, &base, &options).await
+    }
+
+    fn headless_options() -> LoadOptions {
+        LoadOptions {
+            skip_textures: true,
+            ..Default::default()
+        }
+    }
+
+    // With textures skipped the build never waits on the macroquad event loop, so the
+    // future completes on its first poll.
+    fn from_ir_headless(
+        ir: IrMap,
+        base_dir: &Path,
+        options: &LoadOptions,
+    ) -> Result<Self, MapError> {
+        debug_assert!(options.skip_textures);
+        block_on_ready(Self::from_ir_with(ir, base_dir, options))
+    }
+
+    /// Lists the tileset image paths of the map file at `path` without loading textures
+    /// or layer data, e.g. for asset bundling or CI checks.
+    ///
+    /// Paths are relative to the map's directory, one per tileset in firstgid order.
+    pub fn tileset_image_paths_from_file(path: &str) -> Result<Vec<String>, MapError> {
+        let options = LoadOptions {
+            skip_textures: true,
+            skip_tile_layers: true,
+            skip_object_layers: true,
+            skip_tile_metadata: true,
+            ..Default::default()
+        };
+        let (ir, _) = decode_map_file_to_ir_with(path, &options)?;
+        Ok(ir
+            .tilesets
+            .into_iter()
+            .map(|ts| match ts {
+                IrTileset::Atlas { image, .. } => image,
+            })
+            .collect())
+    }
+
     #[doc(hidden)]
     pub fn __new_for_stamp_overflow_test(object_count: usize) -> Self {
         let mut index = GlobalIndex::new();
@@ -211,9 +807,11 @@ impl Map {
             name: "test".to_string(),
             visible: true,
             opacity: 1.0,
+            tint: WHITE,
             offset: Vec2::ZERO,
             properties: Properties::default(),
             objects,
+            draw_order: ObjectDrawOrder::TopDown,
             bucket_layer: 0,
             seen_stamp_tiles: vec![],
             seen_stamp_debug: vec![],
@@ -226,12 +824,28 @@ impl Map {
         Self {
             index,
             tilesets: vec![],
+            source_rects: vec![],
+            tileset_idx_by_name: HashMap::new(),
             object_layers: vec![object_layer],
+            object_index: ObjectIndex::default(),
             renderer: MapRenderer::default(),
-            gid_lut: vec![],
+            gids: GidRanges::default(),
             tile_layers: vec![],
             draw_order: vec![0],
             layer_kind_by_id,
+            layer_offsets: HashMap::new(),
+            layer_names: vec!["test".to_string()],
+            layer_idx_by_name: HashMap::from([("test".to_string(), 0)]),
+            tile_classes: HashMap::new(),
+            tile_properties: HashMap::new(),
+            properties: Properties::default(),
+            load_warnings: Vec::new(),
+            map_dir: PathBuf::from("."),
+            render_mode: ChunkRenderMode::Standard,
+            width: 0,
+            height: 0,
+            tile_w: 16,
+            tile_h: 16,
         }
     }
 
@@ -254,209 +868,160 @@ impl Map {
     }
 
     pub(crate) async fn from_ir(ir: IrMap, base_dir: &Path) -> Result<Self, MapError> {
-        let mut tilesets = Vec::new();
-
-        let mut max_gid = 0u32;
-        for t in &ir.tilesets {
-            match t {
-                IrTileset::Atlas {
-                    first_gid,
-                    tilecount,
-                    ..
-                } => {
-                    max_gid = max_gid.max(*first_gid + tilecount - 1);
-                }
-            }
+        Self::from_ir_with(ir, base_dir, &LoadOptions::default()).await
+    }
+
+    pub(crate) async fn from_ir_with(
+        ir: IrMap,
+        base_dir: &Path,
+        options: &LoadOptions,
+    ) -> Result<Self, MapError> {
+        Self::from_ir_textured(ir, base_dir, options, TextureSource::Files).await
+    }
+
+    async fn from_ir_textured(
+        ir: IrMap,
+        base_dir: &Path,
+        options: &LoadOptions,
+        textures: TextureSource<'_>,
+    ) -> Result<Self, MapError> {
+        let mut build = MapBuild::new(ir, base_dir, options);
+        while !build.is_done() {
+            build.advance(options, textures).await?;
         }
+        Ok(build.map)
+    }
 
-        let mut gid_lut = vec![u16::MAX; (max_gid + 1) as usize];
-
-        for (i, t) in ir.tilesets.iter().enumerate() {
-            match t {
-                IrTileset::Atlas {
-                    first_gid,
-                    image,
-                    tile_w,
-                    tile_h,
-                    tilecount,
-                    columns,
-                    spacing,
-                    margin,
-                    ..
-                } => {
-                    let img_path = base_dir.join(image);
-                    let img_path_str = img_path
-                        .to_str()
-                        .ok_or_else(|| MapError::InvalidUtf8Path(img_path.clone()))?;
-                    let tex =
-                        load_texture(img_path_str)
-                            .await
-                            .map_err(|e| MapError::TextureLoad {
-                                path: img_path.clone(),
-                                message: e.to_string(),
-                            })?;
-                    tex.set_filter(FilterMode::Nearest);
-
-                    tilesets.push(TilesetInfo {
-                        first_gid: *first_gid,
-                        tilecount: *tilecount,
-                        cols: *columns,
-                        tex,
-                        tile_w: *tile_w,
-                        tile_h: *tile_h,
-                        spacing: *spacing,
-                        margin: *margin,
-                    });
+    /// Starts an incremental load that is driven by [`MapLoader::step`].
+    ///
+    /// Nothing is read until the first `step` call. Use this instead of [`Map::load_with`]
+    /// when a loading screen must keep rendering while a large map is decoded and indexed.
+    pub fn load_in_steps(path: &str, options: LoadOptions) -> MapLoader {
+        MapLoader {
+            options,
+            state: LoaderState::Parse {
+                path: path.to_string(),
+            },
+        }
+    }
 
-                    for gid in *first_gid..(*first_gid + *tilecount) {
-                        gid_lut[gid as usize] = i as u16;
-                    }
-                }
+    fn index_tiles(
+        index: &mut GlobalIndex,
+        layer: &mut TileLayer,
+        range: std::ops::Range<usize>,
+        offset: Vec2,
+        tile_w: u32,
+        tile_h: u32,
+    ) {
+        let tw = tile_w as f32;
+        let th = tile_h as f32;
+        for idx in range {
+            let gid = layer.data[idx];
+            if gid == 0 {
+                continue;
             }
+            let col = idx % layer.width;
+            let row = idx / layer.width;
+            let mut world = vec2(col as f32 * tw, row as f32 * th);
+            world += offset;
+            layer.handles[idx] = Some(index.add_tile(TileId(gid), layer.layer_id, world));
         }
+    }
 
-        let mut index = GlobalIndex::new();
-        let mut object_layers = Vec::new();
-        let mut tile_layers: Vec<TileLayerDrawInfo> = Vec::new();
-        let (draw_order, layer_kind_by_id) = build_draw_order_and_kind(&ir.layers);
-
-        for (lz, layer) in ir.layers.iter().enumerate() {
-            match &layer.kind {
-                IrLayerKind::Objects { objects } => {
-                    let bucket_layer = lz as LayerIdx;
-                    let layer_idx = object_layers.len();
-                    object_layers.push(ObjectLayer {
-                        id: lz as LayerId,
-                        name: layer.name.clone(),
-                        visible: layer.visible,
-                        opacity: layer.opacity,
-                        offset: layer.offset,
-                        properties: layer.properties.clone(),
-                        objects: objects.clone(),
+    fn index_object_layer(
+        index: &mut GlobalIndex,
+        bucket_layer: LayerIdx,
+        objects: &[IrObject],
+        offset: Vec2,
+    ) {
+        for (object_idx, obj) in objects.iter().enumerate() {
+            let world = vec2(obj.x, obj.y) + offset;
+            let (chunk_min, chunk_max) = Self::object_chunk_span(obj, offset);
+
+            for cy in chunk_min.y..=chunk_max.y {
+                for cx in chunk_min.x..=chunk_max.x {
+                    let cc = crate::spatial::ChunkCoord { x: cx, y: cy };
+                    let chunk_origin = vec2((cc.x * CHUNK_SIZE) as f32, (cc.y * CHUNK_SIZE) as f32);
+                    index.insert_object(
                         bucket_layer,
-                        seen_stamp_tiles: vec![0; objects.len()],
-                        seen_stamp_debug: vec![0; objects.len()],
-                    });
-                    if let Some(last) = object_layers.last_mut() {
-                        ensure_object_layer_stamp_invariant(last);
-                    }
-
-                    for (object_idx, obj) in objects.iter().enumerate() {
-                        let world = vec2(obj.x, obj.y) + layer.offset;
-                        let (chunk_min, chunk_max) = Self::object_chunk_span(obj, layer.offset);
-
-                        for cy in chunk_min.y..=chunk_max.y {
-                            for cx in chunk_min.x..=chunk_max.x {
-                                let cc = crate::spatial::ChunkCoord { x: cx, y: cy };
-                                let chunk_origin =
-                                    vec2((cc.x * CHUNK_SIZE) as f32, (cc.y * CHUNK_SIZE) as f32);
-                                index.insert_object(
-                                    bucket_layer,
-                                    cc,
-                                    crate::spatial::ObjectRec {
-                                        handle: crate::spatial::ObjectHandle(object_idx as u32),
-                                        // Objects may be inserted into multiple chunks. Store
-                                        // position relative to each inserted chunk so world
-                                        // reconstruction is stable regardless of which bucket
-                                        // is visited first for deduped rendering.
-                                        rel_pos: world - chunk_origin,
-                                    },
-                                );
-                            }
-                        }
-                    }
-                    debug_assert!(matches!(
-                        layer_kind_by_id.get(&(lz as LayerId)),
-                        Some(LayerKindInfo::Objects(idx)) if *idx == layer_idx
-                    ));
-                }
-                IrLayerKind::Tiles {
-                    width,
-                    height: _,
-                    data,
-                } => {
-                    let lid = lz as LayerIdx;
-                    let tile_layer_idx = tile_layers.len();
-
-                    let tw = ir.tile_w as f32;
-                    let th = ir.tile_h as f32;
-                    for (idx, gid) in data.iter().enumerate() {
-                        if *gid == 0 {
-                            continue;
-                        }
-                        let col = idx % *width;
-                        let row = idx / *width;
-                        let mut world = vec2(col as f32 * tw, row as f32 * th);
-                        world += layer.offset;
-                        index.add_tile(TileId(*gid), lid, world);
-                    }
-
-                    tile_layers.push(TileLayerDrawInfo {
-                        layer_id: lid,
-                        visible: layer.visible,
-                        opacity: layer.opacity.clamp(0.0, 1.0),
-                    });
-                    debug_assert!(matches!(
-                        layer_kind_by_id.get(&(lz as LayerId)),
-                        Some(LayerKindInfo::Tiles(idx)) if *idx == tile_layer_idx
-                    ));
+                        cc,
+                        crate::spatial::ObjectRec {
+                            handle: crate::spatial::ObjectHandle(object_idx as u32),
+                            // Objects may be inserted into multiple chunks. Store
+                            // position relative to each inserted chunk so world
+                            // reconstruction is stable regardless of which bucket
+                            // is visited first for deduped rendering.
+                            rel_pos: world - chunk_origin,
+                        },
+                    );
                 }
-                IrLayerKind::Unsupported => {}
             }
         }
+    }
 
-        Ok(Self {
-            index,
-            tilesets,
-            object_layers,
-            renderer: MapRenderer::new(),
-            gid_lut,
-            tile_layers,
-            draw_order,
-            layer_kind_by_id,
-        })
+    fn insert_object_aabbs(
+        object_index: &mut ObjectIndex,
+        layer: usize,
+        objects: &[IrObject],
+        offset: Vec2,
+    ) {
+        for (object, obj) in objects.iter().enumerate() {
+            let (min, max) = Self::object_aabb_world(obj, offset);
+            object_index.insert(
+                ObjectKey { layer, object },
+                Rect::new(min.x, min.y, max.x - min.x, max.y - min.y),
+            );
+        }
+    }
+
+    fn rebuild_object_index(&mut self, cell_size: f32) {
+        let mut object_index = ObjectIndex::new(cell_size);
+        for (layer_idx, layer) in self.object_layers.iter().enumerate() {
+            Self::insert_object_aabbs(&mut object_index, layer_idx, &layer.objects, layer.offset);
+        }
+        self.object_index = object_index;
     }
 
     fn object_aabb_world(obj: &IrObject, layer_offset: Vec2) -> (Vec2, Vec2) {
         let origin = vec2(obj.x, obj.y) + layer_offset;
 
-        match &obj.shape {
-            IrObjectShape::Rectangle => {
-                let x2 = origin.x + obj.width;
-                let y2 = origin.y + obj.height;
-                (
-                    vec2(origin.x.min(x2), origin.y.min(y2)),
-                    vec2(origin.x.max(x2), origin.y.max(y2)),
-                )
+        // Bounds relative to the object origin, before rotation.
+        let (min, max) = match &obj.shape {
+            IrObjectShape::Rectangle | IrObjectShape::Ellipse => {
+                let size = vec2(obj.width, obj.height);
+                (size.min(Vec2::ZERO), size.max(Vec2::ZERO))
             }
-            IrObjectShape::Point => (origin - vec2(0.5, 0.5), origin + vec2(0.5, 0.5)),
+            IrObjectShape::Point => (vec2(-0.5, -0.5), vec2(0.5, 0.5)),
             IrObjectShape::Polygon(points) | IrObjectShape::Polyline(points) => {
                 if points.is_empty() {
-                    return (origin - vec2(0.5, 0.5), origin + vec2(0.5, 0.5));
+                    (vec2(-0.5, -0.5), vec2(0.5, 0.5))
+                } else {
+                    points
+                        .iter()
+                        .fold((Vec2::ZERO, Vec2::ZERO), |(min, max), p| {
+                            (min.min(*p), max.max(*p))
+                        })
                 }
-
-                let mut min_x = origin.x;
-                let mut min_y = origin.y;
-                let mut max_x = origin.x;
-                let mut max_y = origin.y;
-
-                for p in points {
-                    let wp = origin + *p;
-                    min_x = min_x.min(wp.x);
-                    min_y = min_y.min(wp.y);
-                    max_x = max_x.max(wp.x);
-                    max_y = max_y.max(wp.y);
-                }
-
-                (vec2(min_x, min_y), vec2(max_x, max_y))
             }
             IrObjectShape::Tile { .. } => {
                 // Tile objects are drawn at (x, y - h), so AABB must match that.
                 let w = if obj.width > 0.0 { obj.width } else { 1.0 };
                 let h = if obj.height > 0.0 { obj.height } else { 1.0 };
-                (vec2(origin.x, origin.y - h), vec2(origin.x + w, origin.y))
+                (vec2(0.0, -h), vec2(w, 0.0))
             }
+        };
+
+        if obj.rotation == 0.0 {
+            return (origin + min, origin + max);
         }
+        // Tiled rotates clockwise (y down) around the object origin.
+        let rot = Vec2::from_angle(obj.rotation.to_radians());
+        let corners = [min, vec2(max.x, min.y), max, vec2(min.x, max.y)].map(|c| rot.rotate(c));
+        let rmin = corners.iter().fold(Vec2::INFINITY, |acc, c| acc.min(*c));
+        let rmax = corners
+            .iter()
+            .fold(Vec2::NEG_INFINITY, |acc, c| acc.max(*c));
+        (origin + rmin, origin + rmax)
     }
 
     fn object_chunk_span(
@@ -479,956 +1044,5874 @@ impl Map {
         &self.object_layers
     }
 
-    /// Iterates all parsed objects across all object layers.
-    pub fn objects(&self) -> impl Iterator<Item = &IrObject> {
-        self.object_layers
-            .iter()
-            .flat_map(|layer| layer.objects.iter())
+    /// Object layers in Tiled layer array order; a lazy form of [`Map::object_layers`].
+    pub fn iter_object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.object_layers.iter()
     }
 
-    #[inline]
-    fn params_for_flips_gid(
-        gid: TileId,
-        tile_w: f32,
-        tile_h: f32,
-    ) -> (f32, bool, bool, Option<Vec2>) {
-        let h = gid.flip_h();
-        let v = gid.flip_v();
-        let d = gid.flip_d();
-
-        let flip_x = h ^ d;
-        let flip_y = v;
-        let pivot = Some(vec2(tile_w / 2.0, tile_h / 2.0));
+    /// Tile layers in Tiled layer array order.
+    pub fn iter_tile_layers(&self) -> impl Iterator<Item = TileLayerRef<'_>> {
+        self.tile_layers.iter().map(|tl| TileLayerRef {
+            id: tl.layer_id,
+            name: self.layer_name(tl.layer_id).unwrap_or_default(),
+            visible: tl.visible,
+            opacity: tl.opacity,
+            width: tl.width,
+            height: tl.data.len().checked_div(tl.width).unwrap_or(0),
+            data: &tl.data,
+            properties: &tl.properties,
+        })
+    }
 
-        let rotation = match (h, v, d) {
-            (false, _, _) => 0.0,
-            (true, false, false) => std::f32::consts::FRAC_PI_2,
-            (true, false, true) => std::f32::consts::FRAC_PI_2,
-            (true, true, false) => std::f32::consts::FRAC_PI_2,
-            (true, true, true) => std::f32::consts::PI,
-        };
+    /// Total number of objects across all object layers.
+    pub fn object_count(&self) -> usize {
+        self.object_layers.iter().map(|l| l.objects.len()).sum()
+    }
 
-        (rotation, flip_x, flip_y, pivot)
+    /// Custom map properties.
+    pub fn properties(&self) -> &Properties {
+        &self.properties
     }
 
-    #[inline]
-    fn params_for_flips(
-        &self,
-        gid: TileId,
-        tile_w: f32,
-        tile_h: f32,
-    ) -> (f32, bool, bool, Option<Vec2>) {
-        Self::params_for_flips_gid(gid, tile_w, tile_h)
+    /// Problems tolerated while decoding, per [`LoadOptions::strictness`]. Empty for
+    /// maps built in code.
+    pub fn load_warnings(&self) -> &[ValidationIssue] {
+        &self.load_warnings
     }
 
-    #[inline]
-    fn ts_for_gid_from<'a>(
-        gid: TileId,
-        gid_lut: &'a [u16],
-        tilesets: &'a [TilesetInfo],
-    ) -> Option<(&'a TilesetInfo, u32)> {
-        let clean = gid.clean() as usize;
-        if clean >= gid_lut.len() {
-            return None;
+    /// Custom properties of the tile or object layer named `layer_name`.
+    pub fn layer_properties(&self, layer_name: &str) -> Option<&Properties> {
+        let lid = self.layer_idx(layer_name)?;
+        match self.layer_kind_by_id.get(&(lid as LayerId))? {
+            LayerKindInfo::Tiles(i) => Some(&self.tile_layers[*i].properties),
+            LayerKindInfo::Objects(i) => Some(&self.object_layers[*i].properties),
+            LayerKindInfo::Unsupported => None,
         }
+    }
 
-        let idx = gid_lut[clean];
-        if idx == u16::MAX {
-            return None;
+    /// Objects of the object layer named `layer` in file order, or `None` if there is no
+    /// such object layer (tile layers included).
+    pub fn objects_in(&self, layer: &str) -> Option<&[IrObject]> {
+        let lid = self.layer_idx(layer)?;
+        match self.layer_kind_by_id.get(&(lid as LayerId))? {
+            LayerKindInfo::Objects(i) => Some(&self.object_layers[*i].objects),
+            _ => None,
         }
-
-        let ts = &tilesets[idx as usize];
-        Some((ts, gid.clean() - ts.first_gid))
     }
 
-    #[inline]
-    fn ts_for_gid(&self, gid: TileId) -> Option<(&TilesetInfo, u32)> {
-        Self::ts_for_gid_from(gid, &self.gid_lut, &self.tilesets)
+    /// Iterates all parsed objects across all object layers.
+    pub fn objects(&self) -> impl Iterator<Item = &IrObject> {
+        self.object_layers
+            .iter()
+            .flat_map(|layer| layer.objects.iter())
     }
 
-    /// Draws only tile layers inside the visible rectangle.
-    ///
-    /// Stable API for tile-only rendering. Object layers are not drawn here.
+    /// Iterates objects whose world-space bounding rect overlaps `rect`.
     ///
-    /// `view_min`/`view_max` are world-space pixel corners.
-    pub fn draw_visible_rect(&self, view_min: Vec2, view_max: Vec2) {
-        let view = query_visible_rect(&self.index, view_min, view_max);
-        self.draw_chunks(view);
+    /// Backed by a uniform grid over object AABBs, so the cost scales with the number of
+    /// grid cells `rect` covers (at most the occupied ones) and the objects in them rather
+    /// than with the total object count. Objects are yielded once each, in layer order then file order.
+    /// Visibility is not considered.
+    pub fn objects_in_rect(&self, rect: Rect) -> impl Iterator<Item = &IrObject> {
+        self.object_index
+            .query(rect)
+            .map(|key| &self.object_layers[key.layer].objects[key.object])
     }
 
-    /// Draws the full map in configured layer order.
+    /// Returns the visible objects whose shape contains `world`, topmost first.
     ///
-    /// Stable API: draws visible tile layers and tile-objects.
-    /// If debug drawing is enabled, object debug overlays are drawn too.
-    ///
-    /// `view_min`/`view_max` are world-space pixel corners.
-    /// Culling uses [`Map::set_cull_padding`] in world-space pixels.
-    pub fn draw(&mut self, view_min: Vec2, view_max: Vec2) {
-        let coords = self.visible_coords_for_draw(view_min, view_max);
-        let stamp = self.next_frame_stamp();
-        for i in 0..self.draw_order.len() {
-            let layer_id = self.draw_order[i];
-            let Some(kind) = self.layer_kind_by_id.get(&layer_id).copied() else {
-                continue;
-            };
-            match kind {
-                LayerKindInfo::Tiles(tile_layer_idx) => {
-                    self.draw_tile_layer_from_coords(&coords, tile_layer_idx);
+    /// Shapes are tested exactly, including rotation ([`IrObject::contains_point`]); points
+    /// and polylines match within a few pixels. Results follow reverse draw order: layers
+    /// drawn later first (see [`Map::set_layer_draw_order`]), then within a layer the
+    /// reverse of its [`ObjectDrawOrder`].
+    pub fn objects_at_point(&self, world: Vec2) -> Vec<ObjectRef<'_>> {
+        let r = OBJECT_PICK_RADIUS;
+        let mut hits: Vec<ObjectKey> = self
+            .object_index
+            .query(Rect::new(world.x - r, world.y - r, 2.0 * r, 2.0 * r))
+            .filter(|key| {
+                let layer = &self.object_layers[key.layer];
+                let obj = &layer.objects[key.object];
+                layer.visible && obj.visible && obj.contains_point(world - layer.offset, r)
+            })
+            .collect();
+
+        // Position of each object layer in the draw order, which set_layer_draw_order
+        // may have moved away from the Tiled array order.
+        let rank: Vec<usize> = self
+            .object_layers
+            .iter()
+            .map(|layer| {
+                let pos = self.draw_order.iter().position(|&id| id == layer.id);
+                pos.unwrap_or(usize::MAX)
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            let layer = &self.object_layers[a.layer];
+            let by_layer = rank[b.layer].cmp(&rank[a.layer]);
+            by_layer.then_with(|| match layer.draw_order {
+                ObjectDrawOrder::TopDown => {
+                    let (ay, by) = (layer.objects[a.object].y, layer.objects[b.object].y);
+                    by.total_cmp(&ay).then(b.object.cmp(&a.object))
                 }
-                LayerKindInfo::Objects(object_layer_idx) => {
-                    self.draw_object_tiles_layer_from_coords(&coords, object_layer_idx, stamp);
-                    if self.renderer.debug_draw {
-                        self.draw_object_debug_layer_from_coords(&coords, object_layer_idx, stamp);
-                    }
+                ObjectDrawOrder::Index => b.object.cmp(&a.object),
+            })
+        });
+
+        hits.into_iter()
+            .map(|key| {
+                let layer = &self.object_layers[key.layer];
+                ObjectRef {
+                    layer,
+                    object: &layer.objects[key.object],
                 }
-                LayerKindInfo::Unsupported => {}
-            }
-        }
+            })
+            .collect()
     }
 
-    /// Enables/disables object debug overlay drawing used by [`Map::draw`].
+    /// The topmost visible object under the world pixel `(x, y)`, for click-to-select.
     ///
-    /// Stable API.
-    pub fn set_debug_draw(&mut self, enabled: bool) {
-        self.renderer.debug_draw = enabled;
+    /// Same hit test and order as [`Map::objects_at_point`]: polygons use the even-odd
+    /// rule, points and polylines match within 4 pixels.
+    pub fn object_at_pixel(&self, x: f32, y: f32) -> Option<&IrObject> {
+        self.objects_at_point(vec2(x, y))
+            .first()
+            .map(|hit| hit.object)
     }
 
-    /// Sets extra culling padding in world-space pixels around the view rectangle.
+    /// Builds a [`Triggers`] set from every object of the object layer named `layer`.
     ///
-    /// Stable API. `0.0` means no extra padding.
-    pub fn set_cull_padding(&mut self, padding: f32) {
-        self.renderer.cull_padding = padding.max(0.0);
+    /// Shapes are captured in world space, so later [`Map::set_layer_offset`] calls are not
+    /// reflected. Returns `None` for unknown or non-object layers.
+    pub fn build_triggers(&self, layer: &str) -> Option<Triggers> {
+        let lid = self.layer_idx(layer)?;
+        let LayerKindInfo::Objects(i) = self.layer_kind_by_id.get(&(lid as LayerId))? else {
+            return None;
+        };
+        let layer = &self.object_layers[*i];
+        let regions = layer
+            .objects
+            .iter()
+            .map(|obj| {
+                let (min, max) = Self::object_aabb_world(obj, layer.offset);
+                let world = IrObject {
+                    x: obj.x + layer.offset.x,
+                    y: obj.y + layer.offset.y,
+                    ..obj.clone()
+                };
+                (world, Rect::new(min.x, min.y, max.x - min.x, max.y - min.y))
+            })
+            .collect();
+        Some(Triggers::new(regions))
     }
 
-    /// Draws debug shapes for visible object layers.
-    ///
-    /// Stable convenience API: acquires an internal frame stamp automatically.
+    /// Rebuilds the object grid used by [`Map::objects_in_rect`] with a new cell edge
+    /// in world pixels (default 256). Non-positive values fall back to the default.
     ///
-    /// Use this when object drawing is a standalone call for the frame.
-    pub fn draw_objects_debug(&mut self, view_min: Vec2, view_max: Vec2) {
-        let stamp = self.next_frame_stamp();
-        self.draw_objects_debug_with_stamp(view_min, view_max, stamp);
+    /// Smaller cells suit many small objects; larger cells suit big query rects.
+    pub fn set_object_index_cell_size(&mut self, cell_size: f32) {
+        self.rebuild_object_index(cell_size);
     }
 
-    /// Advanced API: draws debug shapes for visible object layers using a caller-provided stamp.
+    /// Finds the first object named `name` in the object layer named `layer`.
     ///
-    /// Use this when you want frame-coherent manual composition (for example:
-    /// tile-object pass + debug pass in the same frame using one shared stamp).
+    /// Returns `None` if the layer does not exist or holds no object with that name.
+    pub fn object_by_name_in_layer(&self, layer: &str, name: &str) -> Option<&IrObject> {
+        self.object_layers
+            .iter()
+            .find(|l| l.name == layer)?
+            .objects
+            .iter()
+            .find(|o| o.name == name)
+    }
+
+    /// Returns the current world offset of the layer named `layer_name`.
+    pub fn layer_offset(&self, layer_name: &str) -> Option<Vec2> {
+        self.layer_offsets.get(layer_name).copied()
+    }
+
+    /// Visibility of the layer named `layer_name` (authored, or the last
+    /// [`Map::set_layer_visible`] value).
+    pub fn layer_visible(&self, layer_name: &str) -> Option<bool> {
+        let lid = self.layer_idx(layer_name)?;
+        match self.layer_kind_by_id.get(&(lid as LayerId))? {
+            LayerKindInfo::Tiles(i) => Some(self.tile_layers[*i].visible),
+            LayerKindInfo::Objects(i) => Some(self.object_layers[*i].visible),
+            LayerKindInfo::Unsupported => None,
+        }
+    }
+
+    /// Shows or hides the layer named `layer_name` for drawing and object picking.
     ///
-    /// Stamp rule: pass the same `stamp` to all object passes in a frame.
-    pub fn draw_objects_debug_with_stamp(&mut self, view_min: Vec2, view_max: Vec2, stamp: u32) {
-        let coords = self.visible_coords_for_draw(view_min, view_max);
-        self.draw_object_layers_debug_from_coords(&coords, stamp);
+    /// Layers hidden in Tiled are loaded and indexed like visible ones, so this is O(1)
+    /// in both directions. Fails with [`MapError::LayerNotFound`] if no tile or object
+    /// layer has that name.
+    pub fn set_layer_visible(&mut self, layer_name: &str, visible: bool) -> Result<(), MapError> {
+        let lid = self.layer_idx(layer_name);
+        match lid.and_then(|lid| self.layer_kind_by_id.get(&(lid as LayerId))) {
+            Some(LayerKindInfo::Tiles(i)) => self.tile_layers[*i].visible = visible,
+            Some(LayerKindInfo::Objects(i)) => self.object_layers[*i].visible = visible,
+            _ => return Err(MapError::LayerNotFound(layer_name.to_string())),
+        }
+        Ok(())
     }
 
-    /// Draws tile-objects from visible object layers.
+    /// Effective opacity of the layer named `layer_name` (authored, or the last
+    /// [`Map::set_layer_opacity`] value).
+    pub fn layer_opacity(&self, layer_name: &str) -> Option<f32> {
+        let lid = self.layer_idx(layer_name)?;
+        match self.layer_kind_by_id.get(&(lid as LayerId))? {
+            LayerKindInfo::Tiles(i) => Some(self.tile_layers[*i].opacity),
+            LayerKindInfo::Objects(i) => Some(self.object_layers[*i].opacity),
+            LayerKindInfo::Unsupported => None,
+        }
+    }
+
+    /// Overrides the opacity of the layer named `layer_name`, clamped to `0.0..=1.0`; NaN
+    /// counts as `0.0`.
     ///
-    /// Stable convenience API: acquires an internal frame stamp automatically.
+    /// The value multiplies the alpha of the layer's `tintcolor`, and any tint applied
+    /// on top multiplies both. At `0.0` the layer's tiles (or tile objects) are skipped entirely
+    /// rather than drawn invisibly. Fails with [`MapError::LayerNotFound`] if no tile or
+    /// object layer has that name.
+    pub fn set_layer_opacity(&mut self, layer_name: &str, opacity: f32) -> Result<(), MapError> {
+        let lid = self.layer_idx(layer_name);
+        // `clamp` passes NaN through, and a NaN alpha would poison every tint it multiplies.
+        let opacity = if opacity.is_nan() {
+            0.0
+        } else {
+            opacity.clamp(0.0, 1.0)
+        };
+        match lid.and_then(|lid| self.layer_kind_by_id.get(&(lid as LayerId))) {
+            Some(LayerKindInfo::Tiles(i)) => self.tile_layers[*i].opacity = opacity,
+            Some(LayerKindInfo::Objects(i)) => self.object_layers[*i].opacity = opacity,
+            _ => return Err(MapError::LayerNotFound(layer_name.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Moves the layer named `layer_name` to a new world offset.
     ///
-    /// Use this when object drawing is a standalone call for the frame.
-    pub fn draw_objects_tiles(&mut self, view_min: Vec2, view_max: Vec2) {
-        let stamp = self.next_frame_stamp();
-        self.draw_objects_tiles_with_stamp(view_min, view_max, stamp);
+    /// Every tile (or object) of the layer is re-indexed into the chunk it now falls in,
+    /// so this costs O(layer size). Use it for occasional transitions, not per-frame
+    /// animation. Fails with [`MapError::LayerNotFound`] if no layer has that name.
+    pub fn set_layer_offset(&mut self, layer_name: &str, offset: Vec2) -> Result<(), MapError> {
+        let lid = self.layer_idx(layer_name);
+        let (Some(current), Some(lid)) = (self.layer_offsets.get_mut(layer_name), lid) else {
+            return Err(MapError::LayerNotFound(layer_name.to_string()));
+        };
+        *current = offset;
+
+        match self.layer_kind_by_id.get(&(lid as LayerId)).copied() {
+            Some(LayerKindInfo::Tiles(i)) => {
+                let layer = &mut self.tile_layers[i];
+                self.index.remove_layer_tiles(layer.layer_id);
+                Self::index_tiles(
+                    &mut self.index,
+                    layer,
+                    0..layer.data.len(),
+                    offset,
+                    self.tile_w,
+                    self.tile_h,
+                );
+            }
+            Some(LayerKindInfo::Objects(i)) => {
+                let layer = &mut self.object_layers[i];
+                layer.offset = offset;
+                self.index.remove_layer_objects(layer.bucket_layer);
+                Self::index_object_layer(
+                    &mut self.index,
+                    layer.bucket_layer,
+                    &layer.objects,
+                    offset,
+                );
+                self.rebuild_object_index(self.object_index.cell_size());
+            }
+            Some(LayerKindInfo::Unsupported) | None => {}
+        }
+        Ok(())
     }
 
-    /// Advanced API: draws tile-objects using a caller-provided stamp.
+    /// Panics if the spatial index is internally inconsistent. No-op in release builds.
     ///
-    /// This exists to support explicit control of object deduplication across
-    /// multiple manual object passes in one frame.
+    /// Checks tile handles against chunk records (see `GlobalIndex::assert_consistent`)
+    /// and that every object record refers to an existing object of its layer.
+    pub fn assert_consistent(&self) {
+        #[cfg(debug_assertions)]
+        {
+            self.index.assert_consistent();
+            for layer in &self.object_layers {
+                for chunk in self.index.buckets.values() {
+                    let Some(bucket) = chunk.layers.get(&layer.bucket_layer) else {
+                        continue;
+                    };
+                    for rec in &bucket.objects {
+                        assert!(
+                            (rec.handle.0 as usize) < layer.objects.len(),
+                            "object handle {} out of bounds for layer {:?}",
+                            rec.handle.0,
+                            layer.name
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolves a layer name to its index in Tiled layer array order.
     ///
-    /// Stamp rule: pass the same `stamp` to all object passes in a frame.
-    pub fn draw_objects_tiles_with_stamp(&mut self, view_min: Vec2, view_max: Vec2, stamp: u32) {
-        let coords = self.visible_coords_for_draw(view_min, view_max);
-        self.draw_object_layers_tiles_from_coords(&coords, stamp);
+    /// Every loaded layer gets an index, visible or not, so indices never shift when
+    /// visibility changes. With duplicate names the first layer wins. O(1).
+    ///
+    /// Cache the result for layers looked up every frame to skip the string hash.
+    pub fn layer_idx(&self, name: &str) -> Option<LayerIdx> {
+        self.layer_idx_by_name.get(name).copied()
+    }
+
+    /// Returns the name of the layer at `idx`, the reverse of [`Map::layer_idx`]. O(1).
+    pub fn layer_name(&self, idx: LayerIdx) -> Option<&str> {
+        self.layer_names.get(idx as usize).map(String::as_str)
+    }
+
+    /// Names of all layers, bottom first, in the order they are drawn.
+    ///
+    /// Includes every layer kind, hidden layers and layers that are never drawn
+    /// (unsupported kinds). Matches the Tiled layer array until
+    /// [`Map::set_layer_draw_order`] is called.
+    pub fn layer_draw_order(&self) -> Vec<String> {
+        self.draw_order
+            .iter()
+            .filter_map(|&id| self.layer_name(id as LayerIdx))
+            .map(str::to_string)
+            .collect()
     }
 
-    fn draw_chunks(&self, view: LocalView) {
-        for tile_layer_idx in 0..self.tile_layers.len() {
-            self.draw_tile_layer_from_view(&view, tile_layer_idx);
+    /// Reorders layers for drawing, bottom first.
+    ///
+    /// `order` must name every layer exactly once; otherwise nothing changes and an
+    /// error is returned. Only rendering and topmost-first queries follow the new order;
+    /// layer indices keep their Tiled array positions.
+    ///
+    /// Layers are found by name, and a duplicate name always means the first layer with
+    /// it, so maps with two layers of the same name cannot be reordered.
+    pub fn set_layer_draw_order(&mut self, order: &[&str]) -> Result<(), MapError> {
+        let mut ids = Vec::with_capacity(order.len());
+        for name in order {
+            let id = self
+                .layer_idx(name)
+                .ok_or_else(|| MapError::LayerNotFound(name.to_string()))?;
+            if ids.contains(&(id as LayerId)) {
+                return Err(MapError::InvalidDrawOrder(format!(
+                    "layer '{name}' appears twice"
+                )));
+            }
+            ids.push(id as LayerId);
+        }
+        if ids.len() != self.draw_order.len() {
+            return Err(MapError::InvalidDrawOrder(format!(
+                "names {} of {} layers",
+                ids.len(),
+                self.draw_order.len()
+            )));
         }
+        self.draw_order = ids;
+        Ok(())
     }
 
-    fn draw_tile_layer_from_view(&self, view: &LocalView, tile_layer_idx: usize) {
-        let Some(layer) = self.tile_layers.get(tile_layer_idx) else {
-            return;
-        };
-        if !layer.visible {
-            return;
+    /// Returns the class of the tile under world position `pos` in the tile layer named
+    /// `layer`.
+    ///
+    /// Flip flags are ignored. Returns `None` for unknown or non-tile layers, positions
+    /// outside the layer, empty cells, and tiles without a class.
+    pub fn tile_class_at(&self, pos: Vec2, layer: &str) -> Option<&str> {
+        let gid = self.tile_gid_at(self.tile_layer_by_name(layer)?, pos)?;
+        self.tile_classes.get(&gid.clean()).map(String::as_str)
+    }
+
+    /// Gid of the tile under world pixel `(x, y)` in the tile layer named `layer_name`,
+    /// flip flags included, for hover and click handling.
+    ///
+    /// Follows the layer offset. Returns `None` for unknown or non-tile layers, positions
+    /// outside the layer and empty cells.
+    pub fn tile_at_pixel(&self, x: f32, y: f32, layer_name: &str) -> Option<TileId> {
+        self.tile_gid_at(self.tile_layer_by_name(layer_name)?, vec2(x, y))
+    }
+
+    /// Returns the class of the topmost classified tile under `pos`, checking tile layers
+    /// from the top of the draw order down (hidden layers included).
+    ///
+    /// Unclassified tiles do not stop the search.
+    pub fn top_tile_class_at(&self, pos: Vec2) -> Option<&str> {
+        self.draw_order.iter().rev().find_map(|layer_id| {
+            let Some(LayerKindInfo::Tiles(i)) = self.layer_kind_by_id.get(layer_id) else {
+                return None;
+            };
+            let gid = self.tile_gid_at(&self.tile_layers[*i], pos)?;
+            self.tile_classes.get(&gid.clean()).map(String::as_str)
+        })
+    }
+
+    /// Traces walkable regions into closed polygons in world pixels, for feeding an
+    /// external navmesh or physics library.
+    ///
+    /// The grid covers the largest tile layer; cells are blocked per [`NavOptions`].
+    /// Each region yields one outer contour, clockwise on screen (positive signed area
+    /// with y pointing down), plus one counter-clockwise contour per interior hole.
+    /// Collinear points are removed, so contours only contain corners.
+    pub fn walkable_polygons(&self, options: &NavOptions) -> Vec<Vec<Vec2>> {
+        let (width, height, blocked) = self.blocked_mask(options);
+        let tile = vec2(self.tile_w as f32, self.tile_h as f32);
+        trace_walkable_contours(width, height, &blocked)
+            .into_iter()
+            .map(|contour| contour.into_iter().map(|p| p.as_vec2() * tile).collect())
+            .collect()
+    }
+
+    // Row-major blocked flags over the largest tile layer, sampled at cell centers.
+    fn blocked_mask(&self, options: &NavOptions) -> (usize, usize, Vec<bool>) {
+        let width = self.tile_layers.iter().map(|l| l.width).max().unwrap_or(0);
+        let height = self
+            .tile_layers
+            .iter()
+            .filter(|l| l.width > 0)
+            .map(|l| l.data.len().div_ceil(l.width))
+            .max()
+            .unwrap_or(0);
+
+        let blocking_layers: Vec<&TileLayer> = options
+            .blocking_layers
+            .iter()
+            .filter_map(|name| self.tile_layer_by_name(name))
+            .collect();
+        let tile = vec2(self.tile_w as f32, self.tile_h as f32);
+
+        let mut blocked = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                let pos = (vec2(col as f32, row as f32) + 0.5) * tile;
+                let by_layer = blocking_layers
+                    .iter()
+                    .any(|layer| self.tile_gid_at(layer, pos).is_some());
+                let by_class = !options.blocking_classes.is_empty()
+                    && self.tile_layers.iter().any(|layer| {
+                        self.tile_gid_at(layer, pos)
+                            .and_then(|gid| self.tile_classes.get(&gid.clean()))
+                            .is_some_and(|class| options.blocking_classes.contains(class))
+                    });
+                blocked.push(by_layer || by_class);
+            }
         }
-        let tint = Color::new(1.0, 1.0, 1.0, layer.opacity);
+        (width, height, blocked)
+    }
 
-        for LocalChunkView { coord: cc, layers } in &view.chunks {
-            if let Some(bucket) = layers.get(&layer.layer_id) {
-                for rec in &bucket.tiles {
-                    let (ts, local) = match self.ts_for_gid(rec.id) {
-                        Some(x) => x,
-                        None => continue,
-                    };
+    /// Rebuilds the tile layer named `layer_name` as a row-major gid grid from the
+    /// spatial index, where `row * width + col` holds the raw gid (flip flags kept) and
+    /// `0` marks an empty cell.
+    ///
+    /// This matches the layer's JSON `data` array. Returns `None` for unknown or
+    /// non-tile layers.
+    pub fn tile_layer_as_flat_array(&self, layer_name: &str) -> Option<Vec<u32>> {
+        let layer = self.tile_layer_by_name(layer_name)?;
+        let offset = self.layer_offset(layer_name).unwrap_or(Vec2::ZERO);
+        let width = layer.width;
+        let height = layer.data.len().checked_div(width).unwrap_or(0);
+        let tile = vec2(self.tile_w as f32, self.tile_h as f32);
+
+        let mut grid = vec![0u32; width * height];
+        for (cc, chunk) in &self.index.buckets {
+            let Some(bucket) = chunk.layers.get(&layer.layer_id) else {
+                continue;
+            };
+            for rec in &bucket.tiles {
+                let cell = ((rec.world_pos(*cc) - offset) / tile).round();
+                let (col, row) = (cell.x as usize, cell.y as usize);
+                if cell.x < 0.0 || cell.y < 0.0 || col >= width || row >= height {
+                    debug_assert!(false, "indexed tile outside its layer grid");
+                    continue;
+                }
+                grid[row * width + col] = rec.id.raw();
+            }
+        }
+        Some(grid)
+    }
 
-                    let col = local % ts.cols;
-                    let row = local / ts.cols;
-                    let sx = ts.margin + col * (ts.tile_w + ts.spacing);
-                    let sy = ts.margin + row * (ts.tile_h + ts.spacing);
-
-                    let x = ((cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x).round();
-                    let y = ((cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y).round();
-
-                    let (rotation, flip_x, flip_y, pivot) =
-                        self.params_for_flips(rec.id, ts.tile_w as f32, ts.tile_h as f32);
-
-                    draw_texture_ex(
-                        &ts.tex,
-                        x,
-                        y,
-                        tint,
-                        DrawTextureParams {
-                            source: Some(Rect::new(
-                                sx as f32,
-                                sy as f32,
-                                ts.tile_w as f32,
-                                ts.tile_h as f32,
-                            )),
-                            rotation,
-                            flip_x,
-                            flip_y,
-                            pivot,
-                            ..Default::default()
-                        },
-                    );
+    /// Finds every tile whose gid and tile properties satisfy `pred`, in the tile layer
+    /// named `layer_filter` or in all tile layers when it is `None`.
+    ///
+    /// `pred` receives the gid with flip flags masked and that tile's properties (empty if
+    /// it has none). It runs once per gid of the registered tilesets, then only the layer
+    /// grids are scanned, so the cost is independent of how many cells match. Matches are
+    /// ordered by layer, then row-major.
+    pub fn find_tiles(
+        &self,
+        pred: impl Fn(u32, &Properties) -> bool,
+        layer_filter: Option<&str>,
+    ) -> Vec<TileMatch> {
+        let empty = Properties::default();
+        let matching: HashSet<u32> = self
+            .gids
+            .iter()
+            .map(|(gid, _)| gid)
+            .filter(|gid| pred(*gid, self.tile_properties.get(gid).unwrap_or(&empty)))
+            .collect();
+
+        let mut out = Vec::new();
+        if matching.is_empty() {
+            return out;
+        }
+        let tile = vec2(self.tile_w as f32, self.tile_h as f32);
+        for layer in &self.tile_layers {
+            let name = self.layer_name(layer.layer_id).unwrap_or_default();
+            if layer.width == 0 || layer_filter.is_some_and(|want| want != name) {
+                continue;
+            }
+            let offset = self.layer_offset(name).unwrap_or(Vec2::ZERO);
+            for (i, &raw) in layer.data.iter().enumerate() {
+                let clean = raw & GID_MASK;
+                if clean == 0 || !matching.contains(&clean) {
+                    continue;
                 }
+                let (col, row) = (i % layer.width, i / layer.width);
+                out.push(TileMatch {
+                    layer: layer.layer_id,
+                    col,
+                    row,
+                    world: offset + vec2(col as f32, row as f32) * tile,
+                    gid: raw,
+                });
             }
         }
+        out
     }
 
-    fn draw_tile_layer_from_coords(
+    /// Finds every tile whose property `name` equals `value`, across all tile layers.
+    pub fn find_tiles_with_property(&self, name: &str, value: &PropertyValue) -> Vec<TileMatch> {
+        self.find_tiles(|_, props| props.get(name) == Some(value), None)
+    }
+
+    /// Counts how often each clean gid (flip flags stripped) occurs in the tile layer
+    /// named `layer_name`. Empty cells are not counted; unknown layers yield an empty map.
+    pub fn count_tiles_by_gid(&self, layer_name: &str) -> HashMap<u32, u32> {
+        let mut counts = HashMap::new();
+        if let Some(layer) = self.tile_layer_by_name(layer_name) {
+            Self::count_layer_gids(layer, &mut counts);
+        }
+        counts
+    }
+
+    /// Like [`Map::count_tiles_by_gid`], summed over every tile layer.
+    pub fn count_tiles_by_gid_all_layers(&self) -> HashMap<u32, u32> {
+        let mut counts = HashMap::new();
+        for layer in &self.tile_layers {
+            Self::count_layer_gids(layer, &mut counts);
+        }
+        counts
+    }
+
+    fn count_layer_gids(layer: &TileLayer, counts: &mut HashMap<u32, u32>) {
+        for &raw in &layer.data {
+            let gid = raw & GID_MASK;
+            if gid != 0 {
+                *counts.entry(gid).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Map size in world pixels (`width * tile_w`, `height * tile_h`), layer offsets
+    /// not included.
+    pub fn size_px(&self) -> (f32, f32) {
+        (
+            self.width as f32 * self.tile_w as f32,
+            self.height as f32 * self.tile_h as f32,
+        )
+    }
+
+    /// Map grid cell containing world position `pos`, or `None` outside the map
+    /// (negative coordinates and the far edges included).
+    pub fn world_to_tile(&self, pos: Vec2) -> Option<(u32, u32)> {
+        // Written so NaN fails the test as well.
+        if !(pos.x >= 0.0 && pos.y >= 0.0) {
+            return None;
+        }
+        let x = (pos.x / self.tile_w as f32).floor();
+        let y = (pos.y / self.tile_h as f32).floor();
+        (x < self.width as f32 && y < self.height as f32).then_some((x as u32, y as u32))
+    }
+
+    /// World position of the top-left corner of grid cell `(x, y)`.
+    pub fn tile_to_world(&self, x: u32, y: u32) -> Vec2 {
+        // In `f32`, since the `u32` product overflows for far cells.
+        vec2(x as f32 * self.tile_w as f32, y as f32 * self.tile_h as f32)
+    }
+
+    /// World-space rect of grid cell `(x, y)`.
+    pub fn tile_rect(&self, x: u32, y: u32) -> Rect {
+        let origin = self.tile_to_world(x, y);
+        Rect::new(origin.x, origin.y, self.tile_w as f32, self.tile_h as f32)
+    }
+
+    /// Number of non-empty cells in the `w x h` cell rect at `(col, row)` of the tile
+    /// layer named `layer_name` (0 for unknown layers).
+    ///
+    /// Queries the spatial index, so the cost follows the rect's size, capped by the map's
+    /// chunk count for huge rects, not the layer's tile count.
+    pub fn tile_count_in_rect(
         &self,
-        coords: &[crate::spatial::ChunkCoord],
-        tile_layer_idx: usize,
-    ) {
-        let Some(layer) = self.tile_layers.get(tile_layer_idx) else {
-            return;
+        layer_name: &str,
+        col: u32,
+        row: u32,
+        w: u32,
+        h: u32,
+    ) -> usize {
+        let Some(layer) = self.tile_layer_by_name(layer_name) else {
+            return 0;
         };
-        if !layer.visible {
-            return;
+        let offset = self.layer_offset(layer_name).unwrap_or(Vec2::ZERO);
+        let (tw, th) = (self.tile_w as f32, self.tile_h as f32);
+        let rect = Rect::new(
+            offset.x + col as f32 * tw,
+            offset.y + row as f32 * th,
+            w as f32 * tw,
+            h as f32 * th,
+        );
+        self.index
+            .tiles_in_rect(layer.layer_id, rect)
+            .filter(|(_, rec)| rec.id.clean() != 0)
+            .count()
+    }
+
+    /// Raw gid (flip flags included, `0` for empty) at cell `(x, y)` of the tile layer
+    /// named `layer`, or `None` for unknown layers and cells outside the grid.
+    pub fn get_tile(&self, layer: &str, x: u32, y: u32) -> Option<u32> {
+        let tl = self.tile_layer_by_name(layer)?;
+        if x as usize >= tl.width {
+            return None;
         }
-        let tint = Color::new(1.0, 1.0, 1.0, layer.opacity);
+        tl.data.get(y as usize * tl.width + x as usize).copied()
+    }
 
-        Self::for_each_visible_layer_bucket(&self.index, coords, layer.layer_id, |cc, bucket| {
-            for rec in &bucket.tiles {
-                let (ts, local) = match self.ts_for_gid(rec.id) {
-                    Some(x) => x,
-                    None => continue,
-                };
+    /// Iterates `(x, y, raw gid)` over every cell of the tile layer named `layer`, row by
+    /// row, empty cells included. `None` for unknown layers.
+    pub fn iter_cells(&self, layer: &str) -> Option<impl Iterator<Item = (u32, u32, u32)> + '_> {
+        let tl = self.tile_layer_by_name(layer)?;
+        let width = tl.width.max(1);
+        Some(
+            tl.data
+                .iter()
+                .enumerate()
+                .map(move |(i, &gid)| ((i % width) as u32, (i / width) as u32, gid)),
+        )
+    }
 
-                let col = local % ts.cols;
-                let row = local / ts.cols;
-                let sx = ts.margin + col * (ts.tile_w + ts.spacing);
-                let sy = ts.margin + row * (ts.tile_h + ts.spacing);
-
-                let x = ((cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x).round();
-                let y = ((cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y).round();
-
-                let (rotation, flip_x, flip_y, pivot) =
-                    self.params_for_flips(rec.id, ts.tile_w as f32, ts.tile_h as f32);
-
-                draw_texture_ex(
-                    &ts.tex,
-                    x,
-                    y,
-                    tint,
-                    DrawTextureParams {
-                        source: Some(Rect::new(
-                            sx as f32,
-                            sy as f32,
-                            ts.tile_w as f32,
-                            ts.tile_h as f32,
-                        )),
-                        rotation,
-                        flip_x,
-                        flip_y,
-                        pivot,
-                        ..Default::default()
-                    },
-                );
+    /// Largest axis-aligned rectangle of empty (gid `0`) cells in the tile layer named
+    /// `layer_name`, as `(col, row, width, height)`.
+    ///
+    /// Runs the maximal-rectangle-in-histogram scan row by row, so it is linear in the
+    /// layer's cell count. Ties keep the rectangle whose bottom row is scanned first.
+    /// `None` for unknown layers and layers without empty cells.
+    pub fn largest_contiguous_empty_region(
+        &self,
+        layer_name: &str,
+    ) -> Option<(u32, u32, u32, u32)> {
+        let tl = self.tile_layer_by_name(layer_name)?;
+        let width = tl.width;
+        if width == 0 {
+            return None;
+        }
+
+        // Run length of empty cells ending at the current row, per column.
+        let mut heights = vec![0u32; width];
+        let mut stack: Vec<usize> = Vec::with_capacity(width);
+        let mut best = None;
+        let mut best_area = 0;
+        for (row, cells) in tl.data.chunks_exact(width).enumerate() {
+            for (h, &raw) in heights.iter_mut().zip(cells) {
+                *h = if raw & GID_MASK == 0 { *h + 1 } else { 0 };
             }
-        });
+            stack.clear();
+            for col in 0..=width {
+                let h = heights.get(col).copied().unwrap_or(0);
+                while let Some(&top) = stack.last() {
+                    if heights[top] < h {
+                        break;
+                    }
+                    stack.pop();
+                    let height = heights[top];
+                    let left = stack.last().map_or(0, |&s| s + 1);
+                    let w = (col - left) as u32;
+                    if w * height > best_area {
+                        best_area = w * height;
+                        best = Some((left as u32, row as u32 + 1 - height, w, height));
+                    }
+                }
+                stack.push(col);
+            }
+        }
+        best
     }
 
-    fn draw_object_layers_debug_from_coords(
-        &mut self,
-        coords: &[crate::spatial::ChunkCoord],
-        stamp: u32,
-    ) {
-        for layer_idx in 0..self.object_layers.len() {
-            self.draw_object_debug_layer_from_coords(coords, layer_idx, stamp);
+    /// Replaces the tile at cell `(x, y)` of the tile layer named `layer` with the raw
+    /// `gid` (flip flags are stored as given; `0` clears the cell).
+    ///
+    /// Updates both the layer grid and the spatial index, so drawing and lookups see the
+    /// change immediately. Fails with [`MapError::LayerNotFound`] unless `layer` names a
+    /// tile layer.
+    pub fn set_tile(&mut self, layer: &str, x: u32, y: u32, gid: u32) -> Result<(), MapError> {
+        let Some(i) = self.tile_layer_pos(layer) else {
+            return Err(MapError::LayerNotFound(layer.to_string()));
+        };
+        self.check_tile_gid(layer, gid)?;
+        let clean = gid & GID_MASK;
+        let offset = self.layer_offset(layer).unwrap_or(Vec2::ZERO);
+        let tile = vec2(self.tile_w as f32, self.tile_h as f32);
+
+        let tl = &mut self.tile_layers[i];
+        let height = tl.data.len().checked_div(tl.width).unwrap_or(0);
+        if x as usize >= tl.width || y as usize >= height {
+            return Err(MapError::TileOutOfBounds {
+                layer: layer.to_string(),
+                x,
+                y,
+            });
+        }
+        let cell = y as usize * tl.width + x as usize;
+        tl.data[cell] = gid;
+        match (tl.handles[cell], clean) {
+            (Some(handle), 0) => {
+                self.index.remove_tile(handle);
+                tl.handles[cell] = None;
+            }
+            (Some(handle), _) => {
+                if let Some(rec) = self.index.tile_mut(handle) {
+                    rec.id = TileId(gid);
+                }
+            }
+            (None, 0) => {}
+            (None, _) => {
+                let world = offset + vec2(x as f32, y as f32) * tile;
+                tl.handles[cell] = Some(self.index.add_tile(TileId(gid), tl.layer_id, world));
+            }
         }
+        Ok(())
     }
 
-    fn draw_object_layers_tiles_from_coords(
+    /// Re-picks the autotile gid of `cell` and its four neighbours on `layer`.
+    ///
+    /// Call after painting or clearing `cell`; only cells that are members of `tiler` are
+    /// rewritten, and flip flags are dropped on rewritten cells.
+    pub fn autotile_cell(
         &mut self,
-        coords: &[crate::spatial::ChunkCoord],
-        stamp: u32,
-    ) {
-        for layer_idx in 0..self.object_layers.len() {
-            self.draw_object_tiles_layer_from_coords(coords, layer_idx, stamp);
-        }
+        layer: &str,
+        cell: (u32, u32),
+        tiler: &AutoTiler,
+    ) -> Result<(), MapError> {
+        let (x, y) = cell;
+        self.autotile_region(layer, TileRegion::new(x, y, 1, 1), tiler)
     }
 
-    fn draw_object_debug_layer_from_coords(
+    /// Re-picks the autotile gid of every cell in `region` plus a one-cell border.
+    ///
+    /// Cells whose mask maps to gid `0` in `tiler` are left as they are rather than
+    /// cleared. Every new gid is checked before any cell changes, so a tiler gid no
+    /// tileset owns fails with [`MapError::InvalidTileGid`] and leaves the layer untouched.
+    pub fn autotile_region(
         &mut self,
-        coords: &[crate::spatial::ChunkCoord],
-        layer_idx: usize,
-        stamp: u32,
-    ) {
-        let Some(layer) = self.object_layers.get_mut(layer_idx) else {
-            return;
+        layer: &str,
+        region: TileRegion,
+        tiler: &AutoTiler,
+    ) -> Result<(), MapError> {
+        let Some(i) = self.tile_layer_pos(layer) else {
+            return Err(MapError::LayerNotFound(layer.to_string()));
         };
-        ensure_object_layer_stamp_invariant(layer);
-        if !layer.visible {
-            return;
+        let tl = &self.tile_layers[i];
+        let width = tl.width as i64;
+        let height = tl.data.len().checked_div(tl.width).unwrap_or(0) as i64;
+        let member = |c: i64, r: i64| {
+            c < 0
+                || r < 0
+                || c >= width
+                || r >= height
+                || tiler.is_member(tl.data[(r * width + c) as usize])
+        };
+
+        let mut updates = Vec::new();
+        let (x0, y0) = (region.x as i64 - 1, region.y as i64 - 1);
+        let (x1, y1) = (
+            region.x as i64 + region.w as i64,
+            region.y as i64 + region.h as i64,
+        );
+        for r in y0.max(0)..=y1.min(height - 1) {
+            for c in x0.max(0)..=x1.min(width - 1) {
+                if !member(c, r) {
+                    continue;
+                }
+                let mask = [
+                    (0, -1, AUTOTILE_N),
+                    (1, 0, AUTOTILE_E),
+                    (0, 1, AUTOTILE_S),
+                    (-1, 0, AUTOTILE_W),
+                ]
+                .iter()
+                .filter(|(dc, dr, _)| member(c + dc, r + dr))
+                .fold(0, |m, (_, _, bit)| m | bit);
+                let gid = tiler.gid_for_mask(mask);
+                if gid != 0 && gid != tl.data[(r * width + c) as usize] {
+                    updates.push((c as u32, r as u32, gid));
+                }
+            }
         }
-        let alpha = layer.opacity.clamp(0.0, 1.0);
-        let rect_color = Color::new(YELLOW.r, YELLOW.g, YELLOW.b, alpha);
-        let point_color = Color::new(GREEN.r, GREEN.g, GREEN.b, alpha);
-        let polygon_color = Color::new(SKYBLUE.r, SKYBLUE.g, SKYBLUE.b, alpha);
-        let polyline_color = Color::new(PINK.r, PINK.g, PINK.b, alpha);
-        let tile_color = Color::new(MAGENTA.r, MAGENTA.g, MAGENTA.b, alpha);
-        let bucket_layer = layer.bucket_layer;
+        for &(_, _, gid) in &updates {
+            self.check_tile_gid(layer, gid)?;
+        }
+        for (c, r, gid) in updates {
+            self.set_tile(layer, c, r, gid)?;
+        }
+        Ok(())
+    }
 
-        Self::for_each_visible_layer_bucket(
-            &self.index,
-            coords,
-            bucket_layer,
-            |cc, layer_bucket| {
-                let records = &layer_bucket.objects;
-                for rec in records {
-                    let object_idx = rec.handle.0 as usize;
-                    if object_idx >= layer.objects.len() {
-                        debug_assert!(false, "ObjectHandle out of bounds for debug draw");
-                        continue;
-                    }
-                    debug_assert!(object_idx < layer.seen_stamp_debug.len());
-                    if layer.seen_stamp_debug[object_idx] == stamp {
-                        continue;
-                    }
-                    layer.seen_stamp_debug[object_idx] = stamp;
+    // Fails unless the raw `gid` is empty or owned by a tileset; `layer` names the error.
+    fn check_tile_gid(&self, layer: &str, gid: u32) -> Result<(), MapError> {
+        let clean = gid & GID_MASK;
+        if clean != 0 && self.gids.get(clean).is_none() {
+            return Err(MapError::InvalidTileGid {
+                path: None,
+                layer: layer.to_string(),
+                gid: clean,
+                raw_gid: gid,
+                max_gid: self.max_gid(),
+            });
+        }
+        Ok(())
+    }
 
-                    let Some(obj) = layer.objects.get(object_idx) else {
-                        continue;
-                    };
-                    if !obj.visible {
-                        continue;
-                    }
+    // Position in `tile_layers` of the tile layer named `name`.
+    fn tile_layer_pos(&self, name: &str) -> Option<usize> {
+        let lid = self.layer_idx(name)?;
+        match self.layer_kind_by_id.get(&(lid as LayerId))? {
+            LayerKindInfo::Tiles(i) => Some(*i),
+            _ => None,
+        }
+    }
 
-                    let origin = vec2(
-                        (cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x,
-                        (cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y,
-                    );
+    fn tile_layer_by_name(&self, name: &str) -> Option<&TileLayer> {
+        self.tile_layers.get(self.tile_layer_pos(name)?)
+    }
 
-                    match &obj.shape {
-                        IrObjectShape::Rectangle => {
-                            draw_rectangle_lines(
-                                origin.x,
-                                origin.y,
-                                obj.width.max(2.0),
-                                obj.height.max(2.0),
-                                2.0,
-                                rect_color,
-                            );
-                        }
-                        IrObjectShape::Point => {
-                            draw_circle(origin.x, origin.y, 5.0, point_color);
-                        }
-                        IrObjectShape::Polygon(points) => {
-                            if points.len() < 2 {
-                                continue;
-                            }
-                            for i in 0..points.len() {
-                                let a = origin + points[i];
-                                let b = origin + points[(i + 1) % points.len()];
-                                draw_line(a.x, a.y, b.x, b.y, 2.0, polygon_color);
-                            }
-                        }
-                        IrObjectShape::Polyline(points) => {
-                            for seg in points.windows(2) {
-                                let a = origin + seg[0];
-                                let b = origin + seg[1];
-                                draw_line(a.x, a.y, b.x, b.y, 2.0, polyline_color);
-                            }
-                        }
-                        IrObjectShape::Tile { .. } => {
-                            draw_rectangle_lines(
-                                origin.x,
-                                origin.y - obj.height,
-                                obj.width.max(16.0),
-                                obj.height.max(16.0),
-                                2.0,
-                                tile_color,
-                            );
-                        }
-                    }
-                }
-            },
-        );
+    /// Reads the raw gid under world position `pos` from a layer's dense grid.
+    fn tile_gid_at(&self, layer: &TileLayer, pos: Vec2) -> Option<TileId> {
+        let offset = self
+            .layer_name(layer.layer_id)
+            .and_then(|name| self.layer_offset(name))
+            .unwrap_or(Vec2::ZERO);
+        let local = pos - offset;
+        // Written so NaN fails the test as well.
+        if !(local.x >= 0.0 && local.y >= 0.0) || layer.width == 0 {
+            return None;
+        }
+        let col = (local.x / self.tile_w as f32) as usize;
+        let row = (local.y / self.tile_h as f32) as usize;
+        if col >= layer.width || row >= layer.data.len() / layer.width {
+            return None;
+        }
+        let raw = *layer.data.get(row * layer.width + col)?;
+        (raw & GID_MASK != 0).then_some(TileId(raw))
+    }
+
+    /// Image path of every tileset, relative to the map directory, in tileset order.
+    pub fn tileset_image_paths(&self) -> Vec<&str> {
+        self.tilesets.iter().map(|ts| ts.image.as_str()).collect()
     }
 
-    fn draw_object_tiles_layer_from_coords(
+    /// Looks up a tileset by its Tiled name. With duplicate names the first tileset wins.
+    pub fn tileset_by_name(&self, name: &str) -> Option<&TilesetInfo> {
+        self.tilesets.get(*self.tileset_idx_by_name.get(name)?)
+    }
+
+    /// Swaps the texture of the tileset named `tileset_name`, e.g. after its image changed
+    /// on disk. The texture gets the filter used at load time; gids and tile positions
+    /// are unchanged.
+    ///
+    /// Returns `false` (dropping `new_texture`) if no tileset has that name. With duplicate
+    /// names the first tileset is replaced. A tileset packed by [`Map::build_atlas`]
+    /// leaves the atlas.
+    pub fn replace_tileset_texture(&mut self, tileset_name: &str, new_texture: Texture2D) -> bool {
+        let Some(i) = self.swap_tileset_texture(tileset_name, new_texture) else {
+            return false;
+        };
+        if let Some(tex) = &self.tilesets[i].tex {
+            tex.set_filter(self.renderer.texture_filter);
+        }
+        true
+    }
+
+    // The GL-free part of `replace_tileset_texture`: installs the texture and points the
+    // source rects back at the tileset's own image. Returns the tileset's position.
+    fn swap_tileset_texture(
         &mut self,
-        coords: &[crate::spatial::ChunkCoord],
-        layer_idx: usize,
-        stamp: u32,
-    ) {
-        let gid_lut = &self.gid_lut;
-        let tilesets = &self.tilesets;
-        let Some(layer) = self.object_layers.get_mut(layer_idx) else {
+        tileset_name: &str,
+        new_texture: Texture2D,
+    ) -> Option<usize> {
+        let &i = self.tileset_idx_by_name.get(tileset_name)?;
+        self.tilesets[i].tex = Some(new_texture);
+        self.source_rects[i] = self.tilesets[i].source_rects();
+        Some(i)
+    }
+
+    /// Packs the tileset textures into one atlas texture of at most `max_size` pixels per
+    /// side, so tiles of different tilesets draw without switching textures. Returns how
+    /// many tilesets were packed.
+    ///
+    /// Tilesets are shelf-packed tallest first with `padding` transparent pixels between
+    /// them, so filtering does not bleed across images. Tilesets that do not fit keep
+    /// their own texture, as do tilesets without one (headless loads). Packed tilesets
+    /// get the atlas as [`TilesetInfo::tex`] and draw commands carry atlas source rects;
+    /// [`TilesetInfo::source_rect`] still describes the tileset's own image, and
+    /// [`Map::execute_draw_commands`] maps atlas rects back to it for caller textures.
+    ///
+    /// Needs a GL context. Nothing changes when fewer than two tilesets fit, or once an
+    /// atlas was built.
+    pub fn build_atlas(&mut self, max_size: u32, padding: u32) -> usize {
+        if self.renderer.atlas.is_some() {
+            return 0;
+        }
+        let images: Vec<(usize, Image)> = self
+            .tilesets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, ts)| Some((i, ts.tex.as_ref()?.get_texture_data())))
+            .collect();
+        let sizes: Vec<(u32, u32)> = images
+            .iter()
+            .map(|(_, image)| (image.width as u32, image.height as u32))
+            .collect();
+        let layout = pack_shelves(&sizes, max_size.min(u16::MAX as u32), padding);
+        let packed: Vec<(usize, (u32, u32))> = images
+            .iter()
+            .zip(&layout.positions)
+            .filter_map(|((i, _), pos)| Some((*i, (*pos)?)))
+            .collect();
+        if packed.len() < 2 {
+            return 0;
+        }
+
+        let refs: Vec<&Image> = images.iter().map(|(_, image)| image).collect();
+        let atlas = Texture2D::from_image(&compose_atlas(&refs, &layout));
+        atlas.set_filter(self.renderer.texture_filter);
+        for &(i, origin) in &packed {
+            self.tilesets[i].tex = Some(atlas.clone());
+            self.place_in_atlas(i, origin);
+        }
+        self.renderer.atlas = Some(atlas);
+        packed.len()
+    }
+
+    // Points the source rects of tileset `i` at its image placed at `origin` in an atlas.
+    fn place_in_atlas(&mut self, i: usize, (x, y): (u32, u32)) {
+        let offset = vec2(x as f32, y as f32);
+        self.source_rects[i] = self.tilesets[i]
+            .source_rects()
+            .iter()
+            .map(|r| r.offset(offset))
+            .collect();
+    }
+
+    /// The highest gid any registered tileset covers, or 0 without tilesets.
+    ///
+    /// Every gid in `1..=max_gid()` that falls inside a tileset can be placed with
+    /// [`Map::set_tile`].
+    pub fn max_gid(&self) -> u32 {
+        self.gids.max_gid()
+    }
+
+    /// Returns the `(min_gid, max_gid)` covered by registered tilesets, or `None` if the
+    /// map has no tilesets.
+    ///
+    /// GIDs always start at 1; gaps between tilesets still count as inside the range.
+    pub fn gid_range(&self) -> Option<(u32, u32)> {
+        let max_gid = self.max_gid();
+        (max_gid > 0).then_some((1, max_gid))
+    }
+
+    /// Maps Tiled flip flags to `(rotation, flip_x, flip_y, pivot)` for `draw_texture_ex`.
+    ///
+    /// Tiled applies the diagonal flip (a transpose) first, then the horizontal and
+    /// vertical flips. Macroquad flips the texture and then rotates it clockwise around
+    /// the pivot; `None` keeps the pivot at the destination center so tiles turn in place.
+    #[inline]
+    fn params_for_flips_gid(gid: TileId) -> (f32, bool, bool, Option<Vec2>) {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        let (rotation, flip_x, flip_y) = match (gid.flip_h(), gid.flip_v(), gid.flip_d()) {
+            (h, v, false) => (0.0, h, v),
+            (false, false, true) => (FRAC_PI_2, false, true),
+            (true, false, true) => (FRAC_PI_2, false, false),
+            (false, true, true) => (PI + FRAC_PI_2, false, false),
+            (true, true, true) => (FRAC_PI_2, true, false),
+        };
+
+        (rotation, flip_x, flip_y, None)
+    }
+
+    // Tileset owning `gid` and the tile's atlas rect.
+    #[inline]
+    fn tile_source(&self, gid: TileId) -> Option<(&TilesetInfo, Rect)> {
+        let tileset = self.gids.get(gid.clean())?;
+        Some((
+            &self.tilesets[tileset as usize],
+            self.source_rect_of(tileset, gid),
+        ))
+    }
+
+    #[inline]
+    fn source_rect_of(&self, tileset: u16, gid: TileId) -> Rect {
+        let ts = &self.tilesets[tileset as usize];
+        let local = gid.clean() - ts.first_gid;
+        // Gid ranges never reach past a tileset's tiles; the formula is only a backstop.
+        self.source_rects[tileset as usize]
+            .get(local as usize)
+            .copied()
+            .unwrap_or_else(|| ts.source_rect(local))
+    }
+
+    /// Draws one tile at a screen position, ignoring the active camera (HUD icons,
+    /// palettes, previews).
+    ///
+    /// `scale` multiplies the tileset's tile size. The camera state is restored afterwards.
+    /// Unknown gids and tilesets loaded without textures draw nothing.
+    pub fn draw_tile_at_screen(&self, gid: TileId, screen_x: f32, screen_y: f32, scale: f32) {
+        let Some((ts, source)) = self.tile_source(gid) else {
             return;
         };
-        ensure_object_layer_stamp_invariant(layer);
-        if !layer.visible {
+        let Some(tex) = &ts.tex else {
             return;
+        };
+        let pos = vec2(screen_x, screen_y);
+        let size = vec2(ts.tile_w as f32, ts.tile_h as f32) * scale;
+        let (rotation, flip_x, flip_y, pivot) = Self::params_for_flips_gid(gid);
+
+        push_camera_state();
+        set_default_camera();
+        draw_texture_ex(
+            tex,
+            pos.x,
+            pos.y,
+            WHITE,
+            DrawTextureParams {
+                source: Some(source),
+                dest_size: Some(size),
+                rotation,
+                flip_x,
+                flip_y,
+                pivot,
+            },
+        );
+        pop_camera_state();
+    }
+
+    /// Returns `true` if `gid` (flip flags ignored) is non-zero and belongs to a loaded
+    /// tileset, i.e. it is safe to draw or look up.
+    pub fn has_tileset_for_gid(&self, gid: TileId) -> bool {
+        self.gids.get(gid.clean()).is_some()
+    }
+
+    /// Draws only tile layers inside the visible rectangle.
+    ///
+    /// Stable API for tile-only rendering. Object layers are not drawn here.
+    ///
+    /// `view_min`/`view_max` are world-space pixel corners.
+    pub fn draw_visible_rect(&self, view_min: Vec2, view_max: Vec2) {
+        self.draw_chunks(view_min, view_max);
+    }
+
+    /// Fills the view rect (world-space corners) with a tile-sized checkerboard of
+    /// `color_a` and `color_b`, snapped to the tile grid, like editors show behind
+    /// transparent pixels.
+    ///
+    /// Uses no textures, so it also works on headless maps. Call it before
+    /// [`Map::draw_visible_rect`] so tiles draw on top; `GRAY` and `DARKGRAY` are the
+    /// usual colors.
+    pub fn draw_checkerboard_background(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+        color_a: Color,
+        color_b: Color,
+    ) {
+        for (rect, is_a) in self.checkerboard_cells(view_min, view_max) {
+            let color = if is_a { color_a } else { color_b };
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
         }
-        let tint = Color::new(1.0, 1.0, 1.0, layer.opacity.clamp(0.0, 1.0));
-        let bucket_layer = layer.bucket_layer;
+    }
 
-        Self::for_each_visible_layer_bucket(
-            &self.index,
-            coords,
-            bucket_layer,
-            |cc, layer_bucket| {
-                let records = &layer_bucket.objects;
-                for rec in records {
-                    let object_idx = rec.handle.0 as usize;
-                    if object_idx >= layer.objects.len() {
-                        debug_assert!(false, "ObjectHandle out of bounds for tile draw");
-                        continue;
-                    }
-                    debug_assert!(object_idx < layer.seen_stamp_tiles.len());
-                    if layer.seen_stamp_tiles[object_idx] == stamp {
-                        continue;
-                    }
-                    layer.seen_stamp_tiles[object_idx] = stamp;
+    /// Outlines grid cell `(col, row)` in `color`, for highlighting the tile under an
+    /// editor cursor.
+    ///
+    /// Uses no textures or index data. The usual highlight converts the mouse position to
+    /// world space, snaps it to a cell with [`Map::world_to_tile`] and passes that cell
+    /// here with `GREEN`.
+    pub fn draw_tile_cursor(&self, col: u32, row: u32, color: Color) {
+        let rect = self.tile_rect(col, row);
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, color);
+    }
 
-                    let Some(obj) = layer.objects.get(object_idx) else {
-                        continue;
-                    };
-                    if !obj.visible {
-                        continue;
-                    }
+    /// Fills grid cell `(col, row)` with `color` like [`Map::draw_tile_cursor`] outlines
+    /// it; give `color` some transparency to keep the tile visible.
+    pub fn draw_tile_cursor_filled(&self, col: u32, row: u32, color: Color) {
+        let rect = self.tile_rect(col, row);
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+    }
 
-                    let IrObjectShape::Tile { gid } = obj.shape else {
-                        continue;
-                    };
+    // Grid cells overlapping the view, flagged `true` where column + row is even.
+    fn checkerboard_cells(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+    ) -> impl Iterator<Item = (Rect, bool)> {
+        let tile = vec2(self.tile_w.max(1) as f32, self.tile_h.max(1) as f32);
+        let first = (view_min.min(view_max) / tile).floor();
+        let end = (view_min.max(view_max) / tile).ceil();
+        let (c0, r0) = (first.x as i64, first.y as i64);
+        let (c1, r1) = (end.x as i64, end.y as i64);
+        (r0..r1).flat_map(move |row| {
+            (c0..c1).map(move |col| {
+                let pos = vec2(col as f32, row as f32) * tile;
+                (
+                    Rect::new(pos.x, pos.y, tile.x, tile.y),
+                    (col + row) % 2 == 0,
+                )
+            })
+        })
+    }
 
-                    let origin = vec2(
-                        (cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x,
-                        (cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y,
-                    );
+    /// Calls `f` for every chunk of the spatial index in the view rect (world-space
+    /// corners, plus the [`Map::set_cull_padding`] margin [`Map::draw_visible_rect`]
+    /// uses), row by row.
+    ///
+    /// Only chunks holding tiles or objects are visited, and huge views cost no more than
+    /// the map's chunk count. See [`Map::try_each_chunk_in_view`] to stop early.
+    pub fn each_chunk_in_view(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+        mut f: impl FnMut(ChunkCoord, &GlobalChunk),
+    ) {
+        let _ = self.try_each_chunk_in_view(view_min, view_max, |cc, chunk| {
+            f(cc, chunk);
+            ControlFlow::Continue(())
+        });
+    }
 
-                    let gid = TileId(gid);
-                    let Some((ts, local)) = Self::ts_for_gid_from(gid, gid_lut, tilesets) else {
-                        continue;
-                    };
+    /// Like [`Map::each_chunk_in_view`], stopping as soon as `f` returns
+    /// [`ControlFlow::Break`]; returns whether it did.
+    pub fn try_each_chunk_in_view(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+        f: impl FnMut(ChunkCoord, &GlobalChunk) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let (min, max) = self.view_chunk_range(view_min, view_max);
+        let mut scratch = self.renderer.visit_scratch.take();
+        let flow = try_for_each_chunk_in_range(&self.index, min, max, &mut scratch, f);
+        self.renderer.visit_scratch.set(scratch);
+        flow
+    }
 
-                    let col = local % ts.cols;
-                    let row = local / ts.cols;
-                    let sx = ts.margin + col * (ts.tile_w + ts.spacing);
-                    let sy = ts.margin + row * (ts.tile_h + ts.spacing);
+    /// Draws the full map in configured layer order.
+    ///
+    /// Stable API: draws visible tile layers and tile-objects.
+    /// If debug drawing is enabled, object debug overlays are drawn too.
+    ///
+    /// `view_min`/`view_max` are world-space pixel corners.
+    /// Culling uses [`Map::set_cull_padding`] in world-space pixels.
+    pub fn draw(&mut self, view_min: Vec2, view_max: Vec2) {
+        self.draw_interleaved(view_min, view_max, &[], |_| {});
+    }
 
-                    let w = if obj.width > 0.0 {
-                        obj.width
-                    } else {
-                        ts.tile_w as f32
-                    };
-                    let h = if obj.height > 0.0 {
-                        obj.height
-                    } else {
-                        ts.tile_h as f32
-                    };
+    /// Draws the map like [`Map::draw`], calling `draw_sprite` with the index of each
+    /// entry of `sprites` at its place in the owning object layer's draw order.
+    ///
+    /// Sprites whose layer is not a visible object layer are not drawn.
+    pub fn draw_interleaved<F: FnMut(usize)>(
+        &mut self,
+        view_min: Vec2,
+        view_max: Vec2,
+        sprites: &[InjectedSprite],
+        mut draw_sprite: F,
+    ) {
+        self.draw_layers_filtered(view_min, view_max, |_| true, sprites, &mut draw_sprite);
+    }
 
-                    let (flag_rotation, flip_x, flip_y, _) = Self::params_for_flips_gid(gid, w, h);
-                    let rotation = obj.rotation.to_radians() + flag_rotation;
-                    draw_texture_ex(
-                        &ts.tex,
-                        origin.x,
-                        origin.y - h,
-                        tint,
-                        DrawTextureParams {
-                            source: Some(Rect::new(
-                                sx as f32,
-                                sy as f32,
-                                ts.tile_w as f32,
-                                ts.tile_h as f32,
-                            )),
-                            dest_size: Some(vec2(w, h)),
-                            rotation,
-                            flip_x,
-                            flip_y,
-                            // Macroquad expects pivot in screen-space coordinates.
-                            // Keep Tiled-style bottom-left anchoring at the object's (x, y).
-                            pivot: Some(origin),
-                        },
-                    );
-                }
-            },
+    /// Draws the layers named in `layers` (all layers if empty) in their map draw order,
+    /// culled to the view of `cam`.
+    ///
+    /// `cam` is active only for this call; the previous camera is restored afterwards, so
+    /// a HUD pass can follow with the default camera. Unknown names are ignored.
+    pub fn draw_with_custom_camera(&mut self, cam: &Camera2D, layers: &[&str]) {
+        let (view_min, view_max) = camera_view_rect(cam);
+        let wanted: Vec<LayerIdx> = layers.iter().filter_map(|n| self.layer_idx(n)).collect();
+        push_camera_state();
+        set_camera(cam);
+        self.draw_layers_filtered(
+            view_min,
+            view_max,
+            |id| layers.is_empty() || wanted.contains(&(id as LayerIdx)),
+            &[],
+            &mut |_| {},
         );
+        pop_camera_state();
     }
 
-    fn for_each_visible_layer_bucket<F>(
-        index: &GlobalIndex,
-        coords: &[crate::spatial::ChunkCoord],
-        bucket_layer: LayerIdx,
-        mut f: F,
-    ) where
-        F: FnMut(crate::spatial::ChunkCoord, &crate::spatial::LayerBucket),
-    {
-        for cc in coords {
-            let Some(chunk) = index.buckets.get(cc) else {
+    /// Renders the layers named in `layer_names` (all layers if empty) at 1:1 scale and
+    /// writes the result to `path` as an RGBA PNG.
+    ///
+    /// The image is [`Map::size_px`] large, from the world origin; cells without tiles
+    /// stay transparent. Rendering goes through an offscreen render target, so the screen
+    /// and the current camera are left untouched. Needs a GL context (call from the
+    /// macroquad main loop). Unknown names fail with [`MapError::LayerNotFound`] before
+    /// anything is rendered, as do maps wider or taller than 65535 pixels.
+    pub async fn export_as_png(
+        &mut self,
+        layer_names: &[&str],
+        path: &Path,
+    ) -> Result<(), MapError> {
+        let (width, height) = self.export_size()?;
+        if let Some(name) = layer_names.iter().find(|n| self.layer_idx(n).is_none()) {
+            return Err(MapError::LayerNotFound(name.to_string()));
+        }
+
+        let target = render_target(width, height);
+        target.texture.set_filter(FilterMode::Nearest);
+        let mut cam = Camera2D::from_display_rect(Rect::new(0.0, 0.0, width as f32, height as f32));
+        cam.render_target = Some(target.clone());
+
+        push_camera_state();
+        set_camera(&cam);
+        clear_background(BLANK);
+        self.draw_with_custom_camera(&cam, layer_names);
+        // Popping flushes the queued draw calls into the target.
+        pop_camera_state();
+
+        write_png_flipped(&target.texture.get_texture_data(), path)
+    }
+
+    // Pixel size of the PNG `export_as_png` renders, checked before any GL call.
+    fn export_size(&self) -> Result<(u32, u32), MapError> {
+        let width = self.width.checked_mul(self.tile_w);
+        let height = self.height.checked_mul(self.tile_h);
+        match (width, height) {
+            (Some(0), _) | (_, Some(0)) => Err(MapError::invalid(
+                "map has no tile area to export".to_string(),
+            )),
+            (Some(w), Some(h)) if w <= MAX_EXPORT_SIDE && h <= MAX_EXPORT_SIDE => Ok((w, h)),
+            _ => Err(MapError::invalid(format!(
+                "map of {}x{} tiles of {}x{} px is too large to export \
+                 (at most {MAX_EXPORT_SIDE} px per side)",
+                self.width, self.height, self.tile_w, self.tile_h
+            ))),
+        }
+    }
+
+    fn draw_layers_filtered<F: FnMut(usize)>(
+        &mut self,
+        view_min: Vec2,
+        view_max: Vec2,
+        include: impl Fn(LayerId) -> bool,
+        sprites: &[InjectedSprite],
+        draw_sprite: &mut F,
+    ) {
+        let mut coords = self.renderer.chunk_coords.take();
+        self.visible_coords_for_draw_into(view_min, view_max, &mut coords);
+        let stamp = self.next_frame_stamp();
+        for i in 0..self.draw_order.len() {
+            let layer_id = self.draw_order[i];
+            if !include(layer_id) {
                 continue;
-            };
-            let Some(bucket) = chunk.layers.get(&bucket_layer) else {
+            }
+            let Some(kind) = self.layer_kind_by_id.get(&layer_id).copied() else {
                 continue;
             };
-            f(*cc, bucket);
+            match kind {
+                LayerKindInfo::Tiles(tile_layer_idx) => {
+                    self.draw_tile_layer_from_coords(&coords, tile_layer_idx, Vec2::ZERO);
+                }
+                LayerKindInfo::Objects(object_layer_idx) => {
+                    self.draw_object_tiles_layer_from_coords(
+                        &coords,
+                        object_layer_idx,
+                        stamp,
+                        sprites,
+                        draw_sprite,
+                    );
+                    if self.renderer.debug_draw {
+                        self.draw_object_debug_layer_from_coords(&coords, object_layer_idx, stamp);
+                    }
+                }
+                LayerKindInfo::Unsupported => {}
+            }
+        }
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Draws only the tile layer named `layer_name`, shifted by `extra_offset` on screen.
+    ///
+    /// Rendering-only: the spatial index and collision queries keep the layer's real
+    /// positions, so this suits per-frame wobble or shake of a single layer. Culling is
+    /// widened by `|extra_offset|` so shifted tiles do not pop at the view edges.
+    /// Does nothing for unknown or non-tile layers.
+    pub fn draw_layer_at_offset(
+        &self,
+        layer_name: &str,
+        view_min: Vec2,
+        view_max: Vec2,
+        extra_offset: Vec2,
+    ) {
+        let Some(lid) = self.layer_idx(layer_name) else {
+            return;
+        };
+        let Some(LayerKindInfo::Tiles(tile_layer_idx)) =
+            self.layer_kind_by_id.get(&(lid as LayerId)).copied()
+        else {
+            return;
+        };
+        let inflate = extra_offset.abs();
+        let coords = self.take_visible_coords(
+            view_min.min(view_max) - inflate,
+            view_min.max(view_max) + inflate,
+        );
+        self.draw_tile_layer_from_coords(&coords, tile_layer_idx, extra_offset);
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Draws only the tile layer named `layer_name`, mirrored across the map's vertical
+    /// center line (`flip_h`) and/or its horizontal one (`flip_v`).
+    ///
+    /// Each tile moves to its mirrored cell and has its Tiled flip flag toggled, so
+    /// flipped, rotated and oversized tiles mirror correctly. `view_min`/`view_max` are
+    /// in the mirrored world. Rendering-only like [`Map::draw_layer_at_offset`]: the
+    /// spatial index and queries keep the unflipped layout. Does nothing for unknown or
+    /// non-tile layers.
+    pub fn draw_layer_flipped(
+        &self,
+        layer_name: &str,
+        view_min: Vec2,
+        view_max: Vec2,
+        flip_h: bool,
+        flip_v: bool,
+    ) {
+        self.for_each_flipped_command(layer_name, view_min, view_max, flip_h, flip_v, |cmd| {
+            self.draw_command(&cmd)
+        });
+    }
+
+    fn for_each_flipped_command(
+        &self,
+        layer_name: &str,
+        view_min: Vec2,
+        view_max: Vec2,
+        flip_h: bool,
+        flip_v: bool,
+        mut f: impl FnMut(DrawCommand),
+    ) {
+        let Some(LayerKindInfo::Tiles(tile_layer_idx)) = self
+            .layer_idx(layer_name)
+            .and_then(|lid| self.layer_kind_by_id.get(&(lid as LayerId)).copied())
+        else {
+            return;
+        };
+        let (map_w, map_h) = self.size_px();
+        let (lo, hi) = (view_min.min(view_max), view_min.max(view_max));
+        // The unflipped region that lands inside the view once mirrored.
+        let (lo, hi) = (
+            vec2(
+                if flip_h { map_w - hi.x } else { lo.x },
+                if flip_v { map_h - hi.y } else { lo.y },
+            ),
+            vec2(
+                if flip_h { map_w - lo.x } else { hi.x },
+                if flip_v { map_h - lo.y } else { hi.y },
+            ),
+        );
+        let coords = self.take_visible_coords(lo, hi);
+
+        let flags = if flip_h { FLIP_H } else { 0 } | if flip_v { FLIP_V } else { 0 };
+        self.for_each_tile_command(&coords, tile_layer_idx, Vec2::ZERO, |mut cmd| {
+            if flip_h {
+                cmd.dest.x = map_w - cmd.dest.x - cmd.dest_w;
+            }
+            if flip_v {
+                cmd.dest.y = map_h - cmd.dest.y - cmd.dest_h;
+            }
+            cmd.gid ^= flags;
+            (cmd.rotation, cmd.flip_x, cmd.flip_y, cmd.pivot) =
+                Self::params_for_flips_gid(TileId(cmd.gid));
+            f(cmd);
+        });
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Draws only the tile layer named `layer_name`, column by column from left to right
+    /// and top to bottom within each column.
+    ///
+    /// Meant for pseudo-3D top-down maps whose tall tiles (trees, pillars, wall tops)
+    /// overhang the cell above: the lower tile of a column is drawn last and covers the
+    /// one it overlaps. [`ChunkRenderMode::YSort`] instead sorts a chunk's tiles by their
+    /// bottom edge across all columns, and [`Map::draw`] keeps storage order; use this when
+    /// tall tiles must stack within a column but columns may overlap sideways. The sort
+    /// spans chunk boundaries, so the visible tiles are gathered and sorted on each call,
+    /// in a buffer reused across calls. Does nothing for unknown or non-tile layers.
+    pub fn draw_tile_columns_sorted(&self, layer_name: &str, view_min: Vec2, view_max: Vec2) {
+        self.for_each_column_sorted_command(layer_name, view_min, view_max, |cmd| {
+            self.draw_command(&cmd)
+        });
+    }
+
+    fn for_each_column_sorted_command(
+        &self,
+        layer_name: &str,
+        view_min: Vec2,
+        view_max: Vec2,
+        f: impl FnMut(DrawCommand),
+    ) {
+        let Some(layer) = self.layer_idx(layer_name).and_then(|lid| {
+            match self.layer_kind_by_id.get(&(lid as LayerId)) {
+                Some(LayerKindInfo::Tiles(i)) => self.tile_layers.get(*i),
+                _ => None,
+            }
+        }) else {
+            return;
+        };
+        if !layer.visible || layer.opacity <= 0.0 {
+            return;
+        }
+        let tint = layer_tint(layer.tint, layer.opacity);
+
+        let coords = self.take_visible_coords(view_min, view_max);
+        let mut tiles = self.renderer.column_scratch.take();
+        tiles.clear();
+        Self::for_each_visible_layer_bucket(&self.index, &coords, layer.layer_id, |cc, bucket| {
+            tiles.extend(bucket.tiles.iter().filter_map(|rec| {
+                let cell = (rec.col(cc, self.tile_w), rec.row(cc, self.tile_h));
+                let cmd = self.tile_draw_command(layer.layer_id, cc, rec, tint, Vec2::ZERO)?;
+                Some((cell, cmd))
+            }));
+        });
+        self.renderer.chunk_coords.set(coords);
+        // A stable sort would allocate; cells are unique within a layer, so an unstable
+        // one gives the same order.
+        tiles.sort_unstable_by_key(|&(cell, _)| cell);
+        tiles.drain(..).map(|(_, cmd)| cmd).for_each(f);
+        self.renderer.column_scratch.set(tiles);
+    }
+
+    /// Enables/disables object debug overlay drawing used by [`Map::draw`].
+    ///
+    /// Stable API.
+    pub fn set_debug_draw(&mut self, enabled: bool) {
+        self.renderer.debug_draw = enabled;
+    }
+
+    /// Replaces the default sprite draw of the tile object with id `object_id`.
+    ///
+    /// The override runs in the tile-object pass in the object's normal draw-order slot, at
+    /// most once per frame and only when the object survives culling and is visible.
+    /// Registering again for the same id replaces the previous override.
+    pub fn set_object_draw_override(&mut self, object_id: u32, f: ObjectDrawOverride) {
+        self.renderer.object_draw_overrides.insert(object_id, f);
+    }
+
+    /// Removes the draw override for `object_id`, returning it if one was registered.
+    pub fn clear_object_draw_override(&mut self, object_id: u32) -> Option<ObjectDrawOverride> {
+        self.renderer.object_draw_overrides.remove(&object_id)
+    }
+
+    /// Sets extra culling padding in world-space pixels around the view rectangle.
+    ///
+    /// Stable API. `0.0` means no extra padding.
+    pub fn set_cull_padding(&mut self, padding: f32) {
+        self.renderer.cull_padding = padding.max(0.0);
+    }
+
+    /// Draws debug shapes for visible object layers.
+    ///
+    /// Stable convenience API: acquires an internal frame stamp automatically.
+    ///
+    /// Use this when object drawing is a standalone call for the frame.
+    pub fn draw_objects_debug(&mut self, view_min: Vec2, view_max: Vec2) {
+        let stamp = self.next_frame_stamp();
+        self.draw_objects_debug_with_stamp(view_min, view_max, stamp);
+    }
+
+    /// Advanced API: draws debug shapes for visible object layers using a caller-provided stamp.
+    ///
+    /// Use this when you want frame-coherent manual composition (for example:
+    /// tile-object pass + debug pass in the same frame using one shared stamp).
+    ///
+    /// Stamp rule: pass the same `stamp` to all object passes in a frame.
+    pub fn draw_objects_debug_with_stamp(&mut self, view_min: Vec2, view_max: Vec2, stamp: u32) {
+        let coords = self.take_visible_coords(view_min, view_max);
+        self.draw_object_layers_debug_from_coords(&coords, stamp);
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Draws tile-objects from visible object layers.
+    ///
+    /// Stable convenience API: acquires an internal frame stamp automatically.
+    ///
+    /// Use this when object drawing is a standalone call for the frame.
+    pub fn draw_objects_tiles(&mut self, view_min: Vec2, view_max: Vec2) {
+        let stamp = self.next_frame_stamp();
+        self.draw_objects_tiles_with_stamp(view_min, view_max, stamp);
+    }
+
+    /// Advanced API: draws tile-objects using a caller-provided stamp.
+    ///
+    /// This exists to support explicit control of object deduplication across
+    /// multiple manual object passes in one frame.
+    ///
+    /// Stamp rule: pass the same `stamp` to all object passes in a frame.
+    pub fn draw_objects_tiles_with_stamp(&mut self, view_min: Vec2, view_max: Vec2, stamp: u32) {
+        let coords = self.take_visible_coords(view_min, view_max);
+        self.draw_object_layers_tiles_from_coords(&coords, stamp);
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    fn draw_chunks(&self, view_min: Vec2, view_max: Vec2) {
+        self.for_each_visible_rect_command(view_min, view_max, |cmd| self.draw_command(&cmd));
+    }
+
+    // The commands [`Map::draw_visible_rect`] draws: visible tile layers in draw order.
+    fn for_each_visible_rect_command(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+        mut f: impl FnMut(DrawCommand),
+    ) {
+        // The occupied chunks are found once; each layer then only visits those.
+        let mut coords = self.renderer.chunk_coords.take();
+        coords.clear();
+        self.each_chunk_in_view(view_min, view_max, |cc, _| coords.push(cc));
+        for layer_id in &self.draw_order {
+            if let Some(LayerKindInfo::Tiles(i)) = self.layer_kind_by_id.get(layer_id) {
+                self.for_each_tile_command(&coords, *i, Vec2::ZERO, &mut f);
+            }
+        }
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    fn draw_tile_layer_from_coords(
+        &self,
+        coords: &[crate::spatial::ChunkCoord],
+        tile_layer_idx: usize,
+        extra_offset: Vec2,
+    ) {
+        self.for_each_tile_command(coords, tile_layer_idx, extra_offset, |cmd| {
+            self.draw_command(&cmd);
+        });
+    }
+
+    #[inline]
+    fn draw_command(&self, cmd: &DrawCommand) {
+        if let Some(tex) = &self.tilesets[cmd.tileset].tex {
+            cmd.draw(tex);
+        }
+    }
+
+    fn for_each_tile_command(
+        &self,
+        coords: &[crate::spatial::ChunkCoord],
+        tile_layer_idx: usize,
+        extra_offset: Vec2,
+        mut f: impl FnMut(DrawCommand),
+    ) {
+        let Some(layer) = self.tile_layers.get(tile_layer_idx) else {
+            return;
+        };
+        if !layer.visible || layer.opacity <= 0.0 {
+            return;
+        }
+        let tint = layer_tint(layer.tint, layer.opacity);
+
+        let mut sorted = self.renderer.ysort_scratch.take();
+        Self::for_each_visible_layer_bucket(&self.index, coords, layer.layer_id, |cc, bucket| {
+            self.emit_bucket_commands(layer, cc, bucket, tint, extra_offset, &mut sorted, &mut f);
+        });
+        self.renderer.ysort_scratch.set(sorted);
+    }
+
+    // Emits the commands of one chunk bucket, y-sorted when `layer` is the render mode's
+    // base layer. `sorted` is scratch space reused across buckets.
+    #[allow(clippy::too_many_arguments)]
+    fn emit_bucket_commands(
+        &self,
+        layer: &TileLayer,
+        cc: crate::spatial::ChunkCoord,
+        bucket: &crate::spatial::LayerBucket,
+        tint: Color,
+        extra_offset: Vec2,
+        sorted: &mut Vec<(usize, DrawCommand)>,
+        f: impl FnMut(DrawCommand),
+    ) {
+        let commands = bucket
+            .tiles
+            .iter()
+            .filter_map(|rec| self.tile_draw_command(layer.layer_id, cc, rec, tint, extra_offset));
+        match self.render_mode {
+            ChunkRenderMode::YSort { base_layer } if base_layer == layer.layer_id => {
+                sorted.clear();
+                sorted.extend(commands.enumerate());
+                // Unstable sort does not allocate; the index keeps equal bottoms in order.
+                sorted.sort_unstable_by(|(ai, a), (bi, b)| {
+                    (a.dest.y + a.dest_h)
+                        .total_cmp(&(b.dest.y + b.dest_h))
+                        .then(ai.cmp(bi))
+                });
+                sorted.drain(..).map(|(_, cmd)| cmd).for_each(f);
+            }
+            _ => commands.for_each(f),
+        }
+    }
+
+    fn tile_draw_command(
+        &self,
+        layer_id: LayerIdx,
+        cc: crate::spatial::ChunkCoord,
+        rec: &crate::spatial::TileRec,
+        tint: Color,
+        extra_offset: Vec2,
+    ) -> Option<DrawCommand> {
+        let tileset = self.gids.get(rec.id.clean())?;
+        let ts = &self.tilesets[tileset as usize];
+
+        let dest = (rec.world_pos(cc) + extra_offset).round();
+
+        let (rotation, flip_x, flip_y, pivot) = Self::params_for_flips_gid(rec.id);
+
+        Some(DrawCommand {
+            layer: layer_id as LayerId,
+            tileset: tileset as usize,
+            gid: rec.id.raw(),
+            source: self.source_rect_of(tileset, rec.id),
+            dest,
+            dest_w: ts.tile_w as f32,
+            dest_h: ts.tile_h as f32,
+            flip_x,
+            flip_y,
+            rotation,
+            pivot,
+            tint,
+        })
+    }
+
+    /// Sets how tiles are ordered inside each chunk when drawn; see [`ChunkRenderMode`].
+    pub fn set_render_mode(&mut self, mode: ChunkRenderMode) {
+        self.render_mode = mode;
+    }
+
+    /// Current chunk render mode.
+    pub fn render_mode(&self) -> ChunkRenderMode {
+        self.render_mode
+    }
+
+    /// Resolves the tiles [`Map::draw`] would draw for this view into [`DrawCommand`]s,
+    /// without touching the GPU.
+    ///
+    /// Covers visible tile layers in draw order, culled like [`Map::draw`]; tile objects
+    /// are not included. Works on headless maps, where textures are never loaded.
+    pub fn collect_draw_commands(&self, view_min: Vec2, view_max: Vec2) -> Vec<DrawCommand> {
+        let coords = self.take_visible_coords(view_min, view_max);
+        let mut out = Vec::new();
+        for layer_id in &self.draw_order {
+            if let Some(LayerKindInfo::Tiles(i)) = self.layer_kind_by_id.get(layer_id) {
+                self.for_each_tile_command(&coords, *i, Vec2::ZERO, |cmd| out.push(cmd));
+            }
+        }
+        self.renderer.chunk_coords.set(coords);
+        out
+    }
+
+    /// Draws the tile layers visible in the world-space view like
+    /// [`Map::draw_visible_rect`], then scales, offsets and tints every tile per `params`.
+    pub fn draw_ex(&self, view_min: Vec2, view_max: Vec2, params: DrawParams) {
+        let coords = self.take_visible_coords(view_min, view_max);
+        for layer_id in &self.draw_order {
+            if let Some(LayerKindInfo::Tiles(i)) = self.layer_kind_by_id.get(layer_id) {
+                self.for_each_tile_command(&coords, *i, Vec2::ZERO, |cmd| {
+                    self.draw_command(&cmd.transformed(&params));
+                });
+            }
+        }
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Resolves every tile layer to [`DrawCommand`]s, restricted to `region` (world grid
+    /// cells, i.e. after layer offsets) when given.
+    ///
+    /// Unlike [`Map::collect_draw_commands`] this ignores the view and cull padding, so the
+    /// result depends only on map contents: chunks row by row, layers in draw order.
+    pub fn build_draw_commands(&self, region: Option<TileRegion>) -> Vec<DrawCommand> {
+        let mut out = Vec::new();
+        self.for_each_region_command(region, None, |cmd| out.push(cmd));
+        out
+    }
+
+    /// [`Map::build_draw_commands`], optionally restricted to the tile layer `only`, one
+    /// command at a time.
+    fn for_each_region_command(
+        &self,
+        region: Option<TileRegion>,
+        only: Option<LayerId>,
+        mut f: impl FnMut(DrawCommand),
+    ) {
+        let (tw, th) = (self.tile_w as f32, self.tile_h as f32);
+        // Exclusive end cell of the region; saturating so huge regions cannot overflow.
+        let end = |r: TileRegion| (r.x.saturating_add(r.w), r.y.saturating_add(r.h));
+        // Only existing buckets are candidates, so regions reaching far outside the map
+        // cost nothing extra.
+        let chunk_range = region.map(|r| {
+            let (x1, y1) = end(r);
+            (
+                world_to_chunk(vec2(r.x as f32 * tw, r.y as f32 * th)),
+                world_to_chunk(vec2(x1 as f32 * tw, y1 as f32 * th)),
+            )
+        });
+        let mut coords = self.renderer.chunk_coords.take();
+        coords.clear();
+        coords.extend(self.index.buckets.keys().copied().filter(|cc| {
+            chunk_range.is_none_or(|(min, max)| {
+                cc.x >= min.x && cc.y >= min.y && cc.x <= max.x && cc.y <= max.y
+            })
+        }));
+        coords.sort_unstable_by_key(|cc| (cc.y, cc.x));
+        let in_region = |cmd: &DrawCommand| {
+            region.is_none_or(|r| {
+                let (x1, y1) = end(r);
+                let cell = (cmd.dest / vec2(tw, th)).floor();
+                cell.x >= r.x as f32
+                    && cell.y >= r.y as f32
+                    && cell.x < x1 as f32
+                    && cell.y < y1 as f32
+            })
+        };
+
+        for layer_id in &self.draw_order {
+            if only.is_some_and(|only| only != *layer_id) {
+                continue;
+            }
+            if let Some(LayerKindInfo::Tiles(i)) = self.layer_kind_by_id.get(layer_id) {
+                self.for_each_tile_command(&coords, *i, Vec2::ZERO, |cmd| {
+                    if in_region(&cmd) {
+                        f(cmd);
+                    }
+                });
+            }
+        }
+        self.renderer.chunk_coords.set(coords);
+    }
+
+    /// Draws only the tiles inside `region` (world grid cells, after layer offsets), then
+    /// scales, offsets and tints them per `params` like [`Map::draw_ex`].
+    ///
+    /// Regions reaching past the map are clipped to its tiles; regions entirely outside
+    /// draw nothing. Useful for scrolling windows or chunked redraws into render targets.
+    pub fn draw_region_tiles(&self, region: TileRegion, params: DrawParams) {
+        self.for_each_region_command(Some(region), None, |cmd| {
+            self.draw_command(&cmd.transformed(&params))
+        });
+    }
+
+    /// Draws the `w` x `h` cells of tile layer `layer_name` starting at `(col, row)`,
+    /// placed so that cell `(col, row)` lands at `dest` and drawn at map scale.
+    ///
+    /// Cells are world grid cells like [`Map::draw_region_tiles`]; tiles outside the
+    /// rectangle are skipped even when they share a chunk with it. Does nothing for
+    /// unknown or non-tile layers. Suits minimaps, thumbnails and picture-in-picture.
+    pub fn draw_region(&self, layer_name: &str, col: u32, row: u32, w: u32, h: u32, dest: Vec2) {
+        let region = TileRegion::new(col, row, w, h);
+        self.for_each_layer_region_command(layer_name, region, dest, |cmd| self.draw_command(&cmd));
+    }
+
+    fn for_each_layer_region_command(
+        &self,
+        layer_name: &str,
+        region: TileRegion,
+        dest: Vec2,
+        mut f: impl FnMut(DrawCommand),
+    ) {
+        let Some(lid) = self.layer_idx(layer_name) else {
+            return;
+        };
+        let origin = vec2(
+            region.x as f32 * self.tile_w as f32,
+            region.y as f32 * self.tile_h as f32,
+        );
+        let params = DrawParams {
+            offset: dest - origin,
+            ..DrawParams::default()
+        };
+        self.for_each_region_command(Some(region), Some(lid as LayerId), |cmd| {
+            f(cmd.transformed(&params))
+        });
+    }
+
+    /// Draws `cmds` with `textures[cmd.tileset]`, falling back to the map's own texture
+    /// for tilesets past the end of `textures`.
+    ///
+    /// `textures` hold each tileset's own image. Commands of tilesets packed by
+    /// [`Map::build_atlas`] carry atlas source rects, which are mapped back to the
+    /// tileset's image before drawing with a caller texture.
+    pub fn execute_draw_commands(&self, cmds: &[DrawCommand], textures: &[Texture2D]) {
+        for cmd in cmds {
+            match textures.get(cmd.tileset) {
+                Some(tex) => {
+                    let source = self.own_image_source(cmd);
+                    if source == cmd.source {
+                        cmd.draw(tex);
+                    } else {
+                        DrawCommand {
+                            source,
+                            ..cmd.clone()
+                        }
+                        .draw(tex);
+                    }
+                }
+                None => self.draw_command(cmd),
+            }
+        }
+    }
+
+    // Source rect of `cmd` in its tileset's own image. Only rects the map handed out
+    // are translated; a caller's custom source is kept.
+    fn own_image_source(&self, cmd: &DrawCommand) -> Rect {
+        let Some(ts) = self.tilesets.get(cmd.tileset) else {
+            return cmd.source;
+        };
+        let local = (cmd.gid & GID_MASK).wrapping_sub(ts.first_gid);
+        match self.source_rects[cmd.tileset].get(local as usize) {
+            Some(&mapped) if mapped == cmd.source => ts.source_rect(local),
+            _ => cmd.source,
+        }
+    }
+
+    fn draw_object_layers_debug_from_coords(
+        &mut self,
+        coords: &[crate::spatial::ChunkCoord],
+        stamp: u32,
+    ) {
+        for layer_idx in 0..self.object_layers.len() {
+            self.draw_object_debug_layer_from_coords(coords, layer_idx, stamp);
+        }
+    }
+
+    fn draw_object_layers_tiles_from_coords(
+        &mut self,
+        coords: &[crate::spatial::ChunkCoord],
+        stamp: u32,
+    ) {
+        for layer_idx in 0..self.object_layers.len() {
+            self.draw_object_tiles_layer_from_coords(coords, layer_idx, stamp, &[], &mut |_| {});
+        }
+    }
+
+    fn draw_object_debug_layer_from_coords(
+        &mut self,
+        coords: &[crate::spatial::ChunkCoord],
+        layer_idx: usize,
+        stamp: u32,
+    ) {
+        let Some(layer) = self.object_layers.get_mut(layer_idx) else {
+            return;
+        };
+        ensure_object_layer_stamp_invariant(layer);
+        if !layer.visible {
+            return;
+        }
+        let alpha = layer.opacity.clamp(0.0, 1.0);
+        let rect_color = Color::new(YELLOW.r, YELLOW.g, YELLOW.b, alpha);
+        let point_color = Color::new(GREEN.r, GREEN.g, GREEN.b, alpha);
+        let polygon_color = Color::new(SKYBLUE.r, SKYBLUE.g, SKYBLUE.b, alpha);
+        let polyline_color = Color::new(PINK.r, PINK.g, PINK.b, alpha);
+        let tile_color = Color::new(MAGENTA.r, MAGENTA.g, MAGENTA.b, alpha);
+        let bucket_layer = layer.bucket_layer;
+
+        Self::for_each_visible_layer_bucket(
+            &self.index,
+            coords,
+            bucket_layer,
+            |cc, layer_bucket| {
+                let records = &layer_bucket.objects;
+                for rec in records {
+                    let object_idx = rec.handle.0 as usize;
+                    if object_idx >= layer.objects.len() {
+                        debug_assert!(false, "ObjectHandle out of bounds for debug draw");
+                        continue;
+                    }
+                    debug_assert!(object_idx < layer.seen_stamp_debug.len());
+                    if layer.seen_stamp_debug[object_idx] == stamp {
+                        continue;
+                    }
+                    layer.seen_stamp_debug[object_idx] = stamp;
+
+                    let Some(obj) = layer.objects.get(object_idx) else {
+                        continue;
+                    };
+                    if !obj.visible {
+                        continue;
+                    }
+
+                    let origin = vec2(
+                        (cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x,
+                        (cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y,
+                    );
+
+                    match &obj.shape {
+                        IrObjectShape::Rectangle => {
+                            draw_rectangle_lines(
+                                origin.x,
+                                origin.y,
+                                obj.width.max(2.0),
+                                obj.height.max(2.0),
+                                2.0,
+                                rect_color,
+                            );
+                        }
+                        IrObjectShape::Ellipse => {
+                            let radii = vec2(obj.width, obj.height).abs().max(vec2(2.0, 2.0)) / 2.0;
+                            draw_ellipse_lines(
+                                origin.x + obj.width / 2.0,
+                                origin.y + obj.height / 2.0,
+                                radii.x,
+                                radii.y,
+                                0.0,
+                                2.0,
+                                rect_color,
+                            );
+                        }
+                        IrObjectShape::Point => {
+                            draw_circle(origin.x, origin.y, 5.0, point_color);
+                        }
+                        IrObjectShape::Polygon(points) => {
+                            if points.len() < 2 {
+                                continue;
+                            }
+                            for i in 0..points.len() {
+                                let a = origin + points[i];
+                                let b = origin + points[(i + 1) % points.len()];
+                                draw_line(a.x, a.y, b.x, b.y, 2.0, polygon_color);
+                            }
+                        }
+                        IrObjectShape::Polyline(points) => {
+                            for seg in points.windows(2) {
+                                let a = origin + seg[0];
+                                let b = origin + seg[1];
+                                draw_line(a.x, a.y, b.x, b.y, 2.0, polyline_color);
+                            }
+                        }
+                        IrObjectShape::Tile { .. } => {
+                            draw_rectangle_lines(
+                                origin.x,
+                                origin.y - obj.height,
+                                obj.width.max(16.0),
+                                obj.height.max(16.0),
+                                2.0,
+                                tile_color,
+                            );
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    fn draw_object_tiles_layer_from_coords<F: FnMut(usize)>(
+        &mut self,
+        coords: &[crate::spatial::ChunkCoord],
+        layer_idx: usize,
+        stamp: u32,
+        sprites: &[InjectedSprite],
+        draw_sprite: &mut F,
+    ) {
+        let mut items = std::mem::take(&mut self.renderer.object_draw_items);
+        let mut overrides = std::mem::take(&mut self.renderer.object_draw_overrides);
+        self.collect_object_draw_items(coords, layer_idx, stamp, sprites, &mut items);
+
+        if let Some(layer) = self.object_layers.get(layer_idx) {
+            let tint = layer_tint(layer.tint, layer.opacity.clamp(0.0, 1.0));
+            for item in &items {
+                match *item {
+                    // Fully transparent layers skip their objects but keep injected sprites.
+                    ObjectDrawItem::Object { .. } if tint.a <= 0.0 => {}
+                    ObjectDrawItem::Object { object_idx, origin } => {
+                        let obj = &layer.objects[object_idx];
+                        match overrides.get_mut(&obj.id) {
+                            Some(draw_override) => draw_override(obj, origin),
+                            None => self.draw_tile_object(obj, origin, tint),
+                        }
+                    }
+                    ObjectDrawItem::Sprite { sprite_idx, .. } => draw_sprite(sprite_idx),
+                }
+            }
+        }
+
+        self.renderer.object_draw_items = items;
+        self.renderer.object_draw_overrides = overrides;
+    }
+
+    /// Gathers the culled, deduped tile objects of one layer plus the injected sprites
+    /// that belong to it, in the order they must be drawn.
+    fn collect_object_draw_items(
+        &mut self,
+        coords: &[crate::spatial::ChunkCoord],
+        layer_idx: usize,
+        stamp: u32,
+        sprites: &[InjectedSprite],
+        items: &mut Vec<ObjectDrawItem>,
+    ) {
+        items.clear();
+        let Some(layer) = self.object_layers.get_mut(layer_idx) else {
+            return;
+        };
+        ensure_object_layer_stamp_invariant(layer);
+        if !layer.visible {
+            return;
+        }
+        let bucket_layer = layer.bucket_layer;
+
+        Self::for_each_visible_layer_bucket(
+            &self.index,
+            coords,
+            bucket_layer,
+            |cc, layer_bucket| {
+                let records = &layer_bucket.objects;
+                for rec in records {
+                    let object_idx = rec.handle.0 as usize;
+                    if object_idx >= layer.objects.len() {
+                        debug_assert!(false, "ObjectHandle out of bounds for tile draw");
+                        continue;
+                    }
+                    debug_assert!(object_idx < layer.seen_stamp_tiles.len());
+                    if layer.seen_stamp_tiles[object_idx] == stamp {
+                        continue;
+                    }
+                    layer.seen_stamp_tiles[object_idx] = stamp;
+
+                    let Some(obj) = layer.objects.get(object_idx) else {
+                        continue;
+                    };
+                    if !obj.visible || !matches!(obj.shape, IrObjectShape::Tile { .. }) {
+                        continue;
+                    }
+
+                    let origin = vec2(
+                        (cc.x * CHUNK_SIZE) as f32 + rec.rel_pos.x,
+                        (cc.y * CHUNK_SIZE) as f32 + rec.rel_pos.y,
+                    );
+                    items.push(ObjectDrawItem::Object { object_idx, origin });
+                }
+            },
+        );
+
+        items.extend(
+            sprites
+                .iter()
+                .enumerate()
+                .filter(|(_, sprite)| sprite.layer == layer.id)
+                .map(|(sprite_idx, sprite)| ObjectDrawItem::Sprite {
+                    sprite_idx,
+                    y: sprite.y,
+                }),
+        );
+
+        // Buckets are visited in chunk order, so restore authoring order explicitly.
+        // Objects sort before sprites at equal keys. Order keys are unique, so the
+        // non-allocating unstable sort gives the same order a stable one would.
+        match layer.draw_order {
+            ObjectDrawOrder::TopDown => items.sort_unstable_by(|a, b| {
+                a.y()
+                    .total_cmp(&b.y())
+                    .then_with(|| a.order_key().cmp(&b.order_key()))
+            }),
+            ObjectDrawOrder::Index => items.sort_unstable_by_key(ObjectDrawItem::order_key),
+        }
+    }
+
+    fn draw_tile_object(&self, obj: &IrObject, origin: Vec2, tint: Color) {
+        let IrObjectShape::Tile { gid } = obj.shape else {
+            return;
+        };
+
+        let gid = TileId(gid);
+        let Some((ts, source)) = self.tile_source(gid) else {
+            return;
+        };
+        let Some(tex) = &ts.tex else {
+            return;
+        };
+
+        let w = if obj.width > 0.0 {
+            obj.width
+        } else {
+            ts.tile_w as f32
+        };
+        let h = if obj.height > 0.0 {
+            obj.height
+        } else {
+            ts.tile_h as f32
+        };
+
+        let (flag_rotation, flip_x, flip_y, _) = Self::params_for_flips_gid(gid);
+        let rotation = obj.rotation.to_radians() + flag_rotation;
+        draw_texture_ex(
+            tex,
+            origin.x,
+            origin.y - h,
+            tint,
+            DrawTextureParams {
+                source: Some(source),
+                dest_size: Some(vec2(w, h)),
+                rotation,
+                flip_x,
+                flip_y,
+                // Macroquad expects pivot in screen-space coordinates.
+                // Keep Tiled-style bottom-left anchoring at the object's (x, y).
+                pivot: Some(origin),
+            },
+        );
+    }
+
+    fn for_each_visible_layer_bucket<'a, F>(
+        index: &'a GlobalIndex,
+        coords: &[crate::spatial::ChunkCoord],
+        bucket_layer: LayerIdx,
+        mut f: F,
+    ) where
+        F: FnMut(crate::spatial::ChunkCoord, &'a crate::spatial::LayerBucket),
+    {
+        for cc in coords {
+            let Some(chunk) = index.buckets.get(cc) else {
+                continue;
+            };
+            let Some(bucket) = chunk.layers.get(&bucket_layer) else {
+                continue;
+            };
+            f(*cc, bucket);
+        }
+    }
+
+    // The occupied chunks of the padded view, in the renderer's reused buffer. Callers
+    // hand the buffer back through `self.renderer.chunk_coords` once they are done.
+    fn take_visible_coords(&self, view_min: Vec2, view_max: Vec2) -> Vec<ChunkCoord> {
+        let mut coords = self.renderer.chunk_coords.take();
+        self.visible_coords_for_draw_into(view_min, view_max, &mut coords);
+        coords
+    }
+
+    fn visible_coords_for_draw_into(
+        &self,
+        view_min: Vec2,
+        view_max: Vec2,
+        coords: &mut Vec<crate::spatial::ChunkCoord>,
+    ) {
+        let (min, max) = self.view_chunk_range(view_min, view_max);
+        occupied_chunk_coords_into(&self.index, min, max, coords);
+    }
+
+    // Inclusive chunk range of the view rect grown by the cull padding.
+    fn view_chunk_range(&self, view_min: Vec2, view_max: Vec2) -> (ChunkCoord, ChunkCoord) {
+        let pad = self.renderer.cull_padding;
+        chunk_range(
+            vec2(view_min.x - pad, view_min.y - pad),
+            vec2(view_max.x + pad, view_max.y + pad),
+        )
+    }
+}
+
+/// Draw color of a layer: its tint with `opacity` multiplied into the alpha.
+#[inline]
+fn layer_tint(tint: Color, opacity: f32) -> Color {
+    Color {
+        a: tint.a * opacity,
+        ..tint
+    }
+}
+
+/// Writes a render target readback to `path` as an RGBA PNG. GL rows run bottom-up and
+/// PNG rows top-down, so the rows are flipped on the way.
+fn write_png_flipped(image: &Image, path: &Path) -> Result<(), MapError> {
+    let row = image.width as usize * 4;
+    let bytes: Vec<u8> = image
+        .bytes
+        .chunks_exact(row.max(1))
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+    image::save_buffer_with_format(
+        path,
+        &bytes,
+        image.width as u32,
+        image.height as u32,
+        image::ColorType::Rgba8,
+        image::ImageFormat::Png,
+    )
+    .map_err(|e| MapError::Io {
+        path: path.to_path_buf(),
+        source: match e {
+            image::ImageError::IoError(source) => source,
+            other => std::io::Error::other(other),
+        },
+    })
+}
+
+/// Checks a tileset without a declared image size against its loaded texture's `size`,
+/// through [`Issues`] like a mismatch found while decoding. Returns the tolerated warnings.
+fn texture_geometry_warnings(
+    tileset: &IrTileset,
+    size: (u32, u32),
+    base_dir: &Path,
+    options: &LoadOptions,
+) -> Result<Vec<ValidationIssue>, MapError> {
+    let IrTileset::Atlas {
+        source,
+        tile_w,
+        tile_h,
+        tilecount,
+        columns,
+        spacing,
+        margin,
+        ..
+    } = tileset;
+    let mut issues = Issues::new(options);
+    let geometry = check_tileset_geometry(
+        source, size, *tile_w, *tile_h, *tilecount, *columns, *spacing, *margin,
+    );
+    if let Err(err) = geometry {
+        let err = err.in_file(&base_dir.join(source));
+        let at = || IssueLocation::Tileset(source.clone());
+        issues.tolerate(
+            Strictness::Lenient,
+            IssueCode::TilesetGeometryMismatch,
+            at,
+            err,
+        )?;
+    }
+    issues.finish()
+}
+
+/// Polls a future that never suspends (no texture loads) to completion.
+fn block_on_ready<F: std::future::Future>(fut: F) -> F::Output {
+    use std::task::{Context, Poll, Waker};
+
+    let mut fut = std::pin::pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+/// Describes a failed tileset image load by tileset, gid range and absolute image path.
+fn texture_load_error(
+    image: &Path,
+    tileset: &str,
+    first_gid: u32,
+    tilecount: u32,
+    source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> MapError {
+    MapError::TextureLoad {
+        path: std::path::absolute(image).unwrap_or_else(|_| image.to_path_buf()),
+        tileset: tileset.to_string(),
+        gids: first_gid..=first_gid.saturating_add(tilecount.saturating_sub(1)),
+        source: source.into(),
+    }
+}
+
+type TextureResolver<'a> =
+    &'a dyn Fn(&str) -> Result<Texture2D, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Where a [`MapBuild`] gets tileset textures from.
+#[derive(Clone, Copy)]
+enum TextureSource<'a> {
+    /// macroquad's `load_texture`, from the map directory.
+    Files,
+    /// A caller's resolver, given the image path relative to the map directory.
+    Resolver(TextureResolver<'a>),
+}
+
+/// Number of tiles indexed between progress reports and step budget checks.
+const INDEX_BATCH: usize = 4096;
+
+/// A map under construction, advanced one unit of work at a time.
+///
+/// A unit is one tileset texture, one batch of [`INDEX_BATCH`] tiles, or one whole
+/// object/unsupported layer. [`Map::from_ir_with`] and [`MapLoader`] share this path.
+struct MapBuild {
+    base_dir: PathBuf,
+    map: Map,
+    ir_tilesets: Vec<IrTileset>,
+    next_tileset: usize,
+    layers: VecDeque<(LayerIdx, IrLayer)>,
+    // Tile layer currently being indexed, with its offset and next data index.
+    pending_tiles: Option<(TileLayer, Vec2, usize)>,
+    total_units: usize,
+    done_units: usize,
+}
+
+impl MapBuild {
+    fn new(ir: IrMap, base_dir: &Path, options: &LoadOptions) -> Self {
+        if ir.tilesets.is_empty() {
+            options.report(LoadPhase::Textures, 1.0);
+        }
+
+        let (draw_order, layer_kind_by_id) = build_draw_order_and_kind(&ir.layers);
+        let mut layer_offsets = HashMap::with_capacity(ir.layers.len());
+        let mut layer_names = Vec::with_capacity(ir.layers.len());
+        let mut layer_idx_by_name = HashMap::with_capacity(ir.layers.len());
+        let mut total_units = 0usize;
+        let (mut width, mut height) = (0u32, 0u32);
+        for (lz, layer) in ir.layers.iter().enumerate() {
+            if let IrLayerKind::Tiles {
+                width: w,
+                height: h,
+                ..
+            } = layer.kind
+            {
+                width = width.max(w as u32);
+                height = height.max(h as u32);
+            }
+            layer_offsets
+                .entry(layer.name.clone())
+                .or_insert(layer.offset);
+            layer_idx_by_name
+                .entry(layer.name.clone())
+                .or_insert(lz as LayerIdx);
+            layer_names.push(layer.name.clone());
+            total_units += match &layer.kind {
+                IrLayerKind::Tiles { data, .. } => data.len(),
+                IrLayerKind::Objects { objects, .. } => objects.len(),
+                IrLayerKind::Unsupported => 0,
+            };
+        }
+
+        let mut renderer = MapRenderer::new();
+        if let Some(padding) = options.cull_padding {
+            renderer.cull_padding = padding.max(0.0);
+        }
+        if let Some(filter) = options.texture_filter {
+            renderer.texture_filter = filter;
+        }
+
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            map: Map {
+                index: GlobalIndex::new(),
+                tilesets: Vec::with_capacity(ir.tilesets.len()),
+                source_rects: Vec::with_capacity(ir.tilesets.len()),
+                tileset_idx_by_name: HashMap::new(),
+                object_layers: Vec::new(),
+                object_index: ObjectIndex::default(),
+                renderer,
+                gids: GidRanges::default(),
+                tile_layers: Vec::new(),
+                draw_order,
+                layer_kind_by_id,
+                layer_offsets,
+                layer_names,
+                layer_idx_by_name,
+                tile_classes: HashMap::new(),
+                tile_properties: HashMap::new(),
+                properties: ir.properties,
+                load_warnings: ir.warnings,
+                map_dir: base_dir.to_path_buf(),
+                render_mode: ChunkRenderMode::Standard,
+                width,
+                height,
+                tile_w: ir.tile_w,
+                tile_h: ir.tile_h,
+            },
+            ir_tilesets: ir.tilesets,
+            next_tileset: 0,
+            layers: ir
+                .layers
+                .into_iter()
+                .enumerate()
+                .map(|(lz, layer)| (lz as LayerIdx, layer))
+                .collect(),
+            pending_tiles: None,
+            total_units,
+            done_units: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.next_tileset >= self.ir_tilesets.len()
+            && self.layers.is_empty()
+            && self.pending_tiles.is_none()
+    }
+
+    fn phase(&self) -> LoadPhase {
+        if self.next_tileset < self.ir_tilesets.len() {
+            LoadPhase::Textures
+        } else {
+            LoadPhase::Index
+        }
+    }
+
+    /// Completion fraction of the current phase.
+    fn phase_progress(&self) -> f32 {
+        match self.phase() {
+            LoadPhase::Textures => self.next_tileset as f32 / self.ir_tilesets.len() as f32,
+            _ if self.total_units == 0 => {
+                if self.is_done() {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            _ => self.done_units as f32 / self.total_units as f32,
+        }
+    }
+
+    async fn advance(
+        &mut self,
+        options: &LoadOptions,
+        textures: TextureSource<'_>,
+    ) -> Result<(), MapError> {
+        if self.next_tileset < self.ir_tilesets.len() {
+            self.load_next_tileset(options, textures).await?;
+            options.report(
+                LoadPhase::Textures,
+                self.next_tileset as f32 / self.ir_tilesets.len() as f32,
+            );
+        } else {
+            self.index_next_unit();
+            options.report(LoadPhase::Index, self.phase_progress());
+        }
+        Ok(())
+    }
+
+    async fn load_next_tileset(
+        &mut self,
+        options: &LoadOptions,
+        textures: TextureSource<'_>,
+    ) -> Result<(), MapError> {
+        let i = self.next_tileset;
+        match &self.ir_tilesets[i] {
+            IrTileset::Atlas {
+                name,
+                first_gid,
+                source,
+                image,
+                tile_w,
+                tile_h,
+                image_size,
+                tilecount,
+                columns,
+                spacing,
+                margin,
+                tiles,
+                ..
+            } => {
+                let tex = if options.skip_textures {
+                    None
+                } else {
+                    let img_path = self.base_dir.join(image);
+                    let fail = |e: Box<dyn std::error::Error + Send + Sync>| {
+                        texture_load_error(&img_path, source, *first_gid, *tilecount, e)
+                    };
+                    let tex = match textures {
+                        TextureSource::Files => {
+                            let img_path_str = img_path
+                                .to_str()
+                                .ok_or_else(|| MapError::InvalidUtf8Path(img_path.clone()))?;
+                            let tex = load_texture(img_path_str)
+                                .await
+                                .map_err(|e| fail(Box::new(e)))?;
+                            tex.set_filter(self.map.renderer.texture_filter);
+                            tex
+                        }
+                        // The caller may share these textures, so their filter is theirs.
+                        TextureSource::Resolver(resolve) => resolve(image).map_err(fail)?,
+                    };
+                    if image_size.is_none() {
+                        let size = (tex.width() as u32, tex.height() as u32);
+                        let ts = &self.ir_tilesets[i];
+                        let warnings = texture_geometry_warnings(ts, size, &self.base_dir, options);
+                        self.map.load_warnings.extend(warnings?);
+                    }
+                    Some(tex)
+                };
+
+                self.map
+                    .tileset_idx_by_name
+                    .entry(name.clone())
+                    .or_insert(self.map.tilesets.len());
+                self.map.tilesets.push(TilesetInfo {
+                    name: name.clone(),
+                    first_gid: *first_gid,
+                    source: source.clone(),
+                    image: image.clone(),
+                    tilecount: *tilecount,
+                    cols: *columns,
+                    tex,
+                    tile_w: *tile_w,
+                    tile_h: *tile_h,
+                    spacing: *spacing,
+                    margin: *margin,
+                });
+                let rects = self.map.tilesets[i].source_rects();
+                self.map.source_rects.push(rects);
+
+                self.map.gids.insert(*first_gid, *tilecount, i as u16);
+                for tile in tiles {
+                    let gid = match first_gid.checked_add(tile.id) {
+                        Some(gid) if tile.id < *tilecount => gid,
+                        _ => continue,
+                    };
+                    if !tile.class_name.is_empty() {
+                        self.map.tile_classes.insert(gid, tile.class_name.clone());
+                    }
+                    if !tile.properties.is_empty() {
+                        self.map
+                            .tile_properties
+                            .insert(gid, tile.properties.clone());
+                    }
+                }
+            }
+        }
+        self.next_tileset += 1;
+        Ok(())
+    }
+
+    fn index_next_unit(&mut self) {
+        let map = &mut self.map;
+
+        if let Some((layer, offset, cursor)) = &mut self.pending_tiles {
+            let end = (*cursor + INDEX_BATCH).min(layer.data.len());
+            Map::index_tiles(
+                &mut map.index,
+                layer,
+                *cursor..end,
+                *offset,
+                map.tile_w,
+                map.tile_h,
+            );
+            self.done_units += end - *cursor;
+            *cursor = end;
+
+            if end == layer.data.len() {
+                if let Some((layer, _, _)) = self.pending_tiles.take() {
+                    debug_assert!(matches!(
+                        map.layer_kind_by_id.get(&(layer.layer_id as LayerId)),
+                        Some(LayerKindInfo::Tiles(idx)) if *idx == map.tile_layers.len()
+                    ));
+                    map.tile_layers.push(layer);
+                }
+            }
+            return;
+        }
+
+        let Some((lz, layer)) = self.layers.pop_front() else {
+            return;
+        };
+        match layer.kind {
+            IrLayerKind::Objects {
+                objects,
+                draw_order,
+            } => {
+                let layer_idx = map.object_layers.len();
+                self.done_units += objects.len();
+                map.object_layers.push(ObjectLayer {
+                    id: lz as LayerId,
+                    name: layer.name,
+                    visible: layer.visible,
+                    opacity: layer.opacity,
+                    tint: layer.tint,
+                    offset: layer.offset,
+                    properties: layer.properties,
+                    objects,
+                    draw_order,
+                    bucket_layer: lz,
+                    seen_stamp_tiles: vec![],
+                    seen_stamp_debug: vec![],
+                });
+                if let Some(last) = map.object_layers.last_mut() {
+                    ensure_object_layer_stamp_invariant(last);
+                    Map::index_object_layer(&mut map.index, lz, &last.objects, last.offset);
+                    Map::insert_object_aabbs(
+                        &mut map.object_index,
+                        layer_idx,
+                        &last.objects,
+                        last.offset,
+                    );
+                }
+
+                debug_assert!(matches!(
+                    map.layer_kind_by_id.get(&(lz as LayerId)),
+                    Some(LayerKindInfo::Objects(idx)) if *idx == layer_idx
+                ));
+            }
+            IrLayerKind::Tiles { width, data, .. } => {
+                let tiles = TileLayer {
+                    layer_id: lz,
+                    visible: layer.visible,
+                    opacity: layer.opacity.clamp(0.0, 1.0),
+                    tint: layer.tint,
+                    width,
+                    handles: vec![None; data.len()],
+                    data,
+                    properties: layer.properties,
+                };
+                self.pending_tiles = Some((tiles, layer.offset, 0));
+                // Index the first batch right away so every unit makes progress.
+                self.index_next_unit();
+            }
+            IrLayerKind::Unsupported => {}
+        }
+    }
+}
+
+/// Incremental map loader created by [`Map::load_in_steps`].
+///
+/// Call [`MapLoader::step`] once per frame with a time budget; it returns the map once
+/// loading has finished. Decoding the JSON happens in one step because it cannot be
+/// split, while textures and indexing are spread over as many steps as needed.
+pub struct MapLoader {
+    options: LoadOptions,
+    state: LoaderState,
+}
+
+enum LoaderState {
+    Parse { path: String },
+    Build(Box<MapBuild>),
+    Finished,
+}
+
+impl MapLoader {
+    /// Current loading phase.
+    pub fn phase(&self) -> LoadPhase {
+        match &self.state {
+            LoaderState::Parse { .. } => LoadPhase::Parse,
+            LoaderState::Build(build) => build.phase(),
+            LoaderState::Finished => LoadPhase::Index,
+        }
+    }
+
+    /// Overall completion fraction (`0.0..=1.0`) across all phases.
+    ///
+    /// Never decreases between steps.
+    pub fn progress(&self) -> f32 {
+        let phase_count = LoadPhase::ALL.len() as f32;
+        match &self.state {
+            LoaderState::Parse { .. } => 0.0,
+            LoaderState::Build(build) => {
+                (build.phase().ordinal() as f32 + build.phase_progress()) / phase_count
+            }
+            LoaderState::Finished => 1.0,
+        }
+    }
+
+    /// Does loading work for roughly `budget_ms` milliseconds.
+    ///
+    /// At least one unit of work is done per call, regardless of budget. Returns
+    /// `Ok(Some(map))` exactly once when loading completes and `Ok(None)` while work
+    /// remains. After the map is returned or an error occurs, further calls fail with
+    /// [`MapError::LoaderFinished`].
+    pub async fn step(&mut self, budget_ms: f32) -> Result<Option<Map>, MapError> {
+        let result = self.step_inner(budget_ms).await;
+        if result.is_err() {
+            self.state = LoaderState::Finished;
+        }
+        result
+    }
+
+    async fn step_inner(&mut self, budget_ms: f32) -> Result<Option<Map>, MapError> {
+        let deadline = miniquad::date::now() + f64::from(budget_ms.max(0.0)) / 1000.0;
+        loop {
+            match &mut self.state {
+                LoaderState::Parse { path } => {
+                    let (ir, base) = decode_map_file_to_ir_with(path, &self.options)?;
+                    self.state =
+                        LoaderState::Build(Box::new(MapBuild::new(ir, &base, &self.options)));
+                }
+                LoaderState::Build(build) => {
+                    if !build.is_done() {
+                        build.advance(&self.options, TextureSource::Files).await?;
+                    }
+                    if build.is_done() {
+                        let LoaderState::Build(build) =
+                            std::mem::replace(&mut self.state, LoaderState::Finished)
+                        else {
+                            unreachable!("state checked above");
+                        };
+                        return Ok(Some(build.map));
+                    }
+                }
+                LoaderState::Finished => {
+                    return Err(MapError::LoaderFinished);
+                }
+            }
+
+            if miniquad::date::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spatial::DEFAULT_OBJECT_CELL_SIZE;
+    use std::path::PathBuf;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(name)
+    }
+
+    fn load_fixture_ir(name: &str) -> IrMap {
+        let path = fixture_path(name);
+        let path_str = path.to_str().expect("fixture path must be utf-8");
+        let (ir, _) = decode_map_file_to_ir(path_str).expect("fixture should decode");
+        ir
+    }
+
+    /// Drives a future to completion; headless loads never actually suspend.
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        block_on_ready(fut)
+    }
+
+    #[test]
+    fn async_reader_path_matches_the_sync_loader() {
+        let path = fixture_path("two_tilesets_map.json");
+        let path_str = path.to_str().expect("fixture path must be utf-8");
+        let mut reads = Vec::new();
+        let map = block_on(Map::load_through(
+            path_str,
+            LoadOptions::default().headless(),
+            async |p: &Path| {
+                reads.push(p.file_name().unwrap().to_string_lossy().into_owned());
+                std::fs::read_to_string(p).map_err(|source| MapError::Io {
+                    path: p.to_path_buf(),
+                    source,
+                })
+            },
+        ))
+        .expect("async load");
+        let sync = Map::load_from_file(path_str).expect("sync load");
+
+        assert_eq!(
+            reads.first().map(String::as_str),
+            Some("two_tilesets_map.json")
+        );
+        assert_eq!(reads.len(), 1 + sync.tilesets.len());
+        assert_eq!(
+            map.build_draw_commands(None),
+            sync.build_draw_commands(None)
+        );
+
+        let missing = fixture_path("missing.json");
+        let err = block_on(Map::load_through(
+            missing.to_str().expect("utf-8"),
+            LoadOptions::default().headless(),
+            async |p: &Path| {
+                Err(MapError::Io {
+                    path: p.to_path_buf(),
+                    source: std::io::ErrorKind::NotFound.into(),
+                })
+            },
+        ))
+        .err()
+        .expect("missing file");
+        assert!(matches!(err, MapError::Io { path, .. } if path == missing));
+    }
+
+    fn load_fixture_headless(name: &str, options: LoadOptions) -> Map {
+        let path = fixture_path(name);
+        let path_str = path.to_str().expect("fixture path must be utf-8");
+        let options = LoadOptions {
+            skip_textures: true,
+            ..options
+        };
+        let map =
+            block_on(Map::load_with(path_str, options)).expect("fixture should load headless");
+        map.assert_consistent();
+        map
+    }
+
+    #[test]
+    fn object_chunk_span_covers_multi_chunk_rectangles() {
+        let obj = IrObject {
+            id: 1,
+            name: String::new(),
+            class_name: String::new(),
+            x: 250.0,
+            y: 10.0,
+            width: 20.0,
+            height: 20.0,
+            rotation: 0.0,
+            visible: true,
+            shape: IrObjectShape::Rectangle,
+            properties: Properties::default(),
+        };
+
+        let (chunk_min, chunk_max) = Map::object_chunk_span(&obj, Vec2::ZERO);
+        assert_eq!(chunk_min.x, 0);
+        assert_eq!(chunk_max.x, 1);
+        assert_eq!(chunk_min.y, 0);
+        assert_eq!(chunk_max.y, 0);
+    }
+
+    #[test]
+    fn draw_order_matches_tiled_layer_order() {
+        let layers = vec![
+            IrLayer {
+                name: "tiles_a".to_string(),
+                visible: true,
+                opacity: 1.0,
+                tint: WHITE,
+                offset: Vec2::ZERO,
+                properties: Properties::default(),
+                kind: IrLayerKind::Tiles {
+                    width: 1,
+                    height: 1,
+                    data: vec![0],
+                },
+            },
+            IrLayer {
+                name: "objects_a".to_string(),
+                visible: true,
+                opacity: 1.0,
+                tint: WHITE,
+                offset: Vec2::ZERO,
+                properties: Properties::default(),
+                kind: IrLayerKind::Objects {
+                    objects: vec![],
+                    draw_order: ObjectDrawOrder::TopDown,
+                },
+            },
+            IrLayer {
+                name: "tiles_b".to_string(),
+                visible: true,
+                opacity: 1.0,
+                tint: WHITE,
+                offset: Vec2::ZERO,
+                properties: Properties::default(),
+                kind: IrLayerKind::Tiles {
+                    width: 1,
+                    height: 1,
+                    data: vec![0],
+                },
+            },
+        ];
+
+        let (draw_order, kind_by_id) = build_draw_order_and_kind(&layers);
+        assert_eq!(draw_order, vec![0, 1, 2]);
+        assert!(matches!(kind_by_id.get(&0), Some(LayerKindInfo::Tiles(0))));
+        assert!(matches!(
+            kind_by_id.get(&1),
+            Some(LayerKindInfo::Objects(0))
+        ));
+        assert!(matches!(kind_by_id.get(&2), Some(LayerKindInfo::Tiles(1))));
+    }
+
+    #[test]
+    fn stamp_buffers_are_realigned_and_all_objects_are_seen_once() {
+        fn make_object(id: u32) -> IrObject {
+            IrObject {
+                id,
+                name: String::new(),
+                class_name: String::new(),
+                x: 0.0,
+                y: 0.0,
+                width: 16.0,
+                height: 16.0,
+                rotation: 0.0,
+                visible: true,
+                shape: IrObjectShape::Rectangle,
+                properties: Properties::default(),
+            }
+        }
+
+        let mut layer = ObjectLayer {
+            id: 0,
+            name: "objects".to_string(),
+            visible: true,
+            opacity: 1.0,
+            tint: WHITE,
+            offset: Vec2::ZERO,
+            properties: Properties::default(),
+            objects: vec![make_object(1), make_object(2), make_object(3)],
+            draw_order: ObjectDrawOrder::TopDown,
+            bucket_layer: 0,
+            seen_stamp_tiles: vec![0],
+            seen_stamp_debug: vec![],
+        };
+
+        ensure_object_layer_stamp_invariant(&mut layer);
+        assert_eq!(layer.seen_stamp_tiles.len(), layer.objects.len());
+        assert_eq!(layer.seen_stamp_debug.len(), layer.objects.len());
+
+        let stamp = 42;
+        let mut first_pass_drawn = 0usize;
+        for object_idx in 0..layer.objects.len() {
+            if layer.seen_stamp_tiles[object_idx] == stamp {
+                continue;
+            }
+            layer.seen_stamp_tiles[object_idx] = stamp;
+            first_pass_drawn += 1;
+        }
+
+        let mut second_pass_drawn = 0usize;
+        for object_idx in 0..layer.objects.len() {
+            if layer.seen_stamp_tiles[object_idx] == stamp {
+                continue;
+            }
+            layer.seen_stamp_tiles[object_idx] = stamp;
+            second_pass_drawn += 1;
+        }
+
+        assert_eq!(first_pass_drawn, 3);
+        assert_eq!(second_pass_drawn, 0);
+    }
+
+    fn collect_draw_sequence_for_test(
+        map: &mut Map,
+        view_min: Vec2,
+        view_max: Vec2,
+    ) -> Vec<(char, LayerId, i32, i32, u32)> {
+        let coords = map.take_visible_coords(view_min, view_max);
+        let stamp = map.next_frame_stamp();
+        let mut out = Vec::new();
+
+        for i in 0..map.draw_order.len() {
+            let layer_id = map.draw_order[i];
+            let Some(kind) = map.layer_kind_by_id.get(&layer_id).copied() else {
+                continue;
+            };
+
+            match kind {
+                LayerKindInfo::Tiles(tile_layer_idx) => {
+                    let Some(layer) = map.tile_layers.get(tile_layer_idx) else {
+                        continue;
+                    };
+                    if !layer.visible {
+                        continue;
+                    }
+
+                    Map::for_each_visible_layer_bucket(
+                        &map.index,
+                        &coords,
+                        layer.layer_id,
+                        |cc, bucket| {
+                            for rec in &bucket.tiles {
+                                out.push(('T', layer_id, cc.x, cc.y, rec.id.clean()));
+                            }
+                        },
+                    );
+                }
+                LayerKindInfo::Objects(object_layer_idx) => {
+                    let Some(layer) = map.object_layers.get_mut(object_layer_idx) else {
+                        continue;
+                    };
+                    ensure_object_layer_stamp_invariant(layer);
+                    if !layer.visible {
+                        continue;
+                    }
+                    let bucket_layer = layer.bucket_layer;
+
+                    Map::for_each_visible_layer_bucket(
+                        &map.index,
+                        &coords,
+                        bucket_layer,
+                        |cc, bucket| {
+                            for rec in &bucket.objects {
+                                let object_idx = rec.handle.0 as usize;
+                                if object_idx >= layer.objects.len() {
+                                    continue;
+                                }
+                                if layer.seen_stamp_tiles[object_idx] == stamp {
+                                    continue;
+                                }
+                                layer.seen_stamp_tiles[object_idx] = stamp;
+
+                                let Some(obj) = layer.objects.get(object_idx) else {
+                                    continue;
+                                };
+                                if !obj.visible {
+                                    continue;
+                                }
+                                if !matches!(obj.shape, IrObjectShape::Tile { .. }) {
+                                    continue;
+                                }
+                                out.push(('O', layer_id, cc.x, cc.y, obj.id));
+                            }
+                        },
+                    );
+                }
+                LayerKindInfo::Unsupported => {}
+            }
+        }
+
+        out
+    }
+
+    #[test]
+    fn draw_sequence_is_deterministic_across_runs() {
+        let mut index = GlobalIndex::new();
+        index.add_tile(TileId(1), 0, vec2(10.0, 10.0));
+        index.add_tile(TileId(1), 0, vec2((CHUNK_SIZE + 10) as f32, 10.0));
+        index.add_tile(TileId(1), 2, vec2(20.0, (CHUNK_SIZE + 10) as f32));
+
+        let objects = vec![IrObject {
+            id: 77,
+            name: "coin".to_string(),
+            class_name: String::new(),
+            x: (CHUNK_SIZE - 8) as f32,
+            y: 32.0,
+            width: 16.0,
+            height: 16.0,
+            rotation: 0.0,
+            visible: true,
+            shape: IrObjectShape::Tile { gid: 1 },
+            properties: Properties::default(),
+        }];
+
+        // Same object appears in multiple chunks (as with AABB multi-chunk insertion).
+        index.insert_object(
+            1,
+            crate::spatial::ChunkCoord { x: 0, y: 0 },
+            crate::spatial::ObjectRec {
+                handle: crate::spatial::ObjectHandle(0),
+                rel_pos: vec2((CHUNK_SIZE - 8) as f32, 32.0),
+            },
+        );
+        index.insert_object(
+            1,
+            crate::spatial::ChunkCoord { x: 1, y: 0 },
+            crate::spatial::ObjectRec {
+                handle: crate::spatial::ObjectHandle(0),
+                rel_pos: vec2(0.0, 32.0),
+            },
+        );
+
+        let mut map = Map {
+            index,
+            tilesets: vec![],
+            source_rects: vec![],
+            tileset_idx_by_name: HashMap::new(),
+            object_layers: vec![ObjectLayer {
+                id: 1,
+                name: "objects".to_string(),
+                visible: true,
+                opacity: 1.0,
+                tint: WHITE,
+                offset: Vec2::ZERO,
+                properties: Properties::default(),
+                objects,
+                draw_order: ObjectDrawOrder::TopDown,
+                bucket_layer: 1,
+                seen_stamp_tiles: vec![0],
+                seen_stamp_debug: vec![0],
+            }],
+            object_index: ObjectIndex::default(),
+            renderer: MapRenderer {
+                debug_draw: false,
+                cull_padding: CHUNK_SIZE as f32,
+                texture_filter: FilterMode::Nearest,
+                frame_stamp: 0,
+                ..Default::default()
+            },
+            gids: GidRanges::default(),
+            tile_layers: vec![
+                TileLayer {
+                    layer_id: 0,
+                    visible: true,
+                    opacity: 1.0,
+                    tint: WHITE,
+                    width: 0,
+                    data: vec![],
+                    handles: vec![],
+                    properties: Properties::default(),
+                },
+                TileLayer {
+                    layer_id: 2,
+                    visible: true,
+                    opacity: 1.0,
+                    tint: WHITE,
+                    width: 0,
+                    data: vec![],
+                    handles: vec![],
+                    properties: Properties::default(),
+                },
+            ],
+            draw_order: vec![0, 1, 2],
+            layer_kind_by_id: {
+                let mut m = HashMap::new();
+                m.insert(0, LayerKindInfo::Tiles(0));
+                m.insert(1, LayerKindInfo::Objects(0));
+                m.insert(2, LayerKindInfo::Tiles(1));
+                m
+            },
+            layer_offsets: HashMap::new(),
+            layer_names: vec![
+                "tiles_a".to_string(),
+                "objects".to_string(),
+                "tiles_b".to_string(),
+            ],
+            layer_idx_by_name: HashMap::new(),
+            tile_classes: HashMap::new(),
+            tile_properties: HashMap::new(),
+            properties: Properties::default(),
+            load_warnings: Vec::new(),
+            map_dir: PathBuf::from("."),
+            render_mode: ChunkRenderMode::Standard,
+            width: 0,
+            height: 0,
+            tile_w: 16,
+            tile_h: 16,
+        };
+
+        let seq1 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(520.0, 520.0));
+        let seq2 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(520.0, 520.0));
+        assert_eq!(seq1, seq2);
+    }
+
+    #[test]
+    fn visible_rect_drawing_follows_the_layer_draw_order() {
+        let mut map = terrain_map(1, 1, 1);
+        map.add_layer("top", vec![2]).expect("add layer");
+        let drawn_layers = |map: &Map| {
+            let mut layers = Vec::new();
+            map.for_each_visible_rect_command(Vec2::ZERO, vec2(16.0, 16.0), |cmd| {
+                layers.push((cmd.layer, cmd.gid))
+            });
+            layers
+        };
+        assert_eq!(drawn_layers(&map), vec![(0, 1), (1, 2)]);
+
+        map.set_layer_draw_order(&["top", "ground"])
+            .expect("valid order");
+        assert_eq!(drawn_layers(&map), vec![(1, 2), (0, 1)]);
+    }
+
+    #[test]
+    fn fixture_layer_ordering_matches_tiled_order() {
+        let ir = load_fixture_ir("external_props_map.json");
+        let (draw_order, kinds) = build_draw_order_and_kind(&ir.layers);
+        assert_eq!(draw_order, vec![0, 1, 2]);
+        assert!(matches!(kinds.get(&0), Some(LayerKindInfo::Tiles(0))));
+        assert!(matches!(kinds.get(&1), Some(LayerKindInfo::Objects(0))));
+        assert!(matches!(kinds.get(&2), Some(LayerKindInfo::Tiles(1))));
+    }
+
+    #[test]
+    fn layer_draw_order_follows_json_and_can_be_rearranged() {
+        let mut map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        assert_eq!(map.layer_draw_order(), vec!["bg", "objects", "fg"]);
+
+        map.set_layer_draw_order(&["fg", "bg", "objects"])
+            .expect("valid order");
+        assert_eq!(map.layer_draw_order(), vec!["fg", "bg", "objects"]);
+        assert_eq!(map.layer_idx("fg"), Some(2), "indices keep Tiled positions");
+
+        assert!(matches!(
+            map.set_layer_draw_order(&["fg", "bg", "sky"]),
+            Err(MapError::LayerNotFound(name)) if name == "sky"
+        ));
+        for (bad, reason) in [
+            (&["fg", "bg"][..], "names 2 of 3 layers"),
+            (&["fg", "bg", "bg"][..], "layer 'bg' appears twice"),
+        ] {
+            let err = map.set_layer_draw_order(bad).expect_err("bad order");
+            assert!(
+                matches!(&err, MapError::InvalidDrawOrder(r) if r == reason),
+                "{err:?}"
+            );
+            assert_eq!(
+                err.to_string(),
+                format!("Invalid layer draw order: {reason}")
+            );
+        }
+        assert_eq!(map.layer_draw_order(), vec!["fg", "bg", "objects"]);
+
+        let object_layer = |name: &str, id| IrLayer {
+            kind: IrLayerKind::Objects {
+                objects: vec![named_object(id, "box")],
+                draw_order: ObjectDrawOrder::Index,
+            },
+            ..tile_ir_layer(name, true)
+        };
+        let mut map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![object_layer("low", 1), object_layer("high", 2)],
+            warnings: Vec::new(),
+        });
+        let picked = |map: &Map| map.object_at_pixel(8.0, 8.0).map(|o| o.id);
+        assert_eq!(picked(&map), Some(2));
+        map.set_layer_draw_order(&["high", "low"])
+            .expect("valid order");
+        assert_eq!(picked(&map), Some(1), "drawn last, picked first");
+        let ids: Vec<u32> = map
+            .objects_at_point(vec2(8.0, 8.0))
+            .iter()
+            .map(|hit| hit.object.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        // Duplicate names resolve to the first layer, so such maps cannot be reordered.
+        let mut map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![object_layer("twin", 1), object_layer("twin", 2)],
+            warnings: Vec::new(),
+        });
+        assert!(matches!(
+            map.set_layer_draw_order(&["twin", "twin"]),
+            Err(MapError::InvalidDrawOrder(reason)) if reason == "layer 'twin' appears twice"
+        ));
+    }
+
+    #[test]
+    fn layer_iterators_split_layers_by_kind() {
+        let map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        let objects = map
+            .iter_object_layers()
+            .flat_map(|l| l.objects.iter())
+            .count();
+        assert_eq!(objects, map.object_count());
+        assert_eq!(objects, 1);
+
+        let tiles: Vec<_> = map
+            .iter_tile_layers()
+            .map(|l| (l.name, l.id, l.width * l.height == l.data.len()))
+            .collect();
+        assert_eq!(tiles, vec![("bg", 0, true), ("fg", 2, true)]);
+    }
+
+    #[test]
+    fn new_maps_reject_zero_tile_sizes() {
+        for (tile_w, tile_h, zero) in [(0, 16, "tilewidth"), (16, 0, "tileheight")] {
+            let err = Map::new(4, 4, tile_w, tile_h)
+                .err()
+                .expect("zero tile size");
+            assert!(
+                matches!(&err, MapError::InvalidGeometry { path: None, entity, field }
+                    if entity == "map" && field == zero),
+                "{err:?}"
+            );
+        }
+        let empty = Map::new(0, 0, 16, 8).expect("an empty grid is fine");
+        assert_eq!(empty.tile_rect(0, 0), Rect::new(0.0, 0.0, 16.0, 8.0));
+    }
+
+    #[test]
+    fn checkerboard_cells_snap_to_the_tile_grid() {
+        let map = Map::new(4, 4, 16, 8).expect("valid tile size");
+        let cells: Vec<_> = map
+            .checkerboard_cells(vec2(-4.0, 2.0), vec2(20.0, 10.0))
+            .collect();
+        assert_eq!(
+            cells,
+            vec![
+                (Rect::new(-16.0, 0.0, 16.0, 8.0), false),
+                (Rect::new(0.0, 0.0, 16.0, 8.0), true),
+                (Rect::new(16.0, 0.0, 16.0, 8.0), false),
+                (Rect::new(-16.0, 8.0, 16.0, 8.0), true),
+                (Rect::new(0.0, 8.0, 16.0, 8.0), false),
+                (Rect::new(16.0, 8.0, 16.0, 8.0), true),
+            ]
+        );
+        assert_eq!(map.checkerboard_cells(Vec2::ZERO, Vec2::ZERO).count(), 0);
+    }
+
+    #[test]
+    fn fixture_object_spans_multiple_chunks() {
+        let ir = load_fixture_ir("multichunk_objects_map.json");
+        let object_layer = ir
+            .layers
+            .iter()
+            .find(|l| matches!(l.kind, IrLayerKind::Objects { .. }))
+            .expect("object layer exists");
+        let IrLayerKind::Objects { ref objects, .. } = object_layer.kind else {
+            panic!("expected object layer");
+        };
+        let obj = &objects[0];
+        let (chunk_min, chunk_max) = Map::object_chunk_span(obj, object_layer.offset);
+        assert_eq!(chunk_min.x, 0);
+        assert_eq!(chunk_max.x, 1);
+        assert_eq!(chunk_min.y, 0);
+        assert_eq!(chunk_max.y, 0);
+    }
+
+    #[test]
+    fn multi_chunk_object_reconstructs_same_world_pos_from_any_bucket() {
+        let world = vec2(591.5974, 604.84875);
+        let rel_home = crate::spatial::rel(world);
+        let chunk_home = world_to_chunk(world);
+        assert_eq!(chunk_home.x, 2);
+        assert_eq!(chunk_home.y, 2);
+
+        // Simulate a tall object spanning into the chunk above.
+        let cc_other = crate::spatial::ChunkCoord { x: 2, y: 1 };
+        let wrong_origin = vec2(
+            (cc_other.x * CHUNK_SIZE) as f32,
+            (cc_other.y * CHUNK_SIZE) as f32,
+        ) + rel_home;
+        assert_ne!(wrong_origin.y, world.y);
+
+        let correct_rel = world
+            - vec2(
+                (cc_other.x * CHUNK_SIZE) as f32,
+                (cc_other.y * CHUNK_SIZE) as f32,
+            );
+        let rebuilt = vec2(
+            (cc_other.x * CHUNK_SIZE) as f32,
+            (cc_other.y * CHUNK_SIZE) as f32,
+        ) + correct_rel;
+        assert_eq!(rebuilt, world);
+    }
+
+    #[test]
+    fn fixture_culling_returns_expected_chunks() {
+        let ir = load_fixture_ir("minimal_finite_map.json");
+        let mut index = GlobalIndex::new();
+        let tw = ir.tile_w as f32;
+        let th = ir.tile_h as f32;
+
+        for (lz, layer) in ir.layers.iter().enumerate() {
+            let IrLayerKind::Tiles { width, data, .. } = &layer.kind else {
+                continue;
+            };
+            for (idx, gid) in data.iter().enumerate() {
+                if *gid == 0 {
+                    continue;
+                }
+                let col = idx % *width;
+                let row = idx / *width;
+                index.add_tile(
+                    TileId(*gid),
+                    lz as LayerIdx,
+                    vec2(col as f32 * tw, row as f32 * th) + layer.offset,
+                );
+            }
+        }
+
+        let near = query_visible_rect(&index, vec2(0.0, 0.0), vec2(40.0, 20.0));
+        assert!(near.chunks.iter().any(|c| c.coord.x == 0 && c.coord.y == 0));
+
+        let far = query_visible_rect(&index, vec2(2000.0, 2000.0), vec2(2100.0, 2100.0));
+        assert!(far.chunks.is_empty());
+    }
+
+    fn named_object(id: u32, name: &str) -> IrObject {
+        IrObject {
+            id,
+            name: name.to_string(),
+            class_name: String::new(),
+            x: 0.0,
+            y: 0.0,
+            width: 16.0,
+            height: 16.0,
+            rotation: 0.0,
+            visible: true,
+            shape: IrObjectShape::Rectangle,
+            properties: Properties::default(),
+        }
+    }
+
+    fn object_layer_for_test(id: LayerId, name: &str, objects: Vec<IrObject>) -> ObjectLayer {
+        let mut layer = ObjectLayer {
+            id,
+            name: name.to_string(),
+            visible: true,
+            opacity: 1.0,
+            tint: WHITE,
+            offset: Vec2::ZERO,
+            properties: Properties::default(),
+            objects,
+            draw_order: ObjectDrawOrder::TopDown,
+            bucket_layer: id as LayerIdx,
+            seen_stamp_tiles: vec![],
+            seen_stamp_debug: vec![],
+        };
+        ensure_object_layer_stamp_invariant(&mut layer);
+        layer
+    }
+
+    #[test]
+    fn png_export_flips_gl_rows_and_reports_io_errors() {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mq_tiled_png_{nanos}"));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = dir.join("export.png");
+
+        // A 2x3 readback whose first (GL bottom) row is red and the rest blue.
+        let mut readback = Image::gen_image_color(2, 3, BLUE);
+        readback.set_pixel(0, 0, RED);
+        readback.set_pixel(1, 0, RED);
+        write_png_flipped(&readback, &path).expect("write png");
+
+        let png = image::open(&path).expect("decode png").to_rgba8();
+        assert_eq!(png.dimensions(), (2, 3));
+        let red: [u8; 4] = RED.into();
+        let blue: [u8; 4] = BLUE.into();
+        assert_eq!(png.get_pixel(1, 2).0, red, "bottom row of the PNG");
+        assert_eq!(png.get_pixel(0, 0).0, blue);
+
+        let missing = dir.join("no_such_dir").join("export.png");
+        assert!(matches!(
+            write_png_flipped(&readback, &missing),
+            Err(MapError::Io { path, .. }) if path == missing
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn png_export_size_rejects_empty_and_oversized_maps() {
+        let size = |w, h, tile_w, tile_h| {
+            let map = Map::new(w, h, tile_w, tile_h).expect("valid tile size");
+            map.export_size().ok()
+        };
+        assert_eq!(size(4, 3, 16, 8), Some((64, 24)));
+        assert_eq!(size(4095, 1, 16, 16), Some((65520, 16)));
+        assert_eq!(size(0, 3, 16, 8), None);
+        assert_eq!(size(4096, 1, 16, 16), None, "wider than a texture can be");
+        // The pixel size overflows `u32`; this must not panic or wrap.
+        assert_eq!(size(u32::MAX, 1, 16, 16), None);
+        assert_eq!(size(0x1_0000, 0x1_0000, 0x1_0000, 1), None);
+    }
+
+    #[test]
+    fn saved_maps_reload_with_edits_and_properties() {
+        let mut map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        map.set_tile("bg", 1, 0, 1 | 0x8000_0000).expect("edit");
+        map.set_layer_visible("fg", false).expect("known layer");
+
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mq_tiled_save_{nanos}"));
+        let path = dir.join("levels").join("saved.json");
+        map.save_to_file(&path, path.parent().unwrap())
+            .expect("save");
+        // Saving again replaces the file in place.
+        map.save_to_file(&path, path.parent().unwrap())
+            .expect("resave");
+
+        let path_str = path.to_str().expect("utf-8 temp path");
+        let reloaded = Map::load_from_file(path_str).expect("reload");
+        assert_eq!(reloaded.get_tile("bg", 1, 0), Some(1 | 0x8000_0000));
+        assert_eq!(reloaded.layer_visible("fg"), Some(false));
+        assert_eq!(
+            reloaded.build_draw_commands(None),
+            map.build_draw_commands(None)
+        );
+        assert_eq!(reloaded.objects_in("objects"), map.objects_in("objects"));
+
+        let (ir, _) = decode_map_file_to_ir(path_str).expect("decode saved");
+        assert_eq!(ir.properties.get_string("theme"), Some("forest"));
+        assert_eq!(ir.layers[0].properties.get_f32("parallax"), Some(0.5));
+        let _ = std::fs::remove_dir_all(dir);
+
+        let mut code_map = Map::new(1, 1, 16, 16).expect("valid tile size");
+        code_map
+            .add_tileset(code_tileset("inline", 1, 1))
+            .expect("tileset");
+        assert!(code_map.serialize_to_json(Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn saving_keeps_tints_and_refuses_layers_it_cannot_write() {
+        let layers = r##"
+            { "type": "tilelayer", "name": "ground", "width": 2, "height": 1,
+              "data": [1, 2], "tintcolor": "#80ff0000" },
+            { "type": "tilelayer", "name": "plain", "width": 2, "height": 1, "data": [3, 0] },
+            { "type": "objectgroup", "name": "things", "tintcolor": "#00ff00", "objects": [] }"##;
+        let map_json = |extra: &str| {
+            format!(
+                r#"{{ "width": 2, "height": 1, "tilewidth": 16, "tileheight": 16,
+                    "tilesets": [{{ "firstgid": 1, "source": "basic_tileset.json" }}],
+                    "layers": [{layers}{extra}] }}"#
+            )
+        };
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mq_tiled_save_tint_{nanos}"));
+        let path = dir.join("tinted.json");
+        let fixtures = fixture_path("basic_tileset.json").with_file_name("");
+
+        let map = Map::load_from_str(&map_json(""), &fixtures).expect("load");
+        map.save_to_file(&path, &dir).expect("save");
+        let saved = std::fs::read_to_string(&path).expect("saved map");
+        let reloaded = Map::load_from_str(&saved, &dir).expect("reload");
+        let tints = |m: &Map| {
+            let tiles = m.tile_layers.iter().map(|l| l.tint);
+            tiles
+                .chain(m.object_layers.iter().map(|l| l.tint))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tints(&reloaded), tints(&map));
+        assert_eq!(tints(&map)[0], Color::from_rgba(255, 0, 0, 0x80));
+        let saved: serde_json::Value = serde_json::from_str(&saved).expect("json");
+        let written: Vec<_> = (0..3)
+            .map(|i| saved["layers"][i]["tintcolor"].clone())
+            .collect();
+        assert_eq!(
+            written,
+            [
+                serde_json::json!("#80ff0000"),
+                serde_json::Value::Null,
+                serde_json::json!("#00ff00")
+            ]
+        );
+
+        // An image layer would be lost, so the saved file must stay as it was.
+        let image_layer = r#", { "type": "imagelayer", "name": "sky", "image": "sky.png" }"#;
+        let with_image = Map::load_from_str(&map_json(image_layer), &fixtures).expect("load");
+        assert_eq!(
+            with_image.layer_names.len(),
+            4,
+            "the image layer is kept as a placeholder"
+        );
+        let before = std::fs::read_to_string(&path).expect("saved map");
+        let err = with_image
+            .save_to_file(&path, &dir)
+            .expect_err("lossy save");
+        assert!(err.to_string().contains("'sky'"), "{err}");
+        assert_eq!(std::fs::read_to_string(&path).expect("saved map"), before);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn map_and_layer_properties_are_readable_after_load_from_str() {
+        let json =
+            std::fs::read_to_string(fixture_path("external_props_map.json")).expect("read fixture");
+        let map = Map::load_from_str(&json, fixture_path("")).expect("load");
+        assert_eq!(map.properties().get_string("theme"), Some("forest"));
+        let bg = map.layer_properties("bg").expect("tile layer");
+        assert_eq!(bg.get_f32("parallax"), Some(0.5));
+        let objects = map.layer_properties("objects").expect("object layer");
+        assert!(objects.is_empty());
+        assert!(map.layer_properties("missing").is_none());
+    }
+
+    #[test]
+    fn objects_in_reads_object_layers_next_to_tile_layers() {
+        let map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        let objects = map.objects_in("objects").expect("object layer");
+        let read: Vec<_> = objects
+            .iter()
+            .map(|o| (o.id, o.name.as_str(), o.x, o.y))
+            .collect();
+        assert_eq!(read, vec![(7, "spawn", 8.0, 8.0)]);
+        assert!(map.objects_in("bg").is_none());
+        assert!(map.objects_in("missing").is_none());
+    }
+
+    #[test]
+    fn object_by_name_in_layer_distinguishes_layers() {
+        let mut map = Map::__new_for_stamp_overflow_test(0);
+        map.object_layers = vec![
+            object_layer_for_test(0, "events", vec![named_object(1, "PlayerStart")]),
+            object_layer_for_test(
+                1,
+                "decor",
+                vec![named_object(2, "Tree"), named_object(3, "PlayerStart")],
+            ),
+        ];
+
+        let events = map
+            .object_by_name_in_layer("events", "PlayerStart")
+            .expect("object in events layer");
+        assert_eq!(events.id, 1);
+        let decor = map
+            .object_by_name_in_layer("decor", "PlayerStart")
+            .expect("object in decor layer");
+        assert_eq!(decor.id, 3);
+
+        assert!(map.object_by_name_in_layer("events", "Tree").is_none());
+        assert!(map
+            .object_by_name_in_layer("missing", "PlayerStart")
+            .is_none());
+    }
+
+    #[test]
+    fn cull_margin_from_the_load_config_widens_visible_rect_drawing() {
+        // Four chunks side by side; the view only touches the first one.
+        let tiles_per_chunk = (CHUNK_SIZE / 16) as usize;
+        let drawn = |margin: i32| {
+            let options = LoadOptions {
+                skip_textures: true,
+                ..MapLoadConfig::default().with_cull_margin(margin).into()
+            };
+            let ir = terrain_ir(4 * tiles_per_chunk, 1, 1);
+            let map = block_on(Map::from_ir_with(ir, Path::new("."), &options)).expect("build");
+            let mut count = 0;
+            map.for_each_visible_rect_command(Vec2::ZERO, vec2(16.0, 16.0), |_| count += 1);
+            count / tiles_per_chunk
+        };
+        assert_eq!(drawn(0), 1);
+        assert_eq!(drawn(1), 2);
+        assert_eq!(drawn(3), 4);
+    }
+
+    #[test]
+    fn chunk_visitor_uses_the_cull_padding_of_visible_rect_drawing() {
+        // Four chunks side by side; the view only touches the first one.
+        let tiles_per_chunk = (CHUNK_SIZE / 16) as usize;
+        let mut map = terrain_map(4 * tiles_per_chunk, 1, 1);
+        let (view_min, view_max) = (Vec2::ZERO, vec2(16.0, 16.0));
+        for (chunks, visited) in [(0, 1), (2, 3), (3, 4)] {
+            map.set_cull_padding((chunks * CHUNK_SIZE) as f32);
+            let mut seen = Vec::new();
+            map.each_chunk_in_view(view_min, view_max, |cc, _| seen.push(cc.x));
+            assert_eq!(seen, (0..visited).collect::<Vec<_>>(), "{chunks} chunks");
+
+            let mut drawn = 0;
+            map.for_each_visible_rect_command(view_min, view_max, |_| drawn += 1);
+            assert_eq!(drawn, visited as usize * tiles_per_chunk, "{chunks} chunks");
+        }
+
+        let mut visits = 0;
+        let flow = map.try_each_chunk_in_view(view_min, view_max, |_, _| {
+            visits += 1;
+            ControlFlow::Break(())
+        });
+        assert_eq!((flow, visits), (ControlFlow::Break(()), 1));
+    }
+
+    #[test]
+    fn load_with_config_rejects_bad_chunk_sizes_and_margins_and_passes_load_errors_through() {
+        let config = MapLoadConfig::default().with_chunk_size(128);
+        let err = block_on_ready(Map::load_with_config("tests/missing.json", config))
+            .err()
+            .expect("chunk size");
+        assert!(err.to_string().contains("chunk size 128"), "{err}");
+
+        let config = MapLoadConfig::default().with_cull_margin(-1);
+        let err = block_on_ready(Map::load_with_config("tests/missing.json", config))
+            .err()
+            .expect("negative margin");
+        assert!(err.to_string().contains("cull margin -1"), "{err}");
+
+        let config = MapLoadConfig::default();
+        let err = block_on_ready(Map::load_with_config("tests/missing.json", config))
+            .err()
+            .expect("missing file");
+        assert!(
+            matches!(err.downcast_ref::<MapError>(), Some(MapError::Io { .. })),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn load_with_object_layers_only_never_touches_tiles_or_textures() {
+        // Both tilesets share one file, so it must be read once for two tileset entries.
+        let path = fixture_path("external_props_map.json");
+        let mut map_json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).expect("fixture"))
+                .expect("fixture json");
+        map_json["tilesets"] = serde_json::json!([
+            { "firstgid": 1, "source": "props_tileset.json" },
+            { "firstgid": 101, "source": "props_tileset.json" },
+        ]);
+        let reads = std::cell::RefCell::new(HashMap::<PathBuf, usize>::new());
+        let read = async |file: &Path| {
+            *reads.borrow_mut().entry(file.to_path_buf()).or_default() += 1;
+            if file == path {
+                Ok(map_json.to_string())
+            } else {
+                std::fs::read_to_string(file).map_err(|source| MapError::Io {
+                    path: file.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        let options = LoadOptions {
+            skip_tile_layers: true,
+            skip_textures: true,
+            ..Default::default()
+        };
+        let (ir, base) = block_on_ready(decode_map_file_to_ir_async(
+            path.to_str().expect("utf-8"),
+            &options,
+            read,
+        ))
+        .expect("decode");
+        let tileset = path.with_file_name("props_tileset.json");
+        assert_eq!(*reads.borrow(), HashMap::from([(path, 1), (tileset, 1)]));
+
+        let resolved = Cell::new(0);
+        let resolve = |_: &str| -> Result<Texture2D, Box<dyn std::error::Error + Send + Sync>> {
+            resolved.set(resolved.get() + 1);
+            Err("textures are skipped".into())
+        };
+        let textures = TextureSource::Resolver(&resolve);
+        let map = block_on_ready(Map::from_ir_textured(ir, &base, &options, textures))
+            .expect("object-only map");
+        assert_eq!(resolved.get(), 0, "no texture may be resolved");
+        assert_eq!(map.tilesets.len(), 2);
+
+        assert!(map.tile_layers.is_empty());
+        assert!(map.index.handles.is_empty());
+        assert!(map.tilesets.iter().all(|ts| ts.tex.is_none()));
+
+        // The object layer is second in the file but gets the first id.
+        assert_eq!(map.object_layers.len(), 1);
+        assert_eq!(map.object_layers[0].name, "objects");
+        assert_eq!(map.object_layers[0].id, 0);
+        assert_eq!(map.draw_order, vec![0]);
+    }
+
+    #[test]
+    fn resolved_textures_keep_their_filter() {
+        use macroquad::miniquad::{RawId, TextureId};
+
+        let path = fixture_path("external_props_map.json");
+        let tileset_path = path.with_file_name("props_tileset.json");
+        let read = async |file: &Path| {
+            let json = std::fs::read_to_string(file).map_err(|source| MapError::Io {
+                path: file.to_path_buf(),
+                source,
+            })?;
+            if file != tileset_path {
+                return Ok(json);
+            }
+            // Known image size, so the texture is never asked for its size.
+            let mut tileset: serde_json::Value = serde_json::from_str(&json).expect("json");
+            tileset["imagewidth"] = 32.into();
+            tileset["imageheight"] = 32.into();
+            Ok(tileset.to_string())
+        };
+        let options = LoadOptions::default().with_texture_filter(FilterMode::Linear);
+        let path_str = path.to_str().expect("utf-8");
+        let (ir, base) =
+            block_on_ready(decode_map_file_to_ir_async(path_str, &options, read)).expect("decode");
+
+        // Setting a filter on this raw handle would need a GL context, so loading only
+        // succeeds if resolved textures are left alone.
+        let resolve = |_: &str| -> Result<Texture2D, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(Texture2D::from_miniquad_texture(TextureId::from_raw_id(
+                RawId::OpenGl(3),
+            )))
+        };
+        let textures = TextureSource::Resolver(&resolve);
+        let map = block_on_ready(Map::from_ir_textured(ir, &base, &options, textures))
+            .expect("resolved map");
+        assert!(map.tilesets.iter().all(|ts| ts.tex.is_some()));
+    }
+
+    fn layer_tile_count_in_chunk(map: &Map, layer: LayerIdx, x: i32, y: i32) -> usize {
+        map.index
+            .buckets
+            .get(&crate::spatial::ChunkCoord { x, y })
+            .and_then(|c| c.layers.get(&layer))
+            .map(|b| b.tiles.len())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn set_layer_offset_moves_tiles_into_new_chunks() {
+        let mut map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        assert_eq!(map.layer_offset("bg"), Some(Vec2::ZERO));
+        assert_eq!(layer_tile_count_in_chunk(&map, 0, 0, 0), 1);
+
+        map.set_layer_offset("bg", vec2(CHUNK_SIZE as f32 + 4.0, 0.0))
+            .expect("known layer");
+        map.assert_consistent();
+        assert_eq!(
+            map.layer_offset("bg"),
+            Some(vec2(CHUNK_SIZE as f32 + 4.0, 0.0))
+        );
+        assert_eq!(layer_tile_count_in_chunk(&map, 0, 0, 0), 0);
+        assert_eq!(layer_tile_count_in_chunk(&map, 0, 1, 0), 1);
+        // The untouched foreground layer keeps its tile in the origin chunk.
+        assert_eq!(layer_tile_count_in_chunk(&map, 2, 0, 0), 1);
+
+        let live_handles = map.index.handles.iter().filter(|h| h.is_some()).count();
+        assert_eq!(live_handles, 2);
+
+        // Animating the offset re-indexes the layer each time without growing the table.
+        let table_len = map.index.handles.len();
+        for step in 0..50 {
+            map.set_layer_offset("bg", vec2(step as f32 * 7.0, -(step as f32)))
+                .expect("known layer");
+        }
+        map.assert_consistent();
+        assert_eq!(map.index.handles.len(), table_len);
+    }
+
+    #[test]
+    fn set_layer_offset_reindexes_objects_and_rejects_unknown_layers() {
+        let mut map = load_fixture_headless("multichunk_objects_map.json", LoadOptions::default());
+        map.set_layer_offset("objects", vec2(0.0, CHUNK_SIZE as f32))
+            .expect("known layer");
+        map.assert_consistent();
+        assert_eq!(map.object_layers[0].offset, vec2(0.0, CHUNK_SIZE as f32));
+
+        let chunks_with_objects: Vec<_> = {
+            let mut v: Vec<_> = map
+                .index
+                .buckets
+                .iter()
+                .filter(|(_, c)| c.layers.get(&0).is_some_and(|b| !b.objects.is_empty()))
+                .map(|(cc, _)| (cc.x, cc.y))
+                .collect();
+            v.sort();
+            v
+        };
+        assert_eq!(chunks_with_objects, vec![(0, 1), (1, 1)]);
+
+        assert!(matches!(
+            map.set_layer_offset("missing", vec2(1.0, 1.0)),
+            Err(MapError::LayerNotFound(name)) if name == "missing"
+        ));
+        map.assert_consistent();
+        assert_eq!(map.layer_offset("missing"), None);
+    }
+
+    #[test]
+    fn load_in_steps_finishes_over_multiple_steps_with_rising_progress() {
+        let path = fixture_path("external_props_map.json");
+        let mut loader = Map::load_in_steps(
+            path.to_str().expect("fixture path must be utf-8"),
+            LoadOptions {
+                skip_textures: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(loader.phase(), LoadPhase::Parse);
+
+        let mut progress = vec![loader.progress()];
+        let mut steps = 0;
+        let map = loop {
+            steps += 1;
+            assert!(steps < 100, "loader never finished");
+            if let Some(map) = block_on(loader.step(0.0)).expect("step") {
+                break map;
+            }
+            progress.push(loader.progress());
+        };
+        progress.push(loader.progress());
+
+        assert!(steps > 1);
+        assert!(progress.windows(2).all(|w| w[0] <= w[1]), "{progress:?}");
+        assert_eq!(progress.last(), Some(&1.0));
+        assert_eq!(map.tile_layers.len(), 2);
+        assert_eq!(map.object_layers.len(), 1);
+        assert!(matches!(
+            block_on(loader.step(0.0)),
+            Err(MapError::LoaderFinished)
+        ));
+    }
+
+    #[test]
+    fn progress_callback_reports_every_phase_in_order() {
+        use std::sync::Mutex;
+        static EVENTS: Mutex<Vec<(LoadPhase, f32)>> = Mutex::new(Vec::new());
+        fn record(phase: LoadPhase, fraction: f32) {
+            EVENTS.lock().expect("events lock").push((phase, fraction));
+        }
+
+        let map = load_fixture_headless(
+            "external_props_map.json",
+            LoadOptions {
+                on_progress: Some(record),
+                ..Default::default()
+            },
+        );
+        assert_eq!(map.tile_layers.len(), 2);
+
+        let events = EVENTS.lock().expect("events lock").clone();
+        let phases: Vec<LoadPhase> = events.iter().map(|(p, _)| *p).collect();
+        assert!(phases.windows(2).all(|w| w[0].ordinal() <= w[1].ordinal()));
+        for phase in LoadPhase::ALL {
+            let last = events.iter().rev().find(|(p, _)| *p == phase);
+            assert_eq!(last.map(|(_, f)| *f), Some(1.0), "{phase:?}");
+        }
+    }
+
+    fn tile_ir_layer(name: &str, visible: bool) -> IrLayer {
+        IrLayer {
+            name: name.to_string(),
+            visible,
+            opacity: 1.0,
+            tint: WHITE,
+            offset: Vec2::ZERO,
+            properties: Properties::default(),
+            kind: IrLayerKind::Tiles {
+                width: 2,
+                height: 1,
+                data: vec![1, 0],
+            },
+        }
+    }
+
+    fn map_from_ir_headless(ir: IrMap) -> Map {
+        let options = LoadOptions {
+            skip_textures: true,
+            ..Default::default()
+        };
+        let map =
+            block_on(Map::from_ir_with(ir, Path::new("."), &options)).expect("headless build");
+        map.assert_consistent();
+        map
+    }
+
+    #[test]
+    fn layer_indices_stay_stable_across_invisible_layers() {
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![
+                tile_ir_layer("ground", true),
+                tile_ir_layer("collision_sketch", false),
+                tile_ir_layer("trees", true),
+            ],
+            warnings: Vec::new(),
+        });
+
+        assert_eq!(map.layer_idx("ground"), Some(0));
+        assert_eq!(map.layer_idx("collision_sketch"), Some(1));
+        assert_eq!(map.layer_idx("trees"), Some(2));
+        assert_eq!(map.layer_idx("missing"), None);
+
+        assert_eq!(map.layer_name(2), Some("trees"));
+        assert_eq!(map.layer_name(3), None);
+        assert_eq!(map.tile_layers[2].layer_id, 2);
+    }
+
+    #[test]
+    fn stamp_overflow_does_not_break_dedupe() {
+        use std::collections::HashSet;
+
+        let mut map = Map::__new_for_stamp_overflow_test(3);
+        map.__set_frame_stamp_for_testing(u32::MAX - 1);
+
+        let seq1 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
+        let seq2 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
+        let seq3 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
+
+        assert_eq!(seq1, seq2);
+        assert_eq!(seq2, seq3);
+        assert_eq!(seq1.len(), 3);
+
+        let uniq: HashSet<_> = seq1.iter().copied().collect();
+        assert_eq!(uniq.len(), 3);
+
+        let layer = &map.object_layers[0];
+        assert_eq!(layer.seen_stamp_tiles.len(), layer.objects.len());
+        assert_eq!(layer.seen_stamp_debug.len(), layer.objects.len());
+    }
+
+    fn scattered_object_map(count: usize) -> Map {
+        let objects = (0..count)
+            .map(|i| {
+                let mut obj = named_object(i as u32, "");
+                obj.x = ((i * 37) % 4000) as f32;
+                obj.y = ((i * 91) % 4000) as f32;
+                obj.width = 8.0 + (i % 5) as f32 * 60.0;
+                obj
+            })
+            .collect();
+        let mut map = Map::__new_for_stamp_overflow_test(0);
+        map.object_layers = vec![object_layer_for_test(0, "objects", objects)];
+        map.rebuild_object_index(DEFAULT_OBJECT_CELL_SIZE);
+        map
+    }
+
+    fn objects_in_rect_linear(map: &Map, rect: Rect) -> Vec<u32> {
+        map.object_layers
+            .iter()
+            .flat_map(|layer| layer.objects.iter().map(move |o| (layer.offset, o)))
+            .filter(|(offset, o)| {
+                let (min, max) = Map::object_aabb_world(o, *offset);
+                Rect::new(min.x, min.y, max.x - min.x, max.y - min.y).overlaps(&rect)
+            })
+            .map(|(_, o)| o.id)
+            .collect()
+    }
+
+    #[test]
+    fn objects_in_rect_matches_linear_scan() {
+        let mut map = scattered_object_map(1000);
+        let rects = [
+            Rect::new(0.0, 0.0, 300.0, 300.0),
+            Rect::new(1000.0, 2500.0, 700.0, 40.0),
+            Rect::new(-100.0, -100.0, 50.0, 50.0),
+            Rect::new(0.0, 0.0, 4500.0, 4500.0),
+        ];
+        for cell_size in [DEFAULT_OBJECT_CELL_SIZE, 37.0] {
+            map.set_object_index_cell_size(cell_size);
+            for rect in rects {
+                let indexed: Vec<u32> = map.objects_in_rect(rect).map(|o| o.id).collect();
+                assert_eq!(indexed, objects_in_rect_linear(&map, rect), "{rect:?}");
+            }
+        }
+        assert_eq!(
+            map.objects_in_rect(Rect::new(0.0, 0.0, 4500.0, 4500.0))
+                .count(),
+            1000
+        );
+    }
+
+    #[test]
+    fn objects_in_rect_follows_layer_offset() {
+        let mut map = load_fixture_headless("multichunk_objects_map.json", LoadOptions::default());
+        let near_origin = Rect::new(0.0, 0.0, CHUNK_SIZE as f32 * 2.0, 64.0);
+        assert_eq!(map.objects_in_rect(near_origin).count(), 1);
+
+        map.set_layer_offset("objects", vec2(0.0, 1000.0))
+            .expect("known layer");
+        map.assert_consistent();
+        assert_eq!(map.objects_in_rect(near_origin).count(), 0);
+        assert_eq!(
+            map.objects_in_rect(Rect::new(0.0, 1000.0, CHUNK_SIZE as f32 * 2.0, 64.0))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    #[ignore = "timing comparison; run with `cargo test -- --ignored --nocapture`"]
+    fn bench_objects_in_rect_vs_linear_scan() {
+        use std::time::Instant;
+
+        let map = scattered_object_map(1000);
+        let rect = Rect::new(1200.0, 1200.0, 320.0, 180.0);
+        const ROUNDS: usize = 2000;
+
+        let start = Instant::now();
+        let mut indexed_hits = 0;
+        for _ in 0..ROUNDS {
+            indexed_hits += map.objects_in_rect(rect).count();
+        }
+        let indexed = start.elapsed();
+
+        let start = Instant::now();
+        let mut linear_hits = 0;
+        for _ in 0..ROUNDS {
+            linear_hits += objects_in_rect_linear(&map, rect).len();
+        }
+        let linear = start.elapsed();
+
+        assert_eq!(indexed_hits, linear_hits);
+        println!(
+            "1000 objects, {ROUNDS} queries: grid {indexed:?}, linear {linear:?} ({:.1}x)",
+            linear.as_secs_f64() / indexed.as_secs_f64()
+        );
+    }
+
+    fn overlapping_tile_objects_map(draw_order: ObjectDrawOrder) -> Map {
+        // Authoring order deliberately disagrees with y order; ids 2 and 3 share a y.
+        let objects = [(1, 40.0), (2, 20.0), (3, 20.0)]
+            .into_iter()
+            .map(|(id, y)| IrObject {
+                x: 10.0 + id as f32 * 4.0,
+                y,
+                shape: IrObjectShape::Tile { gid: 1 },
+                ..named_object(id, "")
+            })
+            .collect();
+        map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![IrLayer {
+                name: "actors".to_string(),
+                visible: true,
+                opacity: 1.0,
+                tint: WHITE,
+                offset: Vec2::ZERO,
+                properties: Properties::default(),
+                kind: IrLayerKind::Objects {
+                    objects,
+                    draw_order,
+                },
+            }],
+            warnings: Vec::new(),
+        })
+    }
+
+    fn emitted_object_order(map: &mut Map, sprites: &[InjectedSprite]) -> Vec<String> {
+        let coords = map.take_visible_coords(Vec2::ZERO, vec2(128.0, 128.0));
+        let stamp = map.next_frame_stamp();
+        let mut items = Vec::new();
+        map.collect_object_draw_items(&coords, 0, stamp, sprites, &mut items);
+        items
+            .iter()
+            .map(|item| match *item {
+                ObjectDrawItem::Object { object_idx, .. } => {
+                    format!("o{}", map.object_layers[0].objects[object_idx].id)
+                }
+                ObjectDrawItem::Sprite { sprite_idx, .. } => format!("s{sprite_idx}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn topdown_layers_sort_tile_objects_by_y_and_index_layers_keep_file_order() {
+        let mut topdown = overlapping_tile_objects_map(ObjectDrawOrder::TopDown);
+        assert_eq!(
+            topdown.object_layers[0].draw_order,
+            ObjectDrawOrder::TopDown
+        );
+        assert_eq!(emitted_object_order(&mut topdown, &[]), ["o2", "o3", "o1"]);
+
+        let mut index = overlapping_tile_objects_map(ObjectDrawOrder::Index);
+        assert_eq!(emitted_object_order(&mut index, &[]), ["o1", "o2", "o3"]);
+    }
+
+    #[test]
+    fn injected_sprites_join_the_topdown_sort_of_their_layer() {
+        let sprites = [
+            InjectedSprite { layer: 0, y: 30.0 },
+            InjectedSprite { layer: 0, y: 20.0 },
+            // Not an object layer of this map: never emitted.
+            InjectedSprite { layer: 9, y: 0.0 },
+        ];
+
+        let mut topdown = overlapping_tile_objects_map(ObjectDrawOrder::TopDown);
+        assert_eq!(
+            emitted_object_order(&mut topdown, &sprites),
+            ["o2", "o3", "s1", "s0", "o1"]
+        );
+
+        let mut index = overlapping_tile_objects_map(ObjectDrawOrder::Index);
+        assert_eq!(
+            emitted_object_order(&mut index, &sprites),
+            ["o1", "o2", "o3", "s0", "s1"]
+        );
+    }
+
+    #[test]
+    fn tileset_resolver_receives_image_paths_and_its_errors_are_kept() {
+        let asked = std::cell::RefCell::new(Vec::new());
+        let path = fixture_path("minimal_finite_map.json");
+        let result = block_on_ready(Map::load_with_tileset_resolver(
+            path.to_str().expect("utf-8"),
+            |image| {
+                asked.borrow_mut().push(image.to_string());
+                Err(std::io::Error::other("not in the asset pack"))
+            },
+        ));
+
+        assert_eq!(*asked.borrow(), ["basic_tiles.png"]);
+        let Err(MapError::TextureLoad {
+            tileset, source, ..
+        }) = result
+        else {
+            panic!("resolver errors must surface as TextureLoad");
+        };
+        assert_eq!(tileset, "basic_tileset.json");
+        assert_eq!(source.to_string(), "not in the asset pack");
+    }
+
+    #[test]
+    fn tileset_resolver_loads_follow_the_given_options() {
+        let path = fixture_path("one_broken_layer_map.json");
+        let path = path.to_str().expect("utf-8");
+        let asked = std::cell::Cell::new(0);
+        let resolver = |_: &str| {
+            asked.set(asked.get() + 1);
+            Err(std::io::Error::other("not in the asset pack"))
+        };
+
+        let strict = block_on_ready(Map::load_with_tileset_resolver(path, resolver));
+        assert!(matches!(strict, Err(MapError::InvalidTileGid { .. })));
+        assert_eq!(
+            asked.get(),
+            0,
+            "decoding fails before any texture is resolved"
+        );
+
+        // Skipping the broken layer gets decoding through to the resolver.
+        let options = LoadOptions {
+            skip_broken_layers: true,
+            ..LoadOptions::default()
+        };
+        let skipping = block_on_ready(Map::load_with_tileset_resolver_with_options(
+            path, options, resolver,
+        ));
+        assert!(matches!(skipping, Err(MapError::TextureLoad { .. })));
+        assert_eq!(asked.get(), 1);
+    }
+
+    #[test]
+    fn texture_errors_name_the_tileset_and_its_gids() {
+        // `load_texture` needs a window, so read the missing image the way it would.
+        let image = Path::new("tests/fixtures/missing_tiles.png");
+        let io = std::fs::read(image).expect_err("image must be missing");
+        let backend = macroquad::Error::FileError {
+            kind: macroquad::miniquad::fs::Error::IOError(io),
+            path: image.display().to_string(),
+        };
+
+        let err = texture_load_error(image, "tiles_03.json", 33, 16, backend);
+        let MapError::TextureLoad {
+            path,
+            tileset,
+            gids,
+            source,
+        } = &err
+        else {
+            panic!("expected TextureLoad, got {err}");
+        };
+        assert!(path.is_absolute());
+        assert!(path.ends_with("tests/fixtures/missing_tiles.png"));
+        assert_eq!(tileset, "tiles_03.json");
+        assert_eq!(*gids, 33..=48);
+        assert!(matches!(
+            source.downcast_ref(),
+            Some(macroquad::Error::FileError { .. })
+        ));
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(err
+            .to_string()
+            .contains("tileset 'tiles_03.json' (gids 33..=48)"));
+    }
+
+    #[test]
+    fn max_gid_is_the_last_gid_of_the_highest_tileset() {
+        let tileset = |first_gid: u32, tilecount: u32| IrTileset::Atlas {
+            name: format!("ts{first_gid}"),
+            first_gid,
+            source: String::new(),
+            image: "terrain.png".to_string(),
+            image_size: None,
+            tile_w: 16,
+            tile_h: 16,
+            tilecount,
+            columns: 4,
+            spacing: 0,
+            margin: 0,
+            properties: Properties::default(),
+            tiles: vec![],
+        };
+        let map_with = |tilesets| {
+            map_from_ir_headless(IrMap {
+                tile_w: 16,
+                tile_h: 16,
+                properties: Properties::default(),
+                tilesets,
+                layers: vec![],
+                warnings: Vec::new(),
+            })
+        };
+        assert_eq!(map_with(vec![tileset(1, 16)]).max_gid(), 16);
+        assert_eq!(map_with(vec![tileset(1, 16), tileset(40, 4)]).max_gid(), 43);
+        assert_eq!(map_with(vec![]).max_gid(), 0);
+        // Empty tilesets claim no gids rather than underflowing `first_gid - 1`.
+        assert_eq!(map_with(vec![tileset(0, 0)]).max_gid(), 0);
+        assert_eq!(map_with(vec![tileset(1, 16), tileset(17, 0)]).max_gid(), 16);
+    }
+
+    #[test]
+    fn texture_filter_defaults_to_nearest_and_follows_load_options() {
+        let pixel_art = load_fixture_headless("minimal_finite_map.json", LoadOptions::default());
+        assert_eq!(pixel_art.renderer.texture_filter, FilterMode::Nearest);
+
+        // Smooth, painted terrain looks better sampled linearly.
+        let smooth = LoadOptions::default().with_texture_filter(FilterMode::Linear);
+        let smooth = load_fixture_headless("minimal_finite_map.json", smooth);
+        assert_eq!(smooth.renderer.texture_filter, FilterMode::Linear);
+
+        // Same through the config front end; no tilesets, so nothing needs a GL context.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock went backwards")
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("mq_tiled_filter_{nanos}"));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = dir.join("map.json");
+        let map_json = r#"{"tilewidth":16,"tileheight":16,"layers":[],"tilesets":[]}"#;
+        std::fs::write(&path, map_json).expect("write map");
+        let path = path.to_str().expect("utf-8");
+        let load = |config| block_on(Map::load_with_config(path, config)).expect("load");
+        let pixel_art = load(MapLoadConfig::default());
+        assert_eq!(pixel_art.renderer.texture_filter, FilterMode::Nearest);
+        let smooth = load(MapLoadConfig::default().with_filter_mode(FilterMode::Linear));
+        assert_eq!(smooth.renderer.texture_filter, FilterMode::Linear);
+        std::fs::remove_dir_all(dir).expect("cleanup");
+    }
+
+    #[test]
+    fn broken_layers_are_skipped_and_reported_when_asked() {
+        let name = "one_broken_layer_map.json";
+        let path = fixture_path(name);
+        let headless = LoadOptions::default().headless();
+        assert!(matches!(
+            block_on(Map::load_with(path.to_str().expect("utf-8"), headless)),
+            Err(MapError::InvalidTileGid { gid: 99, .. })
+        ));
+
+        let map = load_fixture_headless(name, LoadOptions::default().with_broken_layers_skipped());
+        let drawn: Vec<(String, u32)> = map
+            .collect_draw_commands(Vec2::ZERO, vec2(32.0, 16.0))
+            .iter()
+            .map(|c| (map.layer_names[c.layer as usize].clone(), c.gid))
+            .collect();
+        assert_eq!(
+            drawn,
+            [
+                ("ground".to_string(), 1),
+                ("ground".to_string(), 2),
+                ("decor".to_string(), 4)
+            ]
+        );
+        assert_eq!(map.layer_draw_order(), ["ground", "broken", "decor"]);
+
+        let skipped: Vec<_> = map
+            .load_warnings()
+            .iter()
+            .filter(|w| w.code == IssueCode::LayerSkipped)
+            .collect();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(
+            skipped[0].location,
+            IssueLocation::Layer("broken".to_string())
+        );
+        assert!(
+            skipped[0].message.contains("gid 99"),
+            "{}",
+            skipped[0].message
+        );
+    }
+
+    #[test]
+    fn gid_range_covers_loaded_tilesets_and_is_none_without_tilesets() {
+        let map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+        assert_eq!(map.gid_range(), Some((1, 4)));
+        assert!(map.has_tileset_for_gid(TileId(4 | 0x8000_0000)));
+        assert!(!map.has_tileset_for_gid(TileId(0)));
+        assert!(!map.has_tileset_for_gid(TileId(5)));
+
+        let empty = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![],
+            warnings: Vec::new(),
+        });
+        assert_eq!(empty.gid_range(), None);
+        assert!(!empty.has_tileset_for_gid(TileId(1)));
+    }
+
+    #[test]
+    fn object_draw_override_fires_once_per_frame_only_for_culled_in_objects() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut map = overlapping_tile_objects_map(ObjectDrawOrder::TopDown);
+        map.set_cull_padding(0.0);
+        // Object 1 spans two chunks; object 3 is far outside the view.
+        map.object_layers[0].objects[0].width = CHUNK_SIZE as f32;
+        map.object_layers[0].objects[2].x = 5000.0;
+        // Same offset: only re-buckets the edited objects.
+        map.set_layer_offset("actors", Vec2::ZERO)
+            .expect("known layer");
+        map.assert_consistent();
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        for id in [1, 3] {
+            let calls = Rc::clone(&calls);
+            map.set_object_draw_override(
+                id,
+                Box::new(move |obj, origin| calls.borrow_mut().push((obj.id, origin))),
+            );
+        }
+
+        let view_max = vec2(CHUNK_SIZE as f32 * 2.0, 64.0);
+        map.draw_objects_tiles(Vec2::ZERO, view_max);
+        assert_eq!(*calls.borrow(), vec![(1, vec2(14.0, 40.0))]);
+
+        map.draw_objects_tiles(Vec2::ZERO, view_max);
+        assert_eq!(calls.borrow().len(), 2);
+
+        assert!(map.clear_object_draw_override(1).is_some());
+        assert!(map.clear_object_draw_override(1).is_none());
+        map.draw_objects_tiles(Vec2::ZERO, view_max);
+        assert_eq!(calls.borrow().len(), 2);
+    }
+
+    #[test]
+    fn tile_class_lookup_masks_flips_and_falls_through_unclassified_tiles() {
+        let classified = |id: u32, class: &str| IrTileMetadata {
+            id,
+            class_name: class.to_string(),
+            properties: Properties::default(),
+            objects: vec![],
+        };
+        let tile_layer = |name: &str, data: Vec<u32>| IrLayer {
+            kind: IrLayerKind::Tiles {
+                width: 3,
+                height: 1,
+                data,
+            },
+            ..tile_ir_layer(name, true)
+        };
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![IrTileset::Atlas {
+                name: "atlas".to_string(),
+                first_gid: 1,
+                source: String::new(),
+                image: String::new(),
+                image_size: None,
+                tile_w: 16,
+                tile_h: 16,
+                tilecount: 4,
+                columns: 2,
+                spacing: 0,
+                margin: 0,
+                properties: Properties::default(),
+                tiles: vec![
+                    classified(0, "water"),
+                    classified(1, ""),
+                    classified(2, "lava"),
+                ],
+            }],
+            layers: vec![
+                tile_layer("ground", vec![1, 2, 1]),
+                tile_layer("top", vec![0, 3 | crate::spatial::FLIP_H, 2]),
+            ],
+            warnings: Vec::new(),
+        });
+
+        // Classified ground tile under an empty top cell.
+        assert_eq!(map.tile_class_at(vec2(8.0, 8.0), "ground"), Some("water"));
+        assert_eq!(map.tile_class_at(vec2(8.0, 8.0), "top"), None);
+        assert_eq!(map.top_tile_class_at(vec2(8.0, 8.0)), Some("water"));
+
+        // Unclassified ground tile under a flipped, classified top tile.
+        assert_eq!(map.tile_class_at(vec2(24.0, 8.0), "ground"), None);
+        assert_eq!(map.tile_class_at(vec2(24.0, 8.0), "top"), Some("lava"));
+        assert_eq!(map.top_tile_class_at(vec2(24.0, 8.0)), Some("lava"));
+
+        // An unclassified top tile does not hide the classified tile below.
+        assert_eq!(map.tile_class_at(vec2(40.0, 8.0), "top"), None);
+        assert_eq!(map.top_tile_class_at(vec2(40.0, 8.0)), Some("water"));
+
+        assert_eq!(map.top_tile_class_at(vec2(-1.0, 8.0)), None);
+        assert_eq!(map.top_tile_class_at(vec2(100.0, 8.0)), None);
+        assert_eq!(map.top_tile_class_at(vec2(8.0, f32::INFINITY)), None);
+        assert_eq!(map.tile_class_at(vec2(8.0, 1e30), "ground"), None);
+        assert_eq!(map.tile_class_at(vec2(8.0, 8.0), "missing"), None);
+    }
+
+    #[test]
+    fn walkable_polygons_trace_room_outline_and_pillar_hole() {
+        // 7x7 walls layer: solid border and a 1x1 pillar in the middle.
+        let mut walls = vec![0u32; 49];
+        for i in 0..7 {
+            for cell in [i, 42 + i, i * 7, i * 7 + 6] {
+                walls[cell] = 1;
+            }
+        }
+        walls[3 * 7 + 3] = 1;
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![IrLayer {
+                kind: IrLayerKind::Tiles {
+                    width: 7,
+                    height: 7,
+                    data: walls,
+                },
+                ..tile_ir_layer("walls", true)
+            }],
+            warnings: Vec::new(),
+        });
+
+        let options = NavOptions {
+            blocking_layers: vec!["walls".to_string()],
+            ..Default::default()
+        };
+        let contours = map.walkable_polygons(&options);
+        assert_eq!(contours.len(), 2);
+
+        let signed_area = |c: &[Vec2]| -> f32 {
+            (0..c.len())
+                .map(|i| c[i].perp_dot(c[(i + 1) % c.len()]))
+                .sum::<f32>()
+                / 2.0
+        };
+        let (outer, hole) = if signed_area(&contours[0]) > 0.0 {
+            (&contours[0], &contours[1])
+        } else {
+            (&contours[1], &contours[0])
+        };
+
+        assert_eq!(outer.len(), 4);
+        assert_eq!(signed_area(outer), 80.0 * 80.0);
+        assert!(outer.contains(&vec2(16.0, 16.0)));
+        assert!(outer.contains(&vec2(96.0, 96.0)));
+
+        assert_eq!(hole.len(), 4);
+        assert_eq!(signed_area(hole), -(16.0 * 16.0));
+        assert!(hole.contains(&vec2(48.0, 48.0)));
+        assert!(hole.contains(&vec2(64.0, 64.0)));
+    }
+
+    #[test]
+    fn tile_layer_as_flat_array_round_trips_json_data() {
+        let ir = load_fixture_ir("minimal_finite_map.json");
+        let expected: Vec<u32> = ir
+            .layers
+            .iter()
+            .find_map(|l| match &l.kind {
+                IrLayerKind::Tiles { data, .. } => Some(data.clone()),
+                _ => None,
+            })
+            .expect("fixture has a tile layer");
+        let name = ir.layers[0].name.clone();
+
+        let mut map = map_from_ir_headless(ir);
+        assert_eq!(map.tile_layer_as_flat_array(&name), Some(expected.clone()));
+
+        // Still matches after the layer was moved across chunks.
+        map.set_layer_offset(&name, vec2(CHUNK_SIZE as f32 - 8.5, 3.0))
+            .expect("known layer");
+        map.assert_consistent();
+        assert_eq!(map.tile_layer_as_flat_array(&name), Some(expected));
+        assert_eq!(map.tile_layer_as_flat_array("missing"), None);
+    }
+
+    #[test]
+    fn objects_at_point_hits_rotated_rects_and_concave_polygons_topmost_first() {
+        // 40x10 bar rotated 90 degrees clockwise around (100, 100): covers x 90..100, y 100..140.
+        let rotated = IrObject {
+            x: 100.0,
+            y: 100.0,
+            width: 40.0,
+            height: 10.0,
+            rotation: 90.0,
+            ..named_object(1, "bar")
+        };
+        // U shape opening upwards; the notch x 10..20, y 0..20 is outside.
+        let concave = IrObject {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            shape: IrObjectShape::Polygon(vec![
+                vec2(0.0, 0.0),
+                vec2(10.0, 0.0),
+                vec2(10.0, 20.0),
+                vec2(20.0, 20.0),
+                vec2(20.0, 0.0),
+                vec2(30.0, 0.0),
+                vec2(30.0, 30.0),
+                vec2(0.0, 30.0),
+            ]),
+            ..named_object(2, "u")
+        };
+        let overlay = IrObject {
+            x: 20.0,
+            y: 20.0,
+            width: 10.0,
+            height: 10.0,
+            ..named_object(3, "overlay")
+        };
+        let object_layer = |name: &str, objects| IrLayer {
+            kind: IrLayerKind::Objects {
+                objects,
+                draw_order: ObjectDrawOrder::Index,
+            },
+            ..tile_ir_layer(name, true)
+        };
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![
+                object_layer("below", vec![rotated, concave]),
+                object_layer("above", vec![overlay]),
+            ],
+            warnings: Vec::new(),
+        });
+        let ids = |p: Vec2| -> Vec<u32> {
+            map.objects_at_point(p)
+                .iter()
+                .map(|o| o.object.id)
+                .collect()
+        };
+
+        assert_eq!(ids(vec2(95.0, 130.0)), [1]);
+        // Inside the unrotated rect but outside the rotated one.
+        assert_eq!(ids(vec2(130.0, 105.0)), Vec::<u32>::new());
+
+        assert_eq!(ids(vec2(5.0, 5.0)), [2]);
+        assert_eq!(ids(vec2(15.0, 10.0)), Vec::<u32>::new());
+        // Overlap of both layers: the later layer comes first.
+        assert_eq!(ids(vec2(25.0, 25.0)), [3, 2]);
+    }
+
+    #[test]
+    fn object_at_pixel_picks_the_last_drawn_of_overlapping_rects() {
+        let rect = |id, at: f32| IrObject {
+            x: at,
+            y: at,
+            width: 20.0,
+            height: 20.0,
+            ..named_object(id, "box")
+        };
+        let spawn = IrObject {
+            x: 100.0,
+            y: 100.0,
+            shape: IrObjectShape::Point,
+            ..named_object(4, "spawn")
+        };
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![IrLayer {
+                kind: IrLayerKind::Objects {
+                    objects: vec![rect(1, 0.0), rect(2, 5.0), rect(3, 10.0), spawn],
+                    draw_order: ObjectDrawOrder::Index,
+                },
+                ..tile_ir_layer("objects", true)
+            }],
+            warnings: Vec::new(),
+        });
+        let id = |x, y| map.object_at_pixel(x, y).map(|o| o.id);
+
+        assert_eq!(id(15.0, 15.0), Some(3), "all three overlap here");
+        assert_eq!(id(7.0, 7.0), Some(2));
+        assert_eq!(id(2.0, 2.0), Some(1));
+        assert_eq!(id(103.0, 100.0), Some(4));
+        assert_eq!(id(106.0, 100.0), None);
+    }
+
+    #[test]
+    fn triggers_report_enter_inside_exit_for_a_moving_point() {
+        let zone = IrObject {
+            x: 40.0,
+            y: 0.0,
+            width: 20.0,
+            height: 20.0,
+            shape: IrObjectShape::Ellipse,
+            ..named_object(5, "zone")
+        };
+        let mut layer = tile_ir_layer("triggers", false);
+        layer.offset = vec2(0.0, 100.0);
+        layer.kind = IrLayerKind::Objects {
+            objects: vec![zone],
+            draw_order: ObjectDrawOrder::TopDown,
+        };
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![],
+            layers: vec![layer],
+            warnings: Vec::new(),
+        });
+        let mut triggers = map.build_triggers("triggers").expect("object layer");
+        assert_eq!(triggers.len(), 1);
+        assert!(map.build_triggers("missing").is_none());
+
+        use crate::triggers::TriggerEvent::*;
+        let frames: Vec<_> = [20.0, 45.0, 50.0, 56.0, 80.0, 90.0]
+            .into_iter()
+            .map(|x| triggers.update(vec2(x, 110.0)))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                vec![],
+                vec![Entered(5)],
+                vec![Inside(5)],
+                vec![Inside(5)],
+                vec![Exited(5)],
+                vec![],
+            ]
+        );
+
+        // A collider box overlapping the ellipse's edge also counts.
+        assert_eq!(
+            triggers.update(Rect::new(55.0, 105.0, 20.0, 10.0)),
+            vec![Entered(5)]
+        );
+    }
+
+    #[test]
+    fn collect_draw_commands_resolves_source_dest_and_flips() {
+        let map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![IrTileset::Atlas {
+                name: "atlas".to_string(),
+                first_gid: 1,
+                source: String::new(),
+                image: String::new(),
+                image_size: None,
+                tile_w: 16,
+                tile_h: 16,
+                tilecount: 4,
+                columns: 2,
+                spacing: 0,
+                margin: 0,
+                properties: Properties::default(),
+                tiles: vec![],
+            }],
+            layers: vec![IrLayer {
+                opacity: 0.5,
+                tint: WHITE,
+                kind: IrLayerKind::Tiles {
+                    width: 2,
+                    height: 1,
+                    data: vec![2 | crate::spatial::FLIP_V, 3],
+                },
+                ..tile_ir_layer("ground", true)
+            }],
+            warnings: Vec::new(),
+        });
+
+        let mut cmds = map.collect_draw_commands(Vec2::ZERO, vec2(64.0, 64.0));
+        cmds.sort_by(|a, b| a.dest.x.total_cmp(&b.dest.x));
+        assert_eq!(cmds.len(), 2);
+
+        assert_eq!(cmds[0].gid, 2 | crate::spatial::FLIP_V);
+        assert_eq!(cmds[0].source, Rect::new(16.0, 0.0, 16.0, 16.0));
+        assert_eq!(cmds[0].dest_rect(), Rect::new(0.0, 0.0, 16.0, 16.0));
+        assert!(!cmds[0].flip_x && cmds[0].flip_y);
+        assert_eq!(cmds[0].tint.a, 0.5);
+
+        assert_eq!(cmds[1].source, Rect::new(0.0, 16.0, 16.0, 16.0));
+        assert_eq!(cmds[1].dest_rect(), Rect::new(16.0, 0.0, 16.0, 16.0));
+        assert!(!cmds[1].flip_x && !cmds[1].flip_y);
+        assert_eq!(cmds[1].rotation, 0.0);
+        assert!(cmds.iter().all(|c| c.layer == 0 && c.tileset == 0));
+    }
+
+    #[test]
+    fn find_tiles_locates_damage_tiles_in_fixture() {
+        let map = load_fixture_headless("external_props_map.json", LoadOptions::default());
+
+        let hits = map.find_tiles_with_property("damage", &PropertyValue::I64(10));
+        let found: Vec<_> = hits
+            .iter()
+            .map(|m| (m.layer, m.col, m.row, m.world))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(0, 0, 0, Vec2::ZERO), (2, 1, 0, vec2(16.0, 0.0))]
+        );
+        assert!(hits.iter().all(|m| m.gid == 1));
+
+        let fg_only = map.find_tiles(|_, props| props.get_i32("damage").is_some(), Some("fg"));
+        assert_eq!(fg_only.len(), 1);
+        assert_eq!(fg_only[0].layer, 2);
+
+        assert!(map
+            .find_tiles_with_property("damage", &PropertyValue::I64(99))
+            .is_empty());
+    }
+
+    #[test]
+    fn tileset_by_name_finds_each_named_tileset() {
+        let map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+
+        let basic = map.tileset_by_name("basic").expect("basic tileset");
+        assert_eq!((basic.first_gid, basic.tilecount), (1, 4));
+        let props = map.tileset_by_name("props").expect("props tileset");
+        assert_eq!((props.first_gid, props.tilecount), (5, 4));
+        assert!(map.tileset_by_name("missing").is_none());
+    }
+
+    #[test]
+    fn tile_at_pixel_reads_the_cell_under_the_pixel() {
+        let mut map = terrain_map(3, 2, 5 | FLIP_H);
+        map.set_tile("ground", 1, 0, 0).expect("clear a cell");
+
+        assert_eq!(
+            map.tile_at_pixel(8.0, 8.0, "ground"),
+            Some(TileId(5 | FLIP_H))
+        );
+        assert_eq!(
+            map.tile_at_pixel(47.9, 31.9, "ground"),
+            Some(TileId(5 | FLIP_H))
+        );
+        assert_eq!(map.tile_at_pixel(20.0, 8.0, "ground"), None, "empty cell");
+        for (x, y) in [
+            (-0.5, 8.0),
+            (8.0, -0.5),
+            (48.0, 8.0),
+            (8.0, 32.0),
+            (8.0, 1e30),
+            (8.0, f32::INFINITY),
+            (f32::NAN, 8.0),
+        ] {
+            assert_eq!(map.tile_at_pixel(x, y, "ground"), None, "({x}, {y})");
         }
+        assert_eq!(map.tile_at_pixel(8.0, 8.0, "missing"), None);
+
+        map.set_layer_offset("ground", vec2(100.0, 0.0))
+            .expect("known layer");
+        assert_eq!(map.tile_at_pixel(8.0, 8.0, "ground"), None);
+        assert_eq!(
+            map.tile_at_pixel(108.0, 8.0, "ground"),
+            Some(TileId(5 | FLIP_H))
+        );
     }
 
-    fn visible_coords_for_draw(
-        &self,
-        view_min: Vec2,
-        view_max: Vec2,
-    ) -> Vec<crate::spatial::ChunkCoord> {
-        let pad = self.renderer.cull_padding;
-        visible_chunk_coords_rect(
-            vec2(view_min.x - pad, view_min.y - pad),
-            vec2(view_max.x + pad, view_max.y + pad),
-        )
+    // Counts heap allocations per thread, so tests running in parallel do not interfere.
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::PathBuf;
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
 
-    fn fixture_path(name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("tests")
-            .join("fixtures")
-            .join(name)
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
     }
 
-    fn load_fixture_ir(name: &str) -> IrMap {
-        let path = fixture_path(name);
-        let path_str = path.to_str().expect("fixture path must be utf-8");
-        let (ir, _) = decode_map_file_to_ir(path_str).expect("fixture should decode");
-        ir
+    #[global_allocator]
+    static COUNTING_ALLOC: CountingAlloc = CountingAlloc;
+
+    fn allocations_during(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(|n| n.get());
+        f();
+        ALLOCATIONS.with(|n| n.get()) - before
     }
 
     #[test]
-    fn object_chunk_span_covers_multi_chunk_rectangles() {
-        let obj = IrObject {
-            id: 1,
-            name: String::new(),
-            class_name: String::new(),
-            x: 250.0,
-            y: 10.0,
-            width: 20.0,
-            height: 20.0,
-            rotation: 0.0,
-            visible: true,
-            shape: IrObjectShape::Rectangle,
+    fn steady_state_draws_do_not_allocate() {
+        let (width, height) = (64, 64);
+        let props: Vec<IrObject> = (0..60)
+            .map(|i| IrObject {
+                x: (i * 53 % 1000) as f32,
+                y: (i * 97 % 1000) as f32,
+                shape: IrObjectShape::Tile { gid: 1 + i % 32 },
+                ..named_object(i, "prop")
+            })
+            .collect();
+        let mut map = map_from_ir_headless(IrMap {
+            tile_w: 16,
+            tile_h: 16,
             properties: Properties::default(),
+            tilesets: vec![terrain_tileset()],
+            layers: vec![
+                IrLayer {
+                    kind: IrLayerKind::Tiles {
+                        width,
+                        height,
+                        data: (0..width * height)
+                            .map(|i| 1 + (i as u32 * 7) % 32)
+                            .collect(),
+                    },
+                    ..tile_ir_layer("ground", true)
+                },
+                IrLayer {
+                    kind: IrLayerKind::Objects {
+                        objects: props,
+                        draw_order: ObjectDrawOrder::TopDown,
+                    },
+                    ..tile_ir_layer("props", true)
+                },
+            ],
+            warnings: Vec::new(),
+        });
+        map.set_render_mode(ChunkRenderMode::YSort { base_layer: 0 });
+        let props_id = map.layer_idx("props").expect("props layer") as LayerId;
+        let sprites = [
+            InjectedSprite {
+                layer: props_id,
+                y: 300.0,
+            },
+            InjectedSprite {
+                layer: props_id,
+                y: 600.0,
+            },
+        ];
+        let (view_min, view_max) = (Vec2::ZERO, vec2(1024.0, 1024.0));
+        let mut sprites_drawn = 0;
+        let mut frame = |map: &mut Map| {
+            map.draw_interleaved(view_min, view_max, &sprites, |_| sprites_drawn += 1);
+            map.draw_visible_rect(view_min, view_max);
+            map.draw_layer_flipped("ground", view_min, view_max, true, false);
+            map.draw_tile_columns_sorted("ground", view_min, view_max);
+            map.draw_region("ground", 2, 2, 20, 20, Vec2::ZERO);
+            map.draw_region_tiles(TileRegion::new(0, 0, 8, 8), DrawParams::default());
+            map.draw_layer_at_offset("ground", view_min, view_max, vec2(3.0, -2.0));
+            map.draw_ex(view_min, view_max, DrawParams::default());
         };
 
-        let (chunk_min, chunk_max) = Map::object_chunk_span(&obj, Vec2::ZERO);
-        assert_eq!(chunk_min.x, 0);
-        assert_eq!(chunk_max.x, 1);
-        assert_eq!(chunk_min.y, 0);
-        assert_eq!(chunk_max.y, 0);
+        // The first frame sizes the scratch buffers.
+        frame(&mut map);
+        assert_eq!(allocations_during(|| frame(&mut map)), 0);
+        assert_eq!(sprites_drawn, 4);
     }
 
-    #[test]
-    fn draw_order_matches_tiled_layer_order() {
-        let layers = vec![
-            IrLayer {
-                name: "tiles_a".to_string(),
-                visible: true,
-                opacity: 1.0,
-                offset: Vec2::ZERO,
-                properties: Properties::default(),
-                kind: IrLayerKind::Tiles {
-                    width: 1,
-                    height: 1,
-                    data: vec![0],
-                },
-            },
-            IrLayer {
-                name: "objects_a".to_string(),
-                visible: true,
-                opacity: 1.0,
-                offset: Vec2::ZERO,
-                properties: Properties::default(),
-                kind: IrLayerKind::Objects { objects: vec![] },
-            },
-            IrLayer {
-                name: "tiles_b".to_string(),
+    fn terrain_tileset() -> IrTileset {
+        IrTileset::Atlas {
+            name: "terrain".to_string(),
+            first_gid: 1,
+            source: String::new(),
+            image: "terrain.png".to_string(),
+            image_size: None,
+            tile_w: 16,
+            tile_h: 16,
+            tilecount: 32,
+            columns: 8,
+            spacing: 0,
+            margin: 0,
+            properties: Properties::default(),
+            tiles: vec![],
+        }
+    }
+
+    fn terrain_map(width: usize, height: usize, gid: u32) -> Map {
+        map_from_ir_headless(terrain_ir(width, height, gid))
+    }
+
+    fn terrain_ir(width: usize, height: usize, gid: u32) -> IrMap {
+        IrMap {
+            tile_w: 16,
+            tile_h: 16,
+            properties: Properties::default(),
+            tilesets: vec![terrain_tileset()],
+            layers: vec![IrLayer {
+                name: "ground".to_string(),
                 visible: true,
                 opacity: 1.0,
+                tint: WHITE,
                 offset: Vec2::ZERO,
                 properties: Properties::default(),
                 kind: IrLayerKind::Tiles {
-                    width: 1,
-                    height: 1,
-                    data: vec![0],
+                    width,
+                    height,
+                    data: vec![gid; width * height],
                 },
-            },
-        ];
+            }],
+            warnings: Vec::new(),
+        }
+    }
 
-        let (draw_order, kind_by_id) = build_draw_order_and_kind(&layers);
-        assert_eq!(draw_order, vec![0, 1, 2]);
-        assert!(matches!(kind_by_id.get(&0), Some(LayerKindInfo::Tiles(0))));
+    fn code_tileset(name: &str, first_gid: u32, tilecount: u32) -> TilesetInfo {
+        TilesetInfo {
+            name: name.to_string(),
+            first_gid,
+            source: String::new(),
+            image: format!("{name}.png"),
+            tilecount,
+            cols: 4,
+            tex: None,
+            tile_w: 16,
+            tile_h: 16,
+            spacing: 0,
+            margin: 0,
+        }
+    }
+
+    #[test]
+    fn maps_built_in_code_index_and_emit_draw_commands() {
+        let mut map = Map::new(16, 16, 16, 16).expect("valid tile size");
+        map.add_tileset(code_tileset("ground", 1, 8))
+            .expect("tileset");
+        map.add_tileset(code_tileset("props", 9, 4))
+            .expect("tileset");
+        map.add_layer("floor", vec![0; 256]).expect("layer");
+        map.add_layer("decor", vec![0; 256]).expect("layer");
+        map.set_tile("floor", 0, 0, 1).expect("tile");
+        map.set_tile("floor", 15, 15, 8).expect("tile");
+        map.set_tile("decor", 3, 2, 10).expect("tile");
+        map.assert_consistent();
+
+        assert_eq!(map.layer_idx("decor"), Some(1));
+        assert!(map.has_tileset_for_gid(TileId(12)));
+        let cmds: Vec<_> = map
+            .build_draw_commands(None)
+            .into_iter()
+            .map(|c| (c.layer, c.tileset, c.gid, c.dest))
+            .collect();
+        assert_eq!(
+            cmds,
+            vec![
+                (0, 0, 1, vec2(0.0, 0.0)),
+                (0, 0, 8, vec2(240.0, 240.0)),
+                (1, 1, 10, vec2(48.0, 32.0)),
+            ]
+        );
+
+        assert!(map.add_layer("short", vec![0; 255]).is_err());
+        assert!(map.add_layer("floor", vec![0; 256]).is_err());
         assert!(matches!(
-            kind_by_id.get(&1),
-            Some(LayerKindInfo::Objects(0))
+            map.add_layer("bad", vec![13; 256]),
+            Err(MapError::InvalidTileGid { gid: 13, .. })
         ));
-        assert!(matches!(kind_by_id.get(&2), Some(LayerKindInfo::Tiles(1))));
+        assert!(map.add_tileset(code_tileset("overlap", 12, 4)).is_err());
+        assert!(map.add_tileset(code_tileset("empty", 13, 0)).is_err());
     }
 
     #[test]
-    fn stamp_buffers_are_realigned_and_all_objects_are_seen_once() {
-        fn make_object(id: u32) -> IrObject {
-            IrObject {
-                id,
-                name: String::new(),
-                class_name: String::new(),
-                x: 0.0,
-                y: 0.0,
-                width: 16.0,
-                height: 16.0,
-                rotation: 0.0,
-                visible: true,
-                shape: IrObjectShape::Rectangle,
-                properties: Properties::default(),
-            }
+    fn set_tile_updates_grid_and_index_and_rejects_bad_cells() {
+        let mut map = terrain_map(3, 2, 1);
+        map.set_tile("ground", 1, 1, 0).expect("clear tile");
+        map.set_tile("ground", 2, 0, 7 | 0x8000_0000)
+            .expect("flipped tile");
+        map.assert_consistent();
+        assert_eq!(
+            map.tile_layer_as_flat_array("ground").expect("layer"),
+            vec![1, 1, 7 | 0x8000_0000, 1, 0, 1]
+        );
+        map.set_tile("ground", 1, 1, 3).expect("refill tile");
+        map.assert_consistent();
+        assert_eq!(map.get_tile("ground", 2, 0), Some(7 | 0x8000_0000));
+        assert_eq!(map.get_tile("ground", 1, 1), Some(3));
+        assert_eq!(map.get_tile("ground", 3, 0), None);
+        assert_eq!(map.get_tile("ground", 0, 2), None);
+        assert_eq!(map.get_tile("missing", 0, 0), None);
+        let cells: Vec<_> = map.iter_cells("ground").expect("layer").collect();
+        assert_eq!(cells.len(), 6);
+        assert_eq!(cells[2], (2, 0, 7 | 0x8000_0000));
+        assert_eq!(cells[4], (1, 1, 3));
+        assert!(map.iter_cells("missing").is_none());
+
+        assert!(matches!(
+            map.set_tile("ground", 3, 0, 1),
+            Err(MapError::TileOutOfBounds { x: 3, y: 0, .. })
+        ));
+        assert!(matches!(
+            map.set_tile("ground", 0, 0, 33),
+            Err(MapError::InvalidTileGid { gid: 33, .. })
+        ));
+        for gid in [1, 33] {
+            assert!(matches!(
+                map.set_tile("missing", 0, 0, gid),
+                Err(MapError::LayerNotFound(name)) if name == "missing"
+            ));
         }
+    }
 
-        let mut layer = ObjectLayer {
-            id: 0,
-            name: "objects".to_string(),
-            visible: true,
-            opacity: 1.0,
-            offset: Vec2::ZERO,
-            properties: Properties::default(),
-            objects: vec![make_object(1), make_object(2), make_object(3)],
-            bucket_layer: 0,
-            seen_stamp_tiles: vec![0],
-            seen_stamp_debug: vec![],
-        };
+    #[test]
+    fn world_and_tile_coordinates_round_trip_inside_the_map() {
+        let map = Map::new(4, 3, 16, 8).expect("valid tile size");
+        assert_eq!(map.size_px(), (64.0, 24.0));
+        assert_eq!(map.world_to_tile(vec2(0.0, 0.0)), Some((0, 0)));
+        assert_eq!(map.world_to_tile(vec2(15.9, 7.9)), Some((0, 0)));
+        assert_eq!(map.world_to_tile(vec2(16.0, 8.0)), Some((1, 1)));
+        assert_eq!(map.world_to_tile(vec2(63.9, 23.9)), Some((3, 2)));
+        assert_eq!(map.world_to_tile(vec2(64.0, 0.0)), None);
+        assert_eq!(map.world_to_tile(vec2(0.0, 24.0)), None);
+        assert_eq!(map.world_to_tile(vec2(-0.1, 0.0)), None);
+        assert_eq!(map.world_to_tile(vec2(0.0, -8.0)), None);
+        assert_eq!(map.world_to_tile(vec2(f32::NAN, 0.0)), None);
+
+        assert_eq!(map.tile_to_world(3, 2), vec2(48.0, 16.0));
+        assert_eq!(map.tile_rect(3, 2), Rect::new(48.0, 16.0, 16.0, 8.0));
+        for (x, y) in [(0, 0), (3, 0), (0, 2), (3, 2)] {
+            assert_eq!(
+                map.world_to_tile(map.tile_rect(x, y).center()),
+                Some((x, y))
+            );
+        }
+        assert_eq!(terrain_map(5, 2, 1).size_px(), (80.0, 32.0));
 
-        ensure_object_layer_stamp_invariant(&mut layer);
-        assert_eq!(layer.seen_stamp_tiles.len(), layer.objects.len());
-        assert_eq!(layer.seen_stamp_debug.len(), layer.objects.len());
+        // Far cells overflow `u32` pixel math; the world position must not wrap.
+        let huge = Map::new(u32::MAX, 2, 256, 256).expect("valid tile size");
+        let far = huge.tile_to_world(u32::MAX, 1);
+        assert_eq!(far, vec2(u32::MAX as f32 * 256.0, 256.0));
+        assert_eq!(huge.size_px(), (u32::MAX as f32 * 256.0, 512.0));
+    }
 
-        let stamp = 42;
-        let mut first_pass_drawn = 0usize;
-        for object_idx in 0..layer.objects.len() {
-            if layer.seen_stamp_tiles[object_idx] == stamp {
-                continue;
-            }
-            layer.seen_stamp_tiles[object_idx] = stamp;
-            first_pass_drawn += 1;
+    #[test]
+    fn tile_count_in_rect_counts_a_sub_rect_of_the_layer() {
+        // 1 . 1 .
+        // . 1 1 .
+        // . . 1 1
+        // 1 . . 1
+        let mut map = terrain_map(4, 4, 0);
+        for (x, y) in [
+            (0, 0),
+            (2, 0),
+            (1, 1),
+            (2, 1),
+            (2, 2),
+            (3, 2),
+            (0, 3),
+            (3, 3),
+        ] {
+            map.set_tile("ground", x, y, 1).expect("tile");
         }
+        assert_eq!(map.tile_count_in_rect("ground", 1, 0, 2, 2), 3);
+        assert_eq!(map.tile_count_in_rect("ground", 2, 2, 2, 2), 3);
+        assert_eq!(map.tile_count_in_rect("ground", 0, 2, 2, 2), 1);
+        assert_eq!(map.tile_count_in_rect("ground", 0, 0, 4, 4), 8);
+        assert_eq!(map.tile_count_in_rect("ground", 3, 3, 5, 5), 1);
+        assert_eq!(map.tile_count_in_rect("ground", 0, 0, 0, 4), 0);
+        assert_eq!(map.tile_count_in_rect("missing", 0, 0, 4, 4), 0);
+        assert_eq!(
+            map.tile_count_in_rect("ground", 0, 0, u32::MAX, u32::MAX),
+            8,
+            "huge rects walk the occupied chunks"
+        );
+    }
 
-        let mut second_pass_drawn = 0usize;
-        for object_idx in 0..layer.objects.len() {
-            if layer.seen_stamp_tiles[object_idx] == stamp {
-                continue;
-            }
-            layer.seen_stamp_tiles[object_idx] = stamp;
-            second_pass_drawn += 1;
+    #[test]
+    fn largest_empty_region_goes_around_an_l_shaped_obstacle() {
+        // . . # . . .
+        // . . # . . .
+        // . . # # # .
+        // . . . . . .
+        let mut map = terrain_map(6, 4, 0);
+        for (x, y) in [(2, 0), (2, 1), (2, 2), (3, 2), (4, 2)] {
+            map.set_tile("ground", x, y, 1).expect("obstacle");
         }
+        assert_eq!(
+            map.largest_contiguous_empty_region("ground"),
+            Some((0, 0, 2, 4))
+        );
 
-        assert_eq!(first_pass_drawn, 3);
-        assert_eq!(second_pass_drawn, 0);
+        map.set_tile("ground", 0, 3, 1).expect("obstacle");
+        map.set_tile("ground", 1, 3, 1).expect("obstacle");
+        // The left strip shrinks to 2x3 and ties the 3x2 block on the right, whose bottom
+        // row is scanned first.
+        assert_eq!(
+            map.largest_contiguous_empty_region("ground"),
+            Some((3, 0, 3, 2))
+        );
+
+        assert_eq!(
+            terrain_map(2, 2, 1).largest_contiguous_empty_region("ground"),
+            None
+        );
+        assert_eq!(map.largest_contiguous_empty_region("missing"), None);
     }
 
-    fn collect_draw_sequence_for_test(
-        map: &mut Map,
-        view_min: Vec2,
-        view_max: Vec2,
-    ) -> Vec<(char, LayerId, i32, i32, u32)> {
-        let coords = map.visible_coords_for_draw(view_min, view_max);
-        let stamp = map.next_frame_stamp();
-        let mut out = Vec::new();
+    #[test]
+    fn autotile_region_reshapes_edges_around_a_hole() {
+        // gid = mask + 1, so 16 is the fully connected tile.
+        let tiler = AutoTiler::from_bitmask_table(std::array::from_fn(|m| m as u32 + 1));
+        let mut map = terrain_map(5, 5, 16);
+        for (x, y) in [(2, 2), (3, 2), (2, 3)] {
+            map.set_tile("ground", x, y, 0).expect("dig");
+        }
+        map.autotile_region("ground", TileRegion::new(2, 2, 2, 2), &tiler)
+            .expect("autotile");
+        map.assert_consistent();
+
+        let data = map.tile_layer_as_flat_array("ground").expect("layer");
+        let at = |x: usize, y: usize| data[y * 5 + x];
+        let gid = |mask: u8| u32::from(mask) + 1;
+        assert_eq!(at(2, 2), 0);
+        assert_eq!(at(2, 1), gid(AUTOTILE_N | AUTOTILE_E | AUTOTILE_W));
+        assert_eq!(at(1, 2), gid(AUTOTILE_N | AUTOTILE_S | AUTOTILE_W));
+        assert_eq!(at(3, 3), gid(AUTOTILE_E | AUTOTILE_S));
+        assert_eq!(at(2, 4), gid(AUTOTILE_E | AUTOTILE_S | AUTOTILE_W));
+        assert_eq!(at(0, 0), gid(15));
+
+        // Refilling one cell and its neighbours through autotile_cell heals (2, 2)'s edges.
+        map.set_tile("ground", 2, 2, 16).expect("fill");
+        map.autotile_cell("ground", (2, 2), &tiler)
+            .expect("autotile");
+        let data = map.tile_layer_as_flat_array("ground").expect("layer");
+        assert_eq!(data[2 * 5 + 2], gid(AUTOTILE_N | AUTOTILE_W));
+        assert_eq!(data[5 + 2], gid(15));
+
+        // A gid no tileset owns fails the whole call before any cell changes.
+        let mut map = terrain_map(5, 5, 16);
+        map.set_tile("ground", 2, 2, 0).expect("dig");
+        let dug = map.tile_layer_as_flat_array("ground").expect("layer");
+        let mut table = std::array::from_fn(|m| m as u32 + 1);
+        table[usize::from(AUTOTILE_N | AUTOTILE_E | AUTOTILE_W)] = 999;
+        let err = map
+            .autotile_cell("ground", (2, 2), &AutoTiler::from_bitmask_table(table))
+            .expect_err("unowned gid");
+        assert!(
+            matches!(err, MapError::InvalidTileGid { gid: 999, .. }),
+            "{err:?}"
+        );
+        assert_eq!(map.tile_layer_as_flat_array("ground"), Some(dug));
+
+        // A 0 entry leaves the cell as it is instead of erasing it.
+        table[usize::from(AUTOTILE_N | AUTOTILE_E | AUTOTILE_W)] = 0;
+        map.autotile_cell("ground", (2, 2), &AutoTiler::from_bitmask_table(table))
+            .expect("autotile");
+        let data = map.tile_layer_as_flat_array("ground").expect("layer");
+        assert_eq!(data[5 + 2], 16);
+        assert_eq!(data[2 * 5 + 1], gid(AUTOTILE_N | AUTOTILE_S | AUTOTILE_W));
+    }
 
-        for i in 0..map.draw_order.len() {
-            let layer_id = map.draw_order[i];
-            let Some(kind) = map.layer_kind_by_id.get(&layer_id).copied() else {
-                continue;
-            };
+    #[test]
+    fn y_sort_mode_orders_base_layer_tiles_by_row_within_a_chunk() {
+        let mut map = terrain_map(2, 2, 1);
+        // Clearing (0, 0) swaps (1, 1) into its slot; refilling appends (0, 0) at the end.
+        map.set_tile("ground", 0, 0, 0).expect("clear");
+        map.set_tile("ground", 0, 0, 2).expect("refill");
+        let rows = |map: &Map| -> Vec<f32> {
+            map.collect_draw_commands(vec2(0.0, 0.0), vec2(32.0, 32.0))
+                .iter()
+                .map(|cmd| cmd.dest.y)
+                .collect()
+        };
+        assert_eq!(rows(&map), vec![16.0, 0.0, 16.0, 0.0]);
 
-            match kind {
-                LayerKindInfo::Tiles(tile_layer_idx) => {
-                    let Some(layer) = map.tile_layers.get(tile_layer_idx) else {
-                        continue;
-                    };
-                    if !layer.visible {
-                        continue;
-                    }
+        let base_layer = map.layer_idx("ground").expect("layer");
+        map.set_render_mode(ChunkRenderMode::YSort { base_layer });
+        assert_eq!(rows(&map), vec![0.0, 0.0, 16.0, 16.0]);
+        map.set_render_mode(ChunkRenderMode::Standard);
+        assert_eq!(rows(&map), vec![16.0, 0.0, 16.0, 0.0]);
+    }
 
-                    Map::for_each_visible_layer_bucket(
-                        &map.index,
-                        &coords,
-                        layer.layer_id,
-                        |cc, bucket| {
-                            for rec in &bucket.tiles {
-                                out.push(('T', layer_id, cc.x, cc.y, rec.id.clean()));
-                            }
-                        },
-                    );
-                }
-                LayerKindInfo::Objects(object_layer_idx) => {
-                    let Some(layer) = map.object_layers.get_mut(object_layer_idx) else {
-                        continue;
-                    };
-                    ensure_object_layer_stamp_invariant(layer);
-                    if !layer.visible {
-                        continue;
-                    }
-                    let bucket_layer = layer.bucket_layer;
+    #[test]
+    fn layer_opacity_override_sets_tint_alpha_and_skips_at_zero() {
+        let mut map = terrain_map(2, 1, 1);
+        let view = (vec2(0.0, 0.0), vec2(32.0, 16.0));
+        assert_eq!(map.layer_opacity("ground"), Some(1.0));
+
+        map.set_layer_opacity("ground", 0.25).expect("known layer");
+        assert_eq!(map.layer_opacity("ground"), Some(0.25));
+        let cmds = map.collect_draw_commands(view.0, view.1);
+        assert_eq!(cmds.len(), 2);
+        assert!(cmds
+            .iter()
+            .all(|c| c.tint == Color::new(1.0, 1.0, 1.0, 0.25)));
 
-                    Map::for_each_visible_layer_bucket(
-                        &map.index,
-                        &coords,
-                        bucket_layer,
-                        |cc, bucket| {
-                            for rec in &bucket.objects {
-                                let object_idx = rec.handle.0 as usize;
-                                if object_idx >= layer.objects.len() {
-                                    continue;
-                                }
-                                if layer.seen_stamp_tiles[object_idx] == stamp {
-                                    continue;
-                                }
-                                layer.seen_stamp_tiles[object_idx] = stamp;
+        map.set_layer_opacity("ground", 3.0).expect("known layer");
+        assert_eq!(map.layer_opacity("ground"), Some(1.0));
+
+        // Opacity multiplies the tintcolor alpha; a per-call tint multiplies on top.
+        map.tile_layers[0].tint = Color::new(1.0, 0.5, 0.25, 0.5);
+        map.set_layer_opacity("ground", 0.5).expect("known layer");
+        let cmds = map.collect_draw_commands(view.0, view.1);
+        assert!(cmds
+            .iter()
+            .all(|c| c.tint == Color::new(1.0, 0.5, 0.25, 0.25)));
+        let params = DrawParams {
+            tint: Color::new(0.5, 1.0, 1.0, 0.5),
+            ..DrawParams::default()
+        };
+        assert_eq!(
+            cmds[0].transformed(&params).tint,
+            Color::new(0.5, 0.5, 0.25, 0.125)
+        );
+
+        map.set_layer_opacity("ground", 0.0).expect("known layer");
+        assert!(map.collect_draw_commands(view.0, view.1).is_empty());
+        map.set_layer_opacity("ground", f32::NAN)
+            .expect("known layer");
+        assert_eq!(map.layer_opacity("ground"), Some(0.0));
+        assert!(map.collect_draw_commands(view.0, view.1).is_empty());
+        assert_eq!(map.layer_opacity("missing"), None);
+        assert!(matches!(
+            map.set_layer_opacity("missing", 0.5),
+            Err(MapError::LayerNotFound(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn count_tiles_by_gid_groups_clean_gids_per_layer_and_overall() {
+        let grid = |name: &str, data: Vec<u32>| IrLayer {
+            name: name.to_string(),
+            visible: true,
+            opacity: 1.0,
+            tint: WHITE,
+            offset: Vec2::ZERO,
+            properties: Properties::default(),
+            kind: IrLayerKind::Tiles {
+                width: 3,
+                height: 2,
+                data,
+            },
+        };
+        let mut ir = load_fixture_ir("two_tilesets_map.json");
+        ir.layers = vec![
+            grid("ground", vec![1, 1, 2, 1, 0, 2 | 0x8000_0000]),
+            grid("top", vec![0, 0, 0, 0, 3, 1]),
+        ];
+        let map = map_from_ir_headless(ir);
+
+        assert_eq!(
+            map.count_tiles_by_gid("ground"),
+            HashMap::from([(1, 3), (2, 2)])
+        );
+        assert_eq!(
+            map.count_tiles_by_gid_all_layers(),
+            HashMap::from([(1, 4), (2, 2), (3, 1)])
+        );
+        assert!(map.count_tiles_by_gid("missing").is_empty());
+    }
+
+    #[test]
+    fn draw_commands_resolve_each_gid_to_its_own_tileset() {
+        let mut map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+        // Last tile of "props": local id 3 sits in column 1, row 1.
+        map.set_tile("ground", 1, 0, 8).expect("props gid");
+        let cmds = map.collect_draw_commands(vec2(0.0, 0.0), vec2(32.0, 16.0));
+        let by_gid = |gid: u32| cmds.iter().find(|c| c.gid == gid).expect("command");
+
+        assert_eq!(by_gid(1).tileset, 0);
+        assert_eq!(by_gid(1).source, Rect::new(0.0, 0.0, 16.0, 16.0));
+        assert_eq!(by_gid(8).tileset, 1);
+        assert_eq!(by_gid(8).source, Rect::new(16.0, 16.0, 16.0, 16.0));
+    }
 
-                                let Some(obj) = layer.objects.get(object_idx) else {
-                                    continue;
-                                };
-                                if !obj.visible {
-                                    continue;
-                                }
-                                if !matches!(obj.shape, IrObjectShape::Tile { .. }) {
-                                    continue;
-                                }
-                                out.push(('O', layer_id, cc.x, cc.y, obj.id));
-                            }
-                        },
-                    );
+    #[test]
+    fn source_rect_honours_margin_and_spacing_at_atlas_edges() {
+        // 3x3 atlas of 16px tiles, 1px margin and 2px spacing: 54px square image.
+        let ts = TilesetInfo {
+            name: String::new(),
+            first_gid: 1,
+            source: String::new(),
+            image: String::new(),
+            tilecount: 9,
+            cols: 3,
+            tex: None,
+            tile_w: 16,
+            tile_h: 16,
+            spacing: 2,
+            margin: 1,
+        };
+        assert_eq!(ts.source_rect(0), Rect::new(1.0, 1.0, 16.0, 16.0));
+        assert_eq!(ts.source_rect(2), Rect::new(37.0, 1.0, 16.0, 16.0));
+        assert_eq!(ts.source_rect(6), Rect::new(1.0, 37.0, 16.0, 16.0));
+        let last = ts.source_rect(8);
+        assert_eq!(last, Rect::new(37.0, 37.0, 16.0, 16.0));
+        assert_eq!(last.right() + ts.margin as f32, 54.0);
+    }
+
+    #[test]
+    fn precomputed_source_rects_match_the_atlas_formula() {
+        let formula = |ts: &TilesetInfo, local: u32| {
+            let (col, row) = (local % ts.cols, local / ts.cols);
+            Rect::new(
+                (ts.margin + col * (ts.tile_w + ts.spacing)) as f32,
+                (ts.margin + row * (ts.tile_h + ts.spacing)) as f32,
+                ts.tile_w as f32,
+                ts.tile_h as f32,
+            )
+        };
+        let check = |map: &Map| {
+            assert!(!map.tilesets.is_empty());
+            for ts in &map.tilesets {
+                for local in 0..ts.tilecount {
+                    let gid = TileId((ts.first_gid + local) | crate::spatial::FLIP_D);
+                    let (owner, rect) = map.tile_source(gid).expect("gid has a tileset");
+                    assert_eq!(owner.first_gid, ts.first_gid);
+                    assert_eq!(rect, formula(ts, local), "{} local {local}", ts.name);
                 }
-                LayerKindInfo::Unsupported => {}
             }
-        }
+        };
 
-        out
+        let mut built = Map::new(4, 4, 16, 16).expect("valid tile size");
+        built
+            .add_tileset(code_tileset("plain", 1, 8))
+            .expect("plain tileset");
+        built
+            .add_tileset(TilesetInfo {
+                spacing: 2,
+                margin: 1,
+                cols: 3,
+                ..code_tileset("padded", 9, 9)
+            })
+            .expect("padded tileset");
+        check(&built);
+
+        let mut ir = load_fixture_ir("two_tilesets_map.json");
+        let IrTileset::Atlas {
+            spacing, margin, ..
+        } = &mut ir.tilesets[1];
+        (*spacing, *margin) = (3, 2);
+        check(&map_from_ir_headless(ir));
     }
 
     #[test]
-    fn draw_sequence_is_deterministic_across_runs() {
-        let mut index = GlobalIndex::new();
-        index.add_tile(TileId(1), 0, vec2(10.0, 10.0));
-        index.add_tile(TileId(1), 0, vec2((CHUNK_SIZE + 10) as f32, 10.0));
-        index.add_tile(TileId(1), 2, vec2(20.0, (CHUNK_SIZE + 10) as f32));
+    fn atlas_placement_moves_draw_sources_of_that_tileset_only() {
+        let mut map = map_from_ir_headless(load_fixture_ir("two_tilesets_map.json"));
+        map.place_in_atlas(1, (40, 8));
 
-        let objects = vec![IrObject {
-            id: 77,
-            name: "coin".to_string(),
-            class_name: String::new(),
-            x: (CHUNK_SIZE - 8) as f32,
-            y: 32.0,
-            width: 16.0,
-            height: 16.0,
-            rotation: 0.0,
-            visible: true,
-            shape: IrObjectShape::Tile { gid: 1 },
-            properties: Properties::default(),
-        }];
+        let sources: Vec<(u32, Rect)> = map
+            .collect_draw_commands(Vec2::ZERO, vec2(32.0, 16.0))
+            .iter()
+            .map(|cmd| (cmd.gid, cmd.source))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                (1, Rect::new(0.0, 0.0, 16.0, 16.0)),
+                (5, Rect::new(40.0, 8.0, 16.0, 16.0)),
+            ]
+        );
+        let (_, last) = map.tile_source(TileId(8)).expect("gid 8");
+        assert_eq!(last, Rect::new(56.0, 24.0, 16.0, 16.0));
+        assert_eq!(
+            map.tilesets[1].source_rect(3),
+            Rect::new(16.0, 16.0, 16.0, 16.0)
+        );
+    }
 
-        // Same object appears in multiple chunks (as with AABB multi-chunk insertion).
-        index.insert_object(
-            1,
-            crate::spatial::ChunkCoord { x: 0, y: 0 },
-            crate::spatial::ObjectRec {
-                handle: crate::spatial::ObjectHandle(0),
-                rel_pos: vec2((CHUNK_SIZE - 8) as f32, 32.0),
-            },
+    #[test]
+    fn build_draw_commands_snapshot_for_two_layers_and_a_region() {
+        let mut ir = load_fixture_ir("two_tilesets_map.json");
+        let mut top = tile_ir_layer("top", true);
+        top.kind = IrLayerKind::Tiles {
+            width: 2,
+            height: 1,
+            data: vec![0, 6 | 0x8000_0000],
+        };
+        ir.layers.push(top);
+        let map = map_from_ir_headless(ir);
+
+        let snapshot = |cmds: Vec<DrawCommand>| -> Vec<(LayerId, usize, u32, Vec2, bool)> {
+            cmds.into_iter()
+                .map(|c| (c.layer, c.tileset, c.gid, c.dest, c.flip_x))
+                .collect()
+        };
+        assert_eq!(
+            snapshot(map.build_draw_commands(None)),
+            vec![
+                (0, 0, 1, vec2(0.0, 0.0), false),
+                (0, 1, 5, vec2(16.0, 0.0), false),
+                (1, 1, 6 | 0x8000_0000, vec2(16.0, 0.0), true),
+            ]
         );
-        index.insert_object(
-            1,
-            crate::spatial::ChunkCoord { x: 1, y: 0 },
-            crate::spatial::ObjectRec {
-                handle: crate::spatial::ObjectHandle(0),
-                rel_pos: vec2(0.0, 32.0),
-            },
+        assert_eq!(
+            snapshot(map.build_draw_commands(Some(TileRegion::new(1, 0, 1, 1)))),
+            vec![
+                (0, 1, 5, vec2(16.0, 0.0), false),
+                (1, 1, 6 | 0x8000_0000, vec2(16.0, 0.0), true),
+            ]
         );
+        assert!(map
+            .build_draw_commands(Some(TileRegion::new(2, 0, 4, 4)))
+            .is_empty());
+    }
 
-        let mut map = Map {
-            index,
-            tilesets: vec![],
-            object_layers: vec![ObjectLayer {
-                id: 1,
-                name: "objects".to_string(),
-                visible: true,
-                opacity: 1.0,
-                offset: Vec2::ZERO,
-                properties: Properties::default(),
-                objects,
-                bucket_layer: 1,
-                seen_stamp_tiles: vec![0],
-                seen_stamp_debug: vec![0],
-            }],
-            renderer: MapRenderer {
-                debug_draw: false,
-                cull_padding: CHUNK_SIZE as f32,
-                frame_stamp: 0,
-            },
-            gid_lut: vec![],
-            tile_layers: vec![
-                TileLayerDrawInfo {
-                    layer_id: 0,
-                    visible: true,
-                    opacity: 1.0,
-                },
-                TileLayerDrawInfo {
-                    layer_id: 2,
-                    visible: true,
-                    opacity: 1.0,
-                },
-            ],
-            draw_order: vec![0, 1, 2],
-            layer_kind_by_id: {
-                let mut m = HashMap::new();
-                m.insert(0, LayerKindInfo::Tiles(0));
-                m.insert(1, LayerKindInfo::Objects(0));
-                m.insert(2, LayerKindInfo::Tiles(1));
-                m
-            },
+    #[test]
+    fn region_commands_visit_exactly_the_cells_inside_the_map() {
+        let map = terrain_map(4, 3, 1);
+        let cells = |region: TileRegion| -> Vec<(u32, u32)> {
+            map.build_draw_commands(Some(region))
+                .iter()
+                .map(|c| ((c.dest.x / 16.0) as u32, (c.dest.y / 16.0) as u32))
+                .collect()
         };
+        assert_eq!(
+            cells(TileRegion::new(1, 1, 2, 1)),
+            vec![(1, 1), (2, 1)],
+            "interior"
+        );
+        assert_eq!(
+            cells(TileRegion::new(2, 1, 10, 10)),
+            vec![(2, 1), (3, 1), (2, 2), (3, 2)],
+            "straddling the bottom-right edge"
+        );
+        assert!(cells(TileRegion::new(4, 0, 2, 2)).is_empty(), "outside");
+        assert!(cells(TileRegion::new(u32::MAX - 1, 0, u32::MAX, 1)).is_empty());
+        assert!(
+            cells(TileRegion::new(0, 0, 0, 3)).is_empty(),
+            "empty region"
+        );
+    }
 
-        let seq1 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(520.0, 520.0));
-        let seq2 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(520.0, 520.0));
-        assert_eq!(seq1, seq2);
+    // The commands the single-layer draws would draw, collected for inspection.
+    impl Map {
+        fn flipped_layer_commands(
+            &self,
+            layer_name: &str,
+            view_min: Vec2,
+            view_max: Vec2,
+            flip_h: bool,
+            flip_v: bool,
+        ) -> Vec<DrawCommand> {
+            let mut out = Vec::new();
+            let push = |cmd| out.push(cmd);
+            self.for_each_flipped_command(layer_name, view_min, view_max, flip_h, flip_v, push);
+            out
+        }
+
+        fn column_sorted_commands(
+            &self,
+            layer_name: &str,
+            view_min: Vec2,
+            view_max: Vec2,
+        ) -> Vec<DrawCommand> {
+            let mut out = Vec::new();
+            self.for_each_column_sorted_command(layer_name, view_min, view_max, |cmd| {
+                out.push(cmd)
+            });
+            out
+        }
+
+        fn layer_region_commands(
+            &self,
+            layer_name: &str,
+            col: u32,
+            row: u32,
+            w: u32,
+            h: u32,
+            dest: Vec2,
+        ) -> Vec<DrawCommand> {
+            let mut out = Vec::new();
+            let region = TileRegion::new(col, row, w, h);
+            self.for_each_layer_region_command(layer_name, region, dest, |cmd| out.push(cmd));
+            out
+        }
     }
 
     #[test]
-    fn fixture_layer_ordering_matches_tiled_order() {
-        let ir = load_fixture_ir("external_props_map.json");
-        let (draw_order, kinds) = build_draw_order_and_kind(&ir.layers);
-        assert_eq!(draw_order, vec![0, 1, 2]);
-        assert!(matches!(kinds.get(&0), Some(LayerKindInfo::Tiles(0))));
-        assert!(matches!(kinds.get(&1), Some(LayerKindInfo::Objects(0))));
-        assert!(matches!(kinds.get(&2), Some(LayerKindInfo::Tiles(1))));
+    fn flipped_layers_mirror_positions_and_toggle_flip_flags() {
+        // 640x32 px, so mirrored tiles land in another chunk than the originals.
+        let mut map = terrain_map(40, 2, 0);
+        let mut data = vec![0; 80];
+        data[0] = 1; // (0, 0)
+        data[42] = 2 | FLIP_H; // (2, 1)
+        map.add_layer("top", data).expect("layer");
+        let placed = |min: Vec2, max: Vec2, flip_h, flip_v| -> Vec<(u32, Vec2)> {
+            let mut cmds = map.flipped_layer_commands("top", min, max, flip_h, flip_v);
+            cmds.sort_by_key(|c| c.gid & GID_MASK);
+            cmds.iter().map(|c| (c.gid, c.dest)).collect()
+        };
+        let (left, right) = (
+            (Vec2::ZERO, vec2(100.0, 32.0)),
+            (vec2(540.0, 0.0), vec2(640.0, 32.0)),
+        );
+
+        assert_eq!(
+            placed(left.0, left.1, false, false),
+            vec![(1, vec2(0.0, 0.0)), (2 | FLIP_H, vec2(32.0, 16.0))]
+        );
+        assert_eq!(
+            placed(right.0, right.1, true, false),
+            vec![(1 | FLIP_H, vec2(624.0, 0.0)), (2, vec2(592.0, 16.0))]
+        );
+        assert_eq!(
+            placed(right.0, right.1, true, true),
+            vec![
+                (1 | FLIP_H | FLIP_V, vec2(624.0, 16.0)),
+                (2 | FLIP_V, vec2(592.0, 0.0))
+            ]
+        );
+        // Culling happens in the mirrored world.
+        assert!(placed(right.0, right.1, false, false).is_empty());
+        assert!(placed(left.0, left.1, true, false).is_empty());
+        assert!(map
+            .flipped_layer_commands("missing", left.0, left.1, true, false)
+            .is_empty());
     }
 
     #[test]
-    fn fixture_object_spans_multiple_chunks() {
-        let ir = load_fixture_ir("multichunk_objects_map.json");
-        let object_layer = ir
-            .layers
+    fn column_sorted_tiles_go_column_by_column_across_chunks() {
+        // 20 columns span two chunks; each cell's gid is its position in `cells` plus one.
+        let mut map = terrain_map(20, 2, 0);
+        let cells = [(17u32, 1u32), (0, 1), (15, 0), (17, 0), (0, 0), (16, 1)];
+        let mut data = vec![0; 40];
+        for (gid, (col, row)) in (1..).zip(cells) {
+            data[(row * 20 + col) as usize] = gid;
+        }
+        map.add_layer("props", data).expect("layer");
+
+        let order: Vec<(u32, u32)> = map
+            .column_sorted_commands("props", Vec2::ZERO, vec2(320.0, 32.0))
             .iter()
-            .find(|l| matches!(l.kind, IrLayerKind::Objects { .. }))
-            .expect("object layer exists");
-        let IrLayerKind::Objects { ref objects } = object_layer.kind else {
-            panic!("expected object layer");
-        };
-        let obj = &objects[0];
-        let (chunk_min, chunk_max) = Map::object_chunk_span(obj, object_layer.offset);
-        assert_eq!(chunk_min.x, 0);
-        assert_eq!(chunk_max.x, 1);
-        assert_eq!(chunk_min.y, 0);
-        assert_eq!(chunk_max.y, 0);
+            .map(|c| cells[c.gid as usize - 1])
+            .collect();
+        assert_eq!(order, [(0, 0), (0, 1), (15, 0), (16, 1), (17, 0), (17, 1)]);
+        assert!(map
+            .column_sorted_commands("missing", Vec2::ZERO, vec2(320.0, 32.0))
+            .is_empty());
+
+        // Shifted left of the origin, columns 0 and 1 land at world columns -3 and -2.
+        let mut data = vec![0; 40];
+        data[20] = 1; // (0, 1)
+        data[1] = 2; // (1, 0)
+        map.add_layer("left", data).expect("layer");
+        map.set_layer_offset("left", vec2(-48.0, 0.0))
+            .expect("known layer");
+        let order: Vec<u32> = map
+            .column_sorted_commands("left", vec2(-64.0, 0.0), vec2(320.0, 32.0))
+            .iter()
+            .map(|c| c.gid)
+            .collect();
+        assert_eq!(order, [1, 2]);
     }
 
     #[test]
-    fn multi_chunk_object_reconstructs_same_world_pos_from_any_bucket() {
-        let world = vec2(591.5974, 604.84875);
-        let rel_home = crate::spatial::rel(world);
-        let chunk_home = world_to_chunk(world);
-        assert_eq!(chunk_home.x, 2);
-        assert_eq!(chunk_home.y, 2);
+    fn layer_regions_land_at_dest_and_skip_other_layers() {
+        let mut map = terrain_map(4, 3, 1);
+        map.add_layer("top", vec![2; 12]).expect("layer");
+        let cmds = map.layer_region_commands("top", 1, 1, 2, 5, vec2(100.0, 50.0));
+        let placed: Vec<_> = cmds.iter().map(|c| (c.gid, c.dest)).collect();
+        assert_eq!(
+            placed,
+            vec![
+                (2, vec2(100.0, 50.0)),
+                (2, vec2(116.0, 50.0)),
+                (2, vec2(100.0, 66.0)),
+                (2, vec2(116.0, 66.0)),
+            ]
+        );
+        assert!(map
+            .layer_region_commands("missing", 0, 0, 4, 3, Vec2::ZERO)
+            .is_empty());
+    }
 
-        // Simulate a tall object spanning into the chunk above.
-        let cc_other = crate::spatial::ChunkCoord { x: 2, y: 1 };
-        let wrong_origin = vec2(
-            (cc_other.x * CHUNK_SIZE) as f32,
-            (cc_other.y * CHUNK_SIZE) as f32,
-        ) + rel_home;
-        assert_ne!(wrong_origin.y, world.y);
+    #[test]
+    fn flip_params_reproduce_tiled_transforms_for_every_flag_combination() {
+        // Where a texel offset from the tile center ends up, per Tiled's definition.
+        let tiled = |h: bool, v: bool, d: bool, p: Vec2| {
+            let p = if d { vec2(p.y, p.x) } else { p };
+            vec2(if h { -p.x } else { p.x }, if v { -p.y } else { p.y })
+        };
+        // Same, per macroquad: flip the texture, then rotate clockwise.
+        let drawn = |(rotation, flip_x, flip_y, _): (f32, bool, bool, Option<Vec2>), p: Vec2| {
+            let p = vec2(
+                if flip_x { -p.x } else { p.x },
+                if flip_y { -p.y } else { p.y },
+            );
+            Vec2::from_angle(rotation).rotate(p)
+        };
 
-        let correct_rel = world
-            - vec2(
-                (cc_other.x * CHUNK_SIZE) as f32,
-                (cc_other.y * CHUNK_SIZE) as f32,
+        let texel = vec2(3.0, 1.0);
+        for bits in 0..8u32 {
+            let (h, v, d) = (bits & 4 != 0, bits & 2 != 0, bits & 1 != 0);
+            let gid = TileId(
+                2 | if h { 0x8000_0000 } else { 0 }
+                    | if v { 0x4000_0000 } else { 0 }
+                    | if d { 0x2000_0000 } else { 0 },
             );
-        let rebuilt = vec2(
-            (cc_other.x * CHUNK_SIZE) as f32,
-            (cc_other.y * CHUNK_SIZE) as f32,
-        ) + correct_rel;
-        assert_eq!(rebuilt, world);
+            assert_eq!(gid.clean(), 2);
+            let params = Map::params_for_flips_gid(gid);
+            assert_eq!(params.3, None);
+            let got = drawn(params, texel);
+            assert!(
+                got.abs_diff_eq(tiled(h, v, d, texel), 1e-4),
+                "flags h={h} v={v} d={d}: {got:?}"
+            );
+        }
+
+        // A plain horizontal flip is just a mirror, no rotation.
+        assert_eq!(
+            Map::params_for_flips_gid(TileId(2 | 0x8000_0000)),
+            (0.0, true, false, None)
+        );
     }
 
     #[test]
-    fn fixture_culling_returns_expected_chunks() {
-        let ir = load_fixture_ir("minimal_finite_map.json");
-        let mut index = GlobalIndex::new();
-        let tw = ir.tile_w as f32;
-        let th = ir.tile_h as f32;
+    fn tileset_image_paths_match_with_and_without_a_loaded_map() {
+        let map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+        assert_eq!(
+            map.tileset_image_paths(),
+            vec!["basic_tiles.png", "props_tiles.png"]
+        );
 
-        for (lz, layer) in ir.layers.iter().enumerate() {
-            let IrLayerKind::Tiles { width, data, .. } = &layer.kind else {
-                continue;
+        let path = fixture_path("two_tilesets_map.json");
+        let from_file =
+            Map::tileset_image_paths_from_file(path.to_str().expect("utf8 path")).expect("decode");
+        assert_eq!(from_file, map.tileset_image_paths());
+        assert!(Map::tileset_image_paths_from_file("missing.json").is_err());
+    }
+
+    #[test]
+    fn sync_headless_loads_match_from_file_and_from_str() {
+        let path = fixture_path("two_tilesets_map.json");
+        let from_file = Map::load_from_file(path.to_str().expect("utf8 path")).expect("file load");
+        from_file.assert_consistent();
+        assert!(from_file.tilesets.iter().all(|ts| ts.tex.is_none()));
+
+        let json = std::fs::read_to_string(&path).expect("read fixture");
+        let from_str =
+            Map::load_from_str(&json, path.parent().expect("fixture dir")).expect("str load");
+        assert_eq!(
+            from_str.tile_layer_as_flat_array("ground"),
+            from_file.tile_layer_as_flat_array("ground")
+        );
+        assert_eq!(
+            from_str.tileset_image_paths(),
+            from_file.tileset_image_paths()
+        );
+
+        assert!(matches!(
+            Map::load_from_str("{ not json", "."),
+            Err(MapError::Json { .. })
+        ));
+        assert!(matches!(
+            Map::load_from_str(&json, "/nonexistent"),
+            Err(MapError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn layers_hidden_in_tiled_can_be_shown_at_runtime() {
+        let mut ir = load_fixture_ir("two_tilesets_map.json");
+        ir.layers.push(tile_ir_layer("secret", false));
+        let mut map = map_from_ir_headless(ir);
+        let view = (vec2(0.0, 0.0), vec2(32.0, 16.0));
+        let secret_cmds = |map: &Map| {
+            let secret = map.layer_idx("secret").expect("layer") as LayerId;
+            map.collect_draw_commands(view.0, view.1)
+                .iter()
+                .filter(|c| c.layer == secret)
+                .count()
+        };
+
+        assert_eq!(map.layer_visible("secret"), Some(false));
+        assert_eq!(secret_cmds(&map), 0);
+        map.set_layer_visible("secret", true).expect("known layer");
+        assert_eq!(map.layer_visible("secret"), Some(true));
+        assert_eq!(secret_cmds(&map), 1);
+        map.set_layer_visible("secret", false).expect("known layer");
+        assert_eq!(secret_cmds(&map), 0);
+        assert_eq!(map.layer_visible("missing"), None);
+        assert!(matches!(
+            map.set_layer_visible("missing", true),
+            Err(MapError::LayerNotFound(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn texture_geometry_mismatches_follow_strictness_and_collection() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static SEEN: AtomicUsize = AtomicUsize::new(0);
+
+        // 32 declared tiles, but a 32x32 image holds only four.
+        let ts = terrain_tileset();
+        let check = |options: LoadOptions| {
+            let options = LoadOptions {
+                on_warning: Some(|_| {
+                    SEEN.fetch_add(1, Ordering::Relaxed);
+                }),
+                ..options
             };
-            for (idx, gid) in data.iter().enumerate() {
-                if *gid == 0 {
-                    continue;
-                }
-                let col = idx % *width;
-                let row = idx / *width;
-                index.add_tile(
-                    TileId(*gid),
-                    lz as LayerIdx,
-                    vec2(col as f32 * tw, row as f32 * th) + layer.offset,
-                );
-            }
-        }
+            texture_geometry_warnings(&ts, (32, 32), Path::new("maps"), &options)
+        };
+        let lenient = || LoadOptions::default().with_strictness(Strictness::Lenient);
 
-        let near = query_visible_rect(&index, vec2(0.0, 0.0), vec2(40.0, 20.0));
-        assert!(near.chunks.iter().any(|c| c.coord.x == 0 && c.coord.y == 0));
+        let warnings = check(lenient()).expect("tolerated when lenient");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, IssueCode::TilesetGeometryMismatch);
+        assert_eq!(warnings[0].location, IssueLocation::Tileset(String::new()));
+        assert_eq!(SEEN.load(Ordering::Relaxed), 1, "on_warning sees it once");
+        let collected = check(lenient().with_all_errors_collected()).expect("still a warning");
+        assert_eq!(collected, warnings);
 
-        let far = query_visible_rect(&index, vec2(2000.0, 2000.0), vec2(2100.0, 2100.0));
-        assert!(far.chunks.is_empty());
+        assert!(matches!(
+            check(LoadOptions::default()),
+            Err(MapError::TilesetGeometryMismatch { .. })
+        ));
+        let Err(MapError::Validation { report, .. }) =
+            check(LoadOptions::default().with_all_errors_collected())
+        else {
+            panic!("a collected mismatch fails as a report");
+        };
+        assert_eq!(report.errors().count(), 1);
+        let fits = texture_geometry_warnings(&ts, (128, 64), Path::new("maps"), &lenient());
+        assert_eq!(fits.ok(), Some(vec![]), "fits exactly");
     }
 
     #[test]
-    fn stamp_overflow_does_not_break_dedupe() {
-        use std::collections::HashSet;
+    fn texture_load_errors_clamp_gid_ranges_at_the_top() {
+        let gids = |first_gid, tilecount| match texture_load_error(
+            Path::new("t.png"),
+            "t",
+            first_gid,
+            tilecount,
+            "gone",
+        ) {
+            MapError::TextureLoad { gids, .. } => gids,
+            other => panic!("expected TextureLoad, got {other:?}"),
+        };
+        assert_eq!(gids(5, 4), 5..=8);
+        // A crafted firstgid near the top must not overflow.
+        assert_eq!(gids(u32::MAX - 1, 5), u32::MAX - 1..=u32::MAX);
+        assert_eq!(gids(u32::MAX, 1), u32::MAX..=u32::MAX);
+    }
 
-        let mut map = Map::__new_for_stamp_overflow_test(3);
-        map.__set_frame_stamp_for_testing(u32::MAX - 1);
+    #[test]
+    fn replacing_an_unknown_tileset_texture_leaves_tilesets_untouched() {
+        use macroquad::miniquad::{RawId, TextureId};
+
+        let mut map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+        // A raw handle needs no GL context as long as it is never bound.
+        let tex = Texture2D::from_miniquad_texture(TextureId::from_raw_id(RawId::OpenGl(1)));
+        assert!(!map.replace_tileset_texture("missing", tex));
+        assert!(map.tilesets.iter().all(|ts| ts.tex.is_none()));
+    }
 
-        let seq1 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
-        let seq2 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
-        let seq3 = collect_draw_sequence_for_test(&mut map, Vec2::ZERO, vec2(64.0, 64.0));
+    #[test]
+    fn atlas_placement_offsets_commands_and_caller_textures_get_own_rects() {
+        use macroquad::miniquad::{RawId, TextureId};
+
+        let mut map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+        let view = (Vec2::ZERO, vec2(64.0, 64.0));
+        let before = map.collect_draw_commands(view.0, view.1);
+        let own: Vec<Rect> = before.iter().map(|c| c.source).collect();
+
+        // What `build_atlas` does per tileset once the layout is known.
+        map.place_in_atlas(1, (64, 32));
+        let after = map.collect_draw_commands(view.0, view.1);
+        let at = |cmds: &[DrawCommand], ts| cmds.iter().find(|c| c.tileset == ts).unwrap().clone();
+        assert_eq!(at(&after, 0).source, at(&before, 0).source);
+        assert_eq!(
+            at(&after, 1).source,
+            at(&before, 1).source.offset(vec2(64.0, 32.0))
+        );
 
-        assert_eq!(seq1, seq2);
-        assert_eq!(seq2, seq3);
-        assert_eq!(seq1.len(), 3);
+        let mapped: Vec<Rect> = after.iter().map(|c| map.own_image_source(c)).collect();
+        assert_eq!(mapped, own);
+        let custom = DrawCommand {
+            source: Rect::new(1.0, 2.0, 3.0, 4.0),
+            ..at(&after, 1)
+        };
+        assert_eq!(map.own_image_source(&custom), custom.source);
 
-        let uniq: HashSet<_> = seq1.iter().copied().collect();
-        assert_eq!(uniq.len(), 3);
+        // Replacing the texture takes the tileset out of the atlas again.
+        let name = map.tilesets[1].name.clone();
+        let tex = Texture2D::from_miniquad_texture(TextureId::from_raw_id(RawId::OpenGl(7)));
+        map.swap_tileset_texture(&name, tex);
+        assert_eq!(map.collect_draw_commands(view.0, view.1), before);
+    }
 
-        let layer = &map.object_layers[0];
-        assert_eq!(layer.seen_stamp_tiles.len(), layer.objects.len());
-        assert_eq!(layer.seen_stamp_debug.len(), layer.objects.len());
+    #[test]
+    fn replacing_a_tileset_texture_installs_it_and_resets_source_rects() {
+        use macroquad::miniquad::{RawId, TextureId};
+
+        let mut map = load_fixture_headless("two_tilesets_map.json", LoadOptions::default());
+        let name = map.tilesets[1].name.clone();
+        let own = map.tilesets[1].source_rects();
+        map.source_rects[1] = own.iter().map(|r| r.offset(vec2(64.0, 32.0))).collect();
+
+        // `replace_tileset_texture` only adds the filter change, which needs GL.
+        let tex = Texture2D::from_miniquad_texture(TextureId::from_raw_id(RawId::OpenGl(7)));
+        assert_eq!(map.swap_tileset_texture(&name, tex.clone()), Some(1));
+        assert_eq!(map.tilesets[1].tex, Some(tex));
+        assert!(map.tilesets[0].tex.is_none());
+        assert_eq!(map.source_rects[1], own);
     }
 }
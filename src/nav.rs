@@ -0,0 +1,133 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// Decides which map cells block movement for navigation queries.
+///
+/// A cell is blocked if any layer in `blocking_layers` has a tile there, or if any tile
+/// layer has a tile there whose class is listed in `blocking_classes`.
+#[derive(Clone, Debug, Default)]
+pub struct NavOptions {
+    /// Tile layer names whose non-empty cells block movement.
+    pub blocking_layers: Vec<String>,
+    /// Tile classes that block movement on every tile layer.
+    pub blocking_classes: Vec<String>,
+}
+
+/// Traces the outlines of walkable (unblocked) regions of a `width x height` cell mask.
+///
+/// Returns closed contours in cell-corner coordinates without a repeated end point.
+/// Outer contours have positive signed area in y-down coordinates (clockwise on screen)
+/// and holes negative. Regions touching only diagonally are traced separately, and
+/// collinear points are dropped.
+pub(crate) fn trace_walkable_contours(
+    width: usize,
+    height: usize,
+    blocked: &[bool],
+) -> Vec<Vec<IVec2>> {
+    debug_assert_eq!(blocked.len(), width * height);
+    let walkable = |c: i32, r: i32| {
+        c >= 0
+            && r >= 0
+            && (c as usize) < width
+            && (r as usize) < height
+            && !blocked[r as usize * width + c as usize]
+    };
+
+    // Boundary edges run clockwise around walkable cells, i.e. walkable is on the right.
+    let mut edges: Vec<(IVec2, IVec2)> = Vec::new();
+    for r in 0..height as i32 {
+        for c in 0..width as i32 {
+            if !walkable(c, r) {
+                continue;
+            }
+            if !walkable(c, r - 1) {
+                edges.push((ivec2(c, r), ivec2(c + 1, r)));
+            }
+            if !walkable(c + 1, r) {
+                edges.push((ivec2(c + 1, r), ivec2(c + 1, r + 1)));
+            }
+            if !walkable(c, r + 1) {
+                edges.push((ivec2(c + 1, r + 1), ivec2(c, r + 1)));
+            }
+            if !walkable(c - 1, r) {
+                edges.push((ivec2(c, r + 1), ivec2(c, r)));
+            }
+        }
+    }
+
+    let mut outgoing: HashMap<IVec2, Vec<usize>> = HashMap::new();
+    for (i, (from, _)) in edges.iter().enumerate() {
+        outgoing.entry(*from).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut contours = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut contour = Vec::new();
+        let mut current = start;
+        loop {
+            used[current] = true;
+            let (from, to) = edges[current];
+            contour.push(from);
+            let dir = to - from;
+            // At pinch points two edges leave the same corner; turning right keeps
+            // hugging the current cell so diagonal neighbours stay separate regions.
+            let preference = [ivec2(-dir.y, dir.x), dir, ivec2(dir.y, -dir.x)];
+            let next = outgoing.get(&to).and_then(|candidates| {
+                preference.iter().find_map(|want| {
+                    candidates
+                        .iter()
+                        .copied()
+                        .find(|&e| !used[e] && edges[e].1 - edges[e].0 == *want)
+                })
+            });
+            match next {
+                Some(e) => current = e,
+                None => break,
+            }
+        }
+        contours.push(drop_collinear(contour));
+    }
+    contours
+}
+
+fn drop_collinear(points: Vec<IVec2>) -> Vec<IVec2> {
+    let n = points.len();
+    (0..n)
+        .filter(|&i| {
+            let prev = points[(i + n - 1) % n];
+            let next = points[(i + 1) % n];
+            (points[i] - prev).perp_dot(next - points[i]) != 0
+        })
+        .map(|i| points[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Twice the signed area in y-down coordinates (positive = clockwise on screen).
+    fn signed_area2(points: &[IVec2]) -> i32 {
+        let n = points.len();
+        (0..n)
+            .map(|i| points[i].perp_dot(points[(i + 1) % n]))
+            .sum()
+    }
+
+    #[test]
+    fn diagonal_neighbours_are_traced_as_separate_regions() {
+        // . #
+        // # .
+        let blocked = [false, true, true, false];
+        let contours = trace_walkable_contours(2, 2, &blocked);
+        assert_eq!(contours.len(), 2);
+        for contour in &contours {
+            assert_eq!(contour.len(), 4);
+            assert_eq!(signed_area2(contour), 2);
+        }
+    }
+}
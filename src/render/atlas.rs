@@ -0,0 +1,145 @@
+use macroquad::texture::Image;
+
+/// Where [`pack_shelves`] put each image, and how large the atlas must be.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtlasLayout {
+    /// Atlas `(width, height)` in pixels, just large enough for the packed images.
+    pub size: (u32, u32),
+    /// Top-left corner of each input image, `None` for images that did not fit.
+    pub positions: Vec<Option<(u32, u32)>>,
+}
+
+/// Shelf-packs images of the given `(width, height)` sizes into an atlas of at most
+/// `max_size` pixels per side, leaving `padding` pixels between neighbours.
+///
+/// Images are placed tallest first, left to right on shelves stacked downwards. One that
+/// fits neither the current shelf nor a new one is skipped, and smaller ones after it
+/// may still be placed.
+pub fn pack_shelves(sizes: &[(u32, u32)], max_size: u32, padding: u32) -> AtlasLayout {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut positions = vec![None; sizes.len()];
+    let (mut x, mut shelf_y, mut shelf_h) = (0u32, 0u32, 0u32);
+    let mut size = (0u32, 0u32);
+    for i in order {
+        let (w, h) = sizes[i];
+        if w == 0 || h == 0 || w > max_size {
+            continue;
+        }
+        let at = if x + w <= max_size && shelf_y + h <= max_size {
+            (x, shelf_y)
+        } else {
+            let next_y = shelf_y + shelf_h + padding;
+            if next_y + h > max_size {
+                continue;
+            }
+            (shelf_y, shelf_h) = (next_y, 0);
+            (0, next_y)
+        };
+        positions[i] = Some(at);
+        x = at.0 + w + padding;
+        shelf_h = shelf_h.max(h);
+        size = (size.0.max(at.0 + w), size.1.max(at.1 + h));
+    }
+    AtlasLayout { size, positions }
+}
+
+/// Copies every placed image of `images` into a transparent atlas laid out by `layout`.
+pub fn compose_atlas(images: &[&Image], layout: &AtlasLayout) -> Image {
+    let (width, height) = layout.size;
+    let mut atlas = Image {
+        bytes: vec![0; width as usize * height as usize * 4],
+        width: width as u16,
+        height: height as u16,
+    };
+    let stride = width as usize * 4;
+    for (image, pos) in images.iter().zip(&layout.positions) {
+        let Some((x, y)) = *pos else {
+            continue;
+        };
+        let row = image.width as usize * 4;
+        for (r, src) in image.bytes.chunks_exact(row).enumerate() {
+            let start = (y as usize + r) * stride + x as usize * 4;
+            atlas.bytes[start..start + row].copy_from_slice(src);
+        }
+    }
+    atlas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::color::Color;
+    use macroquad::math::Rect;
+
+    #[test]
+    fn shelves_keep_padded_images_apart_and_inside_the_atlas() {
+        let sizes = [
+            (64, 32),
+            (128, 128),
+            (32, 96),
+            (100, 20),
+            (300, 10),
+            (50, 200),
+        ];
+        let (max_size, padding) = (256, 2);
+        let layout = pack_shelves(&sizes, max_size, padding);
+
+        assert_eq!(layout.positions[4], None, "wider than the atlas");
+        assert_eq!(layout.positions[5], Some((0, 0)), "tallest goes first");
+        let placed: Vec<Rect> = sizes
+            .iter()
+            .zip(&layout.positions)
+            .filter_map(|(&(w, h), pos)| {
+                let (x, y) = (*pos)?;
+                Some(Rect::new(x as f32, y as f32, w as f32, h as f32))
+            })
+            .collect();
+        assert_eq!(placed.len(), 5);
+        for (i, a) in placed.iter().enumerate() {
+            assert!(a.right() <= layout.size.0 as f32 && a.bottom() <= layout.size.1 as f32);
+            for b in &placed[i + 1..] {
+                // Growing one side by the padding must still not reach the other.
+                let grown = Rect::new(a.x, a.y, a.w + padding as f32, a.h + padding as f32);
+                let grown_b = Rect::new(b.x, b.y, b.w + padding as f32, b.h + padding as f32);
+                assert!(grown.intersect(*b).is_none_or(|r| r.w == 0.0 || r.h == 0.0));
+                assert!(grown_b
+                    .intersect(*a)
+                    .is_none_or(|r| r.w == 0.0 || r.h == 0.0));
+            }
+        }
+        assert!(layout.size.0 <= max_size && layout.size.1 <= max_size);
+
+        // Too tall for a new shelf, but the shorter image after it still fits.
+        let layout = pack_shelves(&[(200, 200), (100, 100), (40, 40)], 256, 0);
+        assert_eq!(
+            layout.positions,
+            [Some((0, 0)), None, Some((200, 0))],
+            "{layout:?}"
+        );
+    }
+
+    #[test]
+    fn composed_atlas_holds_each_image_at_its_position() {
+        let red = Color::from_rgba(255, 0, 0, 255);
+        let blue = Color::from_rgba(0, 0, 255, 255);
+        let a = Image::gen_image_color(4, 6, red);
+        let b = Image::gen_image_color(3, 2, blue);
+        let layout = pack_shelves(&[(4, 6), (3, 2)], 16, 1);
+        assert_eq!(layout.positions, [Some((0, 0)), Some((5, 0))]);
+        assert_eq!(layout.size, (8, 6));
+
+        let atlas = compose_atlas(&[&a, &b], &layout);
+        assert_eq!((atlas.width, atlas.height), (8, 6));
+        assert_eq!(atlas.get_pixel(3, 5), red);
+        assert_eq!(
+            atlas.get_pixel(4, 0),
+            Color::from_rgba(0, 0, 0, 0),
+            "padding"
+        );
+        assert_eq!(atlas.get_pixel(5, 0), blue);
+        assert_eq!(atlas.get_pixel(7, 1), blue);
+        assert_eq!(atlas.get_pixel(5, 2), Color::from_rgba(0, 0, 0, 0));
+    }
+}
@@ -1,6 +1,7 @@
-use crate::spatial::{ChunkCoord, GlobalIndex, LayerBucket, LayerIdx, CHUNK_SIZE};
+use crate::spatial::{ChunkCoord, GlobalChunk, GlobalIndex, LayerBucket, LayerIdx, CHUNK_SIZE};
 use macroquad::prelude::*;
 use std::collections::HashMap;
+use std::ops::ControlFlow;
 
 const CULL_MARGIN_CHUNKS: i32 = 1;
 
@@ -13,6 +14,60 @@ pub struct LocalView<'g> {
 }
 
 pub fn visible_chunk_coords_rect(view_min: Vec2, view_max: Vec2) -> Vec<ChunkCoord> {
+    let mut coords = Vec::new();
+    visible_chunk_coords_rect_into(view_min, view_max, &mut coords);
+    coords
+}
+
+/// Like [`visible_chunk_coords_rect`], refilling `coords` so per-frame callers can reuse
+/// its allocation.
+pub fn visible_chunk_coords_rect_into(
+    view_min: Vec2,
+    view_max: Vec2,
+    coords: &mut Vec<ChunkCoord>,
+) {
+    let (min, max) = chunk_range(view_min, view_max);
+    coords.clear();
+    for cy in min.y..=max.y {
+        for cx in min.x..=max.x {
+            coords.push(ChunkCoord { x: cx, y: cy });
+        }
+    }
+}
+
+/// Refills `coords` with the chunks of `g` inside the inclusive range `min..=max`, row by
+/// row.
+///
+/// Walks the range or the occupied chunks, whichever is smaller, so every chunk is
+/// looked up at most once and callers drawing several layers skip empty chunks.
+pub fn occupied_chunk_coords_into(
+    g: &GlobalIndex,
+    min: ChunkCoord,
+    max: ChunkCoord,
+    coords: &mut Vec<ChunkCoord>,
+) {
+    coords.clear();
+    if g.fewer_chunks_than(min, max) {
+        coords.extend(
+            g.buckets
+                .keys()
+                .filter(|c| c.x >= min.x && c.x <= max.x && c.y >= min.y && c.y <= max.y),
+        );
+        coords.sort_unstable_by_key(|c| (c.y, c.x));
+        return;
+    }
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let coord = ChunkCoord { x, y };
+            if g.buckets.contains_key(&coord) {
+                coords.push(coord);
+            }
+        }
+    }
+}
+
+/// Inclusive chunk range `(min, max)` covering the view rect, corners in either order.
+pub fn chunk_range(view_min: Vec2, view_max: Vec2) -> (ChunkCoord, ChunkCoord) {
     let mut cx_min = (view_min.x as i32).div_euclid(CHUNK_SIZE);
     let mut cy_min = (view_min.y as i32).div_euclid(CHUNK_SIZE);
     let mut cx_max = (view_max.x as i32).div_euclid(CHUNK_SIZE);
@@ -24,26 +79,38 @@ pub fn visible_chunk_coords_rect(view_min: Vec2, view_max: Vec2) -> Vec<ChunkCoo
     if cy_min > cy_max {
         std::mem::swap(&mut cy_min, &mut cy_max);
     }
-
-    let mut coords = Vec::new();
-    for cy in cy_min..=cy_max {
-        for cx in cx_min..=cx_max {
-            coords.push(ChunkCoord { x: cx, y: cy });
-        }
-    }
-    coords
+    (
+        ChunkCoord {
+            x: cx_min,
+            y: cy_min,
+        },
+        ChunkCoord {
+            x: cx_max,
+            y: cy_max,
+        },
+    )
 }
 
-pub fn query_visible<'g>(g: &'g GlobalIndex, cam: &Camera2D) -> LocalView<'g> {
-    let (viewport_width, viewport_height) = match cam.viewport {
-        Some((_, _, w, h)) => (w as f32, h as f32),
-        None => (screen_width(), screen_height()), // Fall back to screen dimensions
+/// World-space `(min, max)` corners of the area `cam` shows.
+///
+/// Camera zoom maps world units around `target` to clip space (`-1..1`) and `offset`
+/// then shifts them in clip space, so the screen edges sit at `(±1 - offset) / zoom`
+/// from the target; flipped axes (negative zoom) are normalized. Rotation is ignored.
+pub fn camera_view_rect(cam: &Camera2D) -> (Vec2, Vec2) {
+    // Matches `Camera2D::matrix`, which flips y unless drawing to a render target.
+    let invert_y = if cam.render_target.is_some() {
+        1.0
+    } else {
+        -1.0
     };
+    let scale = vec2(cam.zoom.x, cam.zoom.y * invert_y);
+    let a = cam.target + (Vec2::NEG_ONE - cam.offset) / scale;
+    let b = cam.target + (Vec2::ONE - cam.offset) / scale;
+    (a.min(b), a.max(b))
+}
 
-    let half_w = viewport_width / cam.zoom.x / 2.0;
-    let half_h = viewport_height / cam.zoom.y / 2.0;
-    let cam_min = cam.target - Vec2::new(half_w, half_h);
-    let cam_max = cam.target + Vec2::new(half_w, half_h);
+pub fn query_visible<'g>(g: &'g GlobalIndex, cam: &Camera2D) -> LocalView<'g> {
+    let (cam_min, cam_max) = camera_view_rect(cam);
 
     //pad by one chunk
     let pad = CHUNK_SIZE as f32;
@@ -69,24 +136,77 @@ pub fn query_visible<'g>(g: &'g GlobalIndex, cam: &Camera2D) -> LocalView<'g> {
     LocalView { chunks }
 }
 
-pub fn query_visible_rect<'g>(g: &'g GlobalIndex, view_min: Vec2, view_max: Vec2) -> LocalView<'g> {
-    let mut cx_min = (view_min.x as i32).div_euclid(CHUNK_SIZE);
-    let mut cy_min = (view_min.y as i32).div_euclid(CHUNK_SIZE);
-    let mut cx_max = (view_max.x as i32).div_euclid(CHUNK_SIZE);
-    let mut cy_max = (view_max.y as i32).div_euclid(CHUNK_SIZE);
+/// Inclusive chunk range `(min, max)` covering the view rect plus the cull margin.
+pub fn chunk_range_rect(view_min: Vec2, view_max: Vec2) -> (ChunkCoord, ChunkCoord) {
+    let (min, max) = chunk_range(view_min, view_max);
 
     //pad by one chunk
-    if cx_min > cx_max {
-        std::mem::swap(&mut cx_min, &mut cx_max);
+    (
+        ChunkCoord {
+            x: min.x - CULL_MARGIN_CHUNKS,
+            y: min.y - CULL_MARGIN_CHUNKS,
+        },
+        ChunkCoord {
+            x: max.x + CULL_MARGIN_CHUNKS,
+            y: max.y + CULL_MARGIN_CHUNKS,
+        },
+    )
+}
+
+/// Calls `f` for every existing chunk in the same range as [`query_visible_rect`], row
+/// by row. Stops early when `f` breaks.
+///
+/// Like [`occupied_chunk_coords_into`], walks the range or the occupied chunks,
+/// whichever is smaller, so huge views cost no more than the map's chunk count. Only the
+/// latter allocates, to sort the chunks it found.
+pub fn try_for_each_chunk_in_rect(
+    g: &GlobalIndex,
+    view_min: Vec2,
+    view_max: Vec2,
+    f: impl FnMut(ChunkCoord, &GlobalChunk) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let (min, max) = chunk_range_rect(view_min, view_max);
+    try_for_each_chunk_in_range(g, min, max, &mut Vec::new(), f)
+}
+
+/// Like [`try_for_each_chunk_in_rect`] over the inclusive range `min..=max`, sorting the
+/// occupied chunks in `scratch` when walking them so callers can reuse its allocation.
+pub fn try_for_each_chunk_in_range(
+    g: &GlobalIndex,
+    min: ChunkCoord,
+    max: ChunkCoord,
+    scratch: &mut Vec<ChunkCoord>,
+    mut f: impl FnMut(ChunkCoord, &GlobalChunk) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    if g.fewer_chunks_than(min, max) {
+        occupied_chunk_coords_into(g, min, max, scratch);
+        for coord in scratch.iter() {
+            f(*coord, &g.buckets[coord])?;
+        }
+        return ControlFlow::Continue(());
     }
-    if cy_min > cy_max {
-        std::mem::swap(&mut cy_min, &mut cy_max);
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let coord = ChunkCoord { x, y };
+            if let Some(chunk) = g.buckets.get(&coord) {
+                f(coord, chunk)?;
+            }
+        }
     }
+    ControlFlow::Continue(())
+}
 
-    cx_min -= CULL_MARGIN_CHUNKS;
-    cy_min -= CULL_MARGIN_CHUNKS;
-    cx_max += CULL_MARGIN_CHUNKS;
-    cy_max += CULL_MARGIN_CHUNKS;
+pub fn query_visible_rect<'g>(g: &'g GlobalIndex, view_min: Vec2, view_max: Vec2) -> LocalView<'g> {
+    let (
+        ChunkCoord {
+            x: cx_min,
+            y: cy_min,
+        },
+        ChunkCoord {
+            x: cx_max,
+            y: cy_max,
+        },
+    ) = chunk_range_rect(view_min, view_max);
 
     let mut chunks = Vec::new();
     for (&coord, bucket) in &g.buckets {
@@ -122,4 +242,101 @@ mod tests {
             .windows(2)
             .all(|w| (w[0].y, w[0].x) <= (w[1].y, w[1].x)));
     }
+
+    #[test]
+    fn chunk_visitor_matches_query_visible_rect_and_stops_early() {
+        let mut index = GlobalIndex::new();
+        for (x, y) in [
+            (520.0, 520.0),
+            (0.0, 0.0),
+            (260.0, 0.0),
+            (0.0, 260.0),
+            (2000.0, 0.0),
+        ] {
+            index.add_tile(TileId(1), 0, vec2(x, y));
+        }
+        let (min, max) = (vec2(0.0, 0.0), vec2(300.0, 300.0));
+        let expected: Vec<ChunkCoord> = query_visible_rect(&index, min, max)
+            .chunks
+            .iter()
+            .map(|c| c.coord)
+            .collect();
+
+        let mut seen = Vec::new();
+        let flow = try_for_each_chunk_in_rect(&index, min, max, |cc, _| {
+            seen.push(cc);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(seen, expected);
+
+        let mut visits = 0;
+        let flow = try_for_each_chunk_in_rect(&index, min, max, |_, _| {
+            visits += 1;
+            ControlFlow::Break(())
+        });
+        assert_eq!((flow, visits), (ControlFlow::Break(()), 1));
+
+        // A view of ~1e13 chunk coords visits just the occupied chunks, in row order.
+        let mut seen = Vec::new();
+        let (huge_min, huge_max) = (Vec2::splat(-1e9), Vec2::splat(1e9));
+        let flow = try_for_each_chunk_in_rect(&index, huge_min, huge_max, |cc, _| {
+            seen.push(cc);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(flow, ControlFlow::Continue(()));
+        let c = |x, y| ChunkCoord { x, y };
+        assert_eq!(seen, [c(0, 0), c(1, 0), c(7, 0), c(0, 1), c(2, 2)]);
+    }
+
+    #[test]
+    fn occupied_chunks_match_the_range_scan_from_either_side() {
+        let mut index = GlobalIndex::new();
+        for (x, y) in [
+            (520.0, 520.0),
+            (0.0, 0.0),
+            (260.0, 0.0),
+            (-300.0, 0.0),
+            (9000.0, 0.0),
+        ] {
+            index.add_tile(TileId(1), 0, vec2(x, y));
+        }
+        let c = |x, y| ChunkCoord { x, y };
+        let mut coords = vec![c(99, 99)];
+
+        // Small range: scanned cell by cell.
+        occupied_chunk_coords_into(&index, c(0, 0), c(1, 0), &mut coords);
+        assert_eq!(coords, [c(0, 0), c(1, 0)]);
+
+        // Range larger than the occupied set: the buckets are filtered and sorted instead.
+        occupied_chunk_coords_into(&index, c(-10, -10), c(10, 10), &mut coords);
+        assert_eq!(coords, [c(-2, 0), c(0, 0), c(1, 0), c(2, 2)]);
+
+        occupied_chunk_coords_into(&index, c(5, 5), c(4, 4), &mut coords);
+        assert!(coords.is_empty());
+    }
+
+    #[test]
+    fn camera_view_rect_recovers_the_display_rect() {
+        let cam = Camera2D::from_display_rect(Rect::new(32.0, 16.0, 800.0, 600.0));
+        let (min, max) = camera_view_rect(&cam);
+        assert!(min.abs_diff_eq(vec2(32.0, 16.0), 1e-3), "{min:?}");
+        assert!(max.abs_diff_eq(vec2(832.0, 616.0), 1e-3), "{max:?}");
+    }
+
+    #[test]
+    fn camera_view_rect_follows_the_offset() {
+        let mut cam = Camera2D::from_display_rect(Rect::new(32.0, 16.0, 800.0, 600.0));
+        // Shifts the drawn world a quarter screen right and an eighth down: 200 and 75 px.
+        cam.offset = vec2(0.5, 0.25);
+        let (min, max) = camera_view_rect(&cam);
+        assert!(min.abs_diff_eq(vec2(-168.0, -59.0), 1e-3), "{min:?}");
+        assert!(max.abs_diff_eq(vec2(632.0, 541.0), 1e-3), "{max:?}");
+
+        // Same corners as unprojecting the clip-space corners through the camera matrix.
+        let inverse = cam.matrix().inverse();
+        let corner = |x, y| inverse.transform_point3(vec3(x, y, 0.0)).truncate();
+        let (a, b) = (corner(-1.0, -1.0), corner(1.0, 1.0));
+        assert!(min.abs_diff_eq(a.min(b), 1e-3) && max.abs_diff_eq(a.max(b), 1e-3));
+    }
 }
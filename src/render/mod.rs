@@ -1,2 +1,3 @@
+pub mod atlas;
 pub mod cull;
 pub use cull::*;
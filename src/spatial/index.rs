@@ -1,44 +1,159 @@
 use macroquad::prelude::*;
 use std::collections::HashMap;
 
+/// Chunk edge length in world pixels.
 pub const CHUNK_SIZE: i32 = 256;
 
+/// Raw Tiled gid: the tile id in the low 29 bits plus flip flags in the high bits.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TileId(pub u32);
 
+/// Id of one tile record in the spatial index.
+///
+/// Released slots are handed out again under a new generation, so a handle kept after
+/// its tile was removed finds nothing rather than the tile that took its slot.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct TileHandle(pub u32);
+pub struct TileHandle {
+    slot: u32,
+    generation: u32,
+}
+
+impl TileHandle {
+    /// Position in [`GlobalIndex::handles`].
+    #[inline]
+    pub const fn slot(self) -> u32 {
+        self.slot
+    }
+}
 
+/// Layer index in Tiled layer array order, used to key spatial buckets.
 pub type LayerIdx = u16;
 
+/// Position of a spatial index chunk, in units of [`CHUNK_SIZE`] world pixels.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkCoord {
+    /// Chunk column.
     pub x: i32,
+    /// Chunk row.
     pub y: i32,
 }
 
+impl ChunkCoord {
+    /// The four edge-adjacent chunks: north, east, south, west.
+    ///
+    /// North is `y - 1`, matching world space where rows grow downwards. Coordinates wrap
+    /// at the `i32` limits, so the neighbors of an edge chunk sit on the opposite edge.
+    pub const fn neighbors(self) -> [ChunkCoord; 4] {
+        let ChunkCoord { x, y } = self;
+        [
+            ChunkCoord {
+                x,
+                y: y.wrapping_sub(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_add(1),
+                y,
+            },
+            ChunkCoord {
+                x,
+                y: y.wrapping_add(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_sub(1),
+                y,
+            },
+        ]
+    }
+
+    /// The eight surrounding chunks, clockwise from north, wrapping like
+    /// [`ChunkCoord::neighbors`].
+    pub const fn neighbors_8(self) -> [ChunkCoord; 8] {
+        let ChunkCoord { x, y } = self;
+        [
+            ChunkCoord {
+                x,
+                y: y.wrapping_sub(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_add(1),
+                y: y.wrapping_sub(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_add(1),
+                y,
+            },
+            ChunkCoord {
+                x: x.wrapping_add(1),
+                y: y.wrapping_add(1),
+            },
+            ChunkCoord {
+                x,
+                y: y.wrapping_add(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_sub(1),
+                y: y.wrapping_add(1),
+            },
+            ChunkCoord {
+                x: x.wrapping_sub(1),
+                y,
+            },
+            ChunkCoord {
+                x: x.wrapping_sub(1),
+                y: y.wrapping_sub(1),
+            },
+        ]
+    }
+}
+
 pub const FLIP_H: u32 = 0x8000_0000; // bit 31
 pub const FLIP_V: u32 = 0x4000_0000; // bit 30
 pub const FLIP_D: u32 = 0x2000_0000; // bit 29
 pub const GID_MASK: u32 = 0x1FFF_FFFF; // keep lower 29 bits (bit 28 is free)
 
 impl TileId {
+    /// Wraps a gid exactly as stored in Tiled layer data.
+    ///
+    /// Tiled packs flip flags into the top bits of each gid (see "Tile Flipping" in the
+    /// Tiled map format docs): bit 31 is the horizontal flip, bit 30 the vertical flip,
+    /// bit 29 the anti-diagonal flip (transpose) and bit 28 the hexagonal 120° rotation,
+    /// which is not supported and kept as part of the id. The remaining low bits are
+    /// the tile's global id.
+    #[inline]
+    pub fn from_tiled_gid(raw: u32) -> TileId {
+        TileId(raw)
+    }
+
+    /// Wraps a global id without flip flags, e.g. `first_gid + local_id`.
+    ///
+    /// Debug builds panic if `gid` has any flip bit set (`gid > GID_MASK`).
+    #[inline]
+    pub fn from_clean_gid(gid: u32) -> TileId {
+        debug_assert!(gid <= GID_MASK, "gid {gid:#x} has flip bits set");
+        TileId(gid)
+    }
+
+    /// Gid including flip flags.
     #[inline]
     pub fn raw(self) -> u32 {
         self.0
     }
+    /// Gid with flip flags stripped.
     #[inline]
     pub fn clean(self) -> u32 {
         self.0 & GID_MASK
     }
+    /// Horizontal flip flag.
     #[inline]
     pub fn flip_h(self) -> bool {
         (self.0 & FLIP_H) != 0
     }
+    /// Vertical flip flag.
     #[inline]
     pub fn flip_v(self) -> bool {
         (self.0 & FLIP_V) != 0
     }
+    /// Diagonal (anti-diagonal transpose) flip flag.
     #[inline]
     pub fn flip_d(self) -> bool {
         (self.0 & FLIP_D) != 0
@@ -61,33 +176,72 @@ pub fn rel(p: Vec2) -> Vec2 {
     )
 }
 
+/// One tile stored in a chunk.
 #[derive(Debug, Clone)]
 pub struct TileRec {
+    /// Handle the index tracks this record by.
     pub handle: TileHandle,
+    /// Gid including flip flags.
     pub id: TileId,
+    /// Top-left corner relative to the chunk origin, in world pixels.
     pub rel_pos: Vec2,
 }
 
+impl TileRec {
+    /// World position of the tile's top-left corner, given the chunk it is stored in.
+    #[inline]
+    pub fn world_pos(&self, chunk: ChunkCoord) -> Vec2 {
+        vec2(
+            (chunk.x * CHUNK_SIZE) as f32 + self.rel_pos.x,
+            (chunk.y * CHUNK_SIZE) as f32 + self.rel_pos.y,
+        )
+    }
+
+    /// Grid column of the tile in world space (layer offset not removed), negative left
+    /// of the origin.
+    #[inline]
+    pub fn col(&self, chunk: ChunkCoord, tile_w: u32) -> i32 {
+        (self.world_pos(chunk).x / tile_w as f32).floor() as i32
+    }
+
+    /// Grid row of the tile in world space (layer offset not removed), negative above
+    /// the origin.
+    #[inline]
+    pub fn row(&self, chunk: ChunkCoord, tile_h: u32) -> i32 {
+        (self.world_pos(chunk).y / tile_h as f32).floor() as i32
+    }
+}
+
+/// One object stored in a chunk.
 #[derive(Debug, Clone)]
 pub struct ObjectRec {
+    /// The object's position in its layer's object list.
     pub handle: ObjectHandle,
+    /// Anchor relative to the chunk origin, in world pixels.
     pub rel_pos: Vec2,
 }
 
+/// Index of an object within its object layer.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct ObjectHandle(pub u32);
 
+/// The tile and object records one layer has in one chunk.
 #[derive(Debug, Clone, Default)]
 pub struct LayerBucket {
+    /// Tile records, in no particular order.
     pub tiles: Vec<TileRec>,
+    /// Object records, in no particular order.
     pub objects: Vec<ObjectRec>,
 }
 
+/// One chunk of the spatial index: the tile and object records of each layer in it.
 pub struct GlobalChunk {
+    /// Records per layer; layers without anything in this chunk are absent.
     pub layers: HashMap<LayerIdx, LayerBucket>,
 }
 
 impl GlobalChunk {
+    /// Creates an empty chunk.
     pub fn new() -> Self {
         GlobalChunk {
             layers: HashMap::new(),
@@ -101,6 +255,7 @@ impl Default for GlobalChunk {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct TileLoc {
     pub chunk: ChunkCoord,
     pub layer: LayerIdx,
@@ -110,7 +265,10 @@ pub struct TileLoc {
 pub struct GlobalIndex {
     pub buckets: HashMap<ChunkCoord, GlobalChunk>,
     pub handles: Vec<Option<TileLoc>>,
-    next_handle: u32,
+    // Current generation of each slot in `handles`, bumped when the slot is released.
+    generations: Vec<u32>,
+    // Released handle slots, reused before the table grows.
+    free_handles: Vec<u32>,
 }
 
 impl GlobalIndex {
@@ -118,15 +276,63 @@ impl GlobalIndex {
         GlobalIndex {
             buckets: HashMap::new(),
             handles: Vec::new(),
-            next_handle: 0,
+            generations: Vec::new(),
+            free_handles: Vec::new(),
         }
     }
 
+    /// Allocated capacities as `(bucket_capacity, total_tile_capacity)`, for profiling.
+    ///
+    /// The tile figure sums the capacity of every per-layer tile vec in every chunk.
+    pub fn capacity(&self) -> (usize, usize) {
+        let tiles = self
+            .buckets
+            .values()
+            .flat_map(|c| c.layers.values())
+            .map(|b| b.tiles.capacity())
+            .sum();
+        (self.buckets.capacity(), tiles)
+    }
+
+    /// Pre-allocates room for `extra_tiles` more tile handles and `extra_chunks` more
+    /// chunks, reducing reallocations during batch insertion.
+    ///
+    /// Per-chunk tile vecs still grow on demand, since which chunks receive the tiles is
+    /// only known at insertion time.
+    pub fn reserve(&mut self, extra_tiles: usize, extra_chunks: usize) {
+        self.handles.reserve(extra_tiles);
+        self.generations.reserve(extra_tiles);
+        self.buckets.reserve(extra_chunks);
+    }
+
+    // Released handles are handed out again, so re-indexing a layer keeps the table size.
     fn alloc_handle(&mut self) -> TileHandle {
-        let h = TileHandle(self.next_handle);
-        self.next_handle += 1;
-        self.handles.push(None);
-        h
+        let slot = self.free_handles.pop().unwrap_or_else(|| {
+            self.handles.push(None);
+            self.generations.push(0);
+            self.handles.len() as u32 - 1
+        });
+        TileHandle {
+            slot,
+            generation: self.generations[slot as usize],
+        }
+    }
+
+    // A slot's generation wraps after 2^32 releases; a handle kept that long could match.
+    fn release_handle(&mut self, handle: TileHandle) {
+        let slot = handle.slot as usize;
+        self.handles[slot] = None;
+        self.generations[slot] = self.generations[slot].wrapping_add(1);
+        self.free_handles.push(handle.slot);
+    }
+
+    // Location of `handle`'s tile, or `None` once it was released.
+    fn live_loc(&self, handle: TileHandle) -> Option<TileLoc> {
+        let slot = handle.slot as usize;
+        if self.generations.get(slot) != Some(&handle.generation) {
+            return None;
+        }
+        self.handles[slot]
     }
 }
 
@@ -149,7 +355,7 @@ impl GlobalIndex {
             id,
             rel_pos: rel(world),
         });
-        self.handles[handle.0 as usize] = Some(TileLoc {
+        self.handles[handle.slot as usize] = Some(TileLoc {
             chunk: cc,
             layer,
             index: idx,
@@ -166,4 +372,418 @@ impl GlobalIndex {
             .objects
             .push(object_rec);
     }
+
+    /// Removes one tile, keeping the other handles of its bucket valid.
+    ///
+    /// Returns `None` if the handle was already released.
+    pub fn remove_tile(&mut self, handle: TileHandle) -> Option<TileRec> {
+        let loc = self.live_loc(handle)?;
+        self.release_handle(handle);
+        let chunk = self.buckets.get_mut(&loc.chunk)?;
+        let bucket = chunk.layers.get_mut(&loc.layer)?;
+        let rec = bucket.tiles.swap_remove(loc.index);
+        if let Some(moved) = bucket.tiles.get(loc.index) {
+            self.handles[moved.handle.slot as usize] = Some(TileLoc {
+                index: loc.index,
+                ..loc
+            });
+        }
+        if bucket.tiles.is_empty() && bucket.objects.is_empty() {
+            chunk.layers.remove(&loc.layer);
+            if chunk.layers.is_empty() {
+                self.buckets.remove(&loc.chunk);
+            }
+        }
+        Some(rec)
+    }
+
+    /// Tiles of `layer` stored in `chunk`, or `None` if that chunk has no bucket for the layer.
+    #[inline]
+    pub fn layer_tile_vec(&self, chunk: ChunkCoord, layer: LayerIdx) -> Option<&[TileRec]> {
+        self.buckets
+            .get(&chunk)?
+            .layers
+            .get(&layer)
+            .map(|bucket| bucket.tiles.as_slice())
+    }
+
+    /// Mutable variant of [`GlobalIndex::layer_tile_vec`] for in-place bulk edits.
+    ///
+    /// Handles record slot positions, so edit records in place (gids, positions within
+    /// the chunk) rather than reordering the slice.
+    #[inline]
+    pub fn layer_tile_vec_mut(
+        &mut self,
+        chunk: ChunkCoord,
+        layer: LayerIdx,
+    ) -> Option<&mut [TileRec]> {
+        self.buckets
+            .get_mut(&chunk)?
+            .layers
+            .get_mut(&layer)
+            .map(|bucket| bucket.tiles.as_mut_slice())
+    }
+
+    /// Tiles of `layer` whose top-left world position lies in `rect` (right and bottom
+    /// edges excluded), with the chunk each is stored in.
+    ///
+    /// Only the chunks overlapping `rect` are looked up, or only the occupied chunks when
+    /// there are fewer of those, so the cost follows the smaller of the rect's size and
+    /// the chunk count rather than the total tile count.
+    pub fn tiles_in_rect(
+        &self,
+        layer: LayerIdx,
+        rect: Rect,
+    ) -> impl Iterator<Item = (ChunkCoord, &TileRec)> + '_ {
+        let min = world_to_chunk(rect.point());
+        let max = world_to_chunk(rect.point() + rect.size());
+        let occupied = self.fewer_chunks_than(min, max).then(|| {
+            let mut coords: Vec<ChunkCoord> = self
+                .buckets
+                .keys()
+                .copied()
+                .filter(|c| c.x >= min.x && c.x <= max.x && c.y >= min.y && c.y <= max.y)
+                .collect();
+            coords.sort_unstable_by_key(|c| (c.y, c.x));
+            coords
+        });
+        let range = occupied.is_none().then(move || {
+            (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| ChunkCoord { x, y }))
+        });
+        occupied
+            .into_iter()
+            .flatten()
+            .chain(range.into_iter().flatten())
+            .filter_map(move |cc| Some((cc, self.layer_tile_vec(cc, layer)?)))
+            .flat_map(move |(cc, tiles)| {
+                tiles.iter().filter_map(move |rec| {
+                    let p = rec.world_pos(cc);
+                    let inside = p.x >= rect.x
+                        && p.y >= rect.y
+                        && p.x < rect.x + rect.w
+                        && p.y < rect.y + rect.h;
+                    inside.then_some((cc, rec))
+                })
+            })
+    }
+
+    /// Whether the index holds fewer chunks than the inclusive range `min..=max` covers,
+    /// so walking its chunks beats stepping through every coord of the range.
+    pub(crate) fn fewer_chunks_than(&self, min: ChunkCoord, max: ChunkCoord) -> bool {
+        let area = (i64::from(max.x) - i64::from(min.x) + 1).max(0)
+            * (i64::from(max.y) - i64::from(min.y) + 1).max(0);
+        (self.buckets.len() as i64) < area
+    }
+
+    /// Returns the live record of `handle` for in-place edits (e.g. changing its gid), or
+    /// `None` once the handle was released.
+    pub fn tile_mut(&mut self, handle: TileHandle) -> Option<&mut TileRec> {
+        let loc = self.live_loc(handle)?;
+        self.buckets
+            .get_mut(&loc.chunk)?
+            .layers
+            .get_mut(&loc.layer)?
+            .tiles
+            .get_mut(loc.index)
+    }
+
+    /// Removes every tile of `layer` from all chunks and releases their handles for reuse.
+    pub fn remove_layer_tiles(&mut self, layer: LayerIdx) {
+        let mut released = Vec::new();
+        for chunk in self.buckets.values_mut() {
+            if let Some(bucket) = chunk.layers.get_mut(&layer) {
+                released.extend(bucket.tiles.drain(..).map(|rec| rec.handle));
+            }
+        }
+        for handle in released {
+            self.release_handle(handle);
+        }
+        self.prune_empty();
+    }
+
+    /// Removes every object record of `layer` from all chunks.
+    pub fn remove_layer_objects(&mut self, layer: LayerIdx) {
+        for chunk in self.buckets.values_mut() {
+            if let Some(bucket) = chunk.layers.get_mut(&layer) {
+                bucket.objects.clear();
+            }
+        }
+        self.prune_empty();
+    }
+
+    /// Panics if tile handles and chunk records disagree. No-op without `debug_assertions`.
+    ///
+    /// Checks that every live handle points at an existing `(chunk, layer, index)` slot
+    /// whose record carries that same handle, that no two handles share a slot, and that
+    /// every stored tile record is reachable from its handle.
+    pub fn assert_consistent(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let mut slots = std::collections::HashSet::new();
+            for (h, loc) in self.handles.iter().enumerate() {
+                let Some(loc) = loc else {
+                    continue;
+                };
+                let rec = self
+                    .buckets
+                    .get(&loc.chunk)
+                    .and_then(|c| c.layers.get(&loc.layer))
+                    .and_then(|b| b.tiles.get(loc.index));
+                let Some(rec) = rec else {
+                    panic!(
+                        "tile handle {h} points past its bucket: chunk {:?}, layer {}, index {}",
+                        loc.chunk, loc.layer, loc.index
+                    );
+                };
+                assert_eq!(
+                    rec.handle.slot as usize, h,
+                    "tile handle {h} points at the record of handle {}",
+                    rec.handle.slot
+                );
+                assert_eq!(
+                    rec.handle.generation, self.generations[h],
+                    "tile handle {h} points at a record of an older generation"
+                );
+                assert!(
+                    slots.insert((loc.chunk, loc.layer, loc.index)),
+                    "tile handle {h} shares its slot with another handle"
+                );
+            }
+
+            let records: usize = self
+                .buckets
+                .values()
+                .flat_map(|c| c.layers.values())
+                .map(|b| b.tiles.len())
+                .sum();
+            assert_eq!(
+                records,
+                slots.len(),
+                "tile records without a live handle are stored in the index"
+            );
+        }
+    }
+
+    // Drops empty layer buckets and chunks so culling never visits dead entries.
+    fn prune_empty(&mut self) {
+        for chunk in self.buckets.values_mut() {
+            chunk
+                .layers
+                .retain(|_, b| !b.tiles.is_empty() || !b.objects.is_empty());
+        }
+        self.buckets.retain(|_, c| !c.layers.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consistent_after_adds_and_layer_removal() {
+        let mut index = GlobalIndex::new();
+        for i in 0..10 {
+            index.add_tile(TileId(1), (i % 2) as LayerIdx, vec2(i as f32 * 100.0, 0.0));
+        }
+        index.assert_consistent();
+        index.remove_layer_tiles(0);
+        index.assert_consistent();
+        assert_eq!(index.handles.iter().filter(|h| h.is_some()).count(), 5);
+    }
+
+    #[test]
+    fn reserve_grows_capacity_and_capacity_counts_tile_vecs() {
+        let mut index = GlobalIndex::new();
+        index.reserve(100, 8);
+        assert!(index.buckets.capacity() >= 8);
+        assert!(index.handles.capacity() >= 100);
+
+        for i in 0..5 {
+            index.add_tile(TileId(1), 0, vec2(i as f32 * 16.0, 0.0));
+        }
+        index.add_tile(TileId(1), 1, vec2(300.0, 0.0));
+        let (buckets, tiles) = index.capacity();
+        assert!(buckets >= 8);
+        assert!(tiles >= 6, "{tiles}");
+    }
+
+    #[test]
+    fn tiles_in_rect_excludes_far_edges_and_other_layers() {
+        let mut index = GlobalIndex::new();
+        for i in 0..20 {
+            index.add_tile(TileId(1), 0, vec2(i as f32 * 16.0, 0.0));
+        }
+        index.add_tile(TileId(2), 1, vec2(16.0, 0.0));
+        // Spans the chunk border at x = 256.
+        let hits: Vec<f32> = index
+            .tiles_in_rect(0, Rect::new(240.0, 0.0, 32.0, 16.0))
+            .map(|(cc, rec)| rec.world_pos(cc).x)
+            .collect();
+        assert_eq!(hits, vec![240.0, 256.0]);
+        assert_eq!(
+            index
+                .tiles_in_rect(1, Rect::new(0.0, 0.0, 64.0, 16.0))
+                .count(),
+            1
+        );
+        assert_eq!(
+            index
+                .tiles_in_rect(2, Rect::new(0.0, 0.0, 64.0, 16.0))
+                .count(),
+            0
+        );
+
+        // A rect of ~1e13 chunks visits only the two occupied ones, in row order.
+        let huge = Rect::new(-1e9, -1e9, 2e9, 2e9);
+        let hits: Vec<f32> = index
+            .tiles_in_rect(0, huge)
+            .map(|(cc, rec)| rec.world_pos(cc).x)
+            .collect();
+        assert_eq!(hits, (0..20).map(|i| i as f32 * 16.0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tiled_gid_constructors_keep_or_check_flip_bits() {
+        let id = TileId::from_tiled_gid(FLIP_H | FLIP_D | 7);
+        assert!(id.flip_h() && !id.flip_v() && id.flip_d());
+        assert_eq!((id.raw(), id.clean()), (FLIP_H | FLIP_D | 7, 7));
+        assert_eq!(TileId::from_clean_gid(GID_MASK).clean(), GID_MASK);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "flip bits")]
+    fn from_clean_gid_rejects_flip_bits_in_debug() {
+        TileId::from_clean_gid(FLIP_V | 1);
+    }
+
+    #[test]
+    fn remove_tile_repoints_the_swapped_record() {
+        let mut index = GlobalIndex::new();
+        let handles: Vec<_> = (0..3)
+            .map(|i| index.add_tile(TileId(i + 1), 0, vec2(i as f32 * 16.0, 0.0)))
+            .collect();
+
+        let removed = index.remove_tile(handles[0]).expect("live handle");
+        assert_eq!(removed.id, TileId(1));
+        index.assert_consistent();
+        assert!(index.remove_tile(handles[0]).is_none());
+        assert_eq!(index.tile_mut(handles[2]).map(|r| r.id), Some(TileId(3)));
+
+        index.remove_tile(handles[1]);
+        index.remove_tile(handles[2]);
+        index.assert_consistent();
+        assert!(index.buckets.is_empty());
+
+        // Released slots are reused rather than growing the table, but under a new
+        // generation: stale handles must not reach the tile now in their slot.
+        let again = index.add_tile(TileId(4), 0, Vec2::ZERO);
+        assert!(handles.iter().any(|h| h.slot() == again.slot()));
+        assert!(!handles.contains(&again));
+        assert_eq!(index.handles.len(), 3);
+        index.assert_consistent();
+        for stale in &handles {
+            assert!(index.tile_mut(*stale).is_none());
+            assert!(index.remove_tile(*stale).is_none());
+        }
+        assert_eq!(index.tile_mut(again).map(|r| r.id), Some(TileId(4)));
+    }
+
+    #[test]
+    fn handles_of_a_cleared_layer_stay_dead_after_reuse() {
+        let mut index = GlobalIndex::new();
+        let old = index.add_tile(TileId(1), 0, Vec2::ZERO);
+        index.remove_layer_tiles(0);
+        let new = index.add_tile(TileId(2), 0, Vec2::ZERO);
+        assert_eq!(old.slot(), new.slot());
+        assert!(index.tile_mut(old).is_none());
+        assert_eq!(index.tile_mut(new).map(|r| r.id), Some(TileId(2)));
+        index.assert_consistent();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "points at the record of handle 1")]
+    fn aliased_handles_are_reported() {
+        let mut index = GlobalIndex::new();
+        let first = index.add_tile(TileId(1), 0, Vec2::ZERO);
+        index.add_tile(TileId(1), 0, vec2(16.0, 0.0));
+        index.handles[first.slot() as usize] = Some(TileLoc {
+            chunk: ChunkCoord { x: 0, y: 0 },
+            layer: 0,
+            index: 1,
+        });
+        index.assert_consistent();
+    }
+
+    #[test]
+    fn tile_rec_reconstructs_world_position_and_grid_cell() {
+        let mut index = GlobalIndex::new();
+        let world = vec2(300.0, 48.0);
+        let handle = index.add_tile(TileId(1), 0, world);
+        let chunk = world_to_chunk(world);
+        let rec = index.tile_mut(handle).expect("live handle").clone();
+
+        assert_eq!(rec.world_pos(chunk), world);
+        assert_eq!(rec.col(chunk, 16), 18);
+        assert_eq!(rec.row(chunk, 16), 3);
+
+        // Left of and above the origin, as with a negative layer offset.
+        let world = vec2(-20.0, -1.0);
+        let handle = index.add_tile(TileId(1), 0, world);
+        let chunk = world_to_chunk(world);
+        let rec = index.tile_mut(handle).expect("live handle").clone();
+        assert_eq!(rec.world_pos(chunk), world);
+        assert_eq!((rec.col(chunk, 16), rec.row(chunk, 16)), (-2, -1));
+    }
+
+    #[test]
+    fn layer_tile_vec_distinguishes_missing_buckets() {
+        let mut index = GlobalIndex::new();
+        index.add_tile(TileId(1), 0, vec2(0.0, 0.0));
+        index.add_tile(TileId(2), 0, vec2(16.0, 0.0));
+        let chunk = ChunkCoord { x: 0, y: 0 };
+
+        assert_eq!(index.layer_tile_vec(chunk, 0).map(<[_]>::len), Some(2));
+        assert!(index.layer_tile_vec(chunk, 1).is_none());
+        assert!(index.layer_tile_vec(ChunkCoord { x: 1, y: 0 }, 0).is_none());
+
+        for rec in index.layer_tile_vec_mut(chunk, 0).expect("bucket") {
+            rec.id = TileId(rec.id.raw() + 10);
+        }
+        let ids: Vec<u32> = index
+            .layer_tile_vec(chunk, 0)
+            .expect("bucket")
+            .iter()
+            .map(|r| r.id.raw())
+            .collect();
+        assert_eq!(ids, vec![11, 12]);
+        index.assert_consistent();
+    }
+
+    #[test]
+    fn chunk_neighbors_surround_the_chunk_once() {
+        const ORIGIN: ChunkCoord = ChunkCoord { x: 2, y: -1 };
+        const EDGES: [ChunkCoord; 4] = ORIGIN.neighbors();
+        let c = |x, y| ChunkCoord { x, y };
+        assert_eq!(EDGES, [c(2, -2), c(3, -1), c(2, 0), c(1, -1)]);
+
+        let ring = ORIGIN.neighbors_8();
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let n = c(ORIGIN.x + dx, ORIGIN.y + dy);
+                let hits = ring.iter().filter(|&&r| r == n).count();
+                assert_eq!(hits, usize::from(n != ORIGIN), "{n:?}");
+            }
+        }
+        assert!(EDGES.iter().all(|e| ring.contains(e)));
+
+        let corner = c(i32::MAX, i32::MIN);
+        let [north, east, ..] = corner.neighbors();
+        assert_eq!(
+            (north, east),
+            (c(i32::MAX, i32::MAX), c(i32::MIN, i32::MIN))
+        );
+        assert_eq!(corner.neighbors_8()[1], c(i32::MIN, i32::MAX));
+    }
 }
@@ -1,3 +1,5 @@
 // spatial/mod.rs
 pub mod index;
+pub mod object_index;
 pub use index::*;
+pub use object_index::*;
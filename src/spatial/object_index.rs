@@ -0,0 +1,201 @@
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// Default grid cell edge for [`ObjectIndex`], in world pixels.
+pub const DEFAULT_OBJECT_CELL_SIZE: f32 = 256.0;
+
+/// Objects spanning more grid cells than this are kept out of the grid and checked by
+/// every query instead.
+const MAX_CELLS_PER_OBJECT: i64 = 1024;
+
+/// Rect coordinates are clamped to this magnitude, so infinite rects still have a
+/// finite extent.
+const COORD_LIMIT: f32 = f32::MAX / 4.0;
+
+/// Identifies an object by object-layer position and object position within that layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectKey {
+    pub layer: usize,
+    pub object: usize,
+}
+
+/// Uniform-grid spatial hash over object bounding rects.
+///
+/// Objects are inserted into every cell their AABB overlaps. Queries only visit cells
+/// overlapping the query rect, then drop duplicates and AABBs that miss the rect.
+/// Queries spanning more cells than the index holds walk the occupied cells instead, and
+/// objects spanning huge areas skip the grid, so neither costs more than the map's size.
+pub struct ObjectIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // Entries too large for the grid, checked by every query.
+    oversized: Vec<usize>,
+    entries: Vec<(ObjectKey, Rect)>,
+}
+
+impl ObjectIndex {
+    pub fn new(cell_size: f32) -> Self {
+        ObjectIndex {
+            cell_size: if cell_size > 0.0 {
+                cell_size
+            } else {
+                DEFAULT_OBJECT_CELL_SIZE
+            },
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.oversized.clear();
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, key: ObjectKey, aabb: Rect) {
+        let aabb = normalized(aabb);
+        let entry = self.entries.len();
+        self.entries.push((key, aabb));
+        let (min, max) = self.cell_span(aabb);
+        if span_area(min, max) > MAX_CELLS_PER_OBJECT {
+            self.oversized.push(entry);
+            return;
+        }
+        for cy in min.1..=max.1 {
+            for cx in min.0..=max.0 {
+                self.cells.entry((cx, cy)).or_default().push(entry);
+            }
+        }
+    }
+
+    /// Returns keys of objects whose AABB overlaps `rect`, in insertion order.
+    pub fn query(&self, rect: Rect) -> impl Iterator<Item = ObjectKey> + '_ {
+        let rect = normalized(rect);
+        let (min, max) = self.cell_span(rect);
+        let mut hits = self.oversized.clone();
+        if (self.cells.len() as i64) < span_area(min, max) {
+            let in_span =
+                |&(cx, cy): &(i32, i32)| cx >= min.0 && cy >= min.1 && cx <= max.0 && cy <= max.1;
+            for (_, cell) in self.cells.iter().filter(|(c, _)| in_span(c)) {
+                hits.extend_from_slice(cell);
+            }
+        } else {
+            for cy in min.1..=max.1 {
+                for cx in min.0..=max.0 {
+                    if let Some(cell) = self.cells.get(&(cx, cy)) {
+                        hits.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        hits.sort_unstable();
+        hits.dedup();
+        hits.into_iter().filter_map(move |entry| {
+            let (key, aabb) = self.entries[entry];
+            aabb.overlaps(&rect).then_some(key)
+        })
+    }
+
+    fn cell_span(&self, rect: Rect) -> ((i32, i32), (i32, i32)) {
+        let cell = |v: f32| (v / self.cell_size).floor() as i32;
+        (
+            (cell(rect.x), cell(rect.y)),
+            (cell(rect.x + rect.w), cell(rect.y + rect.h)),
+        )
+    }
+}
+
+impl Default for ObjectIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_OBJECT_CELL_SIZE)
+    }
+}
+
+/// Cells in the inclusive span `min..=max`.
+fn span_area(min: (i32, i32), max: (i32, i32)) -> i64 {
+    let w = (i64::from(max.0) - i64::from(min.0) + 1).max(0);
+    let h = (i64::from(max.1) - i64::from(min.1) + 1).max(0);
+    w.saturating_mul(h)
+}
+
+/// `rect` with non-negative extents and infinite values clamped to [`COORD_LIMIT`].
+fn normalized(rect: Rect) -> Rect {
+    let clamp = |v: f32, limit: f32| v.clamp(-limit, limit);
+    let (x, y) = (clamp(rect.x, COORD_LIMIT), clamp(rect.y, COORD_LIMIT));
+    let x2 = clamp(x + clamp(rect.w, 2.0 * COORD_LIMIT), COORD_LIMIT);
+    let y2 = clamp(y + clamp(rect.h, 2.0 * COORD_LIMIT), COORD_LIMIT);
+    Rect::new(x.min(x2), y.min(y2), (x2 - x).abs(), (y2 - y).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(object: usize) -> ObjectKey {
+        ObjectKey { layer: 0, object }
+    }
+
+    #[test]
+    fn query_dedupes_multi_cell_objects_and_filters_by_aabb() {
+        let mut index = ObjectIndex::new(64.0);
+        // Spans four cells.
+        index.insert(key(0), Rect::new(50.0, 50.0, 30.0, 30.0));
+        // Same cell as the query but outside the query rect.
+        index.insert(key(1), Rect::new(5.0, 5.0, 4.0, 4.0));
+        index.insert(key(2), Rect::new(500.0, 500.0, 10.0, 10.0));
+
+        let hits: Vec<_> = index.query(Rect::new(40.0, 40.0, 20.0, 20.0)).collect();
+        assert_eq!(hits, vec![key(0)]);
+
+        // Negative extents are normalized.
+        let hits: Vec<_> = index.query(Rect::new(90.0, 90.0, -90.0, -90.0)).collect();
+        assert_eq!(hits, vec![key(0), key(1)]);
+    }
+
+    #[test]
+    fn huge_and_infinite_rects_stay_bounded_by_the_map() {
+        let mut index = ObjectIndex::new(64.0);
+        index.insert(key(0), Rect::new(10.0, 10.0, 4.0, 4.0));
+        // Would cover about 2.4e14 cells if inserted into the grid.
+        index.insert(key(1), Rect::new(-5e8, -5e8, 1e9, 1e9));
+        index.insert(key(2), Rect::new(0.0, 0.0, f32::INFINITY, 8.0));
+        assert!(index.cells.len() <= 2, "{} cells", index.cells.len());
+
+        let all = vec![key(0), key(1), key(2)];
+        for rect in [
+            Rect::new(0.0, 0.0, 1e9, 1e9),
+            Rect::new(
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                f32::INFINITY,
+            ),
+            Rect::new(
+                f32::INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+            ),
+        ] {
+            let hits: Vec<_> = index.query(rect).collect();
+            assert_eq!(hits, all, "{rect:?}");
+        }
+        let hits: Vec<_> = index.query(Rect::new(-100.0, 100.0, 10.0, 10.0)).collect();
+        assert_eq!(hits, vec![key(1)]);
+        let hits: Vec<_> = index.query(Rect::new(f32::NAN, 0.0, 10.0, 10.0)).collect();
+        assert!(hits.is_empty(), "{hits:?}");
+    }
+}
@@ -0,0 +1,115 @@
+use crate::ir_map::IrObject;
+use macroquad::prelude::*;
+use std::collections::HashSet;
+
+/// Tolerance in world pixels for point and polyline trigger regions.
+const TRIGGER_PICK_RADIUS: f32 = 4.0;
+
+/// What a [`Triggers`] set is tested against each frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerProbe {
+    /// A single world-space point, e.g. the player's feet.
+    Point(Vec2),
+    /// A world-space bounding box, e.g. the player's collider.
+    Aabb(Rect),
+}
+
+impl From<Vec2> for TriggerProbe {
+    fn from(p: Vec2) -> Self {
+        TriggerProbe::Point(p)
+    }
+}
+
+impl From<Rect> for TriggerProbe {
+    fn from(r: Rect) -> Self {
+        TriggerProbe::Aabb(r)
+    }
+}
+
+/// Containment change of one trigger region, keyed by Tiled object id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerEvent {
+    /// The probe was outside last update and is inside now.
+    Entered(u32),
+    /// The probe was inside last update and still is.
+    Inside(u32),
+    /// The probe was inside last update and is outside now.
+    Exited(u32),
+}
+
+/// Events produced by one [`Triggers::update`], in region (file) order.
+pub type TriggerEvents = Vec<TriggerEvent>;
+
+/// Trigger regions built from an object layer by [`Map::build_triggers`](crate::Map::build_triggers).
+///
+/// Shapes are snapshotted in world space at build time; rebuild after moving the layer.
+pub struct Triggers {
+    // World-space object copy (layer offset applied) and its bounding rect.
+    regions: Vec<(IrObject, Rect)>,
+    inside: HashSet<u32>,
+}
+
+impl Triggers {
+    pub(crate) fn new(regions: Vec<(IrObject, Rect)>) -> Self {
+        Triggers {
+            regions,
+            inside: HashSet::new(),
+        }
+    }
+
+    /// Number of regions in this set.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Returns `true` if the source layer had no objects.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Tests `probe` against every region and diffs the result against the previous update.
+    ///
+    /// Points use exact shape containment (rotation, ellipses and polygons included). Boxes
+    /// count as inside when they overlap the region's bounds and either a box corner or
+    /// center lies in the shape, or the shape's center lies in the box.
+    pub fn update(&mut self, probe: impl Into<TriggerProbe>) -> TriggerEvents {
+        let probe = probe.into();
+        let mut events = Vec::new();
+        let mut now_inside = HashSet::with_capacity(self.inside.len());
+        for (object, bounds) in &self.regions {
+            let hit = match probe {
+                TriggerProbe::Point(p) => {
+                    let r = TRIGGER_PICK_RADIUS;
+                    bounds.overlaps(&Rect::new(p.x - r, p.y - r, 2.0 * r, 2.0 * r))
+                        && object.contains_point(p, r)
+                }
+                TriggerProbe::Aabb(rect) => {
+                    bounds.overlaps(&rect)
+                        && (rect.contains(object.center())
+                            || [
+                                rect.point(),
+                                rect.point() + vec2(rect.w, 0.0),
+                                rect.point() + rect.size(),
+                                rect.point() + vec2(0.0, rect.h),
+                                rect.center(),
+                            ]
+                            .into_iter()
+                            .any(|p| object.contains_point(p, TRIGGER_PICK_RADIUS)))
+                }
+            };
+
+            let was_inside = self.inside.contains(&object.id);
+            match (was_inside, hit) {
+                (false, true) => events.push(TriggerEvent::Entered(object.id)),
+                (true, true) => events.push(TriggerEvent::Inside(object.id)),
+                (true, false) => events.push(TriggerEvent::Exited(object.id)),
+                (false, false) => {}
+            }
+            if hit {
+                now_inside.insert(object.id);
+            }
+        }
+        self.inside = now_inside;
+        events
+    }
+}